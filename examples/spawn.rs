@@ -33,7 +33,7 @@ async fn main_future() -> Result<()> {
     info!("Waiting for tasks");
 
     for task in tasks {
-        task.await?;
+        task.await??;
     }
 
     info!(
@@ -25,14 +25,15 @@ fn main() -> Result<()> {
             std::thread::current().id()
         });
 
-        let handle_1_thread_id = handle1.await;
-        let handle_2_thread_id = handle2.await;
+        let handle_1_thread_id = handle1.await?;
+        let handle_2_thread_id = handle2.await?;
         info!(message = "task 1", thread_id = ?handle_1_thread_id);
         info!(message = "task 2", thread_id = ?handle_2_thread_id);
+        Result::<()>::Ok(())
     };
 
     let runtime = guillotine::runtime::Runtime::new()?;
 
-    runtime.block_on(future);
+    runtime.block_on(future)?;
     Ok(())
 }
@@ -0,0 +1,89 @@
+//! Running futures from the [async-io](::async_io) (smol) ecosystem on guillotine
+//!
+//! async-io has a reactor of its own. When nobody is inside `async_io::block_on` to drive it, it
+//! runs it on a background thread called `async-io`, and wakes up whichever task was waiting from
+//! there. A guillotine task's waker is happy to be woken from another thread (it puts the task on
+//! the runtime's ready queue and pokes the runtime's epoll), so `async_io::Async<T>`,
+//! `async_io::Timer`, and everything built on them just work inside a guillotine task. The price is
+//! a trip through a second thread for every wakeup.
+//!
+//! On top of that, with this feature `Async<T>` implements this crate's [`io`](crate::io) traits,
+//! so it can be used with this crate's I/O helpers too.
+//!
+//! ```
+//! use async_io::{Async, Timer};
+//! use guillotine::io::{AsyncReadExt, AsyncWriteExt};
+//! use std::os::unix::net::UnixStream;
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let (mut a, mut b) = Async::<UnixStream>::pair().unwrap();
+//!
+//!     guillotine::task::spawn(async move {
+//!         Timer::after(std::time::Duration::from_millis(10)).await;
+//!         a.write_all(b"hello").await.unwrap();
+//!     });
+//!
+//!     let mut buf = [0; 5];
+//!     b.read_exact(&mut buf).await.unwrap();
+//!     assert_eq!(&buf, b"hello");
+//! });
+//! ```
+
+use crate::io::{AsyncRead, AsyncWrite};
+use async_io::Async;
+use std::io::{IoSlice, IoSliceMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+impl<T> AsyncRead for Async<T>
+where
+    Async<T>: futures_io::AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        futures_io::AsyncRead::poll_read(self, cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        futures_io::AsyncRead::poll_read_vectored(self, cx, bufs)
+    }
+}
+
+impl<T> AsyncWrite for Async<T>
+where
+    Async<T>: futures_io::AsyncWrite,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        futures_io::AsyncWrite::poll_write(self, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        futures_io::AsyncWrite::poll_write_vectored(self, cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        futures_io::AsyncWrite::poll_flush(self, cx)
+    }
+
+    /// Note that async-io doesn't shut down a socket when it's closed; use
+    /// `Async::get_ref().shutdown(..)` for that.
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        futures_io::AsyncWrite::poll_close(self, cx)
+    }
+}
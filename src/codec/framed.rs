@@ -0,0 +1,214 @@
+use super::{Decoder, Encoder};
+use crate::io::{poll_read_buf, AsyncRead, AsyncWrite};
+use bytes::{Buf, BytesMut};
+use futures_core::Stream;
+use futures_sink::Sink;
+use pin_project::pin_project;
+use std::io::ErrorKind;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// How much room to make in the read buffer before each read
+const INITIAL_CAPACITY: usize = 8 * 1024;
+
+/// How much can pile up in the write buffer before [`Framed`] stops accepting messages until it's
+/// been written out
+const BACKPRESSURE_BOUNDARY: usize = INITIAL_CAPACITY;
+
+/// A stream of bytes, seen as a [`Stream`] and [`Sink`] of messages
+///
+/// See the [module documentation](super) for an example.
+#[pin_project]
+#[derive(Debug)]
+pub struct Framed<T, C> {
+    #[pin]
+    io: T,
+    codec: C,
+    read_buf: BytesMut,
+    write_buf: BytesMut,
+    /// Whether `read_buf` might have a message in it that hasn't been decoded yet
+    is_readable: bool,
+    /// Whether the underlying stream has ended
+    eof: bool,
+}
+
+impl<T, C> Framed<T, C> {
+    /// Wrap a stream with a codec
+    pub fn new(io: T, codec: C) -> Self {
+        Self {
+            io,
+            codec,
+            read_buf: BytesMut::with_capacity(INITIAL_CAPACITY),
+            write_buf: BytesMut::with_capacity(INITIAL_CAPACITY),
+            is_readable: false,
+            eof: false,
+        }
+    }
+
+    /// Get access to the wrapped stream
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// Get mutable access to the wrapped stream
+    ///
+    /// Reading from or writing to it directly will likely confuse the codec.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    /// Get pinned mutable access to the wrapped stream
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut T> {
+        self.project().io
+    }
+
+    /// Get access to the codec
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Get mutable access to the codec
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// The bytes that have been read but not decoded yet
+    pub fn read_buffer(&self) -> &BytesMut {
+        &self.read_buf
+    }
+
+    /// The bytes that have been encoded but not written yet
+    pub fn write_buffer(&self) -> &BytesMut {
+        &self.write_buf
+    }
+
+    /// Unwrap the stream, throwing away anything left in the buffers
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+
+    /// Swap out the codec, keeping the buffers
+    ///
+    /// Useful for protocols that change partway through, like a text handshake followed by binary
+    /// frames.
+    pub fn map_codec<D>(self, map: impl FnOnce(C) -> D) -> Framed<T, D> {
+        Framed {
+            io: self.io,
+            codec: map(self.codec),
+            read_buf: self.read_buf,
+            write_buf: self.write_buf,
+            is_readable: self.is_readable,
+            eof: self.eof,
+        }
+    }
+}
+
+impl<T: AsyncRead, C: Decoder> Framed<T, C> {
+    /// Get the next message, as a _future_.
+    ///
+    /// Returns `None` once the underlying stream has ended and every message has been decoded.
+    pub async fn next_frame(&mut self) -> Option<Result<C::Item, C::Error>>
+    where
+        T: Unpin,
+    {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl<T: AsyncWrite, C> Framed<T, C> {
+    /// Send a message, and wait for it to be written out, as a _future_.
+    pub async fn send_frame<I>(&mut self, item: I) -> Result<(), C::Error>
+    where
+        T: Unpin,
+        C: Encoder<I>,
+    {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await?;
+        Pin::new(&mut *self).start_send(item)?;
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_flush(cx)).await
+    }
+
+    /// Write out everything in the write buffer
+    fn poll_write_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        let mut projected = self.project();
+        while !projected.write_buf.is_empty() {
+            match projected.io.as_mut().poll_write(cx, projected.write_buf) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(written)) => projected.write_buf.advance(written),
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncRead, C: Decoder> Stream for Framed<T, C> {
+    type Item = Result<C::Item, C::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut projected = self.project();
+        loop {
+            // Decode everything we can out of what's already been read before reading any more
+            if *projected.is_readable {
+                if *projected.eof {
+                    let frame = projected.codec.decode_eof(projected.read_buf);
+                    return Poll::Ready(frame.transpose());
+                }
+                match projected.codec.decode(projected.read_buf) {
+                    Ok(Some(frame)) => return Poll::Ready(Some(Ok(frame))),
+                    Ok(None) => *projected.is_readable = false,
+                    Err(err) => return Poll::Ready(Some(Err(err))),
+                }
+            }
+
+            projected.read_buf.reserve(1);
+            if projected.read_buf.capacity() - projected.read_buf.len() < INITIAL_CAPACITY / 8 {
+                projected.read_buf.reserve(INITIAL_CAPACITY);
+            }
+            match poll_read_buf(projected.io.as_mut(), cx, projected.read_buf) {
+                Poll::Ready(Ok(0)) => *projected.eof = true,
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => continue,
+                Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                Poll::Pending => return Poll::Pending,
+            }
+            *projected.is_readable = true;
+        }
+    }
+}
+
+impl<T: AsyncWrite, C: Encoder<I>, I> Sink<I> for Framed<T, C> {
+    type Error = C::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.write_buf.len() < BACKPRESSURE_BOUNDARY {
+            return Poll::Ready(Ok(()));
+        }
+        self.poll_write_buf(cx).map_err(Into::into)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        let projected = self.project();
+        projected.codec.encode(item, projected.write_buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.as_mut().poll_write_buf(cx) {
+            Poll::Ready(Ok(())) => self.project().io.poll_flush(cx).map_err(Into::into),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err.into())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.as_mut().poll_write_buf(cx) {
+            Poll::Ready(Ok(())) => self.project().io.poll_close(cx).map_err(Into::into),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err.into())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use super::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::io::{Error, ErrorKind};
+
+/// A codec for frames that start with their length
+///
+/// By default, every frame is a four-byte big-endian length followed by that many bytes of
+/// payload, and frames bigger than 8 MiB are rejected. [`LengthDelimitedCodec::builder`] can
+/// describe most other layouts: a different size or byte order for the length, a header before
+/// the length, a length that counts the header too, and so on.
+///
+/// Decoding produces the payload, without the length (unless the builder says otherwise).
+/// Encoding takes the payload and puts the length in front.
+///
+/// ```
+/// use bytes::{BufMut, Bytes, BytesMut};
+/// use guillotine::codec::{Decoder, Encoder, LengthDelimitedCodec};
+///
+/// // A two-byte little-endian length, and at most 1 KiB per frame
+/// let mut codec = LengthDelimitedCodec::builder()
+///     .length_field_length(2)
+///     .little_endian()
+///     .max_frame_length(1024)
+///     .new_codec();
+///
+/// let mut buf = BytesMut::new();
+/// codec.encode(Bytes::from_static(b"hello"), &mut buf).unwrap();
+/// assert_eq!(&buf[..], b"\x05\x00hello");
+///
+/// let frame = codec.decode(&mut buf).unwrap().unwrap();
+/// assert_eq!(&frame[..], b"hello");
+/// ```
+#[derive(Clone, Debug)]
+pub struct LengthDelimitedCodec {
+    builder: LengthDelimitedBuilder,
+    /// The length of the frame being decoded, once its header has been read
+    state: DecodeState,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum DecodeState {
+    /// Waiting for the header
+    Head,
+    /// The header has been read; waiting for this many more bytes
+    Data(usize),
+}
+
+/// Describes the frame layout for a [`LengthDelimitedCodec`]
+#[derive(Copy, Clone, Debug)]
+pub struct LengthDelimitedBuilder {
+    /// How many bytes the length takes up
+    length_field_len: usize,
+    /// How many bytes come before the length
+    length_field_offset: usize,
+    /// Added to the length to get the number of bytes after the length field
+    length_adjustment: i64,
+    /// How many bytes to strip off the front of a frame when decoding
+    num_skip: Option<usize>,
+    /// The biggest frame (not counting the header) that's allowed
+    max_frame_len: usize,
+    big_endian: bool,
+}
+
+impl LengthDelimitedCodec {
+    /// Create a codec with the default layout: a four-byte big-endian length, then the payload
+    pub fn new() -> Self {
+        Self::builder().new_codec()
+    }
+
+    /// Start describing a different layout
+    pub fn builder() -> LengthDelimitedBuilder {
+        LengthDelimitedBuilder {
+            length_field_len: 4,
+            length_field_offset: 0,
+            length_adjustment: 0,
+            num_skip: None,
+            max_frame_len: 8 * 1024 * 1024,
+            big_endian: true,
+        }
+    }
+
+    /// The biggest frame that will be accepted
+    pub fn max_frame_length(&self) -> usize {
+        self.builder.max_frame_len
+    }
+
+    /// Change the biggest frame that will be accepted
+    pub fn set_max_frame_length(&mut self, max: usize) {
+        self.builder.max_frame_len = max;
+    }
+
+    /// Try to read the header of the next frame, returning how many bytes the rest of it takes up
+    fn decode_head(&mut self, src: &mut BytesMut) -> Result<Option<usize>, Error> {
+        let builder = &self.builder;
+        let head_len = builder.length_field_offset + builder.length_field_len;
+        if src.len() < head_len {
+            return Ok(None);
+        }
+
+        let mut field = &src[builder.length_field_offset..head_len];
+        let length = if builder.big_endian {
+            field.get_uint(builder.length_field_len)
+        } else {
+            field.get_uint_le(builder.length_field_len)
+        };
+
+        // Work out how many bytes follow the length field
+        let remaining = i64::try_from(length)
+            .ok()
+            .and_then(|length| length.checked_add(builder.length_adjustment))
+            .and_then(|remaining| usize::try_from(remaining).ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid frame length"))?;
+        if remaining > builder.max_frame_len {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "frame is bigger than the maximum frame length",
+            ));
+        }
+
+        // The header (or as much of it as we're told to) gets stripped
+        let num_skip = builder.skip_len();
+        if num_skip > head_len + remaining {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "frame is smaller than the bytes to skip",
+            ));
+        }
+        src.advance(num_skip);
+        let rest = head_len + remaining - num_skip;
+        src.reserve(rest);
+        Ok(Some(rest))
+    }
+}
+
+impl Default for LengthDelimitedCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LengthDelimitedCodec {
+    type Item = BytesMut;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, Error> {
+        let len = match self.state {
+            DecodeState::Head => match self.decode_head(src)? {
+                Some(len) => {
+                    self.state = DecodeState::Data(len);
+                    len
+                }
+                None => return Ok(None),
+            },
+            DecodeState::Data(len) => len,
+        };
+
+        if src.len() < len {
+            return Ok(None);
+        }
+        self.state = DecodeState::Head;
+        let frame = src.split_to(len);
+        src.reserve(self.builder.length_field_offset + self.builder.length_field_len);
+        Ok(Some(frame))
+    }
+}
+
+impl Encoder<Bytes> for LengthDelimitedCodec {
+    type Error = Error;
+
+    fn encode(&mut self, data: Bytes, dst: &mut BytesMut) -> Result<(), Error> {
+        let builder = &self.builder;
+        if data.len() > builder.max_frame_len {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "frame is bigger than the maximum frame length",
+            ));
+        }
+
+        // Undo the adjustment, so the decoder on the other end gets back to `data.len()`
+        let length = (data.len() as i64)
+            .checked_sub(builder.length_adjustment)
+            .and_then(|length| u64::try_from(length).ok())
+            .filter(|length| {
+                builder.length_field_len == 8 || *length < 1 << (builder.length_field_len * 8)
+            })
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    "frame length doesn't fit in the length field",
+                )
+            })?;
+
+        dst.reserve(builder.length_field_len + data.len());
+        if builder.big_endian {
+            dst.put_uint(length, builder.length_field_len);
+        } else {
+            dst.put_uint_le(length, builder.length_field_len);
+        }
+        dst.extend_from_slice(&data);
+        Ok(())
+    }
+}
+
+impl LengthDelimitedBuilder {
+    /// How many bytes the length takes up, from 1 to 8. The default is 4.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` isn't between 1 and 8.
+    pub fn length_field_length(&mut self, len: usize) -> &mut Self {
+        assert!(
+            (1..=8).contains(&len),
+            "length field must be between 1 and 8 bytes"
+        );
+        self.length_field_len = len;
+        self
+    }
+
+    /// How many bytes of header come before the length. The default is 0.
+    ///
+    /// Only decoding pays attention to this; encoding never writes a header before the length.
+    pub fn length_field_offset(&mut self, offset: usize) -> &mut Self {
+        self.length_field_offset = offset;
+        self
+    }
+
+    /// A number to add to the length to get the number of bytes after the length field. The
+    /// default is 0.
+    ///
+    /// For example, if the length counts itself too, this is minus the size of the length field.
+    pub fn length_adjustment(&mut self, adjustment: i64) -> &mut Self {
+        self.length_adjustment = adjustment;
+        self
+    }
+
+    /// How many bytes to strip off the front of each frame when decoding. The default is the
+    /// whole header: the offset plus the length field.
+    pub fn num_skip(&mut self, num_skip: usize) -> &mut Self {
+        self.num_skip = Some(num_skip);
+        self
+    }
+
+    /// The biggest frame to accept, not counting the header. The default is 8 MiB.
+    ///
+    /// Anything bigger is an error, rather than a very large allocation.
+    pub fn max_frame_length(&mut self, max: usize) -> &mut Self {
+        self.max_frame_len = max;
+        self
+    }
+
+    /// Read and write the length as big-endian (network byte order). This is the default.
+    pub fn big_endian(&mut self) -> &mut Self {
+        self.big_endian = true;
+        self
+    }
+
+    /// Read and write the length as little-endian
+    pub fn little_endian(&mut self) -> &mut Self {
+        self.big_endian = false;
+        self
+    }
+
+    /// Create a codec with this layout
+    pub fn new_codec(&self) -> LengthDelimitedCodec {
+        LengthDelimitedCodec {
+            builder: *self,
+            state: DecodeState::Head,
+        }
+    }
+
+    /// Wrap a stream in a [`Framed`](super::Framed) using a codec with this layout
+    pub fn new_framed<T>(&self, io: T) -> super::Framed<T, LengthDelimitedCodec> {
+        super::Framed::new(io, self.new_codec())
+    }
+
+    fn skip_len(&self) -> usize {
+        self.num_skip
+            .unwrap_or(self.length_field_offset + self.length_field_len)
+    }
+}
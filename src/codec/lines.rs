@@ -0,0 +1,218 @@
+use super::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::io::{Error, ErrorKind};
+
+/// A codec for newline-separated lines of text
+///
+/// Decoded lines don't include the `\n` (or the `\r\n`) at the end. Encoding a line adds a `\n`.
+///
+/// Without a limit, a peer that never sends a newline can make the read buffer grow forever, so
+/// anything facing the network should use [`LinesCodec::new_with_max_length`]. A line that goes
+/// over the limit is an error; after that, the rest of that line is thrown away and decoding
+/// picks up again at the next one.
+///
+/// ```
+/// use guillotine::codec::{Framed, LinesCodec};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (a, b) = guillotine::net::unix::pair().unwrap();
+///     let mut a = Framed::new(a, LinesCodec::new());
+///     let mut b = Framed::new(b, LinesCodec::new_with_max_length(1024));
+///
+///     a.send_frame("PING").await.unwrap();
+///     assert_eq!(b.next_frame().await.unwrap().unwrap(), "PING");
+/// });
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LinesCodec {
+    inner: AnyDelimiterCodec,
+}
+
+impl LinesCodec {
+    /// Create a codec with no limit on the length of a line
+    pub fn new() -> Self {
+        Self::new_with_max_length(usize::MAX)
+    }
+
+    /// Create a codec that rejects lines longer than `max_length` bytes (not counting the
+    /// newline)
+    pub fn new_with_max_length(max_length: usize) -> Self {
+        Self {
+            inner: AnyDelimiterCodec::new_with_max_length(
+                b"\n".to_vec(),
+                b"\n".to_vec(),
+                max_length,
+            ),
+        }
+    }
+
+    /// The longest line that will be accepted
+    pub fn max_length(&self) -> usize {
+        self.inner.max_length()
+    }
+
+    /// Turn a decoded chunk into a line, trimming a trailing `\r`
+    fn into_line(chunk: Bytes) -> Result<String, Error> {
+        let chunk = match chunk.last() {
+            Some(b'\r') => chunk.slice(..chunk.len() - 1),
+            _ => chunk,
+        };
+        String::from_utf8(chunk.to_vec())
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "line is not valid UTF-8"))
+    }
+}
+
+impl Decoder for LinesCodec {
+    type Item = String;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<String>, Error> {
+        match self.inner.decode(src)? {
+            Some(chunk) => Self::into_line(chunk).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<String>, Error> {
+        match self.inner.decode_eof(src)? {
+            Some(chunk) => Self::into_line(chunk).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T: AsRef<str>> Encoder<T> for LinesCodec {
+    type Error = Error;
+
+    fn encode(&mut self, line: T, dst: &mut BytesMut) -> Result<(), Error> {
+        self.inner.encode(line.as_ref().as_bytes(), dst)
+    }
+}
+
+/// A codec for chunks of bytes separated by any of a set of delimiter bytes
+///
+/// Decoded chunks don't include the delimiter. Encoding a chunk appends a separator sequence,
+/// which doesn't have to be one of the delimiters (it could be `\r\n` when the delimiters are
+/// just `\n`, say).
+///
+/// Like [`LinesCodec`], it can be given a maximum chunk length, and the same thing happens when a
+/// chunk goes over it: an error, and then decoding picks up after the next delimiter.
+#[derive(Clone, Debug)]
+pub struct AnyDelimiterCodec {
+    /// The bytes that end a chunk
+    delimiters: Vec<u8>,
+    /// What to put after each encoded chunk
+    separator: Vec<u8>,
+    max_length: usize,
+    /// How much of the buffer has already been searched for a delimiter, so we don't search it
+    /// again every time more bytes arrive
+    next_index: usize,
+    /// Whether we're throwing away the rest of a chunk that was too long
+    is_discarding: bool,
+}
+
+impl AnyDelimiterCodec {
+    /// Create a codec with no limit on the length of a chunk
+    pub fn new(delimiters: Vec<u8>, separator: Vec<u8>) -> Self {
+        Self::new_with_max_length(delimiters, separator, usize::MAX)
+    }
+
+    /// Create a codec that rejects chunks longer than `max_length` bytes (not counting the
+    /// delimiter)
+    pub fn new_with_max_length(delimiters: Vec<u8>, separator: Vec<u8>, max_length: usize) -> Self {
+        Self {
+            delimiters,
+            separator,
+            max_length,
+            next_index: 0,
+            is_discarding: false,
+        }
+    }
+
+    /// The longest chunk that will be accepted
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+}
+
+impl Default for AnyDelimiterCodec {
+    /// Split on commas and semicolons, and separate encoded chunks with commas
+    fn default() -> Self {
+        Self::new(b",;".to_vec(), b",".to_vec())
+    }
+}
+
+impl Decoder for AnyDelimiterCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>, Error> {
+        loop {
+            // Only look as far as one byte past the limit: if there's no delimiter by then, the
+            // chunk is too long no matter what comes next.
+            let end = src.len().min(self.max_length.saturating_add(1));
+            let found = src[self.next_index..end]
+                .iter()
+                .position(|byte| self.delimiters.contains(byte))
+                .map(|offset| self.next_index + offset);
+
+            match (self.is_discarding, found) {
+                (true, Some(index)) => {
+                    // Found the end of the chunk that was too long; drop it and carry on.
+                    src.advance(index + 1);
+                    self.is_discarding = false;
+                    self.next_index = 0;
+                }
+                (true, None) => {
+                    // Still in the middle of the chunk that was too long; drop what we have.
+                    src.advance(end);
+                    self.next_index = 0;
+                    if src.is_empty() {
+                        return Ok(None);
+                    }
+                }
+                (false, Some(index)) => {
+                    self.next_index = 0;
+                    let chunk = src.split_to(index + 1);
+                    return Ok(Some(chunk.freeze().slice(..index)));
+                }
+                (false, None) if src.len() > self.max_length => {
+                    self.is_discarding = true;
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "chunk is longer than the maximum length",
+                    ));
+                }
+                (false, None) => {
+                    self.next_index = src.len();
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>, Error> {
+        match self.decode(src)? {
+            Some(chunk) => Ok(Some(chunk)),
+            None if src.is_empty() || self.is_discarding => Ok(None),
+            None => {
+                // No delimiter at the end of the stream; whatever is left is the last chunk.
+                self.next_index = 0;
+                Ok(Some(src.split().freeze()))
+            }
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for AnyDelimiterCodec {
+    type Error = Error;
+
+    fn encode(&mut self, chunk: T, dst: &mut BytesMut) -> Result<(), Error> {
+        let chunk = chunk.as_ref();
+        dst.reserve(chunk.len() + self.separator.len());
+        dst.put_slice(chunk);
+        dst.put_slice(&self.separator);
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+//! Turning streams of bytes into streams of messages
+//!
+//! Most protocols aren't really about bytes; they're about messages (lines, frames, requests)
+//! that happen to be sent as bytes. A [`Decoder`] knows how to pull messages out of a buffer of
+//! bytes, an [`Encoder`] knows how to turn messages back into bytes, and [`Framed`] glues them to
+//! anything that implements [`AsyncRead`](crate::io::AsyncRead) and
+//! [`AsyncWrite`](crate::io::AsyncWrite), handling all of the buffering in between.
+//!
+//! A `Framed` is a [`Stream`](futures_core::Stream) of decoded messages and a
+//! [`Sink`](futures_sink::Sink) of messages to encode, so the `futures` combinators work with it.
+//! It also has [`Framed::next_frame`] and [`Framed::send_frame`], for when they aren't around.
+//!
+//! For datagram protocols, [`UdpFramed`] does the same for a [`UdpSocket`](crate::net::UdpSocket),
+//! one packet at a time.
+//!
+//! This module needs the `codec` feature.
+//!
+//! ```
+//! use bytes::{Buf, BufMut, BytesMut};
+//! use guillotine::codec::{Decoder, Encoder, Framed};
+//!
+//! /// Messages are a single byte
+//! struct ByteCodec;
+//!
+//! impl Decoder for ByteCodec {
+//!     type Item = u8;
+//!     type Error = std::io::Error;
+//!
+//!     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<u8>, std::io::Error> {
+//!         if src.is_empty() {
+//!             Ok(None)
+//!         } else {
+//!             Ok(Some(src.get_u8()))
+//!         }
+//!     }
+//! }
+//!
+//! impl Encoder<u8> for ByteCodec {
+//!     type Error = std::io::Error;
+//!
+//!     fn encode(&mut self, item: u8, dst: &mut BytesMut) -> Result<(), std::io::Error> {
+//!         dst.put_u8(item);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let (a, b) = guillotine::net::unix::pair().unwrap();
+//!     let mut a = Framed::new(a, ByteCodec);
+//!     let mut b = Framed::new(b, ByteCodec);
+//!
+//!     a.send_frame(42).await.unwrap();
+//!     assert_eq!(b.next_frame().await.unwrap().unwrap(), 42);
+//! });
+//! ```
+
+mod framed;
+mod length_delimited;
+mod lines;
+mod udp;
+
+pub use framed::Framed;
+pub use length_delimited::{LengthDelimitedBuilder, LengthDelimitedCodec};
+pub use lines::{AnyDelimiterCodec, LinesCodec};
+pub use udp::UdpFramed;
+
+use bytes::BytesMut;
+
+/// Pull messages out of a buffer of bytes
+pub trait Decoder {
+    /// The messages this decoder produces
+    type Item;
+
+    /// The error type. I/O errors from the underlying stream are converted into it.
+    type Error: From<std::io::Error>;
+
+    /// Try to decode a message from the front of `src`
+    ///
+    /// If `src` holds a whole message, remove it from `src` and return it. If it doesn't, leave
+    /// `src` alone and return `Ok(None)`; this is called again once more bytes have been read.
+    /// Reserving more space in `src` is a good way of saying how many more bytes are needed.
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error>;
+
+    /// Try to decode a message once the underlying stream has ended
+    ///
+    /// By default, this is the same as [`Decoder::decode`], except that leftover bytes that don't
+    /// make up a whole message are an error.
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() => Ok(None),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "bytes remaining on stream",
+            )
+            .into()),
+        }
+    }
+}
+
+/// Turn messages into bytes
+pub trait Encoder<Item> {
+    /// The error type. I/O errors from the underlying stream are converted into it.
+    type Error: From<std::io::Error>;
+
+    /// Encode `item`, appending it to `dst`
+    fn encode(&mut self, item: Item, dst: &mut BytesMut) -> Result<(), Self::Error>;
+}
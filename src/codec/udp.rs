@@ -0,0 +1,200 @@
+use super::{Decoder, Encoder};
+use crate::net::UdpSocket;
+use bytes::BytesMut;
+use futures_core::Stream;
+use futures_sink::Sink;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Big enough for any UDP packet
+const MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+
+/// A [`UdpSocket`], seen as a [`Stream`] and [`Sink`] of messages and who they're from (or to)
+///
+/// Each packet is decoded on its own: whatever the codec can decode out of a packet comes out of
+/// the stream, and whatever's left over is thrown away. Each message sent goes out as a packet of
+/// its own.
+///
+/// ```
+/// use guillotine::codec::{LinesCodec, UdpFramed};
+/// use guillotine::net::UdpSocket;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let a = UdpSocket::new(std::net::UdpSocket::bind("127.0.0.1:0").unwrap()).unwrap();
+///     let b = UdpSocket::new(std::net::UdpSocket::bind("127.0.0.1:0").unwrap()).unwrap();
+///     let a_addr = a.inner().local_addr().unwrap();
+///     let b_addr = b.inner().local_addr().unwrap();
+///
+///     let mut a = UdpFramed::new(a, LinesCodec::new());
+///     let mut b = UdpFramed::new(b, LinesCodec::new());
+///
+///     a.send_frame("hello", b_addr).await.unwrap();
+///     let (line, from) = b.next_frame().await.unwrap().unwrap();
+///     assert_eq!(line, "hello");
+///     assert_eq!(from, a_addr);
+/// });
+/// ```
+pub struct UdpFramed<C> {
+    socket: UdpSocket,
+    codec: C,
+    read_buf: BytesMut,
+    write_buf: BytesMut,
+    /// Who the packet in `read_buf` came from, while there might be messages left in it
+    read_addr: Option<SocketAddr>,
+    /// Who the message in `write_buf` is going to, until it's been sent
+    write_addr: Option<SocketAddr>,
+}
+
+// Nothing in here is ever pinned, even when the codec is `!Unpin`.
+impl<C> Unpin for UdpFramed<C> {}
+
+impl<C> UdpFramed<C> {
+    /// Wrap a socket with a codec
+    pub fn new(socket: UdpSocket, codec: C) -> Self {
+        Self {
+            socket,
+            codec,
+            read_buf: BytesMut::with_capacity(MAX_DATAGRAM_SIZE),
+            write_buf: BytesMut::with_capacity(MAX_DATAGRAM_SIZE),
+            read_addr: None,
+            write_addr: None,
+        }
+    }
+
+    /// Get access to the wrapped socket
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Get mutable access to the wrapped socket
+    pub fn get_mut(&mut self) -> &mut UdpSocket {
+        &mut self.socket
+    }
+
+    /// Get access to the codec
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Get mutable access to the codec
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Unwrap the socket, throwing away anything left in the buffers
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+
+    /// Send a message to `addr`, and wait for it to be sent, as a _future_.
+    pub async fn send_frame<I>(&mut self, item: I, addr: SocketAddr) -> Result<(), C::Error>
+    where
+        C: Encoder<I>,
+    {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await?;
+        Pin::new(&mut *self).start_send((item, addr))?;
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_flush(cx)).await
+    }
+}
+
+impl<C> std::fmt::Debug for UdpFramed<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UdpFramed").finish_non_exhaustive()
+    }
+}
+
+impl<C: Decoder> UdpFramed<C> {
+    /// Get the next message and who sent it, as a _future_.
+    ///
+    /// A socket never ends, so this never returns `None`.
+    pub async fn next_frame(&mut self) -> Option<Result<(C::Item, SocketAddr), C::Error>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl<C: Decoder> Stream for UdpFramed<C> {
+    type Item = Result<(C::Item, SocketAddr), C::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            // Decode everything we can out of the last packet before receiving another one
+            if let Some(addr) = this.read_addr {
+                match this.codec.decode_eof(&mut this.read_buf) {
+                    Ok(Some(frame)) => return Poll::Ready(Some(Ok((frame, addr)))),
+                    Ok(None) => {}
+                    Err(err) => {
+                        this.read_addr = None;
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                }
+                this.read_addr = None;
+            }
+
+            // The packet might be anything up to the biggest size there is. There's no need to
+            // zero the buffer again after every packet, though: it only ever gets overwritten.
+            this.read_buf.resize(MAX_DATAGRAM_SIZE, 0);
+            match this.socket.poll_recv_from(cx, &mut this.read_buf) {
+                Poll::Ready(Ok((len, addr))) => {
+                    this.read_buf.truncate(len);
+                    this.read_addr = Some(addr);
+                }
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<C: Encoder<I>, I> Sink<(I, SocketAddr)> for UdpFramed<C> {
+    type Error = C::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // There's only room for one packet at a time
+        self.poll_flush(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, (item, addr): (I, SocketAddr)) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        this.codec.encode(item, &mut this.write_buf)?;
+        this.write_addr = Some(addr);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        let Some(addr) = this.write_addr else {
+            return Poll::Ready(Ok(()));
+        };
+        loop {
+            match this.socket.poll_send_to(cx, &this.write_buf, addr) {
+                Poll::Ready(Ok(sent)) => {
+                    let whole = sent == this.write_buf.len();
+                    this.write_buf.clear();
+                    this.write_addr = None;
+                    if !whole {
+                        let err =
+                            std::io::Error::other("failed to send the whole message in one packet");
+                        return Poll::Ready(Err(err.into()));
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(err)) => {
+                    this.write_buf.clear();
+                    this.write_addr = None;
+                    return Poll::Ready(Err(err.into()));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
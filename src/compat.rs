@@ -0,0 +1,217 @@
+//! Compatibility with tokio's I/O traits
+//!
+//! Lots of useful crates are written against `tokio::io::AsyncRead` and `tokio::io::AsyncWrite`.
+//! Wrapping a guillotine type in [`Compat`] makes it implement those traits, so it can be handed
+//! straight to those crates. It works the other way too: wrapping something that implements the
+//! tokio traits in [`Compat`] makes it implement this crate's [`io`](crate::io) traits.
+//!
+//! ```
+//! use guillotine::compat::CompatExt;
+//!
+//! fn takes_tokio_io(_: impl tokio::io::AsyncRead + tokio::io::AsyncWrite) {}
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let (a, _b) = guillotine::net::unix::pair().unwrap();
+//!     takes_tokio_io(a.compat());
+//! });
+//! ```
+
+use pin_project::pin_project;
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A wrapper that translates between this crate's I/O traits and tokio's
+#[pin_project]
+#[derive(Debug)]
+pub struct Compat<T> {
+    #[pin]
+    inner: T,
+}
+
+impl<T> Compat<T> {
+    /// Wrap an I/O object
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Get access to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get mutable access to the wrapped object
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Adds `.compat()` to this crate's readers and writers (sockets, child process pipes, files and
+/// so on), as a shorthand for [`Compat::new`]
+///
+/// Anything else, like a tokio type going the other way, can be wrapped with [`Compat::new`]
+/// directly.
+///
+/// ```
+/// use guillotine::compat::CompatExt;
+/// use guillotine::process::{Command, Stdio};
+///
+/// fn takes_tokio_writer(_: impl tokio::io::AsyncWrite) {}
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let mut child = Command::new("cat").stdin(Stdio::piped()).spawn().unwrap();
+///     takes_tokio_writer(child.stdin.take().unwrap().compat());
+///     child.wait().await.unwrap();
+/// });
+/// ```
+pub trait CompatExt: sealed::Io + Sized {
+    /// Wrap this in a [`Compat`], so it implements tokio's I/O traits
+    fn compat(self) -> Compat<Self> {
+        Compat::new(self)
+    }
+}
+
+impl<T: sealed::Io> CompatExt for T {}
+
+mod sealed {
+    /// This crate's own readers and writers
+    ///
+    /// A blanket implementation for everything that implements [`AsyncRead`](crate::io::AsyncRead)
+    /// (or [`AsyncWrite`](crate::io::AsyncWrite)) would leave out the other half, and a type can't
+    /// get both, so they're listed one by one.
+    pub trait Io {}
+
+    impl Io for crate::net::TcpStream {}
+    impl Io for crate::net::UnixStream {}
+    impl Io for crate::net::unix::ReadHalf<'_> {}
+    impl Io for crate::net::unix::WriteHalf<'_> {}
+    impl Io for crate::net::VsockStream {}
+    impl Io for crate::fs::File {}
+    impl Io for crate::process::ChildStdin {}
+    impl Io for crate::process::ChildStdout {}
+    impl Io for crate::process::ChildStderr {}
+    impl Io for crate::process::Pty {}
+    impl Io for crate::tty::Terminal {}
+    impl Io for crate::io::SerialPort {}
+    impl Io for crate::io::FifoReader {}
+    impl Io for crate::io::FifoWriter {}
+    impl Io for crate::io::Empty {}
+    impl Io for crate::io::Sink {}
+    impl Io for crate::io::Repeat {}
+    impl<R> Io for crate::io::BufReader<R> {}
+    impl<W> Io for crate::io::BufWriter<W> {}
+    impl<A, B> Io for crate::io::Chain<A, B> {}
+    impl<R> Io for crate::io::Take<R> {}
+    impl<T> Io for crate::io::ReadHalf<T> {}
+    impl<T> Io for crate::io::WriteHalf<T> {}
+    impl<T> Io for crate::io::FaultInject<T> {}
+    #[cfg(feature = "sim")]
+    impl Io for crate::sim::TcpStream {}
+    #[cfg(feature = "test-util")]
+    impl Io for crate::test_util::mock::Mock {}
+}
+
+impl<T: crate::io::AsyncRead> tokio::io::AsyncRead for Compat<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        // Our trait wants an initialized slice, so hand it the unfilled part of the buffer
+        // (initializing it first if it needs to be), then tell the buffer how much got filled.
+        let unfilled = buf.initialize_unfilled();
+        match self.project().inner.poll_read(cx, unfilled) {
+            Poll::Ready(Ok(read)) => {
+                buf.advance(read);
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: crate::io::AsyncWrite> tokio::io::AsyncWrite for Compat<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.project().inner.poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+impl<T: tokio::io::AsyncRead> crate::io::AsyncRead for Compat<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut buf = tokio::io::ReadBuf::new(buf);
+        match self.project().inner.poll_read(cx, &mut buf) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.filled().len())),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: tokio::io::AsyncWrite> crate::io::AsyncWrite for Compat<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.project().inner.poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,46 @@
+//! What can go wrong with the runtime itself, as opposed to with whatever the tasks are doing
+
+use std::fmt::Display;
+
+/// Something went wrong with the runtime itself
+///
+/// Tasks deal with their own I/O errors; these are the ones that keep the runtime from running
+/// them to completion. Other than the runtime being shut down on purpose, the most likely cause is
+/// running out of file descriptors (or memory).
+///
+/// This converts into a [`std::io::Error`], so `?` works on it in functions that return those.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Waiting on epoll (or on mio's poll, with the `mio` feature) failed
+    Reactor(std::io::Error),
+    /// The runtime was [shut down](crate::runtime::Handle::shutdown) before the future finished
+    ShutDown,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Reactor(error) => write!(f, "failed to wait for events: {error}"),
+            Error::ShutDown => write!(f, "the runtime shut down before the future finished"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Reactor(error) => Some(error),
+            Error::ShutDown => None,
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Reactor(error) => error,
+            error @ Error::ShutDown => std::io::Error::new(std::io::ErrorKind::Interrupted, error),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Embedding guillotine as the event loop of a C (or C++, or anything that can call C) program
+//!
+//! Everything here is an `extern "C"` function, to be declared on the C side something like this:
+//!
+//! ```c
+//! typedef struct guillotine_runtime guillotine_runtime;
+//! typedef struct guillotine_completion guillotine_completion;
+//!
+//! guillotine_runtime *guillotine_runtime_new(void);
+//! int guillotine_runtime_run(guillotine_runtime *runtime);
+//! void guillotine_runtime_free(guillotine_runtime *runtime);
+//! void guillotine_spawn(guillotine_runtime *runtime, void (*callback)(void *), void *data);
+//! guillotine_completion *guillotine_spawn_completion(
+//!     guillotine_runtime *runtime, void (*on_complete)(void *, int64_t), void *data);
+//! void guillotine_complete(guillotine_completion *completion, int64_t result);
+//! ```
+//!
+//! Callbacks always run on the thread that called [`guillotine_runtime_run`], from inside the
+//! runtime, so they can spawn more work onto it. Completions are for work that happens somewhere
+//! else (another thread, another event loop, a device): the other side calls
+//! [`guillotine_complete`] from whatever thread it likes, and the runtime wakes up and calls
+//! `on_complete` with the result.
+//!
+//! The same thing, from Rust:
+//!
+//! ```
+//! use guillotine::ffi::*;
+//! use std::ffi::c_void;
+//! use std::sync::atomic::{AtomicI64, Ordering};
+//!
+//! static RESULT: AtomicI64 = AtomicI64::new(0);
+//!
+//! extern "C" fn on_complete(_data: *mut c_void, result: i64) {
+//!     RESULT.store(result, Ordering::SeqCst);
+//! }
+//!
+//! unsafe {
+//!     let runtime = guillotine_runtime_new();
+//!     let completion = guillotine_spawn_completion(runtime, on_complete, std::ptr::null_mut());
+//!
+//!     // Raw pointers aren't `Send`, so smuggle it over as a number.
+//!     let completion = completion as usize;
+//!     std::thread::spawn(move || guillotine_complete(completion as *mut _, 42));
+//!
+//!     assert_eq!(guillotine_runtime_run(runtime), 0);
+//!     guillotine_runtime_free(runtime);
+//! }
+//! assert_eq!(RESULT.load(Ordering::SeqCst), 42);
+//! ```
+//!
+//! Only available with the `ffi` feature. To link it into a C program, build a `staticlib` or
+//! `cdylib` crate that depends on guillotine with this feature turned on.
+
+use crate::runtime::{Handle, Runtime};
+use std::cell::Cell;
+use std::ffi::{c_int, c_void};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// A runtime, as far as C is concerned
+///
+/// The runtime itself is taken out to run it, but the handle stays behind, so that callbacks can
+/// keep spawning onto it while it runs.
+pub struct FfiRuntime {
+    /// The runtime, until it's run
+    runtime: Cell<Option<Runtime>>,
+    /// A handle to spawn things with
+    handle: Handle,
+}
+
+/// A callback for [`guillotine_spawn`]
+pub type Callback = extern "C" fn(data: *mut c_void);
+
+/// A callback for [`guillotine_spawn_completion`]
+pub type CompletionCallback = extern "C" fn(data: *mut c_void, result: i64);
+
+/// The result of some work happening outside of the runtime, and the task waiting for it
+///
+/// C gets a pointer to one of these from [`guillotine_spawn_completion`], and gives it back to
+/// [`guillotine_complete`].
+pub struct Completion {
+    /// The result, once there is one, and the task to wake up when there is
+    state: Mutex<(Option<i64>, Option<Waker>)>,
+}
+
+/// Waits for a [`Completion`] to be completed, as a _future_.
+struct WaitForCompletion {
+    completion: Arc<Completion>,
+}
+
+impl Future for WaitForCompletion {
+    type Output = i64;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self
+            .completion
+            .state
+            .lock()
+            .expect("Expected mutex to lock");
+        match state.0 {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Create a new runtime
+///
+/// Returns null if the runtime couldn't be created.
+#[no_mangle]
+pub extern "C" fn guillotine_runtime_new() -> *mut FfiRuntime {
+    match Runtime::new() {
+        Ok(runtime) => {
+            let handle = runtime.handle();
+            Box::into_raw(Box::new(FfiRuntime {
+                runtime: Cell::new(Some(runtime)),
+                handle,
+            }))
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Run the runtime until everything spawned onto it (including everything those things spawn)
+/// has finished
+///
+/// Returns 0 once everything has finished, or -1 if a task panicked, the runtime itself failed, or
+/// the runtime has already been run. Either way, the runtime still needs to be freed with
+/// [`guillotine_runtime_free`].
+///
+/// # Safety
+///
+/// `runtime` must have come from [`guillotine_runtime_new`], and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn guillotine_runtime_run(runtime: *mut FfiRuntime) -> c_int {
+    let Some(inner) = (*runtime).runtime.take() else {
+        return -1;
+    };
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| inner.try_block())) {
+        Ok(Ok(())) => 0,
+        Ok(Err(_)) | Err(_) => -1,
+    }
+}
+
+/// Shut down and free a runtime
+///
+/// Anything that's still spawned on the runtime is dropped without running; its callbacks are
+/// never called.
+///
+/// # Safety
+///
+/// `runtime` must have come from [`guillotine_runtime_new`], and not have been freed already. It
+/// can't be freed from inside one of its own callbacks.
+#[no_mangle]
+pub unsafe extern "C" fn guillotine_runtime_free(runtime: *mut FfiRuntime) {
+    drop(Box::from_raw(runtime));
+}
+
+/// Call `callback(data)` from inside the runtime, once it's running
+///
+/// # Safety
+///
+/// `runtime` must have come from [`guillotine_runtime_new`], and not have been freed. This has to
+/// be called from the thread that runs the runtime. `data` has to stay valid until the callback is
+/// called.
+#[no_mangle]
+pub unsafe extern "C" fn guillotine_spawn(
+    runtime: *mut FfiRuntime,
+    callback: Callback,
+    data: *mut c_void,
+) {
+    (*runtime)
+        .handle
+        .spawn(
+            async move { callback(data) },
+            Some("guillotine_spawn".to_string()),
+        )
+        .detach();
+}
+
+/// Wait, from inside the runtime, for some work to be completed outside of it
+///
+/// Returns a completion for whatever is doing the work to pass to [`guillotine_complete`]. The
+/// runtime doesn't finish running until it has been, at which point it calls
+/// `on_complete(data, result)`.
+///
+/// # Safety
+///
+/// `runtime` must have come from [`guillotine_runtime_new`], and not have been freed. This has to
+/// be called from the thread that runs the runtime. `data` has to stay valid until the callback is
+/// called.
+#[no_mangle]
+pub unsafe extern "C" fn guillotine_spawn_completion(
+    runtime: *mut FfiRuntime,
+    on_complete: CompletionCallback,
+    data: *mut c_void,
+) -> *const Completion {
+    let completion = Arc::new(Completion {
+        state: Mutex::new((None, None)),
+    });
+    let wait = WaitForCompletion {
+        completion: completion.clone(),
+    };
+    (*runtime)
+        .handle
+        .spawn(
+            async move { on_complete(data, wait.await) },
+            Some("guillotine_spawn_completion".to_string()),
+        )
+        .detach();
+    Arc::into_raw(completion)
+}
+
+/// Complete some work, waking up the runtime to call the completion's callback with `result`
+///
+/// This can be called from any thread.
+///
+/// # Safety
+///
+/// `completion` must have come from [`guillotine_spawn_completion`], and this can only be called
+/// once for each completion.
+#[no_mangle]
+pub unsafe extern "C" fn guillotine_complete(completion: *const Completion, result: i64) {
+    let completion = Arc::from_raw(completion);
+    let waker = {
+        let mut state = completion.state.lock().expect("Expected mutex to lock");
+        state.0 = Some(result);
+        state.1.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
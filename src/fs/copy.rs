@@ -0,0 +1,134 @@
+use super::asyncify;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::prelude::AsRawFd;
+use std::path::Path;
+use std::sync::Arc;
+
+/// How much each trip to a blocking thread copies, at most. This is also how often the progress
+/// callback gets called.
+const CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// The buffer size for the fallback copy
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Copy the contents (and permissions) of one file to another, as a _future_.
+///
+/// `to` is created if it doesn't exist and replaced if it does. Resolves to the number of bytes
+/// copied.
+///
+/// The copy uses `copy_file_range(2)` when it can, which keeps the data inside the kernel and lets
+/// filesystems that support it (btrfs, XFS, NFS, ...) share extents or copy on the server instead
+/// of actually moving bytes around. When `copy_file_range` isn't available (an old kernel, or
+/// files on different filesystems), it falls back to reading and writing through a buffer.
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let from = std::env::temp_dir().join("guillotine-copy-from.txt");
+///     let to = std::env::temp_dir().join("guillotine-copy-to.txt");
+///     guillotine::fs::write(&from, "copy me").await.unwrap();
+///
+///     assert_eq!(guillotine::fs::copy(&from, &to).await.unwrap(), 7);
+///     assert_eq!(guillotine::fs::read_to_string(&to).await.unwrap(), "copy me");
+///
+///     guillotine::fs::remove_file(&from).await.unwrap();
+///     guillotine::fs::remove_file(&to).await.unwrap();
+/// });
+/// ```
+pub async fn copy(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<u64, std::io::Error> {
+    copy_with_progress(from, to, |_| {}).await
+}
+
+/// Copy the contents (and permissions) of one file to another, reporting progress along the way,
+/// as a _future_.
+///
+/// This is [`copy`], except that `progress` is called with the total number of bytes copied so far
+/// every time another chunk (up to 8 MiB) is done. It's called from the task doing the copy, not
+/// from a blocking thread, so it doesn't need to be `Send`.
+pub async fn copy_with_progress(
+    from: impl AsRef<Path>,
+    to: impl AsRef<Path>,
+    mut progress: impl FnMut(u64),
+) -> Result<u64, std::io::Error> {
+    let from = from.as_ref().to_owned();
+    let to = to.as_ref().to_owned();
+    let (reader, writer) = asyncify(move || {
+        let reader = std::fs::File::open(from)?;
+        let permissions = reader.metadata()?.permissions();
+        let writer = std::fs::File::create(to)?;
+        writer.set_permissions(permissions)?;
+        Ok((reader, writer))
+    })
+    .await?;
+    let reader = Arc::new(reader);
+    let writer = Arc::new(writer);
+
+    let mut method = Method::CopyFileRange;
+    let mut copied = 0;
+    loop {
+        let (reader, writer) = (reader.clone(), writer.clone());
+        let (chunk, next_method) = asyncify(move || copy_chunk(&reader, &writer, method)).await?;
+        if chunk == 0 {
+            return Ok(copied);
+        }
+        method = next_method;
+        copied += chunk as u64;
+        progress(copied);
+    }
+}
+
+/// How the data is getting copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    CopyFileRange,
+    Buffered,
+}
+
+/// Copy up to `CHUNK_SIZE` bytes from the current position of `reader` to the current position of
+/// `writer`.
+///
+/// Returns how much was copied (zero at the end of the file), and the method to use next time.
+fn copy_chunk(
+    reader: &std::fs::File,
+    writer: &std::fs::File,
+    method: Method,
+) -> Result<(usize, Method), std::io::Error> {
+    if method == Method::CopyFileRange {
+        // Passing null offsets uses (and moves) the files' own positions, so falling back to
+        // read/write at any point carries on from the same place.
+        let result = unsafe {
+            libc::copy_file_range(
+                reader.as_raw_fd(),
+                std::ptr::null_mut(),
+                writer.as_raw_fd(),
+                std::ptr::null_mut(),
+                CHUNK_SIZE,
+                0,
+            )
+        };
+        if result >= 0 {
+            return Ok((result as usize, Method::CopyFileRange));
+        }
+
+        let err = std::io::Error::last_os_error();
+        match err.raw_os_error() {
+            // Not supported by this kernel or for these files: fall back
+            Some(libc::ENOSYS | libc::EXDEV | libc::EOPNOTSUPP | libc::EINVAL | libc::EPERM) => {}
+            _ => return Err(err),
+        }
+    }
+
+    let mut buf = vec![0; BUF_SIZE];
+    let mut copied = 0;
+    while copied < CHUNK_SIZE {
+        let read = match (&*reader).read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        (&*writer).write_all(&buf[..read])?;
+        copied += read;
+    }
+    Ok((copied, Method::Buffered))
+}
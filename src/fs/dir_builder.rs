@@ -0,0 +1,78 @@
+use super::asyncify;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+
+/// Options for creating directories, like [`std::fs::DirBuilder`]
+///
+/// ```
+/// use guillotine::fs::DirBuilder;
+/// use std::os::unix::fs::PermissionsExt;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let dir = guillotine::fs::tempdir().await.unwrap();
+///     let nested = dir.path().join("a/b/c");
+///
+///     DirBuilder::new().recursive(true).mode(0o700).create(&nested).await.unwrap();
+///
+///     let metadata = guillotine::fs::metadata(&nested).await.unwrap();
+///     assert_eq!(metadata.permissions().mode() & 0o777, 0o700);
+///     dir.close().await.unwrap();
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct DirBuilder {
+    recursive: bool,
+    mode: u32,
+}
+
+impl DirBuilder {
+    /// Create a new builder that creates a single directory, with mode `0o777` (before the umask)
+    pub fn new() -> Self {
+        Self {
+            recursive: false,
+            mode: 0o777,
+        }
+    }
+
+    /// Also create any missing parent directories, and don't fail if the directory already exists
+    pub fn recursive(&mut self, recursive: bool) -> &mut Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// The permissions new directories get (before the umask)
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Create the directory at `path`, as a _future_.
+    pub async fn create(&self, path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+        let path = path.as_ref().to_owned();
+        let mut std = std::fs::DirBuilder::new();
+        std.recursive(self.recursive).mode(self.mode);
+        asyncify(move || std.create(path)).await
+    }
+}
+
+impl Default for DirBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Create a directory, as a _future_.
+///
+/// Fails if the parent directory doesn't exist or the directory already does; see
+/// [`create_dir_all`] for the forgiving version.
+pub async fn create_dir(path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+    DirBuilder::new().create(path).await
+}
+
+/// Create a directory and any missing parent directories, as a _future_.
+///
+/// It's not an error if the directory already exists.
+pub async fn create_dir_all(path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+    DirBuilder::new().recursive(true).create(path).await
+}
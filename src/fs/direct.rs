@@ -0,0 +1,98 @@
+use crate::io::AlignedBuf;
+use std::alloc::Layout;
+use std::ops::{Deref, DerefMut};
+
+/// The alignment of buffers from [`AlignedBuffer::new`]
+///
+/// `O_DIRECT` needs buffers aligned to the logical block size of the device, which is 512 bytes
+/// or 4 KiB just about everywhere. Page alignment covers both.
+const DEFAULT_ALIGNMENT: usize = 4096;
+
+/// A fixed-size buffer with a particular alignment, for files opened with
+/// [`OpenOptions::direct`](super::OpenOptions::direct)
+///
+/// Like a [`PooledBuffer`](crate::io::PooledBuffer), it works like a `Vec<u8>` that can't grow: it
+/// has a fixed capacity and a length, and dereferences to the filled part. Unlike a pooled buffer,
+/// it can be sent to other threads, which is how [`File::read_at`](super::File::read_at) and
+/// [`File::write_at`](super::File::write_at) hand it to a blocking thread and back without
+/// copying it.
+#[derive(Debug)]
+pub struct AlignedBuffer {
+    buf: AlignedBuf,
+    len: usize,
+}
+
+impl AlignedBuffer {
+    /// Create a new, empty buffer of `capacity` bytes, aligned to 4 KiB
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_alignment(capacity, DEFAULT_ALIGNMENT)
+    }
+
+    /// Create a new, empty buffer of `capacity` bytes with the provided alignment
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alignment` isn't a power of two, or if `capacity` is zero.
+    pub fn with_alignment(capacity: usize, alignment: usize) -> Self {
+        assert!(capacity > 0, "aligned buffers can't be empty");
+        let layout =
+            Layout::from_size_align(capacity, alignment).expect("alignment must be a power of two");
+        Self {
+            buf: AlignedBuf::new(layout),
+            len: 0,
+        }
+    }
+
+    /// How many bytes the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.buf.layout().size()
+    }
+
+    /// What the buffer is aligned to
+    pub fn alignment(&self) -> usize {
+        self.buf.layout().align()
+    }
+
+    /// The part of the buffer after the filled part
+    pub fn spare_mut(&mut self) -> &mut [u8] {
+        let len = self.len;
+        &mut self.buf.as_mut_slice()[len..]
+    }
+
+    /// Set how much of the buffer is filled
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is more than the capacity.
+    pub fn set_len(&mut self, len: usize) {
+        assert!(
+            len <= self.capacity(),
+            "length is past the end of the buffer"
+        );
+        self.len = len;
+    }
+
+    /// Empty the buffer, keeping its capacity
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf.as_slice()[..self.len]
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let len = self.len;
+        &mut self.buf.as_mut_slice()[..len]
+    }
+}
@@ -0,0 +1,561 @@
+use super::direct::AlignedBuffer;
+use super::lock::{flock, FileLock};
+use super::{asyncify, pool};
+use crate::io::{AsyncRead, AsyncWrite};
+use crate::task::JoinHandle;
+use std::future::Future;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+/// The most that a single read or write hands to a blocking thread at once
+const MAX_BUF: usize = 64 * 1024;
+
+/// A file that can be read from and written to without blocking the runtime
+///
+/// Every operation runs on a thread from a pool shared by every file, so reads and writes go
+/// through an internal buffer that gets passed back and forth. Writes are "write-behind":
+/// `poll_write` copies the data into the buffer and returns right away, while a thread in the pool
+/// writes it out. That means errors from a write show up on the _next_ operation (with the errno
+/// and all), and that a file should be [flushed](crate::io::AsyncWriteExt::flush) before it's
+/// dropped to be sure that everything got written.
+///
+/// ```
+/// use guillotine::io::{AsyncReadExt, AsyncWriteExt};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let mut options = std::fs::OpenOptions::new();
+///     options.read(true).write(true);
+///     let mut full = guillotine::fs::File::open_with("/dev/full", options).await.unwrap();
+///
+///     // The write looks fine, until something else happens
+///     full.write(b"hello").await.unwrap();
+///     let mut buf = [1_u8; 4];
+///     full.read(&mut buf).await.unwrap();
+///     let err = full.flush().await.unwrap_err();
+///     assert_eq!(err.raw_os_error(), Some(libc::ENOSPC));
+/// });
+/// ```
+#[derive(Debug)]
+pub struct File {
+    std: Arc<std::fs::File>,
+    state: State,
+    /// An error from a write that finished after `poll_write` had already returned
+    last_write_err: Option<std::io::Error>,
+}
+
+/// What the file is up to
+#[derive(Debug)]
+enum State {
+    /// Nothing is happening. The buffer might hold data that has been read but not handed out
+    /// yet. (It's only `None` for a moment, while it's being moved to a blocking thread.)
+    Idle(Option<Buf>),
+    /// A blocking thread is working on something, and has the buffer
+    Busy(JoinHandle<(Operation, Buf)>),
+}
+
+/// The result of whatever the blocking thread was working on
+#[derive(Debug)]
+enum Operation {
+    Read(Result<usize, std::io::Error>),
+    Write(Result<(), std::io::Error>),
+    /// A seek, along with where it was asked to go
+    Seek(SeekFrom, Result<u64, std::io::Error>),
+}
+
+impl File {
+    /// Open a file for reading, as a _future_.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let path = path.as_ref().to_owned();
+        let std = asyncify(move || std::fs::File::open(path)).await?;
+        Ok(Self::from_std(std))
+    }
+
+    /// Create a file for writing (truncating it if it already exists), as a _future_.
+    pub async fn create(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let path = path.as_ref().to_owned();
+        let std = asyncify(move || std::fs::File::create(path)).await?;
+        Ok(Self::from_std(std))
+    }
+
+    /// Open a file with the provided options, as a _future_.
+    pub async fn open_with(
+        path: impl AsRef<Path>,
+        options: std::fs::OpenOptions,
+    ) -> Result<Self, std::io::Error> {
+        let path = path.as_ref().to_owned();
+        let std = asyncify(move || options.open(path)).await?;
+        Ok(Self::from_std(std))
+    }
+
+    /// Wrap a `std` file
+    pub fn from_std(std: std::fs::File) -> Self {
+        Self {
+            std: Arc::new(std),
+            state: State::Idle(Some(Buf::default())),
+            last_write_err: None,
+        }
+    }
+
+    /// Seek to a new position in the file, as a _future_.
+    ///
+    /// Returns the new position, from the start of the file. Any writes that are in progress
+    /// finish first.
+    pub async fn seek(&mut self, pos: SeekFrom) -> Result<u64, std::io::Error> {
+        std::future::poll_fn(|cx| self.poll_seek(cx, pos)).await
+    }
+
+    /// Seek to a new position in the file, as a poll function
+    ///
+    /// This is [`seek`](Self::seek) for implementing futures on top of the file. Keep calling it
+    /// with the same `pos` until it's ready.
+    pub fn poll_seek(
+        &mut self,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<Result<u64, std::io::Error>> {
+        loop {
+            match &mut self.state {
+                State::Idle(buf_cell) => {
+                    if let Some(err) = self.last_write_err.take() {
+                        return Poll::Ready(Err(err));
+                    }
+                    let mut buf = buf_cell.take().expect("idle files have a buffer");
+
+                    // Data that has been read into the buffer but not handed out is, as far as
+                    // the caller is concerned, still ahead of the current position.
+                    let requested = pos;
+                    let pos = match pos {
+                        SeekFrom::Current(offset) => SeekFrom::Current(offset - buf.discard_read()),
+                        pos => {
+                            buf.discard_read();
+                            pos
+                        }
+                    };
+
+                    self.start(buf, move |std, _| {
+                        Operation::Seek(requested, (&*std).seek(pos))
+                    })?;
+                }
+                State::Busy(handle) => {
+                    let (operation, buf) = ready!(Pin::new(handle).poll(cx))
+                        .expect("Expected blocking functions not to be cancelled");
+                    self.state = State::Idle(Some(buf));
+                    match operation {
+                        // Any writes that were in progress finish first
+                        Operation::Write(Err(err)) => return Poll::Ready(Err(err)),
+                        Operation::Seek(requested, result) if requested == pos => {
+                            return Poll::Ready(result)
+                        }
+                        // Something else (like a read, or a seek somebody gave up on) finished,
+                        // so now the seek can start.
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Flush everything written so far, and then make sure that it, and the file's metadata, have
+    /// made it to the disk, as a _future_.
+    ///
+    /// This is `fsync(2)`. Without it, data that has been written might only be sitting in the
+    /// kernel's page cache when the power goes out.
+    pub async fn sync_all(&mut self) -> Result<(), std::io::Error> {
+        self.run(|std| std.sync_all()).await
+    }
+
+    /// Like [`sync_all`](Self::sync_all), but without waiting for metadata that isn't needed to
+    /// read the data back (like the modification time), as a _future_.
+    ///
+    /// This is `fdatasync(2)`, and can save a disk write.
+    pub async fn sync_data(&mut self) -> Result<(), std::io::Error> {
+        self.run(|std| std.sync_data()).await
+    }
+
+    /// Truncate or extend the file to `size` bytes, as a _future_.
+    ///
+    /// Extending fills the new space with zeros. The file's position doesn't change, even if it's
+    /// now past the end of the file.
+    ///
+    /// ```
+    /// use guillotine::io::AsyncWriteExt;
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let path = std::env::temp_dir().join("guillotine-set-len.txt");
+    ///     let mut file = guillotine::fs::File::create(&path).await.unwrap();
+    ///     file.write_all(b"0123456789").await.unwrap();
+    ///     file.set_len(4).await.unwrap();
+    ///     file.sync_all().await.unwrap();
+    ///
+    ///     assert_eq!(file.metadata().await.unwrap().len(), 4);
+    ///     guillotine::fs::remove_file(&path).await.unwrap();
+    /// });
+    /// ```
+    pub async fn set_len(&mut self, size: u64) -> Result<(), std::io::Error> {
+        self.run(move |std| std.set_len(size)).await
+    }
+
+    /// Get the metadata of the file, as a _future_.
+    pub async fn metadata(&mut self) -> Result<std::fs::Metadata, std::io::Error> {
+        self.run(|std| std.metadata()).await
+    }
+
+    /// Read into the spare part of `buf` at `offset` in the file, as a _future_.
+    ///
+    /// This is `pread(2)`: it doesn't use or move the file position, and it doesn't go through the
+    /// internal buffer, so it works on files opened with [`direct`](super::OpenOptions::direct)
+    /// (as long as `offset` and the spare part of `buf` are aligned). The buffer is moved to a
+    /// blocking thread and handed back along with the result, filled up by however much was read.
+    ///
+    /// ```
+    /// use guillotine::fs::{AlignedBuffer, File};
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let path = std::env::temp_dir().join("guillotine-read-at.txt");
+    ///     guillotine::fs::write(&path, "0123456789").await.unwrap();
+    ///
+    ///     let file = File::open(&path).await.unwrap();
+    ///     let (result, buf) = file.read_at(AlignedBuffer::new(4096), 4).await;
+    ///     assert_eq!(result.unwrap(), 6);
+    ///     assert_eq!(&buf[..], b"456789");
+    ///
+    ///     guillotine::fs::remove_file(&path).await.unwrap();
+    /// });
+    /// ```
+    pub async fn read_at(
+        &self,
+        buf: AlignedBuffer,
+        offset: u64,
+    ) -> (Result<usize, std::io::Error>, AlignedBuffer) {
+        let std = self.std.clone();
+        let handle = pool::spawn(buf, move |mut buf| {
+            let result = std.read_at(buf.spare_mut(), offset);
+            if let Ok(read) = result {
+                buf.set_len(buf.len() + read);
+            }
+            (result, buf)
+        });
+        match handle {
+            Ok(handle) => handle
+                .await
+                .expect("Expected blocking functions not to be cancelled"),
+            Err((err, buf)) => (Err(err), buf),
+        }
+    }
+
+    /// Write the filled part of `buf` at `offset` in the file, as a _future_.
+    ///
+    /// This is `pwrite(2)`, the writing half of [`read_at`](Self::read_at). It skips the internal
+    /// buffer, so it doesn't wait for (or get ordered with) writes made through
+    /// [`AsyncWrite`](crate::io::AsyncWrite). The buffer is handed back along with the number of
+    /// bytes written, which (like `pwrite`) might be less than all of them.
+    pub async fn write_at(
+        &self,
+        buf: AlignedBuffer,
+        offset: u64,
+    ) -> (Result<usize, std::io::Error>, AlignedBuffer) {
+        let std = self.std.clone();
+        match pool::spawn(buf, move |buf| (std.write_at(&buf, offset), buf)) {
+            Ok(handle) => handle
+                .await
+                .expect("Expected blocking functions not to be cancelled"),
+            Err((err, buf)) => (Err(err), buf),
+        }
+    }
+
+    /// Take an exclusive advisory lock on the file, waiting until nobody else holds a lock on it,
+    /// as a _future_.
+    ///
+    /// The wait happens on a blocking thread. If this future is dropped before it finishes, that
+    /// thread might still end up taking the lock, which then stays held until the file is closed.
+    ///
+    /// ```
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let path = std::env::temp_dir().join("guillotine-lock");
+    ///     let file = guillotine::fs::File::create(&path).await.unwrap();
+    ///     let other = guillotine::fs::File::open(&path).await.unwrap();
+    ///
+    ///     let lock = file.lock_exclusive().await.unwrap();
+    ///     assert!(other.try_lock_shared().unwrap().is_none());
+    ///     drop(lock);
+    ///     assert!(other.try_lock_shared().unwrap().is_some());
+    ///
+    ///     guillotine::fs::remove_file(&path).await.unwrap();
+    /// });
+    /// ```
+    pub async fn lock_exclusive(&self) -> Result<FileLock, std::io::Error> {
+        self.lock(libc::LOCK_EX).await
+    }
+
+    /// Take a shared advisory lock on the file, waiting until nobody holds an exclusive lock on
+    /// it, as a _future_.
+    ///
+    /// See [`lock_exclusive`](Self::lock_exclusive).
+    pub async fn lock_shared(&self) -> Result<FileLock, std::io::Error> {
+        self.lock(libc::LOCK_SH).await
+    }
+
+    /// Take an exclusive advisory lock on the file if nobody else holds a lock on it
+    ///
+    /// Returns `None` instead of waiting if the file is already locked.
+    pub fn try_lock_exclusive(&self) -> Result<Option<FileLock>, std::io::Error> {
+        self.try_lock(libc::LOCK_EX)
+    }
+
+    /// Take a shared advisory lock on the file if nobody holds an exclusive lock on it
+    ///
+    /// Returns `None` instead of waiting if the file is already locked exclusively.
+    pub fn try_lock_shared(&self) -> Result<Option<FileLock>, std::io::Error> {
+        self.try_lock(libc::LOCK_SH)
+    }
+
+    async fn lock(&self, operation: libc::c_int) -> Result<FileLock, std::io::Error> {
+        let std = self.std.clone();
+        asyncify(move || flock(&std, operation)).await?;
+        Ok(FileLock::new(self.std.clone(), operation == libc::LOCK_EX))
+    }
+
+    fn try_lock(&self, operation: libc::c_int) -> Result<Option<FileLock>, std::io::Error> {
+        match flock(&self.std, operation | libc::LOCK_NB) {
+            Ok(()) => Ok(Some(FileLock::new(
+                self.std.clone(),
+                operation == libc::LOCK_EX,
+            ))),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Wait for the file to be idle, and then run `op` on a blocking thread.
+    ///
+    /// Any data that was read into the buffer but not handed out is thrown away first (moving the
+    /// file position back to where the caller thinks it is), since `op` might change the file out
+    /// from under it.
+    async fn run<T, F>(&mut self, op: F) -> Result<T, std::io::Error>
+    where
+        F: FnOnce(&std::fs::File) -> Result<T, std::io::Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        std::future::poll_fn(|cx| self.poll_complete(cx)).await?;
+
+        let State::Idle(Some(buf)) = &mut self.state else {
+            unreachable!("poll_complete leaves the file idle");
+        };
+        let unread = buf.discard_read();
+        let std = self.std.clone();
+        asyncify(move || {
+            if unread != 0 {
+                (&*std).seek(SeekFrom::Current(unread))?;
+            }
+            op(&std)
+        })
+        .await
+    }
+
+    /// Wait for whatever the blocking thread is working on to finish, and for any write errors to
+    /// be reported
+    fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        if let Some(err) = self.last_write_err.take() {
+            return Poll::Ready(Err(err));
+        }
+
+        let handle = match &mut self.state {
+            State::Idle(_) => return Poll::Ready(Ok(())),
+            State::Busy(handle) => handle,
+        };
+        let (operation, buf) = ready!(Pin::new(handle).poll(cx))
+            .expect("Expected blocking functions not to be cancelled");
+        self.state = State::Idle(Some(buf));
+        match operation {
+            Operation::Write(Err(err)) => Poll::Ready(Err(err)),
+            // A read that nobody is waiting for anymore just leaves its data in the buffer
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+
+    /// Hand the buffer to a thread in the pool to run `op` with, leaving the file busy until it's
+    /// done
+    ///
+    /// If there's no thread to run it, the file is left idle, with the buffer back.
+    fn start<F>(&mut self, buf: Buf, op: F) -> Result<(), std::io::Error>
+    where
+        F: FnOnce(&std::fs::File, &mut Buf) -> Operation + Send + 'static,
+    {
+        let std = self.std.clone();
+        match pool::spawn(buf, move |mut buf| (op(&std, &mut buf), buf)) {
+            Ok(handle) => {
+                self.state = State::Busy(handle);
+                Ok(())
+            }
+            Err((err, buf)) => {
+                self.state = State::Idle(Some(buf));
+                Err(err)
+            }
+        }
+    }
+
+    /// Take the buffer out of an idle file
+    fn take_buf(&mut self) -> Buf {
+        match &mut self.state {
+            State::Idle(buf) => buf.take().expect("idle files have a buffer"),
+            State::Busy(_) => unreachable!("the file is busy"),
+        }
+    }
+}
+
+impl AsyncRead for File {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        dst: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                State::Idle(buf_cell) => {
+                    let buf = buf_cell.as_mut().expect("idle files have a buffer");
+                    // Hand out what's already been read, if there's anything
+                    if !buf.is_empty() {
+                        return Poll::Ready(Ok(buf.copy_to(dst)));
+                    }
+
+                    let buf = this.take_buf();
+                    let len = dst.len().min(MAX_BUF);
+                    this.start(buf, move |std, buf| {
+                        Operation::Read(buf.read_from(&mut &*std, len))
+                    })?;
+                }
+                State::Busy(handle) => {
+                    let (operation, mut buf) = ready!(Pin::new(handle).poll(cx))
+                        .expect("Expected blocking functions not to be cancelled");
+                    match operation {
+                        Operation::Read(Ok(_)) => {
+                            let read = buf.copy_to(dst);
+                            this.state = State::Idle(Some(buf));
+                            return Poll::Ready(Ok(read));
+                        }
+                        Operation::Read(Err(err)) => {
+                            this.state = State::Idle(Some(buf));
+                            return Poll::Ready(Err(err));
+                        }
+                        Operation::Write(Err(err)) => {
+                            // The write finished after `poll_write` returned; hold on to its
+                            // error for the next write or flush, and carry on reading.
+                            this.last_write_err = Some(err);
+                            this.state = State::Idle(Some(buf));
+                        }
+                        Operation::Write(Ok(())) | Operation::Seek(..) => {
+                            this.state = State::Idle(Some(buf));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl AsyncWrite for File {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        src: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_complete(cx))?;
+
+        let mut buf = this.take_buf();
+        // If there's read data that hasn't been handed out, the file's real position is ahead of
+        // where the caller thinks it is. Back up before writing.
+        let unread = buf.discard_read();
+        let written = buf.copy_from(src);
+        this.start(buf, move |std, buf| {
+            let result = if unread != 0 {
+                (&*std).seek(SeekFrom::Current(unread)).map(|_| ())
+            } else {
+                Ok(())
+            }
+            .and_then(|()| buf.write_to(&mut &*std));
+            Operation::Write(result)
+        })?;
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        self.get_mut().poll_complete(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // The file itself gets closed when it's dropped
+        self.get_mut().poll_complete(cx)
+    }
+}
+
+/// The buffer that gets passed back and forth between the file and the blocking threads
+#[derive(Debug, Default)]
+struct Buf {
+    buf: Vec<u8>,
+    /// Where the data that hasn't been handed out (or written) yet starts
+    pos: usize,
+}
+
+impl Buf {
+    fn is_empty(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    /// Copy as much as will fit into `dst`
+    fn copy_to(&mut self, dst: &mut [u8]) -> usize {
+        let len = (self.buf.len() - self.pos).min(dst.len());
+        dst[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        len
+    }
+
+    /// Replace the contents with (up to `MAX_BUF` of) `src`
+    fn copy_from(&mut self, src: &[u8]) -> usize {
+        let len = src.len().min(MAX_BUF);
+        self.buf.clear();
+        self.buf.extend_from_slice(&src[..len]);
+        self.pos = 0;
+        len
+    }
+
+    /// Throw away any unread data, and return how far back the file position needs to go to
+    /// undo reading it (zero or negative)
+    fn discard_read(&mut self) -> i64 {
+        let unread = -((self.buf.len() - self.pos) as i64);
+        self.buf.clear();
+        self.pos = 0;
+        unread
+    }
+
+    /// Replace the contents with up to `len` bytes read from `reader`
+    fn read_from(&mut self, reader: &mut impl Read, len: usize) -> Result<usize, std::io::Error> {
+        self.buf.resize(len, 0);
+        self.pos = 0;
+        let result = loop {
+            match reader.read(&mut self.buf) {
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        self.buf.truncate(*result.as_ref().unwrap_or(&0));
+        result
+    }
+
+    /// Write out the whole contents
+    fn write_to(&mut self, writer: &mut impl Write) -> Result<(), std::io::Error> {
+        let result = writer.write_all(&self.buf[self.pos..]);
+        self.buf.clear();
+        self.pos = 0;
+        result
+    }
+}
@@ -0,0 +1,50 @@
+use std::os::unix::prelude::AsRawFd;
+use std::sync::Arc;
+
+/// An advisory lock on a [`File`](super::File), from [`lock_exclusive`](super::File::lock_exclusive),
+/// [`lock_shared`](super::File::lock_shared), or one of their `try_` versions
+///
+/// The lock is released when this is dropped.
+///
+/// These are `flock(2)` locks, so they belong to the open file (not to the process): two `File`s
+/// that opened the same path separately will block each other, even in the same process. And like
+/// all advisory locks, they only keep out other code that asks for a lock too.
+#[derive(Debug)]
+#[must_use = "the lock is released as soon as the FileLock is dropped"]
+pub struct FileLock {
+    std: Arc<std::fs::File>,
+    exclusive: bool,
+}
+
+impl FileLock {
+    pub(super) fn new(std: Arc<std::fs::File>, exclusive: bool) -> Self {
+        Self { std, exclusive }
+    }
+
+    /// Whether this is an exclusive lock (as opposed to a shared one)
+    pub fn is_exclusive(&self) -> bool {
+        self.exclusive
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Unlocking never blocks, so there's no need for a blocking thread here
+        if let Err(err) = flock(&self.std, libc::LOCK_UN) {
+            crate::trace::warn!(?err, "Failed to unlock file");
+        }
+    }
+}
+
+/// `flock(2)`, retrying if it gets interrupted
+pub(super) fn flock(file: &std::fs::File, operation: libc::c_int) -> Result<(), std::io::Error> {
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
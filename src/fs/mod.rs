@@ -0,0 +1,198 @@
+//! Files, without stalling the runtime
+//!
+//! Regular files are always "ready" as far as `epoll` is concerned, so there's no waiting for them
+//! the way there is for sockets; a read from a slow disk just blocks. Blocking the runtime's only
+//! thread blocks _every_ future, so instead, each file operation here is handed off to a pool of
+//! threads (shared by every runtime in the process), and the future waits for it to finish.
+//!
+//! ```
+//! use guillotine::fs::File;
+//! use guillotine::io::{AsyncReadExt, AsyncWriteExt};
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let path = std::env::temp_dir().join("guillotine-fs-example.txt");
+//!
+//!     let mut file = File::create(&path).await.unwrap();
+//!     file.write_all(b"hello, file").await.unwrap();
+//!     file.flush().await.unwrap();
+//!
+//!     let mut file = File::open(&path).await.unwrap();
+//!     let mut contents = String::new();
+//!     file.read_to_string(&mut contents).await.unwrap();
+//!     assert_eq!(contents, "hello, file");
+//!
+//!     std::fs::remove_file(&path).unwrap();
+//! });
+//! ```
+
+mod copy;
+mod dir_builder;
+mod direct;
+mod file;
+mod lock;
+mod open_options;
+mod pool;
+mod read_dir;
+mod statx;
+mod temp;
+mod watch;
+
+pub use copy::{copy, copy_with_progress};
+pub use dir_builder::{create_dir, create_dir_all, DirBuilder};
+pub use direct::AlignedBuffer;
+pub use file::File;
+pub use lock::FileLock;
+pub use open_options::OpenOptions;
+pub use read_dir::{read_dir, DirEntry, ReadDir};
+pub use statx::{statx, symlink_statx, Statx, StatxAttributes, StatxMask};
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+pub use temp::{tempdir, tempdir_in, NamedTempFile, TempDir};
+pub use watch::{watch, watch_recursive, Event, EventKind, Watcher};
+
+/// Read the entire contents of a file, as a _future_.
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let path = std::env::temp_dir().join("guillotine-fs-read.txt");
+///     guillotine::fs::write(&path, "contents").await.unwrap();
+///
+///     assert_eq!(guillotine::fs::read(&path).await.unwrap(), b"contents");
+///     assert_eq!(guillotine::fs::read_to_string(&path).await.unwrap(), "contents");
+///
+///     std::fs::remove_file(&path).unwrap();
+/// });
+/// ```
+pub async fn read(path: impl AsRef<Path>) -> Result<Vec<u8>, std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::read(path)).await
+}
+
+/// Read the entire contents of a file into a string, as a _future_.
+///
+/// Fails with [`InvalidData`](std::io::ErrorKind::InvalidData) if the file isn't UTF-8.
+pub async fn read_to_string(path: impl AsRef<Path>) -> Result<String, std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::read_to_string(path)).await
+}
+
+/// Write `contents` to a file, creating it if it doesn't exist and replacing it if it does, as a
+/// _future_.
+pub async fn write(
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+) -> Result<(), std::io::Error> {
+    let path = path.as_ref().to_owned();
+    // The blocking thread needs its own copy of the contents
+    let contents = contents.as_ref().to_owned();
+    asyncify(move || std::fs::write(path, contents)).await
+}
+
+/// Get the metadata of a file or directory, following symbolic links, as a _future_.
+pub async fn metadata(path: impl AsRef<Path>) -> Result<Metadata, std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::metadata(path)).await
+}
+
+/// Get the metadata of a file or directory, _without_ following symbolic links, as a _future_.
+pub async fn symlink_metadata(path: impl AsRef<Path>) -> Result<Metadata, std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::symlink_metadata(path)).await
+}
+
+/// Rename a file or directory, replacing `to` if it already exists, as a _future_.
+///
+/// Like `rename(2)`, this only works within a single filesystem.
+pub async fn rename(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<(), std::io::Error> {
+    let from = from.as_ref().to_owned();
+    let to = to.as_ref().to_owned();
+    asyncify(move || std::fs::rename(from, to)).await
+}
+
+/// Remove a file, as a _future_.
+pub async fn remove_file(path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::remove_file(path)).await
+}
+
+/// Remove an empty directory, as a _future_.
+pub async fn remove_dir(path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::remove_dir(path)).await
+}
+
+/// Remove a directory and everything in it, as a _future_.
+///
+/// The whole removal happens on a single blocking thread, so a huge tree doesn't hold up the
+/// runtime, but it can't be cancelled partway through either.
+pub async fn remove_dir_all(path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::remove_dir_all(path)).await
+}
+
+/// Create a hard link at `link` pointing to the same file as `original`, as a _future_.
+pub async fn hard_link(
+    original: impl AsRef<Path>,
+    link: impl AsRef<Path>,
+) -> Result<(), std::io::Error> {
+    let original = original.as_ref().to_owned();
+    let link = link.as_ref().to_owned();
+    asyncify(move || std::fs::hard_link(original, link)).await
+}
+
+/// Create a symbolic link at `link` pointing to `original`, as a _future_.
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let dir = std::env::temp_dir().join("guillotine-symlink");
+///     guillotine::fs::remove_dir_all(&dir).await.ok();
+///     std::fs::create_dir(&dir).unwrap();
+///     guillotine::fs::write(dir.join("original"), "hi").await.unwrap();
+///
+///     guillotine::fs::symlink("original", dir.join("link")).await.unwrap();
+///     let metadata = guillotine::fs::symlink_metadata(dir.join("link")).await.unwrap();
+///     assert!(metadata.file_type().is_symlink());
+///     assert_eq!(
+///         guillotine::fs::canonicalize(dir.join("link")).await.unwrap(),
+///         guillotine::fs::canonicalize(dir.join("original")).await.unwrap(),
+///     );
+///
+///     guillotine::fs::remove_dir_all(&dir).await.unwrap();
+/// });
+/// ```
+pub async fn symlink(
+    original: impl AsRef<Path>,
+    link: impl AsRef<Path>,
+) -> Result<(), std::io::Error> {
+    let original = original.as_ref().to_owned();
+    let link = link.as_ref().to_owned();
+    asyncify(move || std::os::unix::fs::symlink(original, link)).await
+}
+
+/// Read where a symbolic link points, as a _future_.
+pub async fn read_link(path: impl AsRef<Path>) -> Result<PathBuf, std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::read_link(path)).await
+}
+
+/// Get the absolute path of a file or directory, with every symbolic link resolved, as a
+/// _future_.
+pub async fn canonicalize(path: impl AsRef<Path>) -> Result<PathBuf, std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::canonicalize(path)).await
+}
+
+/// Run a blocking filesystem operation on the filesystem thread pool, as a _future_.
+async fn asyncify<F, T>(f: F) -> Result<T, std::io::Error>
+where
+    F: FnOnce() -> Result<T, std::io::Error> + Send + 'static,
+    T: Send + 'static,
+{
+    pool::spawn((), |()| f())
+        .map_err(|(err, ())| err)?
+        .await
+        .expect("Expected blocking functions not to be cancelled")
+}
@@ -0,0 +1,119 @@
+use super::File;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// Options for opening a [`File`], like [`std::fs::OpenOptions`]
+///
+/// ```
+/// use guillotine::fs::OpenOptions;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let path = std::env::temp_dir().join("guillotine-open-options.txt");
+///     let file = OpenOptions::new()
+///         .write(true)
+///         .create(true)
+///         .truncate(true)
+///         .open(&path)
+///         .await
+///         .unwrap();
+///     # drop(file);
+///     # guillotine::fs::remove_file(&path).await.unwrap();
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    std: std::fs::OpenOptions,
+    custom_flags: i32,
+    direct: bool,
+}
+
+impl OpenOptions {
+    /// Create a new set of options, with everything turned off
+    pub fn new() -> Self {
+        Self {
+            std: std::fs::OpenOptions::new(),
+            custom_flags: 0,
+            direct: false,
+        }
+    }
+
+    /// Open for reading
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.std.read(read);
+        self
+    }
+
+    /// Open for writing
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.std.write(write);
+        self
+    }
+
+    /// Open for appending: every write goes to the end of the file
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.std.append(append);
+        self
+    }
+
+    /// Truncate the file to nothing when it's opened
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.std.truncate(truncate);
+        self
+    }
+
+    /// Create the file if it doesn't exist
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.std.create(create);
+        self
+    }
+
+    /// Create the file, failing if it already exists
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.std.create_new(create_new);
+        self
+    }
+
+    /// The permissions a newly created file gets (before the umask)
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.std.mode(mode);
+        self
+    }
+
+    /// Extra `open(2)` flags
+    pub fn custom_flags(&mut self, flags: i32) -> &mut Self {
+        self.custom_flags = flags;
+        self
+    }
+
+    /// Open with `O_DIRECT`, which bypasses the page cache
+    ///
+    /// Reads and writes on a direct file go straight between the device and the buffer, which is
+    /// what databases that do their own caching want. The catch is that the buffer, the file
+    /// position, and the length all have to be aligned to the device's logical block size, or the
+    /// kernel returns `EINVAL`. The regular [`AsyncRead`](crate::io::AsyncRead) and
+    /// [`AsyncWrite`](crate::io::AsyncWrite) implementations don't know anything about that, so use
+    /// [`File::read_at`] and [`File::write_at`] with an [`AlignedBuffer`](super::AlignedBuffer)
+    /// instead.
+    ///
+    /// Not every filesystem supports `O_DIRECT` (tmpfs doesn't); opening fails with `EINVAL` on
+    /// those.
+    pub fn direct(&mut self, direct: bool) -> &mut Self {
+        self.direct = direct;
+        self
+    }
+
+    /// Open the file at `path` with these options, as a _future_.
+    pub async fn open(&self, path: impl AsRef<Path>) -> Result<File, std::io::Error> {
+        let mut std = self.std.clone();
+        let direct = if self.direct { libc::O_DIRECT } else { 0 };
+        std.custom_flags(self.custom_flags | direct);
+        File::open_with(path, std).await
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
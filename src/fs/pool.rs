@@ -0,0 +1,108 @@
+//! The threads that filesystem operations run on
+//!
+//! A file operation is usually quick, and a program that's busy with files does a lot of them, so
+//! starting a new thread for each one (like [`spawn_blocking`](crate::task::spawn_blocking) does)
+//! would spend more time starting threads than doing anything. Instead, they share a pool of
+//! threads, which is shared by every runtime in the process. The pool starts threads as they're
+//! needed, up to [`MAX_THREADS`]; past that, operations wait in line. A thread that's had nothing
+//! to do for a while stops.
+
+use crate::task::JoinHandle;
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// The most threads the pool runs at once
+const MAX_THREADS: usize = 64;
+
+/// How long a thread waits for something to do before it stops
+const KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+static POOL: Pool = Pool {
+    state: Mutex::new(PoolState {
+        queue: VecDeque::new(),
+        threads: 0,
+        idle: 0,
+    }),
+    condvar: Condvar::new(),
+};
+
+struct Pool {
+    state: Mutex<PoolState>,
+    /// Wakes an idle thread when there's something for it to do
+    condvar: Condvar,
+}
+
+struct PoolState {
+    /// Operations waiting for a thread
+    queue: VecDeque<async_task::Runnable>,
+    /// How many threads are running, busy or not
+    threads: usize,
+    /// How many of those threads are waiting for something to do
+    idle: usize,
+}
+
+/// Run a blocking filesystem operation on the pool, handing it `arg`
+///
+/// If the function panics, the handle resolves to
+/// [`JoinError::Panic`](crate::task::JoinError::Panic). It's only an error if there are no
+/// threads in the pool and a new one can't be started, in which case `arg` is handed back.
+pub(super) fn spawn<A, F, T>(arg: A, f: F) -> Result<JoinHandle<T>, (std::io::Error, A)>
+where
+    A: Send + 'static,
+    F: FnOnce(A) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let mut state = POOL.state.lock().expect("Expected mutex to lock");
+
+    // An idle thread will pick it up, if there's one that isn't already spoken for. Otherwise,
+    // start another thread, unless there are already plenty.
+    if state.queue.len() >= state.idle && state.threads < MAX_THREADS {
+        match std::thread::Builder::new()
+            .name("guillotine-fs".to_string())
+            .spawn(|| POOL.work())
+        {
+            Ok(_) => state.threads += 1,
+            // Another thread will get to it eventually
+            Err(_) if state.threads > 0 => {}
+            Err(err) => return Err((err, arg)),
+        }
+    }
+
+    // Like `spawn_blocking`, the function is a task that finishes the first time it's run, so it's
+    // never woken and never needs scheduling again.
+    let future = crate::runtime::CatchPanic::new(async move { f(arg) });
+    let (runnable, task) = async_task::spawn(future, |_| {
+        unreachable!("blocking tasks never wait, so they're never woken")
+    });
+    state.queue.push_back(runnable);
+    POOL.condvar.notify_one();
+    Ok(JoinHandle::new(task, None))
+}
+
+impl Pool {
+    /// Run operations until there haven't been any for a while
+    fn work(&self) {
+        let mut state = self.state.lock().expect("Expected mutex to lock");
+        loop {
+            if let Some(runnable) = state.queue.pop_front() {
+                drop(state);
+                runnable.run();
+                state = self.state.lock().expect("Expected mutex to lock");
+                continue;
+            }
+
+            state.idle += 1;
+            let (next, timeout) = self
+                .condvar
+                .wait_timeout(state, KEEP_ALIVE)
+                .expect("Expected mutex to lock");
+            state = next;
+            state.idle -= 1;
+            if timeout.timed_out() && state.queue.is_empty() {
+                state.threads -= 1;
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use super::asyncify;
+use crate::task::{spawn_blocking, JoinHandle};
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fs::{FileType, Metadata};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+/// How many entries a blocking thread reads at once
+const BATCH_SIZE: usize = 32;
+
+/// Read the entries of a directory, as a _future_.
+///
+/// The entries come back from [`ReadDir`] one at a time, either through
+/// [`next_entry`](ReadDir::next_entry) or as a [`Stream`](futures_core::Stream). Behind the
+/// scenes, they're read in batches on a blocking thread.
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let dir = std::env::temp_dir().join("guillotine-read-dir");
+///     std::fs::create_dir_all(&dir).unwrap();
+///     guillotine::fs::write(dir.join("a.txt"), "a").await.unwrap();
+///
+///     let mut entries = guillotine::fs::read_dir(&dir).await.unwrap();
+///     let mut names = Vec::new();
+///     while let Some(entry) = entries.next_entry().await.unwrap() {
+///         assert!(entry.metadata().await.unwrap().is_file());
+///         names.push(entry.file_name());
+///     }
+///     assert_eq!(names, ["a.txt"]);
+///
+///     std::fs::remove_dir_all(&dir).unwrap();
+/// });
+/// ```
+pub async fn read_dir(path: impl AsRef<Path>) -> Result<ReadDir, std::io::Error> {
+    let path = path.as_ref().to_owned();
+    let std = asyncify(move || std::fs::read_dir(path)).await?;
+    Ok(ReadDir(State::Idle(Some(Batch {
+        entries: VecDeque::new(),
+        std,
+        done: false,
+    }))))
+}
+
+/// The entries of a directory, from [`read_dir`]
+#[derive(Debug)]
+pub struct ReadDir(State);
+
+#[derive(Debug)]
+enum State {
+    /// Waiting to be asked for an entry. (It's only `None` for a moment, while the batch is being
+    /// moved to a blocking thread.)
+    Idle(Option<Batch>),
+    /// A blocking thread is reading the next batch
+    Busy(JoinHandle<Batch>),
+}
+
+/// Entries that have been read but not handed out, and what they were read from
+#[derive(Debug)]
+struct Batch {
+    entries: VecDeque<Result<std::fs::DirEntry, std::io::Error>>,
+    std: std::fs::ReadDir,
+    /// Whether `std` has run out of entries
+    done: bool,
+}
+
+impl ReadDir {
+    /// Get the next entry in the directory, as a _future_.
+    ///
+    /// Resolves to `None` once every entry has been returned.
+    pub async fn next_entry(&mut self) -> Result<Option<DirEntry>, std::io::Error> {
+        std::future::poll_fn(|cx| self.poll_next_entry(cx)).await
+    }
+
+    /// Poll for the next entry in the directory
+    pub fn poll_next_entry(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<DirEntry>, std::io::Error>> {
+        loop {
+            match &mut self.0 {
+                State::Idle(batch) => {
+                    let inner = batch.as_mut().expect("idle ReadDirs have a batch");
+                    if let Some(entry) = inner.entries.pop_front() {
+                        return Poll::Ready(entry.map(|entry| Some(DirEntry(Arc::new(entry)))));
+                    }
+                    if inner.done {
+                        return Poll::Ready(Ok(None));
+                    }
+
+                    let mut batch = batch.take().expect("idle ReadDirs have a batch");
+                    self.0 = State::Busy(spawn_blocking(move || {
+                        batch.entries.extend((&mut batch.std).take(BATCH_SIZE));
+                        batch.done = batch.entries.len() < BATCH_SIZE;
+                        batch
+                    }));
+                }
+                State::Busy(handle) => {
+                    let batch = ready!(Pin::new(handle).poll(cx))
+                        .expect("Expected blocking functions not to be cancelled");
+                    self.0 = State::Idle(Some(batch));
+                }
+            }
+        }
+    }
+}
+
+impl futures_core::Stream for ReadDir {
+    type Item = Result<DirEntry, std::io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_next_entry(cx).map(Result::transpose)
+    }
+}
+
+/// An entry in a directory, from [`ReadDir`]
+#[derive(Debug, Clone)]
+pub struct DirEntry(Arc<std::fs::DirEntry>);
+
+impl DirEntry {
+    /// The full path to the entry: the directory passed to [`read_dir`] joined with the entry's
+    /// file name
+    pub fn path(&self) -> PathBuf {
+        self.0.path()
+    }
+
+    /// The file name of the entry, without the rest of the path
+    pub fn file_name(&self) -> OsString {
+        self.0.file_name()
+    }
+
+    /// Get the metadata of the entry, as a _future_.
+    ///
+    /// Like [`std::fs::DirEntry::metadata`], this doesn't follow symbolic links.
+    pub async fn metadata(&self) -> Result<Metadata, std::io::Error> {
+        let entry = self.0.clone();
+        asyncify(move || entry.metadata()).await
+    }
+
+    /// Get the file type of the entry, as a _future_.
+    ///
+    /// On most filesystems this comes along with the directory listing, but some need an extra
+    /// system call to find out.
+    pub async fn file_type(&self) -> Result<FileType, std::io::Error> {
+        let entry = self.0.clone();
+        asyncify(move || entry.file_type()).await
+    }
+}
@@ -0,0 +1,313 @@
+use super::asyncify;
+use std::ffi::CString;
+use std::mem::MaybeUninit;
+use std::ops::BitOr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Which fields [`statx`] should fill in
+///
+/// Some fields cost the filesystem extra work (or a network round trip) to find out, so `statx`
+/// only asks for what it's told to. Masks combine with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatxMask(u32);
+
+impl StatxMask {
+    /// The file type
+    pub const TYPE: Self = Self(libc::STATX_TYPE);
+    /// The permission bits
+    pub const MODE: Self = Self(libc::STATX_MODE);
+    /// The number of hard links
+    pub const NLINK: Self = Self(libc::STATX_NLINK);
+    /// The owner
+    pub const UID: Self = Self(libc::STATX_UID);
+    /// The group
+    pub const GID: Self = Self(libc::STATX_GID);
+    /// The last access time
+    pub const ATIME: Self = Self(libc::STATX_ATIME);
+    /// The last modification time
+    pub const MTIME: Self = Self(libc::STATX_MTIME);
+    /// The last status change time
+    pub const CTIME: Self = Self(libc::STATX_CTIME);
+    /// The inode number
+    pub const INO: Self = Self(libc::STATX_INO);
+    /// The size
+    pub const SIZE: Self = Self(libc::STATX_SIZE);
+    /// The number of blocks allocated
+    pub const BLOCKS: Self = Self(libc::STATX_BLOCKS);
+    /// Everything `stat(2)` returns
+    pub const BASIC_STATS: Self = Self(libc::STATX_BASIC_STATS);
+    /// The creation ("birth") time
+    pub const BTIME: Self = Self(libc::STATX_BTIME);
+    /// The ID of the mount the file is on
+    pub const MNT_ID: Self = Self(libc::STATX_MNT_ID);
+    /// Everything above
+    pub const ALL: Self = Self(libc::STATX_BASIC_STATS | libc::STATX_BTIME | libc::STATX_MNT_ID);
+
+    /// Whether every field in `other` is also in this mask
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The raw `STATX_*` bits
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for StatxMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Flags about a file, from [`Statx::attributes`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatxAttributes(u64);
+
+impl StatxAttributes {
+    /// The file is compressed by the filesystem
+    pub fn is_compressed(self) -> bool {
+        self.has(libc::STATX_ATTR_COMPRESSED)
+    }
+
+    /// The file can't be changed, deleted, renamed, or linked to (`chattr +i`)
+    pub fn is_immutable(self) -> bool {
+        self.has(libc::STATX_ATTR_IMMUTABLE)
+    }
+
+    /// The file can only be opened for appending (`chattr +a`)
+    pub fn is_append_only(self) -> bool {
+        self.has(libc::STATX_ATTR_APPEND)
+    }
+
+    /// The file is skipped by `dump(8)`-style backups (`chattr +d`)
+    pub fn is_nodump(self) -> bool {
+        self.has(libc::STATX_ATTR_NODUMP)
+    }
+
+    /// The file is encrypted by the filesystem
+    pub fn is_encrypted(self) -> bool {
+        self.has(libc::STATX_ATTR_ENCRYPTED)
+    }
+
+    /// The directory is an automount trigger
+    pub fn is_automount(self) -> bool {
+        self.has(libc::STATX_ATTR_AUTOMOUNT)
+    }
+
+    /// The file is the root of a mount
+    pub fn is_mount_root(self) -> bool {
+        self.has(libc::STATX_ATTR_MOUNT_ROOT)
+    }
+
+    /// The file is protected by fs-verity
+    pub fn is_verity(self) -> bool {
+        self.has(libc::STATX_ATTR_VERITY)
+    }
+
+    /// The file is accessed directly, without the page cache (DAX)
+    pub fn is_dax(self) -> bool {
+        self.has(libc::STATX_ATTR_DAX)
+    }
+
+    /// The raw `STATX_ATTR_*` bits
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    fn has(self, flag: libc::c_int) -> bool {
+        self.0 & flag as u64 != 0
+    }
+}
+
+/// Extended metadata about a file, from [`statx`]
+///
+/// Only the fields in [`mask`](Self::mask) are filled in. That's usually what was asked for, but
+/// a filesystem can leave out fields it doesn't support (plenty don't record a creation time) or
+/// throw in extra ones. The accessors for the fields that are often missing return `Option`s.
+#[derive(Clone, Copy)]
+pub struct Statx(libc::statx);
+
+impl Statx {
+    /// Which fields were filled in
+    pub fn mask(&self) -> StatxMask {
+        StatxMask(self.0.stx_mask)
+    }
+
+    /// The file type and permission bits, like `st_mode`
+    pub fn mode(&self) -> u32 {
+        self.0.stx_mode as u32
+    }
+
+    /// The number of hard links
+    pub fn nlink(&self) -> u32 {
+        self.0.stx_nlink
+    }
+
+    /// The owner
+    pub fn uid(&self) -> u32 {
+        self.0.stx_uid
+    }
+
+    /// The group
+    pub fn gid(&self) -> u32 {
+        self.0.stx_gid
+    }
+
+    /// The inode number
+    pub fn ino(&self) -> u64 {
+        self.0.stx_ino
+    }
+
+    /// The size, in bytes
+    pub fn size(&self) -> u64 {
+        self.0.stx_size
+    }
+
+    /// The number of 512-byte blocks allocated
+    pub fn blocks(&self) -> u64 {
+        self.0.stx_blocks
+    }
+
+    /// The preferred block size for I/O
+    pub fn blksize(&self) -> u32 {
+        self.0.stx_blksize
+    }
+
+    /// The major and minor numbers of the device the file is on
+    pub fn dev(&self) -> (u32, u32) {
+        (self.0.stx_dev_major, self.0.stx_dev_minor)
+    }
+
+    /// For device files, the major and minor numbers of the device
+    pub fn rdev(&self) -> (u32, u32) {
+        (self.0.stx_rdev_major, self.0.stx_rdev_minor)
+    }
+
+    /// The last access time
+    pub fn accessed(&self) -> Option<SystemTime> {
+        self.time(StatxMask::ATIME, self.0.stx_atime)
+    }
+
+    /// The last modification time
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.time(StatxMask::MTIME, self.0.stx_mtime)
+    }
+
+    /// The last status change time
+    pub fn changed(&self) -> Option<SystemTime> {
+        self.time(StatxMask::CTIME, self.0.stx_ctime)
+    }
+
+    /// The creation ("birth") time
+    pub fn created(&self) -> Option<SystemTime> {
+        self.time(StatxMask::BTIME, self.0.stx_btime)
+    }
+
+    /// The ID of the mount the file is on, matching the first field of `/proc/self/mountinfo`
+    pub fn mount_id(&self) -> Option<u64> {
+        self.mask()
+            .contains(StatxMask::MNT_ID)
+            .then_some(self.0.stx_mnt_id)
+    }
+
+    /// Flags about the file
+    ///
+    /// A flag that isn't set might just be one the filesystem doesn't support; see
+    /// [`attributes_mask`](Self::attributes_mask).
+    pub fn attributes(&self) -> StatxAttributes {
+        StatxAttributes(self.0.stx_attributes)
+    }
+
+    /// The flags in [`attributes`](Self::attributes) that the filesystem supports
+    pub fn attributes_mask(&self) -> StatxAttributes {
+        StatxAttributes(self.0.stx_attributes_mask)
+    }
+
+    fn time(&self, mask: StatxMask, time: libc::statx_timestamp) -> Option<SystemTime> {
+        if !self.mask().contains(mask) {
+            return None;
+        }
+        let offset = Duration::new(time.tv_sec.unsigned_abs(), time.tv_nsec);
+        if time.tv_sec >= 0 {
+            SystemTime::UNIX_EPOCH.checked_add(offset)
+        } else {
+            SystemTime::UNIX_EPOCH.checked_sub(offset)
+        }
+    }
+}
+
+impl std::fmt::Debug for Statx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Statx")
+            .field("mask", &self.mask())
+            .field("mode", &format_args!("{:o}", self.mode()))
+            .field("ino", &self.ino())
+            .field("size", &self.size())
+            .field("created", &self.created())
+            .field("mount_id", &self.mount_id())
+            .field("attributes", &self.attributes())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Get extended metadata about a file, following symbolic links, as a _future_.
+///
+/// This is `statx(2)`, which knows about things that [`metadata`](super::metadata) doesn't,
+/// like when the file was created, which mount it's on, and whether it's immutable.
+///
+/// ```
+/// use guillotine::fs::StatxMask;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let statx = guillotine::fs::statx("/", StatxMask::SIZE | StatxMask::BTIME)
+///         .await
+///         .unwrap();
+///     assert!(statx.mask().contains(StatxMask::SIZE));
+///     if let Some(created) = statx.created() {
+///         println!("/ was created at {created:?}");
+///     }
+/// });
+/// ```
+pub async fn statx(path: impl AsRef<Path>, mask: StatxMask) -> Result<Statx, std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || statx_blocking(&path, mask, 0)).await
+}
+
+/// Get extended metadata about a file, _without_ following symbolic links, as a _future_.
+pub async fn symlink_statx(
+    path: impl AsRef<Path>,
+    mask: StatxMask,
+) -> Result<Statx, std::io::Error> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || statx_blocking(&path, mask, libc::AT_SYMLINK_NOFOLLOW)).await
+}
+
+fn statx_blocking(
+    path: &Path,
+    mask: StatxMask,
+    flags: libc::c_int,
+) -> Result<Statx, std::io::Error> {
+    let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "path contains a NUL")
+    })?;
+    let mut buf: MaybeUninit<libc::statx> = MaybeUninit::zeroed();
+    let r = unsafe {
+        libc::statx(
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            flags,
+            mask.0,
+            buf.as_mut_ptr(),
+        )
+    };
+    if r < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(Statx(unsafe { buf.assume_init() }))
+}
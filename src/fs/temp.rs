@@ -0,0 +1,237 @@
+use super::{asyncify, File};
+use crate::runtime::RuntimeContext;
+use std::io::ErrorKind;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+
+/// How many random names to try before giving up
+const ATTEMPTS: usize = 64;
+
+/// A file with a random name that gets deleted when it's dropped
+///
+/// The usual reason to want one is to write a file atomically: write everything to a temporary
+/// file in the same directory, then [`persist`](Self::persist) it over the real one, so that
+/// readers see either the old contents or the new ones and never anything in between.
+///
+/// ```
+/// use guillotine::fs::NamedTempFile;
+/// use guillotine::io::AsyncWriteExt;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let dir = guillotine::fs::tempdir().await.unwrap();
+///     let config = dir.path().join("config.toml");
+///
+///     let mut temp = NamedTempFile::new_in(dir.path()).await.unwrap();
+///     temp.file_mut().write_all(b"a = 1").await.unwrap();
+///     temp.file_mut().sync_all().await.unwrap();
+///     temp.persist(&config).await.unwrap();
+///
+///     assert_eq!(guillotine::fs::read_to_string(&config).await.unwrap(), "a = 1");
+///     dir.close().await.unwrap();
+/// });
+/// ```
+#[derive(Debug)]
+pub struct NamedTempFile {
+    path: TempPath,
+    file: File,
+}
+
+/// The path of a temporary file, which deletes the file when it's dropped
+#[derive(Debug)]
+struct TempPath(Option<PathBuf>);
+
+impl NamedTempFile {
+    /// Create a new temporary file in the system's temporary directory, as a _future_.
+    pub async fn new() -> Result<Self, std::io::Error> {
+        Self::new_in(std::env::temp_dir()).await
+    }
+
+    /// Create a new temporary file in `dir`, as a _future_.
+    ///
+    /// To [`persist`](Self::persist) a file over another one, they need to be on the same
+    /// filesystem, so this is usually the directory the real file is in.
+    pub async fn new_in(dir: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let dir = dir.as_ref().to_owned();
+        let (path, std) = asyncify(move || {
+            create_unique(&dir, |path| {
+                std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(path)
+            })
+        })
+        .await?;
+        Ok(Self {
+            path: TempPath(Some(path)),
+            file: File::from_std(std),
+        })
+    }
+
+    /// The path of the temporary file
+    pub fn path(&self) -> &Path {
+        self.path
+            .0
+            .as_deref()
+            .expect("path is only taken when consumed")
+    }
+
+    /// Get access to the file
+    pub fn as_file(&self) -> &File {
+        &self.file
+    }
+
+    /// Get mutable access to the file, to read or write it
+    pub fn file_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Move the temporary file to `new_path`, replacing anything that's already there, and keep
+    /// it, as a _future_.
+    ///
+    /// Anything written to the file is flushed first. If that or the rename fails, the temporary
+    /// file is deleted.
+    pub async fn persist(mut self, new_path: impl AsRef<Path>) -> Result<File, std::io::Error> {
+        crate::io::AsyncWriteExt::flush(&mut self.file).await?;
+        let from = self.path().to_owned();
+        let to = new_path.as_ref().to_owned();
+        asyncify(move || std::fs::rename(from, to)).await?;
+        self.path.0 = None;
+        Ok(self.file)
+    }
+
+    /// Delete the temporary file now, as a _future_.
+    ///
+    /// Dropping the file deletes it too, but any error along the way is lost.
+    pub async fn close(mut self) -> Result<(), std::io::Error> {
+        let path = self
+            .path
+            .0
+            .take()
+            .expect("path is only taken when consumed");
+        asyncify(move || std::fs::remove_file(path)).await
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if let Some(path) = self.0.take() {
+            remove_in_background(move || std::fs::remove_file(path));
+        }
+    }
+}
+
+/// A directory with a random name that gets deleted, along with everything in it, when it's
+/// dropped
+///
+/// Created by [`tempdir`] or [`tempdir_in`].
+#[derive(Debug)]
+pub struct TempDir {
+    /// Only `None` once the directory has been closed or kept
+    path: Option<PathBuf>,
+}
+
+/// Create a new temporary directory in the system's temporary directory, as a _future_.
+pub async fn tempdir() -> Result<TempDir, std::io::Error> {
+    tempdir_in(std::env::temp_dir()).await
+}
+
+/// Create a new temporary directory in `dir`, as a _future_.
+pub async fn tempdir_in(dir: impl AsRef<Path>) -> Result<TempDir, std::io::Error> {
+    let dir = dir.as_ref().to_owned();
+    let (path, ()) = asyncify(move || {
+        create_unique(&dir, |path| {
+            std::fs::DirBuilder::new().mode(0o700).create(path)
+        })
+    })
+    .await?;
+    Ok(TempDir { path: Some(path) })
+}
+
+impl TempDir {
+    /// The path of the temporary directory
+    pub fn path(&self) -> &Path {
+        self.path
+            .as_deref()
+            .expect("path is only taken when consumed")
+    }
+
+    /// Keep the directory instead of deleting it, and return its path
+    pub fn into_path(mut self) -> PathBuf {
+        self.path.take().expect("path is only taken when consumed")
+    }
+
+    /// Delete the directory and everything in it now, as a _future_.
+    ///
+    /// Dropping the directory deletes it too, but any error along the way is lost.
+    pub async fn close(mut self) -> Result<(), std::io::Error> {
+        let path = self.path.take().expect("path is only taken when consumed");
+        asyncify(move || std::fs::remove_dir_all(path)).await
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            remove_in_background(move || std::fs::remove_dir_all(path));
+        }
+    }
+}
+
+/// Run a cleanup operation from a `Drop` implementation.
+///
+/// Inside a runtime, it goes to a blocking thread (and nobody waits for it). Outside of one,
+/// there's nothing to block, so it just runs.
+fn remove_in_background<F>(remove: F)
+where
+    F: FnOnce() -> Result<(), std::io::Error> + Send + 'static,
+{
+    let remove = move || {
+        if let Err(err) = remove() {
+            crate::trace::warn!(?err, "Failed to clean up temporary file");
+        }
+    };
+    if RuntimeContext::try_current().is_some() {
+        drop(crate::task::spawn_blocking(remove));
+    } else {
+        remove();
+    }
+}
+
+/// Try random names in `dir` until `create` doesn't fail with `AlreadyExists`
+fn create_unique<T>(
+    dir: &Path,
+    mut create: impl FnMut(&Path) -> Result<T, std::io::Error>,
+) -> Result<(PathBuf, T), std::io::Error> {
+    for _ in 0..ATTEMPTS {
+        let path = dir.join(random_name()?);
+        match create(&path) {
+            Ok(t) => return Ok((path, t)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Err(std::io::Error::new(
+        ErrorKind::AlreadyExists,
+        "too many temporary files with the same name",
+    ))
+}
+
+/// A name like `.tmpa8Xk2LmQ0pZr`
+fn random_name() -> Result<String, std::io::Error> {
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    let mut random = [0_u8; 12];
+    let r = unsafe { libc::getrandom(random.as_mut_ptr() as *mut libc::c_void, random.len(), 0) };
+    if r < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut name = String::from(".tmp");
+    name.extend(
+        random
+            .iter()
+            .map(|b| CHARS[*b as usize % CHARS.len()] as char),
+    );
+    Ok(name)
+}
@@ -0,0 +1,299 @@
+use crate::io::poll_fd;
+use crate::runtime::Interest;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{CString, OsStr};
+use std::io::Error;
+use std::mem::size_of;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The events a watch asks for
+const WATCH_MASK: u32 = libc::IN_CREATE
+    | libc::IN_MODIFY
+    | libc::IN_CLOSE_WRITE
+    | libc::IN_DELETE
+    | libc::IN_DELETE_SELF
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO;
+
+/// Enough room for a good number of events with file names of a typical length
+const READ_BUF_SIZE: usize = 16 * 1024;
+
+/// Watch a file or directory for changes
+///
+/// For a directory, this watches the files directly inside it, but not the ones in its
+/// subdirectories; see [`watch_recursive`] for that.
+///
+/// ```
+/// use guillotine::fs::EventKind;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let dir = std::env::temp_dir().join("guillotine-watch");
+///     guillotine::fs::remove_dir_all(&dir).await.ok();
+///     std::fs::create_dir(&dir).unwrap();
+///
+///     let mut watcher = guillotine::fs::watch(&dir).unwrap();
+///     guillotine::fs::write(dir.join("config.toml"), "a = 1").await.unwrap();
+///
+///     let event = watcher.next_event().await.unwrap();
+///     assert_eq!(event.kind, EventKind::Create);
+///     assert_eq!(event.path, dir.join("config.toml"));
+///
+///     guillotine::fs::remove_dir_all(&dir).await.unwrap();
+/// });
+/// ```
+pub fn watch(path: impl AsRef<Path>) -> Result<Watcher, std::io::Error> {
+    let mut watcher = Watcher::new()?;
+    watcher.add(path, false)?;
+    Ok(watcher)
+}
+
+/// Watch a directory, and every directory under it, for changes
+///
+/// Directories that get created (or moved in) later are watched too, as soon as the watcher sees
+/// them.
+pub fn watch_recursive(path: impl AsRef<Path>) -> Result<Watcher, std::io::Error> {
+    let mut watcher = Watcher::new()?;
+    watcher.add(path, true)?;
+    Ok(watcher)
+}
+
+/// What happened to a watched file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// A file or directory was created
+    Create,
+    /// A file was written to
+    Modify,
+    /// A file that was open for writing was closed. This is usually the best time to reload it.
+    CloseWrite,
+    /// A file or directory was deleted
+    Delete,
+    /// A file or directory was moved away. The `cookie` matches it up with the [`MovedTo`](Self::MovedTo) event
+    /// on the other side, if the other side is being watched.
+    MovedFrom {
+        /// Matches this event with its `MovedTo`
+        cookie: u32,
+    },
+    /// A file or directory was moved here
+    MovedTo {
+        /// Matches this event with its `MovedFrom`
+        cookie: u32,
+    },
+    /// The kernel's event queue filled up and events were lost. Anything could have changed, so
+    /// rescan whatever matters.
+    Overflow,
+}
+
+/// A change to a watched file or directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// What happened
+    pub kind: EventKind,
+    /// The path of the file it happened to. For [`EventKind::Overflow`], this is empty.
+    pub path: PathBuf,
+    /// Whether the file it happened to is a directory
+    pub is_dir: bool,
+}
+
+/// A set of watched files and directories, built on `inotify(7)`
+///
+/// Events come out of [`next_event`](Self::next_event), or out of the watcher as a
+/// [`Stream`](futures_core::Stream).
+#[derive(Debug)]
+pub struct Watcher {
+    fd: OwnedFd,
+    /// The path (and whether it's recursive) of each watch descriptor
+    watches: HashMap<libc::c_int, (PathBuf, bool)>,
+    /// Events that have been read but not handed out
+    pending: VecDeque<Event>,
+}
+
+impl Watcher {
+    /// Create a watcher that isn't watching anything yet
+    pub fn new() -> Result<Self, std::io::Error> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            watches: HashMap::new(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Start watching a file or directory, and if `recursive` is true, every directory under it
+    ///
+    /// Setting up a recursive watch walks the whole tree right here, on the runtime's thread, so
+    /// it's best done before there's much else going on.
+    pub fn add(&mut self, path: impl AsRef<Path>, recursive: bool) -> Result<(), std::io::Error> {
+        let path = path.as_ref();
+        self.add_one(path, recursive)?;
+        if recursive {
+            self.add_subdirectories(path)?;
+        }
+        Ok(())
+    }
+
+    /// Stop watching a file or directory that was passed to [`add`](Self::add)
+    ///
+    /// Directories that are being watched because they're under a recursive watch are left alone.
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+        let path = path.as_ref();
+        let wd = self
+            .watches
+            .iter()
+            .find(|(_, (watched, _))| watched == path)
+            .map(|(wd, _)| *wd)
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "path isn't being watched"))?;
+        if unsafe { libc::inotify_rm_watch(self.fd.as_raw_fd(), wd) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        self.watches.remove(&wd);
+        Ok(())
+    }
+
+    /// Wait for the next event, as a _future_.
+    pub async fn next_event(&mut self) -> Result<Event, std::io::Error> {
+        std::future::poll_fn(|cx| self.poll_next_event(cx)).await
+    }
+
+    /// Poll for the next event
+    pub fn poll_next_event(&mut self, cx: &mut Context<'_>) -> Poll<Result<Event, std::io::Error>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Poll::Ready(Ok(event));
+            }
+
+            let mut buf = vec![0_u8; READ_BUF_SIZE];
+            let fd = self.fd.as_raw_fd();
+            let read = match poll_fd(cx, fd, Interest::READABLE, || {
+                let r = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+                if r < 0 {
+                    Err(Error::last_os_error())
+                } else {
+                    Ok(r as usize)
+                }
+            }) {
+                Poll::Ready(Ok(read)) => read,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+            self.parse(&buf[..read]);
+        }
+    }
+
+    fn add_one(&mut self, path: &Path, recursive: bool) -> Result<(), std::io::Error> {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| Error::new(std::io::ErrorKind::InvalidInput, "path contains a NUL"))?;
+        let wd =
+            unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), c_path.as_ptr(), WATCH_MASK) };
+        if wd < 0 {
+            return Err(Error::last_os_error());
+        }
+        self.watches.insert(wd, (path.to_owned(), recursive));
+        Ok(())
+    }
+
+    fn add_subdirectories(&mut self, path: &Path) -> Result<(), std::io::Error> {
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                let path = entry.path();
+                self.add_one(&path, true)?;
+                self.add_subdirectories(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Turn a buffer full of `inotify_event`s into `Event`s
+    fn parse(&mut self, mut buf: &[u8]) {
+        const HEADER: usize = size_of::<libc::inotify_event>();
+        while buf.len() >= HEADER {
+            let raw: libc::inotify_event =
+                unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const libc::inotify_event) };
+            let name_len = raw.len as usize;
+            let name = &buf[HEADER..HEADER + name_len];
+            // The name is padded out with NULs
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            buf = &buf[HEADER + name_len..];
+
+            if raw.mask & libc::IN_Q_OVERFLOW != 0 {
+                self.pending.push_back(Event {
+                    kind: EventKind::Overflow,
+                    path: PathBuf::new(),
+                    is_dir: false,
+                });
+                continue;
+            }
+            if raw.mask & libc::IN_IGNORED != 0 {
+                // The watch is gone, because it was removed or the file was deleted
+                self.watches.remove(&raw.wd);
+                continue;
+            }
+
+            let Some((dir, recursive)) = self.watches.get(&raw.wd).cloned() else {
+                continue;
+            };
+            let path = if name.is_empty() {
+                dir
+            } else {
+                dir.join(OsStr::from_bytes(name))
+            };
+            let is_dir = raw.mask & libc::IN_ISDIR != 0;
+
+            let kind = if raw.mask & libc::IN_CREATE != 0 {
+                EventKind::Create
+            } else if raw.mask & libc::IN_MODIFY != 0 {
+                EventKind::Modify
+            } else if raw.mask & libc::IN_CLOSE_WRITE != 0 {
+                EventKind::CloseWrite
+            } else if raw.mask & (libc::IN_DELETE | libc::IN_DELETE_SELF) != 0 {
+                EventKind::Delete
+            } else if raw.mask & libc::IN_MOVED_FROM != 0 {
+                EventKind::MovedFrom { cookie: raw.cookie }
+            } else if raw.mask & libc::IN_MOVED_TO != 0 {
+                EventKind::MovedTo { cookie: raw.cookie }
+            } else {
+                continue;
+            };
+
+            // New directories under a recursive watch get watched too. If that fails (say, the
+            // directory is already gone again), the events for it are just missed.
+            if recursive && is_dir && matches!(kind, EventKind::Create | EventKind::MovedTo { .. })
+            {
+                if let Err(err) = self.add(&path, true) {
+                    crate::trace::debug!(?err, ?path, "Failed to watch new directory");
+                }
+            }
+
+            self.pending.push_back(Event { kind, path, is_dir });
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.fd.as_raw_fd());
+    }
+}
+
+impl futures_core::Stream for Watcher {
+    type Item = Result<Event, std::io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_next_event(cx).map(Some)
+    }
+}
+
+impl AsRawFd for Watcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
@@ -0,0 +1,73 @@
+//! Combining futures
+//!
+//! [`select`] waits for whichever of two futures finishes first: a read or a shutdown signal, a
+//! message or a timer. (For the common case of giving up after a while,
+//! [`time::timeout`](crate::time::timeout) is a little more direct.)
+
+use pin_project::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Wait for whichever of two futures finishes first
+///
+/// Both futures are polled with the same context, so whichever of them is ready first wakes the
+/// task up. When one finishes, the other is dropped. If both are ready at once, `a` wins: it's
+/// always polled first.
+///
+/// ```
+/// use guillotine::future::{select, Either};
+/// use guillotine::net::TcpListener;
+/// use std::time::Duration;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let listener = TcpListener::new(std::net::TcpListener::bind("127.0.0.1:0").unwrap())
+///         .unwrap();
+///
+///     // Nobody's connecting, so the timer goes off first
+///     let sleep = guillotine::time::sleep(Duration::from_millis(10));
+///     match select(listener.accept(), sleep).await {
+///         Either::Left(_) => panic!("nobody should have connected"),
+///         Either::Right(slept) => slept.unwrap(),
+///     }
+/// });
+/// ```
+pub fn select<A: Future, B: Future>(a: A, b: B) -> Select<A, B> {
+    Select { a, b }
+}
+
+/// The future that runs [`select`]
+#[pin_project]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+#[derive(Debug)]
+pub struct Select<A, B> {
+    #[pin]
+    a: A,
+    #[pin]
+    b: B,
+}
+
+impl<A: Future, B: Future> Future for Select<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let projected = self.project();
+        if let Poll::Ready(output) = projected.a.poll(cx) {
+            return Poll::Ready(Either::Left(output));
+        }
+        if let Poll::Ready(output) = projected.b.poll(cx) {
+            return Poll::Ready(Either::Right(output));
+        }
+        Poll::Pending
+    }
+}
+
+/// One thing or the other, like which future in a [`select`] finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<L, R> {
+    /// The first one
+    Left(L),
+    /// The second one
+    Right(R),
+}
@@ -0,0 +1,200 @@
+//! Running [hyper](::hyper) on guillotine
+//!
+//! hyper doesn't come with a runtime of its own; it asks for one through the traits in
+//! [`hyper::rt`](::hyper::rt). These types implement them:
+//!
+//! * [`GuillotineExecutor`], for `hyper::rt::Executor`, spawns hyper's background tasks (like
+//!   HTTP/2 connection drivers) onto the current runtime.
+//! * [`GuillotineTimer`], for `hyper::rt::Timer`, gives hyper its timeouts.
+//! * [`GuillotineIo`], for `hyper::rt::Read` and `hyper::rt::Write`, wraps a guillotine socket
+//!   (or anything else that implements this crate's [`io`](crate::io) traits) for hyper to talk
+//!   over.
+//!
+//! ```
+//! use guillotine::hyper::{GuillotineIo, GuillotineTimer};
+//! use hyper::rt::Timer;
+//!
+//! fn takes_hyper_io(_: impl hyper::rt::Read + hyper::rt::Write) {}
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let (a, _b) = guillotine::net::unix::pair().unwrap();
+//!     takes_hyper_io(GuillotineIo::new(a));
+//!
+//!     GuillotineTimer::new()
+//!         .sleep(std::time::Duration::from_millis(10))
+//!         .await;
+//! });
+//! ```
+
+use crate::io::{AsyncRead, AsyncWrite};
+use crate::time::Sleep;
+use pin_project::pin_project;
+use std::future::Future;
+use std::io::IoSlice;
+use std::mem::MaybeUninit;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Spawns hyper's tasks onto the currently executing runtime
+///
+/// hyper only spawns tasks while it's being polled, so there's always a current runtime to spawn
+/// them onto.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GuillotineExecutor;
+
+impl GuillotineExecutor {
+    /// Create a new executor
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<F> ::hyper::rt::Executor<F> for GuillotineExecutor
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    fn execute(&self, future: F) {
+        drop(crate::task::spawn(future));
+    }
+}
+
+/// Gives hyper timers backed by [`sleep`](crate::time::sleep)
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GuillotineTimer;
+
+impl GuillotineTimer {
+    /// Create a new timer
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ::hyper::rt::Timer for GuillotineTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn ::hyper::rt::Sleep>> {
+        Box::pin(HyperSleep(Sleep::new(duration)))
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn ::hyper::rt::Sleep>> {
+        self.sleep(deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+/// A [`Sleep`] that hyper can use
+///
+/// hyper's timers can't fail, but ours can (creating or reading the `timerfd`). A timer that never
+/// fires would leave hyper waiting forever, so when something goes wrong, the timer fires right
+/// away instead, and whatever it was timing out gets cut short.
+struct HyperSleep(Result<Sleep, std::io::Error>);
+
+impl Future for HyperSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let sleep = match &mut self.get_mut().0 {
+            Ok(sleep) => sleep,
+            Err(err) => {
+                crate::trace::warn!(?err, "Failed to create a timer for hyper");
+                return Poll::Ready(());
+            }
+        };
+        match Pin::new(sleep).poll(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(()),
+            Poll::Ready(Err(err)) => {
+                crate::trace::warn!(?err, "Timer for hyper failed");
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl ::hyper::rt::Sleep for HyperSleep {}
+
+/// A wrapper that lets hyper talk over one of this crate's I/O types
+#[pin_project]
+#[derive(Debug)]
+pub struct GuillotineIo<T> {
+    #[pin]
+    inner: T,
+}
+
+impl<T> GuillotineIo<T> {
+    /// Wrap an I/O object
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Get access to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get mutable access to the wrapped object
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsyncRead> ::hyper::rt::Read for GuillotineIo<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        mut buf: ::hyper::rt::ReadBufCursor<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        // Our trait wants an initialized slice, so zero out the unfilled part of hyper's buffer and
+        // hand it over, then tell hyper how much got filled.
+        let read = unsafe {
+            let unfilled = buf.as_mut();
+            unfilled.fill(MaybeUninit::new(0));
+            let unfilled = &mut *(unfilled as *mut [MaybeUninit<u8>] as *mut [u8]);
+            match self.project().inner.poll_read(cx, unfilled) {
+                Poll::Ready(Ok(read)) => read,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        };
+        unsafe { buf.advance(read) };
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite> ::hyper::rt::Write for GuillotineIo<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.project().inner.poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+}
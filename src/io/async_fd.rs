@@ -0,0 +1,190 @@
+//! Driving any file descriptor through the runtime
+
+use crate::runtime::Interest;
+use std::io::ErrorKind;
+use std::mem::ManuallyDrop;
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Poll;
+
+/// Wraps anything with a file descriptor (a GPIO line, a netlink socket from another crate, a
+/// device node) so the runtime can say when it's ready
+///
+/// The file descriptor is put into non-blocking mode, and [`readable`](Self::readable) and
+/// [`writable`](Self::writable) wait until it's (probably) ready. Then it's up to you to do the
+/// actual reading or writing, through the [guard](AsyncFdReadyGuard) they return. If it turns out
+/// it wasn't ready after all (and the operation fails with `WouldBlock`), the guard clears the
+/// readiness, so the next wait actually waits.
+///
+/// ```
+/// use guillotine::io::AsyncFd;
+/// use std::io::{Read, Write};
+/// use std::os::unix::net::UnixStream;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (a, mut b) = UnixStream::pair().unwrap();
+///     let a = AsyncFd::new(a).unwrap();
+///
+///     guillotine::task::spawn(async move {
+///         guillotine::time::sleep(std::time::Duration::from_millis(10))
+///             .await
+///             .unwrap();
+///         b.write_all(b"hello").unwrap();
+///     });
+///
+///     let mut buf = [0; 5];
+///     let read = loop {
+///         let mut guard = a.readable().await.unwrap();
+///         if let Some(result) = guard.try_io(|fd| fd.get_ref().read(&mut buf)) {
+///             break result.unwrap();
+///         }
+///     };
+///     assert_eq!(&buf[..read], b"hello");
+/// });
+/// ```
+#[derive(Debug)]
+pub struct AsyncFd<T: AsRawFd> {
+    /// The thing with the file descriptor
+    inner: T,
+    /// Whether the file descriptor might be readable
+    ///
+    /// This starts out `true`, since there's no way to know until something tries.
+    read_ready: AtomicBool,
+    /// Whether the file descriptor might be writable
+    write_ready: AtomicBool,
+}
+
+impl<T: AsRawFd> AsyncFd<T> {
+    /// Wrap something with a file descriptor, putting the file descriptor into non-blocking mode
+    pub fn new(inner: T) -> Result<Self, std::io::Error> {
+        let fd = inner.as_raw_fd();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self {
+            inner,
+            read_ready: AtomicBool::new(true),
+            write_ready: AtomicBool::new(true),
+        })
+    }
+
+    /// Get access to the wrapped object
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get mutable access to the wrapped object
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped object
+    ///
+    /// The file descriptor is left in non-blocking mode.
+    pub fn into_inner(self) -> T {
+        crate::runtime::forget_file_descriptor(self.inner.as_raw_fd());
+        let this = ManuallyDrop::new(self);
+        // `Drop` would only forget the file descriptor again, and the readiness flags don't need
+        // dropping.
+        unsafe { std::ptr::read(&this.inner) }
+    }
+
+    /// Wait until the file descriptor might be readable, as a _future_.
+    ///
+    /// Fails if the runtime can't watch the file descriptor (epoll won't take regular files, for
+    /// one).
+    pub async fn readable(&self) -> Result<AsyncFdReadyGuard<'_, T>, std::io::Error> {
+        self.ready(&self.read_ready, Interest::READABLE).await
+    }
+
+    /// Wait until the file descriptor might be writable, as a _future_.
+    ///
+    /// Fails if the runtime can't watch the file descriptor.
+    pub async fn writable(&self) -> Result<AsyncFdReadyGuard<'_, T>, std::io::Error> {
+        self.ready(&self.write_ready, Interest::WRITABLE).await
+    }
+
+    /// Wait until `ready` is set, or until the runtime says something happened
+    async fn ready<'a>(
+        &'a self,
+        ready: &'a AtomicBool,
+        interest: Interest,
+    ) -> Result<AsyncFdReadyGuard<'a, T>, std::io::Error> {
+        let fd = self.inner.as_raw_fd();
+        let mut registered = false;
+        std::future::poll_fn(|cx| {
+            // Once we've been woken up, the runtime knows whether it was for our kind of
+            // readiness. Outside of a runtime, nobody does (and sometimes the task is woken up by
+            // something else entirely), so assume the best and let the caller find out.
+            let woken = registered && crate::runtime::might_be_ready(fd, interest);
+            if woken || ready.load(Ordering::Relaxed) {
+                ready.store(true, Ordering::Relaxed);
+                return Poll::Ready(Ok(AsyncFdReadyGuard {
+                    fd: self,
+                    ready,
+                    interest,
+                }));
+            }
+            crate::runtime::register_file_descriptor(cx, &self.inner, interest)?;
+            registered = true;
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl<T: AsRawFd> Drop for AsyncFd<T> {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.inner.as_raw_fd());
+    }
+}
+
+impl<T: AsRawFd> AsRawFd for AsyncFd<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+/// Returned by [`AsyncFd::readable`] and [`AsyncFd::writable`] when the file descriptor might be
+/// ready
+#[derive(Debug)]
+pub struct AsyncFdReadyGuard<'a, T: AsRawFd> {
+    /// The file descriptor that might be ready
+    fd: &'a AsyncFd<T>,
+    /// The readiness this guard is for
+    ready: &'a AtomicBool,
+    /// Which kind of readiness that is
+    interest: Interest,
+}
+
+impl<'a, T: AsRawFd> AsyncFdReadyGuard<'a, T> {
+    /// Get access to the [`AsyncFd`]
+    pub fn get_ref(&self) -> &'a AsyncFd<T> {
+        self.fd
+    }
+
+    /// Note that the file descriptor isn't ready after all, so the next wait actually waits
+    pub fn clear_ready(&mut self) {
+        self.ready.store(false, Ordering::Relaxed);
+        crate::runtime::clear_readiness(self.fd.as_raw_fd(), self.interest);
+    }
+
+    /// Try an operation on the file descriptor
+    ///
+    /// If it fails with `WouldBlock`, this clears the readiness and returns `None`, and it's time
+    /// to wait again. Otherwise it returns whatever the operation did.
+    pub fn try_io<R>(
+        &mut self,
+        f: impl FnOnce(&'a AsyncFd<T>) -> Result<R, std::io::Error>,
+    ) -> Option<Result<R, std::io::Error>> {
+        match f(self.fd) {
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                self.clear_ready();
+                None
+            }
+            result => Some(result),
+        }
+    }
+}
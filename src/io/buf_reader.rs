@@ -0,0 +1,171 @@
+use super::{AsyncBufRead, AsyncRead, AsyncWrite};
+use pin_project::pin_project;
+use std::io::{IoSlice, IoSliceMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The default capacity of [`BufReader`] and [`BufWriter`](super::BufWriter)
+pub(crate) const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// Add buffering to a reader
+///
+/// Reading a few bytes at a time straight from a socket means a system call for every few bytes.
+/// `BufReader` reads as much as it can in one go instead, and hands it out from its buffer.
+///
+/// If the reader is also a writer, writes pass straight through.
+#[pin_project]
+#[derive(Debug)]
+pub struct BufReader<R> {
+    #[pin]
+    inner: R,
+    buf: Box<[u8]>,
+    /// Where the unread data in `buf` starts
+    pos: usize,
+    /// Where the unread data in `buf` ends
+    filled: usize,
+}
+
+impl<R: AsyncRead> BufReader<R> {
+    /// Create a new `BufReader` with the default capacity (8 KiB)
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Create a new `BufReader` with the provided capacity
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; capacity].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+}
+
+impl<R> BufReader<R> {
+    /// Get access to the wrapped reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get mutable access to the wrapped reader
+    ///
+    /// Reading from it directly will skip over whatever is in the buffer.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Get pinned mutable access to the wrapped reader
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut R> {
+        self.project().inner
+    }
+
+    /// Unwrap the reader, throwing away anything left in the buffer
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// The data that has been read into the buffer but not handed out yet
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    /// How much the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for BufReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        // If the buffer is empty and the caller wants at least a buffer's worth anyway, there's
+        // no point in copying through the buffer: read straight into theirs.
+        if self.pos == self.filled && buf.len() >= self.buf.len() {
+            return self.project().inner.poll_read(cx, buf);
+        }
+
+        let available = match self.as_mut().poll_fill_buf(cx) {
+            Poll::Ready(Ok(available)) => available,
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let total: usize = bufs.iter().map(|buf| buf.len()).sum();
+        if self.pos == self.filled && total >= self.buf.len() {
+            return self.project().inner.poll_read_vectored(cx, bufs);
+        }
+        match bufs.iter_mut().find(|buf| !buf.is_empty()) {
+            Some(buf) => self.poll_read(cx, buf),
+            None => self.poll_read(cx, &mut []),
+        }
+    }
+}
+
+impl<R: AsyncRead> AsyncBufRead for BufReader<R> {
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<&[u8], std::io::Error>> {
+        let projected = self.project();
+        if *projected.pos == *projected.filled {
+            match projected.inner.poll_read(cx, projected.buf) {
+                Poll::Ready(Ok(read)) => {
+                    *projected.pos = 0;
+                    *projected.filled = read;
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(&projected.buf[*projected.pos..*projected.filled]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let projected = self.project();
+        *projected.pos = (*projected.pos + amt).min(*projected.filled);
+    }
+}
+
+impl<R: AsyncWrite> AsyncWrite for BufReader<R> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.project().inner.poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+}
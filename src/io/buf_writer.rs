@@ -0,0 +1,211 @@
+use super::buf_reader::DEFAULT_CAPACITY;
+use super::{AsyncRead, AsyncWrite};
+use pin_project::pin_project;
+use std::io::{ErrorKind, IoSlice, IoSliceMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Add buffering to a writer
+///
+/// Lots of small writes turn into lots of small system calls (and, for TCP, possibly lots of small
+/// packets). `BufWriter` collects them into its buffer and writes them out together once the
+/// buffer fills up.
+///
+/// Nothing is written out on drop: call [`flush`](super::AsyncWriteExt::flush) (or
+/// [`close`](super::AsyncWriteExt::close)) when you're done, or whatever is left in the buffer is
+/// lost.
+///
+/// If the writer is also a reader, reads pass straight through.
+///
+/// ```
+/// use guillotine::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (a, b) = guillotine::net::unix::pair().unwrap();
+///     let mut writer = BufWriter::new(a);
+///     for word in ["one ", "two ", "three"] {
+///         AsyncWriteExt::write_all(&mut writer, word.as_bytes()).await.unwrap();
+///     }
+///     assert_eq!(writer.buffer(), b"one two three");
+///     AsyncWriteExt::close(&mut writer).await.unwrap();
+///
+///     let mut reader = BufReader::with_capacity(4, b);
+///     let mut all = String::new();
+///     reader.read_to_string(&mut all).await.unwrap();
+///     assert_eq!(all, "one two three");
+/// });
+/// ```
+#[pin_project]
+#[derive(Debug)]
+pub struct BufWriter<W> {
+    #[pin]
+    inner: W,
+    buf: Vec<u8>,
+    /// How much of `buf` has already been written out during a flush
+    written: usize,
+}
+
+impl<W: AsyncWrite> BufWriter<W> {
+    /// Create a new `BufWriter` with the default capacity (8 KiB)
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Create a new `BufWriter` with the provided capacity
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(capacity),
+            written: 0,
+        }
+    }
+
+    /// Write everything in the buffer out to the wrapped writer
+    fn flush_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        let mut projected = self.project();
+        while *projected.written < projected.buf.len() {
+            let unwritten = &projected.buf[*projected.written..];
+            match projected.inner.as_mut().poll_write(cx, unwritten) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+                }
+                Poll::Ready(Ok(written)) => *projected.written += written,
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        projected.buf.clear();
+        *projected.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W> BufWriter<W> {
+    /// Get access to the wrapped writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get mutable access to the wrapped writer
+    ///
+    /// Writing to it directly will jump ahead of whatever is in the buffer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Get pinned mutable access to the wrapped writer
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut W> {
+        self.project().inner
+    }
+
+    /// Unwrap the writer, throwing away anything left in the buffer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// The data that has been written into the buffer but not out to the writer yet
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.written..]
+    }
+
+    /// How much the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+}
+
+impl<W: AsyncWrite> AsyncWrite for BufWriter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        // Make room first, if this wouldn't fit
+        if self.buf.len() + buf.len() > self.buf.capacity() {
+            match self.as_mut().flush_buf(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let projected = self.project();
+        if buf.len() >= projected.buf.capacity() {
+            // It's never going to fit, and the buffer is empty now, so skip the copy
+            projected.inner.poll_write(cx, buf)
+        } else {
+            projected.buf.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let total: usize = bufs.iter().map(|buf| buf.len()).sum();
+        if self.buf.len() + total > self.buf.capacity() {
+            match self.as_mut().flush_buf(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let projected = self.project();
+        if total >= projected.buf.capacity() {
+            projected.inner.poll_write_vectored(cx, bufs)
+        } else {
+            for buf in bufs {
+                projected.buf.extend_from_slice(buf);
+            }
+            Poll::Ready(Ok(total))
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        // Vectored writes just get copied into the buffer, which is as good as it gets
+        true
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        match self.as_mut().flush_buf(cx) {
+            Poll::Ready(Ok(())) => self.project().inner.poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        match self.as_mut().flush_buf(cx) {
+            Poll::Ready(Ok(())) => self.project().inner.poll_close(cx),
+            other => other,
+        }
+    }
+}
+
+impl<W: AsyncRead> AsyncRead for BufWriter<W> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.project().inner.poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.project().inner.poll_read_vectored(cx, bufs)
+    }
+}
@@ -0,0 +1,148 @@
+//! Integration with the `bytes` crate
+//!
+//! `BytesMut` and friends keep track of how much of their memory is filled, which means reads can
+//! go straight into the spare capacity at the end and writes can come straight out of whatever is
+//! left, without any bookkeeping by the caller.
+
+use super::{poll_fd, AsyncRead, AsyncWrite};
+use crate::runtime::Interest;
+use bytes::{Buf, BufMut};
+use std::future::Future;
+use std::io::{ErrorKind, IoSlice};
+use std::os::unix::prelude::RawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The most chunks [`WriteAllBuf`] hands to a vectored write at once
+const MAX_VECTORED_CHUNKS: usize = 64;
+
+/// The future returned by [`AsyncReadExt::read_buf`](super::AsyncReadExt::read_buf)
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReadBuf<'a, R: ?Sized, B: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut B,
+}
+
+impl<'a, R: ?Sized, B: ?Sized> ReadBuf<'a, R, B> {
+    pub(crate) fn new(reader: &'a mut R, buf: &'a mut B) -> Self {
+        Self { reader, buf }
+    }
+}
+
+impl<R, B> Future for ReadBuf<'_, R, B>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    B: BufMut + ?Sized,
+{
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        poll_read_buf(Pin::new(&mut *this.reader), cx, &mut *this.buf)
+    }
+}
+
+/// Read into the spare capacity of `buf`, advancing it by however much was read
+pub(crate) fn poll_read_buf<R, B>(
+    reader: Pin<&mut R>,
+    cx: &mut Context<'_>,
+    buf: &mut B,
+) -> Poll<Result<usize, std::io::Error>>
+where
+    R: AsyncRead + ?Sized,
+    B: BufMut + ?Sized,
+{
+    if !buf.has_remaining_mut() {
+        return Poll::Ready(Ok(0));
+    }
+
+    // `AsyncRead` only knows how to read into initialized memory, so the spare capacity has to be
+    // zeroed first. The crate's own I/O types have inherent `read_buf` methods that skip this,
+    // since the kernel doesn't care.
+    let chunk = buf.chunk_mut();
+    let len = chunk.len();
+    let chunk = unsafe {
+        std::ptr::write_bytes(chunk.as_mut_ptr(), 0, len);
+        std::slice::from_raw_parts_mut(chunk.as_mut_ptr(), len)
+    };
+    match reader.poll_read(cx, chunk) {
+        Poll::Ready(Ok(read)) => {
+            unsafe { buf.advance_mut(read) };
+            Poll::Ready(Ok(read))
+        }
+        other => other,
+    }
+}
+
+/// The future returned by [`AsyncWriteExt::write_all_buf`](super::AsyncWriteExt::write_all_buf)
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WriteAllBuf<'a, W: ?Sized, B: ?Sized> {
+    writer: &'a mut W,
+    buf: &'a mut B,
+}
+
+impl<'a, W: ?Sized, B: ?Sized> WriteAllBuf<'a, W, B> {
+    pub(crate) fn new(writer: &'a mut W, buf: &'a mut B) -> Self {
+        Self { writer, buf }
+    }
+}
+
+impl<W, B> Future for WriteAllBuf<'_, W, B>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+    B: Buf + ?Sized,
+{
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        while this.buf.has_remaining() {
+            let mut writer = Pin::new(&mut *this.writer);
+            let result = if writer.is_write_vectored() {
+                // Hand over as many chunks as we can at once; a `Chain` of a header and a body
+                // goes out in one go.
+                let mut slices = [IoSlice::new(&[]); MAX_VECTORED_CHUNKS];
+                let count = this.buf.chunks_vectored(&mut slices);
+                writer.as_mut().poll_write_vectored(cx, &slices[..count])
+            } else {
+                writer.as_mut().poll_write(cx, this.buf.chunk())
+            };
+            match result {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(written)) => this.buf.advance(written),
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Read from a file descriptor straight into the spare capacity of a buffer, as a _future_.
+///
+/// This is what the inherent `read_buf` methods on the crate's I/O types use. The kernel is happy
+/// to write into uninitialized memory, so unlike [`ReadBuf`], nothing gets zeroed first.
+pub(crate) async fn read_fd_buf<B: BufMut + ?Sized>(
+    fd: RawFd,
+    buf: &mut B,
+) -> Result<usize, std::io::Error> {
+    if !buf.has_remaining_mut() {
+        return Ok(0);
+    }
+    std::future::poll_fn(|cx| {
+        poll_fd(cx, fd, Interest::READABLE, || {
+            let chunk = buf.chunk_mut();
+            let r = unsafe { libc::read(fd, chunk.as_mut_ptr() as *mut libc::c_void, chunk.len()) };
+            if r < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let read = r as usize;
+            unsafe { buf.advance_mut(read) };
+            Ok(read)
+        })
+    })
+    .await
+}
@@ -0,0 +1,91 @@
+use super::{AsyncBufRead, AsyncRead};
+use pin_project::pin_project;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A reader that reads everything from one reader and then everything from another, created by
+/// [`AsyncReadExt::chain`](super::AsyncReadExt::chain)
+#[pin_project]
+#[derive(Debug)]
+pub struct Chain<A, B> {
+    #[pin]
+    first: A,
+    #[pin]
+    second: B,
+    /// Whether `first` has reached the end of its stream
+    first_done: bool,
+}
+
+impl<A, B> Chain<A, B> {
+    pub(crate) fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            first_done: false,
+        }
+    }
+
+    /// Get access to the wrapped readers
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.first, &self.second)
+    }
+
+    /// Get mutable access to the wrapped readers
+    pub fn get_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.first, &mut self.second)
+    }
+
+    /// Get pinned mutable access to the wrapped readers
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> (Pin<&mut A>, Pin<&mut B>) {
+        let projected = self.project();
+        (projected.first, projected.second)
+    }
+
+    /// Unwrap the readers
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: AsyncRead, B: AsyncRead> AsyncRead for Chain<A, B> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let projected = self.project();
+        if !*projected.first_done {
+            match projected.first.poll_read(cx, buf) {
+                // The end of the first stream (as long as the caller actually asked for something)
+                Poll::Ready(Ok(0)) if !buf.is_empty() => *projected.first_done = true,
+                other => return other,
+            }
+        }
+        projected.second.poll_read(cx, buf)
+    }
+}
+
+impl<A: AsyncBufRead, B: AsyncBufRead> AsyncBufRead for Chain<A, B> {
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<&[u8], std::io::Error>> {
+        let projected = self.project();
+        if !*projected.first_done {
+            match projected.first.poll_fill_buf(cx) {
+                Poll::Ready(Ok([])) => *projected.first_done = true,
+                other => return other,
+            }
+        }
+        projected.second.poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let projected = self.project();
+        if *projected.first_done {
+            projected.second.consume(amt)
+        } else {
+            projected.first.consume(amt)
+        }
+    }
+}
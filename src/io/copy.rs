@@ -0,0 +1,130 @@
+use super::{AsyncRead, AsyncWrite};
+use std::future::Future;
+use std::io::ErrorKind;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The size of the buffer that [`copy`] copies through
+const COPY_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Copy everything from a reader to a writer, as a _future_.
+///
+/// Reads until the end of the stream, writing out everything it reads, and flushes the writer once
+/// it's done. Returns the number of bytes copied.
+///
+/// ```
+/// use guillotine::io::AsyncReadExt;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (mut client, mut proxy_in) = guillotine::net::unix::pair().unwrap();
+///     let (mut proxy_out, mut server) = guillotine::net::unix::pair().unwrap();
+///
+///     client.write(b"ping").await.unwrap();
+///     drop(client);
+///     let copied = guillotine::io::copy(&mut proxy_in, &mut proxy_out).await.unwrap();
+///     drop(proxy_out);
+///     assert_eq!(copied, 4);
+///
+///     let mut received = Vec::new();
+///     AsyncReadExt::read_to_end(&mut server, &mut received).await.unwrap();
+///     assert_eq!(received, b"ping");
+/// });
+/// ```
+pub fn copy<'a, R, W>(reader: &'a mut R, writer: &'a mut W) -> Copy<'a, R, W>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    Copy {
+        reader,
+        writer,
+        buf: vec![0; COPY_BUFFER_SIZE].into_boxed_slice(),
+        pos: 0,
+        filled: 0,
+        copied: 0,
+        read_done: false,
+        need_flush: false,
+    }
+}
+
+/// The future returned by [`copy`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Copy<'a, R: ?Sized, W: ?Sized> {
+    reader: &'a mut R,
+    writer: &'a mut W,
+    buf: Box<[u8]>,
+    /// Where the data that hasn't been written yet starts
+    pos: usize,
+    /// Where the data that hasn't been written yet ends
+    filled: usize,
+    /// How much has been written in total
+    copied: u64,
+    /// Whether the reader has reached the end of the stream
+    read_done: bool,
+    /// Whether anything has been written since the last flush
+    need_flush: bool,
+}
+
+impl<R, W> Future for Copy<'_, R, W>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    type Output = Result<u64, std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            // If the buffer is empty, fill it back up
+            if this.pos == this.filled && !this.read_done {
+                match Pin::new(&mut *this.reader).poll_read(cx, &mut this.buf) {
+                    Poll::Ready(Ok(0)) => this.read_done = true,
+                    Poll::Ready(Ok(read)) => {
+                        this.pos = 0;
+                        this.filled = read;
+                    }
+                    Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => continue,
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => {
+                        // Nothing to read for now. Whatever we've written might be sitting in a
+                        // buffer on the writer's side, so push it along while we wait; the other
+                        // end might be waiting on it before it sends any more.
+                        if this.need_flush {
+                            match Pin::new(&mut *this.writer).poll_flush(cx) {
+                                Poll::Ready(Ok(())) => this.need_flush = false,
+                                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                                Poll::Pending => {}
+                            }
+                        }
+                        return Poll::Pending;
+                    }
+                }
+            }
+
+            // Write out whatever is in the buffer
+            while this.pos < this.filled {
+                match Pin::new(&mut *this.writer).poll_write(cx, &this.buf[this.pos..this.filled]) {
+                    Poll::Ready(Ok(0)) => return Poll::Ready(Err(ErrorKind::WriteZero.into())),
+                    Poll::Ready(Ok(written)) => {
+                        this.pos += written;
+                        this.copied += written as u64;
+                        this.need_flush = true;
+                    }
+                    Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {}
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            if this.read_done {
+                return match Pin::new(&mut *this.writer).poll_flush(cx) {
+                    Poll::Ready(Ok(())) => Poll::Ready(Ok(this.copied)),
+                    Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                    Poll::Pending => Poll::Pending,
+                };
+            }
+        }
+    }
+}
@@ -0,0 +1,657 @@
+//! Convenience futures built on top of [`AsyncRead`] and [`AsyncWrite`]
+
+use super::{AsyncBufRead, AsyncRead, AsyncWrite, Chain, Take};
+use futures_core::Stream;
+use std::future::Future;
+use std::io::{Error, ErrorKind, IoSlice, IoSliceMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// How much to grow the buffer by in [`AsyncReadExt::read_to_end`] when it runs out of room
+const READ_TO_END_CHUNK: usize = 4096;
+
+/// Extra methods for everything that implements [`AsyncRead`]
+pub trait AsyncReadExt: AsyncRead {
+    /// Read some bytes into `buf`, as a _future_.
+    ///
+    /// Returns the number of bytes read, which is zero at the end of the stream.
+    fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> Read<'a, Self>
+    where
+        Self: Unpin,
+    {
+        Read { reader: self, buf }
+    }
+
+    /// Read some bytes into a series of buffers, as a _future_.
+    ///
+    /// Returns the total number of bytes read, which is zero at the end of the stream.
+    fn read_vectored<'a, 'b>(
+        &'a mut self,
+        bufs: &'a mut [IoSliceMut<'b>],
+    ) -> ReadVectored<'a, 'b, Self>
+    where
+        Self: Unpin,
+    {
+        ReadVectored { reader: self, bufs }
+    }
+
+    /// Read exactly enough bytes to fill `buf`, as a _future_.
+    ///
+    /// Fails with [`ErrorKind::UnexpectedEof`] if the stream ends first. In that case, some of
+    /// `buf` may have been overwritten already.
+    fn read_exact<'a>(&'a mut self, buf: &'a mut [u8]) -> ReadExact<'a, Self>
+    where
+        Self: Unpin,
+    {
+        ReadExact {
+            reader: self,
+            buf,
+            filled: 0,
+        }
+    }
+
+    /// Read everything until the end of the stream, appending it to `buf`, as a _future_.
+    ///
+    /// Returns the number of bytes that were appended.
+    fn read_to_end<'a>(&'a mut self, buf: &'a mut Vec<u8>) -> ReadToEnd<'a, Self>
+    where
+        Self: Unpin,
+    {
+        let start_len = buf.len();
+        ReadToEnd {
+            reader: self,
+            buf,
+            start_len,
+        }
+    }
+
+    /// Read everything until the end of the stream, appending it to `buf`, as a _future_.
+    ///
+    /// Fails with [`ErrorKind::InvalidData`] if what was read isn't valid UTF-8, in which case
+    /// `buf` is left alone.
+    fn read_to_string<'a>(&'a mut self, buf: &'a mut String) -> ReadToString<'a, Self>
+    where
+        Self: Unpin,
+    {
+        ReadToString {
+            reader: self,
+            buf,
+            bytes: Vec::new(),
+        }
+    }
+
+    /// Read some bytes into the spare capacity of `buf`, as a _future_.
+    ///
+    /// Returns the number of bytes read, which is zero at the end of the stream (or if `buf` is
+    /// full). The spare capacity gets zeroed before it's read into; the crate's own I/O types
+    /// have inherent `read_buf` methods that skip that.
+    #[cfg(feature = "bytes")]
+    fn read_buf<'a, B>(&'a mut self, buf: &'a mut B) -> super::ReadBuf<'a, Self, B>
+    where
+        Self: Unpin,
+        B: bytes::BufMut + ?Sized,
+    {
+        super::ReadBuf::new(self, buf)
+    }
+
+    /// Limit the reader to at most `limit` more bytes
+    fn take(self, limit: u64) -> Take<Self>
+    where
+        Self: Sized,
+    {
+        Take::new(self, limit)
+    }
+
+    /// Read everything from this reader, and then everything from `next`
+    fn chain<R: AsyncRead>(self, next: R) -> Chain<Self, R>
+    where
+        Self: Sized,
+    {
+        Chain::new(self, next)
+    }
+}
+
+impl<R: AsyncRead + ?Sized> AsyncReadExt for R {}
+
+/// Extra methods for everything that implements [`AsyncBufRead`]
+pub trait AsyncBufReadExt: AsyncBufRead {
+    /// Read until `delimiter` (or the end of the stream), appending everything read to `buf`, as a
+    /// _future_.
+    ///
+    /// The delimiter is included in `buf`, if it was found. Returns the number of bytes appended,
+    /// which is zero at the end of the stream.
+    fn read_until<'a>(&'a mut self, delimiter: u8, buf: &'a mut Vec<u8>) -> ReadUntil<'a, Self>
+    where
+        Self: Unpin,
+    {
+        ReadUntil {
+            reader: self,
+            delimiter,
+            buf,
+            read: 0,
+        }
+    }
+
+    /// Read a line (up to and including a `\n`) and append it to `buf`, as a _future_.
+    ///
+    /// Returns the number of bytes appended, which is zero at the end of the stream. Fails with
+    /// [`ErrorKind::InvalidData`] if the line isn't valid UTF-8, in which case `buf` is left
+    /// alone.
+    fn read_line<'a>(&'a mut self, buf: &'a mut String) -> ReadLine<'a, Self>
+    where
+        Self: Unpin,
+    {
+        ReadLine {
+            reader: self,
+            buf,
+            bytes: Vec::new(),
+            read: 0,
+        }
+    }
+
+    /// Turn the reader into a [`Stream`] of its lines
+    ///
+    /// The lines don't include the `\n` (or `\r\n`) at the end. For use without any stream
+    /// combinators, there's also [`Lines::next_line`].
+    ///
+    /// ```
+    /// use guillotine::io::{AsyncBufReadExt, BufReader};
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let (mut a, b) = guillotine::net::unix::pair().unwrap();
+    ///     a.write(b"HELO example.com\r\nQUIT\r\n").await.unwrap();
+    ///     drop(a);
+    ///
+    ///     let mut lines = BufReader::new(b).lines();
+    ///     assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("HELO example.com"));
+    ///     assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("QUIT"));
+    ///     assert_eq!(lines.next_line().await.unwrap(), None);
+    /// });
+    /// ```
+    fn lines(self) -> Lines<Self>
+    where
+        Self: Sized,
+    {
+        Lines {
+            reader: self,
+            bytes: Vec::new(),
+            read: 0,
+        }
+    }
+}
+
+impl<R: AsyncBufRead + ?Sized> AsyncBufReadExt for R {}
+
+/// Extra methods for everything that implements [`AsyncWrite`]
+pub trait AsyncWriteExt: AsyncWrite {
+    /// Write some bytes from `buf`, as a _future_.
+    ///
+    /// Returns the number of bytes written, which may be less than all of them.
+    fn write<'a>(&'a mut self, buf: &'a [u8]) -> Write<'a, Self>
+    where
+        Self: Unpin,
+    {
+        Write { writer: self, buf }
+    }
+
+    /// Write some bytes from a series of buffers, as a _future_.
+    ///
+    /// Returns the total number of bytes written, which may be less than all of them.
+    fn write_vectored<'a, 'b>(&'a mut self, bufs: &'a [IoSlice<'b>]) -> WriteVectored<'a, 'b, Self>
+    where
+        Self: Unpin,
+    {
+        WriteVectored { writer: self, bufs }
+    }
+
+    /// Write all of `buf`, as a _future_.
+    ///
+    /// Fails with [`ErrorKind::WriteZero`] if the writer stops accepting bytes.
+    fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> WriteAll<'a, Self>
+    where
+        Self: Unpin,
+    {
+        WriteAll { writer: self, buf }
+    }
+
+    /// Write everything that's left in `buf`, advancing it as it goes, as a _future_.
+    ///
+    /// If the writer supports vectored writes, buffers made of several chunks (like a `Chain` of a
+    /// header and a body) are written without copying them together first.
+    #[cfg(feature = "bytes")]
+    fn write_all_buf<'a, B>(&'a mut self, buf: &'a mut B) -> super::WriteAllBuf<'a, Self, B>
+    where
+        Self: Unpin,
+        B: bytes::Buf + ?Sized,
+    {
+        super::WriteAllBuf::new(self, buf)
+    }
+
+    /// Flush any buffered data, as a _future_.
+    fn flush(&mut self) -> Flush<'_, Self>
+    where
+        Self: Unpin,
+    {
+        Flush { writer: self }
+    }
+
+    /// Close the writer, as a _future_.
+    fn close(&mut self) -> Close<'_, Self>
+    where
+        Self: Unpin,
+    {
+        Close { writer: self }
+    }
+}
+
+impl<W: AsyncWrite + ?Sized> AsyncWriteExt for W {}
+
+/// The future returned by [`AsyncReadExt::read`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Read<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut [u8],
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for Read<'_, R> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        Pin::new(&mut *this.reader).poll_read(cx, this.buf)
+    }
+}
+
+/// The future returned by [`AsyncReadExt::read_vectored`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReadVectored<'a, 'b, R: ?Sized> {
+    reader: &'a mut R,
+    bufs: &'a mut [IoSliceMut<'b>],
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadVectored<'_, '_, R> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        Pin::new(&mut *this.reader).poll_read_vectored(cx, this.bufs)
+    }
+}
+
+/// The future returned by [`AsyncReadExt::read_exact`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReadExact<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut [u8],
+    /// How much of `buf` has been read into so far
+    filled: usize,
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadExact<'_, R> {
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        while this.filled < this.buf.len() {
+            let read = match Pin::new(&mut *this.reader).poll_read(cx, &mut this.buf[this.filled..])
+            {
+                Poll::Ready(Ok(read)) => read,
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => continue,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+            if read == 0 {
+                return Poll::Ready(Err(ErrorKind::UnexpectedEof.into()));
+            }
+            this.filled += read;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The future returned by [`AsyncReadExt::read_to_end`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReadToEnd<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut Vec<u8>,
+    /// How long `buf` was to begin with, so we know how much we added
+    start_len: usize,
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadToEnd<'_, R> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        poll_read_to_end(&mut *this.reader, cx, this.buf, this.start_len)
+    }
+}
+
+/// The future returned by [`AsyncReadExt::read_to_string`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReadToString<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut String,
+    /// The bytes read so far. They don't go in `buf` until we know they're valid UTF-8.
+    bytes: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadToString<'_, R> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        match poll_read_to_end(&mut *this.reader, cx, &mut this.bytes, 0) {
+            Poll::Ready(Ok(read)) => {
+                let string = into_string(std::mem::take(&mut this.bytes))?;
+                this.buf.push_str(&string);
+                Poll::Ready(Ok(read))
+            }
+            other => other,
+        }
+    }
+}
+
+/// Keep reading into the end of `buf` until the reader runs dry
+///
+/// `buf` is grown a chunk at a time, and trimmed back down to what was actually read before
+/// returning, whether that's because of the end of the stream, an error, or `Pending`.
+fn poll_read_to_end<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    cx: &mut Context<'_>,
+    buf: &mut Vec<u8>,
+    start_len: usize,
+) -> Poll<Result<usize, std::io::Error>> {
+    loop {
+        let len = buf.len();
+        buf.resize(len + READ_TO_END_CHUNK, 0);
+        let result = Pin::new(&mut *reader).poll_read(cx, &mut buf[len..]);
+        match result {
+            Poll::Ready(Ok(0)) => {
+                buf.truncate(len);
+                return Poll::Ready(Ok(len - start_len));
+            }
+            Poll::Ready(Ok(read)) => buf.truncate(len + read),
+            Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => buf.truncate(len),
+            Poll::Ready(Err(err)) => {
+                buf.truncate(len);
+                return Poll::Ready(Err(err));
+            }
+            Poll::Pending => {
+                buf.truncate(len);
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// The future returned by [`AsyncBufReadExt::read_until`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReadUntil<'a, R: ?Sized> {
+    reader: &'a mut R,
+    delimiter: u8,
+    buf: &'a mut Vec<u8>,
+    /// How much has been appended to `buf` so far
+    read: usize,
+}
+
+impl<R: AsyncBufRead + Unpin + ?Sized> Future for ReadUntil<'_, R> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        poll_read_until(
+            Pin::new(&mut *this.reader),
+            cx,
+            this.delimiter,
+            this.buf,
+            &mut this.read,
+        )
+    }
+}
+
+/// The future returned by [`AsyncBufReadExt::read_line`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReadLine<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut String,
+    /// The bytes of the line so far. They don't go in `buf` until we know they're valid UTF-8.
+    bytes: Vec<u8>,
+    read: usize,
+}
+
+impl<R: AsyncBufRead + Unpin + ?Sized> Future for ReadLine<'_, R> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        match poll_read_until(
+            Pin::new(&mut *this.reader),
+            cx,
+            b'\n',
+            &mut this.bytes,
+            &mut this.read,
+        ) {
+            Poll::Ready(Ok(read)) => {
+                let line = into_string(std::mem::take(&mut this.bytes))?;
+                this.buf.push_str(&line);
+                Poll::Ready(Ok(read))
+            }
+            other => other,
+        }
+    }
+}
+
+/// A stream of the lines of a reader, created by [`AsyncBufReadExt::lines`]
+#[pin_project::pin_project]
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct Lines<R> {
+    #[pin]
+    reader: R,
+    /// The bytes of the current line so far
+    bytes: Vec<u8>,
+    read: usize,
+}
+
+impl<R: AsyncBufRead> Lines<R> {
+    /// Get the next line, as a _future_.
+    ///
+    /// Returns `None` at the end of the stream.
+    pub async fn next_line(&mut self) -> Result<Option<String>, std::io::Error>
+    where
+        R: Unpin,
+    {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next_line(cx)).await
+    }
+
+    /// Attempt to get the next line
+    pub fn poll_next_line(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<String>, std::io::Error>> {
+        let projected = self.project();
+        let read =
+            match poll_read_until(projected.reader, cx, b'\n', projected.bytes, projected.read) {
+                Poll::Ready(Ok(read)) => read,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+        if read == 0 && projected.bytes.is_empty() {
+            return Poll::Ready(Ok(None));
+        }
+
+        let mut bytes = std::mem::take(projected.bytes);
+        if bytes.last() == Some(&b'\n') {
+            bytes.pop();
+            if bytes.last() == Some(&b'\r') {
+                bytes.pop();
+            }
+        }
+        Poll::Ready(into_string(bytes).map(Some))
+    }
+
+    /// Unwrap the reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: AsyncBufRead> Stream for Lines<R> {
+    type Item = Result<String, std::io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.poll_next_line(cx) {
+            Poll::Ready(Ok(Some(line))) => Poll::Ready(Some(Ok(line))),
+            Poll::Ready(Ok(None)) => Poll::Ready(None),
+            Poll::Ready(Err(err)) => Poll::Ready(Some(Err(err))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Keep appending to `buf` until `delimiter` shows up or the reader runs dry
+///
+/// `read` keeps track of how much has been appended across calls, since this is called again
+/// after every `Pending`; it's reset once the delimiter (or the end of the stream) is found.
+fn poll_read_until<R: AsyncBufRead + ?Sized>(
+    mut reader: Pin<&mut R>,
+    cx: &mut Context<'_>,
+    delimiter: u8,
+    buf: &mut Vec<u8>,
+    read: &mut usize,
+) -> Poll<Result<usize, std::io::Error>> {
+    loop {
+        let (done, used) = {
+            let available = match reader.as_mut().poll_fill_buf(cx) {
+                Poll::Ready(Ok(available)) => available,
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => continue,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+            match available.iter().position(|byte| *byte == delimiter) {
+                Some(index) => {
+                    buf.extend_from_slice(&available[..=index]);
+                    (true, index + 1)
+                }
+                None => {
+                    buf.extend_from_slice(available);
+                    (available.is_empty(), available.len())
+                }
+            }
+        };
+        reader.as_mut().consume(used);
+        *read += used;
+        if done {
+            return Poll::Ready(Ok(std::mem::take(read)));
+        }
+    }
+}
+
+/// Turn bytes into a `String`, or an `InvalidData` error
+fn into_string(bytes: Vec<u8>) -> Result<String, std::io::Error> {
+    String::from_utf8(bytes)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "stream did not contain valid UTF-8"))
+}
+
+/// The future returned by [`AsyncWriteExt::write`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Write<'a, W: ?Sized> {
+    writer: &'a mut W,
+    buf: &'a [u8],
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for Write<'_, W> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        Pin::new(&mut *this.writer).poll_write(cx, this.buf)
+    }
+}
+
+/// The future returned by [`AsyncWriteExt::write_vectored`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WriteVectored<'a, 'b, W: ?Sized> {
+    writer: &'a mut W,
+    bufs: &'a [IoSlice<'b>],
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for WriteVectored<'_, '_, W> {
+    type Output = Result<usize, std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        Pin::new(&mut *this.writer).poll_write_vectored(cx, this.bufs)
+    }
+}
+
+/// The future returned by [`AsyncWriteExt::write_all`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WriteAll<'a, W: ?Sized> {
+    writer: &'a mut W,
+    /// What's left to write
+    buf: &'a [u8],
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for WriteAll<'_, W> {
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        while !this.buf.is_empty() {
+            let written = match Pin::new(&mut *this.writer).poll_write(cx, this.buf) {
+                Poll::Ready(Ok(written)) => written,
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => continue,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+            if written == 0 {
+                return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+            }
+            this.buf = &this.buf[written..];
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The future returned by [`AsyncWriteExt::flush`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Flush<'a, W: ?Sized> {
+    writer: &'a mut W,
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for Flush<'_, W> {
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.get_mut().writer).poll_flush(cx)
+    }
+}
+
+/// The future returned by [`AsyncWriteExt::close`]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Close<'a, W: ?Sized> {
+    writer: &'a mut W,
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for Close<'_, W> {
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.get_mut().writer).poll_close(cx)
+    }
+}
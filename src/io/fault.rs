@@ -0,0 +1,313 @@
+use super::{AsyncRead, AsyncWrite};
+use crate::net::{TcpListener, TcpStream};
+use crate::rng::Rng;
+use crate::time::Sleep;
+use std::cell::RefCell;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+/// Which faults to inject, and how often
+///
+/// Every probability is from 0 to 1, and is rolled separately for every read, write, or accept.
+/// The rolls come from a random number generator seeded with the seed given to [`Faults::new`], so
+/// the same seed injects the same faults, as long as the code under test does the same things.
+///
+/// ```
+/// use std::io::ErrorKind;
+/// use std::time::Duration;
+/// use guillotine::io::Faults;
+///
+/// let faults = Faults::new(42)
+///     .read_error(0.01, ErrorKind::ConnectionReset)
+///     .short_reads(0.5)
+///     .short_writes(0.5)
+///     .delay(0.1, Duration::from_millis(5));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Faults {
+    seed: u64,
+    read_error: Option<(f64, ErrorKind)>,
+    write_error: Option<(f64, ErrorKind)>,
+    accept_error: Option<(f64, ErrorKind)>,
+    short_reads: f64,
+    short_writes: f64,
+    delay: Option<(f64, Duration)>,
+}
+
+impl Faults {
+    /// No faults at all, yet
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            read_error: None,
+            write_error: None,
+            accept_error: None,
+            short_reads: 0.0,
+            short_writes: 0.0,
+            delay: None,
+        }
+    }
+
+    /// Fail reads with `kind`, with the given probability
+    pub fn read_error(mut self, probability: f64, kind: ErrorKind) -> Self {
+        self.read_error = Some((probability, kind));
+        self
+    }
+
+    /// Fail writes with `kind`, with the given probability
+    pub fn write_error(mut self, probability: f64, kind: ErrorKind) -> Self {
+        self.write_error = Some((probability, kind));
+        self
+    }
+
+    /// Fail accepts on a [`FaultInjectListener`] with `kind`, with the given probability
+    pub fn accept_error(mut self, probability: f64, kind: ErrorKind) -> Self {
+        self.accept_error = Some((probability, kind));
+        self
+    }
+
+    /// Read fewer bytes than there's room for, with the given probability
+    ///
+    /// Code that assumes a read fills the buffer tends to work fine against a fast local socket,
+    /// and then fall over in production.
+    pub fn short_reads(mut self, probability: f64) -> Self {
+        self.short_reads = probability;
+        self
+    }
+
+    /// Write fewer bytes than were given, with the given probability
+    pub fn short_writes(mut self, probability: f64) -> Self {
+        self.short_writes = probability;
+        self
+    }
+
+    /// Wait `duration` before reading, writing, or accepting, with the given probability
+    pub fn delay(mut self, probability: f64, duration: Duration) -> Self {
+        self.delay = Some((probability, duration));
+        self
+    }
+}
+
+/// Wraps a reader or writer and makes it misbehave, for testing what protocol code does when I/O
+/// goes wrong
+///
+/// ```
+/// use guillotine::io::{AsyncReadExt, AsyncWriteExt, FaultInject, Faults};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (a, mut b) = guillotine::net::unix::pair().unwrap();
+///     let mut a = FaultInject::new(a, Faults::new(7).short_writes(1.0));
+///
+///     // Every write is short, but `write_all` keeps going until it's all written.
+///     a.write_all(b"hello, world").await.unwrap();
+///
+///     let mut buf = [0_u8; 12];
+///     b.read_exact(&mut buf).await.unwrap();
+///     assert_eq!(&buf, b"hello, world");
+/// });
+/// ```
+pub struct FaultInject<T> {
+    inner: T,
+    faults: Faults,
+    rng: Rng,
+    /// The delay being waited out before the next read or write, if there is one
+    delay: Option<Sleep>,
+    /// Whether the next read or write has already been delayed, so it doesn't get delayed again
+    delayed: bool,
+}
+
+impl<T> FaultInject<T> {
+    /// Wrap `inner`, injecting `faults` into everything done with it
+    pub fn new(inner: T, faults: Faults) -> Self {
+        Self {
+            inner,
+            rng: Rng::new(faults.seed),
+            faults,
+            delay: None,
+            delayed: false,
+        }
+    }
+
+    /// Get access to the wrapped reader or writer
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get mutable access to the wrapped reader or writer
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwrap the reader or writer, so that it behaves itself again
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Roll for a delay, and wait it out if there is one
+    fn poll_delay(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        if self.delay.is_none() && !self.delayed {
+            if let Some((probability, duration)) = self.faults.delay {
+                if self.rng.next_f64() < probability {
+                    self.delay = Some(Sleep::new(duration)?);
+                }
+            }
+        }
+
+        if let Some(delay) = &mut self.delay {
+            ready!(Pin::new(delay).poll(cx))?;
+            self.delay = None;
+            self.delayed = true;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Roll for an error
+    fn roll_error(&mut self, error: Option<(f64, ErrorKind)>) -> Result<(), std::io::Error> {
+        match error {
+            Some((probability, kind)) if self.rng.next_f64() < probability => {
+                Err(std::io::Error::new(kind, "injected fault"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Roll for a short read or write, and pick how much of `len` to use
+    fn roll_len(&mut self, probability: f64, len: usize) -> usize {
+        if len > 1 && self.rng.next_f64() < probability {
+            1 + (self.rng.next_u64() % (len as u64 - 1)) as usize
+        } else {
+            len
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for FaultInject<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FaultInject")
+            .field("inner", &self.inner)
+            .field("faults", &self.faults)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for FaultInject<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_delay(cx))?;
+        this.delayed = false;
+        this.roll_error(this.faults.read_error)?;
+
+        let len = this.roll_len(this.faults.short_reads, buf.len());
+        let result = Pin::new(&mut this.inner).poll_read(cx, &mut buf[..len]);
+        // If the read has to wait, it's the same read when it's polled again, and it's been
+        // delayed already.
+        this.delayed = result.is_pending();
+        result
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for FaultInject<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_delay(cx))?;
+        this.delayed = false;
+        this.roll_error(this.faults.write_error)?;
+
+        let len = this.roll_len(this.faults.short_writes, buf.len());
+        let result = Pin::new(&mut this.inner).poll_write(cx, &buf[..len]);
+        this.delayed = result.is_pending();
+        result
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
+
+/// Wraps a [`TcpListener`] so that accepting fails now and then, and every stream it accepts is
+/// wrapped in a [`FaultInject`]
+///
+/// Each accepted stream gets its own seed, picked (reproducibly) from the listener's.
+pub struct FaultInjectListener {
+    inner: TcpListener,
+    faults: Faults,
+    rng: RefCell<Rng>,
+}
+
+impl FaultInjectListener {
+    /// Wrap `inner`, injecting `faults` into everything accepted from it
+    pub fn new(inner: TcpListener, faults: Faults) -> Self {
+        Self {
+            inner,
+            rng: RefCell::new(Rng::new(faults.seed)),
+            faults,
+        }
+    }
+
+    /// Get access to the wrapped listener
+    pub fn get_ref(&self) -> &TcpListener {
+        &self.inner
+    }
+
+    /// Unwrap the listener, so that it behaves itself again
+    pub fn into_inner(self) -> TcpListener {
+        self.inner
+    }
+
+    /// Wait until a new connection is available and accept that connection, unless a fault gets
+    /// injected first
+    pub async fn accept(&self) -> Result<(FaultInject<TcpStream>, SocketAddr), std::io::Error> {
+        let (delay, error) = {
+            let mut rng = self.rng.borrow_mut();
+            let delay = match self.faults.delay {
+                Some((probability, duration)) if rng.next_f64() < probability => Some(duration),
+                _ => None,
+            };
+            let error = match self.faults.accept_error {
+                Some((probability, kind)) if rng.next_f64() < probability => Some(kind),
+                _ => None,
+            };
+            (delay, error)
+        };
+
+        if let Some(duration) = delay {
+            crate::time::sleep(duration).await?;
+        }
+        if let Some(kind) = error {
+            return Err(std::io::Error::new(kind, "injected fault"));
+        }
+
+        let (stream, addr) = self.inner.accept().await?;
+        let seed = self.rng.borrow_mut().next_u64();
+        let faults = Faults {
+            seed,
+            ..self.faults.clone()
+        };
+        Ok((FaultInject::new(stream, faults), addr))
+    }
+}
+
+impl std::fmt::Debug for FaultInjectListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FaultInjectListener")
+            .field("faults", &self.faults)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,166 @@
+//! Named pipes (FIFOs)
+//!
+//! A FIFO is a file that's really a pipe: whatever one process writes into it, another process
+//! reads out. They're a handy way for a shell script to push data at a long-running service
+//! (`echo reload > /run/service.fifo`).
+//!
+//! Opening them comes with a couple of quirks, which these functions deal with:
+//!
+//! * Opening for writing fails with `ENXIO` while nobody has the FIFO open for reading.
+//!   [`open_fifo_writer`] keeps trying until a reader shows up.
+//! * Reading returns the end of the stream whenever there are no writers, including before the
+//!   first one shows up and after each one goes away. That's fine for a single writer, but for a
+//!   service that takes one-shot writes from scripts, [`open_fifo_reader_persistent`] opens the
+//!   FIFO so that the end of the stream never comes.
+
+use super::{poll_fd, AsyncRead, AsyncWrite};
+use crate::runtime::Interest;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::prelude::{AsRawFd, FromRawFd, RawFd};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// How long to wait between attempts to open a FIFO for writing while there's no reader
+const WRITER_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The read end of a FIFO, created by [`open_fifo_reader`]
+#[derive(Debug)]
+pub struct FifoReader(File);
+
+/// The write end of a FIFO, created by [`open_fifo_writer`]
+#[derive(Debug)]
+pub struct FifoWriter(File);
+
+/// Open a FIFO for reading
+///
+/// This succeeds whether or not there's a writer yet. Reads return the end of the stream while
+/// there are no writers; see the [module documentation](self) for details.
+pub fn open_fifo_reader(path: impl AsRef<Path>) -> Result<FifoReader, std::io::Error> {
+    open(path.as_ref(), libc::O_RDONLY).map(FifoReader)
+}
+
+/// Open a FIFO for reading, in a way that never sees the end of the stream
+///
+/// The FIFO is opened for reading _and_ writing, so there's always at least one writer (us), and
+/// reads just wait when the real writers go away.
+pub fn open_fifo_reader_persistent(path: impl AsRef<Path>) -> Result<FifoReader, std::io::Error> {
+    open(path.as_ref(), libc::O_RDWR).map(FifoReader)
+}
+
+/// Open a FIFO for writing, as a _future_.
+///
+/// If nobody has the FIFO open for reading yet, this waits until somebody does. There's no way
+/// to be told when that happens, so it tries again every 50 milliseconds.
+pub async fn open_fifo_writer(path: impl AsRef<Path>) -> Result<FifoWriter, std::io::Error> {
+    let path = path.as_ref();
+    loop {
+        match open(path, libc::O_WRONLY) {
+            Ok(file) => return Ok(FifoWriter(file)),
+            Err(err) if err.raw_os_error() == Some(libc::ENXIO) => {
+                crate::time::sleep(WRITER_RETRY_INTERVAL).await?;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Open a FIFO non-blocking, making sure it really is a FIFO
+fn open(path: &Path, access: libc::c_int) -> Result<File, std::io::Error> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let file = unsafe {
+        let fd = libc::open(c_path.as_ptr(), access | libc::O_NONBLOCK | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        File::from_raw_fd(fd)
+    };
+
+    if !file.metadata()?.file_type().is_fifo() {
+        return Err(Error::new(ErrorKind::InvalidInput, "not a FIFO"));
+    }
+    Ok(file)
+}
+
+impl FifoReader {
+    /// Read bytes from the FIFO, as a future
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, buf)).await
+    }
+}
+
+impl Drop for FifoReader {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.0.as_raw_fd());
+    }
+}
+
+impl FifoWriter {
+    /// Write bytes to the FIFO, as a future
+    ///
+    /// Writes of up to `PIPE_BUF` (4096) bytes are atomic: they won't be interleaved with
+    /// writes from other writers.
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_write(cx, buf)).await
+    }
+}
+
+impl Drop for FifoWriter {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.0.as_raw_fd());
+    }
+}
+
+impl AsRawFd for FifoReader {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsRawFd for FifoWriter {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsyncRead for FifoReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        use std::io::Read;
+
+        let mut file = &self.0;
+        poll_fd(cx, file.as_raw_fd(), Interest::READABLE, || file.read(buf))
+    }
+}
+
+impl AsyncWrite for FifoWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        use std::io::Write;
+
+        let mut file = &self.0;
+        poll_fd(cx, file.as_raw_fd(), Interest::WRITABLE, || file.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // Writes go straight to the pipe; there's nothing to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // There's no half-closing a pipe. The reader sees the end of the stream once every writer
+        // has been dropped.
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,182 @@
+//! Implementations of the `futures-io` traits for this crate's I/O types
+//!
+//! These all forward to the crate's own [`AsyncRead`], [`AsyncWrite`] and [`AsyncBufRead`]
+//! implementations (and [`File::poll_seek`]), so the two sets of traits always behave the same
+//! way.
+//!
+//! ```
+//! use futures_io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite};
+//! use guillotine::io::BufReader;
+//! use std::future::poll_fn;
+//! use std::io::SeekFrom;
+//! use std::pin::Pin;
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let path = std::env::temp_dir().join("guillotine-futures-io.txt");
+//!     let mut file = guillotine::fs::File::create(&path).await.unwrap();
+//!     poll_fn(|cx| Pin::new(&mut file).poll_write(cx, b"hello\nworld\n")).await.unwrap();
+//!     poll_fn(|cx| Pin::new(&mut file).poll_flush(cx)).await.unwrap();
+//!
+//!     let mut file = guillotine::fs::File::open(&path).await.unwrap();
+//!     let pos = poll_fn(|cx| Pin::new(&mut file).poll_seek(cx, SeekFrom::Start(6)))
+//!         .await
+//!         .unwrap();
+//!     assert_eq!(pos, 6);
+//!
+//!     let mut reader = BufReader::new(file);
+//!     let line = poll_fn(|cx| Pin::new(&mut reader).poll_fill_buf(cx).map_ok(|buf| buf.to_vec()))
+//!         .await
+//!         .unwrap();
+//!     assert_eq!(line, b"world\n");
+//!
+//!     guillotine::fs::remove_file(&path).await.unwrap();
+//! });
+//! ```
+//!
+//! Pipes to and from a child process work the same way:
+//!
+//! ```
+//! use futures_io::{AsyncRead, AsyncWrite};
+//! use guillotine::process::{Command, Stdio};
+//! use std::future::poll_fn;
+//! use std::pin::Pin;
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let mut child = Command::new("cat")
+//!         .stdin(Stdio::piped())
+//!         .stdout(Stdio::piped())
+//!         .spawn()
+//!         .unwrap();
+//!     let mut stdin = child.stdin.take().unwrap();
+//!     let mut stdout = child.stdout.take().unwrap();
+//!
+//!     poll_fn(|cx| Pin::new(&mut stdin).poll_write(cx, b"hello")).await.unwrap();
+//!     poll_fn(|cx| Pin::new(&mut stdin).poll_close(cx)).await.unwrap();
+//!     drop(stdin);
+//!
+//!     let mut buf = [0_u8; 5];
+//!     let read = poll_fn(|cx| Pin::new(&mut stdout).poll_read(cx, &mut buf)).await.unwrap();
+//!     assert_eq!(&buf[..read], &b"hello"[..read]);
+//!     child.wait().await.unwrap();
+//! });
+//! ```
+
+use super::{AsyncBufRead, AsyncRead, AsyncWrite, BufReader, BufWriter};
+use crate::fs::File;
+use crate::net::unix::{ReadHalf, WriteHalf};
+use crate::net::{TcpStream, UnixStream, VsockStream};
+use crate::process::{ChildStderr, ChildStdin, ChildStdout};
+use std::io::{IoSlice, IoSliceMut, SeekFrom};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Implement `futures_io::AsyncRead` for types that implement [`AsyncRead`]
+macro_rules! impl_futures_read {
+    ($($ty:ty $(where $gen:ident: $bound:ident)?),* $(,)?) => {
+        $(
+            impl$(<$gen>)? futures_io::AsyncRead for $ty $(where $gen: $bound)? {
+                fn poll_read(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context<'_>,
+                    buf: &mut [u8],
+                ) -> Poll<Result<usize, std::io::Error>> {
+                    AsyncRead::poll_read(self, cx, buf)
+                }
+
+                fn poll_read_vectored(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context<'_>,
+                    bufs: &mut [IoSliceMut<'_>],
+                ) -> Poll<Result<usize, std::io::Error>> {
+                    AsyncRead::poll_read_vectored(self, cx, bufs)
+                }
+            }
+        )*
+    };
+}
+
+/// Implement `futures_io::AsyncWrite` for types that implement [`AsyncWrite`]
+macro_rules! impl_futures_write {
+    ($($ty:ty $(where $gen:ident: $bound:ident)?),* $(,)?) => {
+        $(
+            impl$(<$gen>)? futures_io::AsyncWrite for $ty $(where $gen: $bound)? {
+                fn poll_write(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context<'_>,
+                    buf: &[u8],
+                ) -> Poll<Result<usize, std::io::Error>> {
+                    AsyncWrite::poll_write(self, cx, buf)
+                }
+
+                fn poll_write_vectored(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context<'_>,
+                    bufs: &[IoSlice<'_>],
+                ) -> Poll<Result<usize, std::io::Error>> {
+                    AsyncWrite::poll_write_vectored(self, cx, bufs)
+                }
+
+                fn poll_flush(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context<'_>,
+                ) -> Poll<Result<(), std::io::Error>> {
+                    AsyncWrite::poll_flush(self, cx)
+                }
+
+                fn poll_close(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context<'_>,
+                ) -> Poll<Result<(), std::io::Error>> {
+                    AsyncWrite::poll_close(self, cx)
+                }
+            }
+        )*
+    };
+}
+
+impl_futures_read!(
+    TcpStream,
+    UnixStream,
+    ReadHalf<'_>,
+    VsockStream,
+    File,
+    ChildStdout,
+    ChildStderr,
+    BufReader<R> where R: AsyncRead,
+    BufWriter<W> where W: AsyncRead,
+);
+impl_futures_write!(
+    TcpStream,
+    UnixStream,
+    WriteHalf<'_>,
+    VsockStream,
+    File,
+    ChildStdin,
+    BufReader<R> where R: AsyncWrite,
+    BufWriter<W> where W: AsyncWrite,
+);
+
+impl<R: AsyncRead> futures_io::AsyncBufRead for BufReader<R> {
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<&[u8], std::io::Error>> {
+        AsyncBufRead::poll_fill_buf(self, cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        AsyncBufRead::consume(self, amt)
+    }
+}
+
+impl futures_io::AsyncSeek for File {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<Result<u64, std::io::Error>> {
+        File::poll_seek(self.get_mut(), cx, pos)
+    }
+}
@@ -0,0 +1,448 @@
+//! Traits for asynchronous reading and writing
+//!
+//! The I/O types in this crate have inherent `read` and `write` futures, which is all you need when
+//! you know exactly what you're working with. But generic code (buffering, copying, framing) needs
+//! something to be generic over, and that's what [`AsyncRead`] and [`AsyncWrite`] are for.
+//!
+//! They're poll-based, in the same shape as [`Future`](std::future::Future): instead of returning a
+//! future, `poll_read` and friends are called over and over again until they return
+//! [`Poll::Ready`].
+//!
+//! Calling `poll_read` by hand gets old quickly, though. [`AsyncReadExt`] and [`AsyncWriteExt`]
+//! turn the traits back into futures, and take care of the loops that are easy to get wrong, like
+//! reading until a buffer is full or writing until everything has been written.
+//!
+//! ```
+//! use guillotine::io::{AsyncReadExt, AsyncWriteExt};
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let (mut a, mut b) = guillotine::net::unix::pair().unwrap();
+//!     AsyncWriteExt::write_all(&mut a, b"hello, world").await.unwrap();
+//!     AsyncWriteExt::close(&mut a).await.unwrap();
+//!
+//!     let mut buf = [0_u8; 5];
+//!     b.read_exact(&mut buf).await.unwrap();
+//!     assert_eq!(&buf, b"hello");
+//!
+//!     let mut rest = String::new();
+//!     b.read_to_string(&mut rest).await.unwrap();
+//!     assert_eq!(rest, ", world");
+//! });
+//! ```
+//!
+//! With the `bytes` feature enabled, there's also `read_buf` and `write_all_buf`, for reading into
+//! and writing out of the buffer types from the `bytes` crate.
+//!
+//! With the `futures-io` feature enabled, the crate's I/O types also implement the `futures-io`
+//! versions of these traits, so the combinators and codecs from the `futures` ecosystem work with
+//! them directly. That covers the TCP, Unix and vsock streams, the pipes to and from a
+//! [child process](crate::process::Child), [`File`](crate::fs::File) (which is `AsyncSeek`, too),
+//! and [`BufReader`] and [`BufWriter`].
+//!
+//! ```
+//! use guillotine::io::{AsyncRead, AsyncWrite};
+//! use std::future::poll_fn;
+//! use std::pin::Pin;
+//!
+//! async fn echo_once<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> std::io::Result<usize> {
+//!     let mut buf = [0_u8; 64];
+//!     let read = poll_fn(|cx| Pin::new(&mut *stream).poll_read(cx, &mut buf)).await?;
+//!     poll_fn(|cx| Pin::new(&mut *stream).poll_write(cx, &buf[..read])).await
+//! }
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let (mut a, mut b) = guillotine::net::unix::pair().unwrap();
+//!     a.write(b"hello").await.unwrap();
+//!     echo_once(&mut b).await.unwrap();
+//!
+//!     let mut buf = [0_u8; 5];
+//!     a.read(&mut buf).await.unwrap();
+//!     assert_eq!(&buf, b"hello");
+//! });
+//! ```
+
+mod async_fd;
+mod buf_reader;
+mod buf_writer;
+#[cfg(feature = "bytes")]
+mod bytes;
+mod chain;
+mod copy;
+mod ext;
+mod fault;
+pub mod fifo;
+#[cfg(feature = "futures-io")]
+mod futures_io;
+mod pool;
+mod serial;
+mod split;
+mod take;
+mod util;
+
+pub use async_fd::{AsyncFd, AsyncFdReadyGuard};
+pub use buf_reader::BufReader;
+pub use buf_writer::BufWriter;
+#[cfg(feature = "codec")]
+pub(crate) use bytes::poll_read_buf;
+#[cfg(feature = "bytes")]
+pub(crate) use bytes::read_fd_buf;
+#[cfg(feature = "bytes")]
+pub use bytes::{ReadBuf, WriteAllBuf};
+pub use chain::Chain;
+pub use copy::{copy, Copy};
+pub use ext::{
+    AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, Close, Flush, Lines, Read, ReadExact, ReadLine,
+    ReadToEnd, ReadToString, ReadUntil, ReadVectored, Write, WriteAll, WriteVectored,
+};
+pub use fault::{FaultInject, FaultInjectListener, Faults};
+pub use fifo::{
+    open_fifo_reader, open_fifo_reader_persistent, open_fifo_writer, FifoReader, FifoWriter,
+};
+pub(crate) use pool::AlignedBuf;
+pub use pool::{BufferPool, PooledBuffer};
+pub use serial::{FlowControl, Parity, SerialPort, SerialPortBuilder, StopBits};
+pub use split::{split, ReadHalf, WriteHalf};
+pub use take::Take;
+pub use util::{empty, repeat, sink, Empty, Repeat, Sink};
+
+use crate::runtime::Interest;
+use std::io::{ErrorKind, IoSlice, IoSliceMut};
+use std::ops::DerefMut;
+use std::os::unix::prelude::RawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Read bytes asynchronously
+pub trait AsyncRead {
+    /// Attempt to read bytes into `buf`
+    ///
+    /// On success, returns `Poll::Ready(Ok(n))`, where `n` is the number of bytes read. Zero means
+    /// the end of the stream (unless `buf` was empty).
+    ///
+    /// If no data is available yet, returns `Poll::Pending` and arranges for the current task to
+    /// be woken up when it is.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>>;
+
+    /// Attempt to read bytes into a series of buffers, filling each one before moving on to the
+    /// next
+    ///
+    /// The default implementation just reads into the first non-empty buffer. Types that can do
+    /// better (with `readv`, say) should override it.
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        match bufs.iter_mut().find(|buf| !buf.is_empty()) {
+            Some(buf) => self.poll_read(cx, buf),
+            None => self.poll_read(cx, &mut []),
+        }
+    }
+}
+
+/// Write bytes asynchronously
+pub trait AsyncWrite {
+    /// Attempt to write bytes from `buf`
+    ///
+    /// On success, returns `Poll::Ready(Ok(n))`, where `n` is the number of bytes written.
+    ///
+    /// If the object isn't ready for writing, returns `Poll::Pending` and arranges for the current
+    /// task to be woken up when it is.
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>>;
+
+    /// Attempt to write bytes from a series of buffers, in order
+    ///
+    /// This lets a header and a body go out together without first copying them into one buffer.
+    /// The default implementation just writes the first non-empty buffer. Types that can do better
+    /// (with `writev`, say) should override it, along with [`AsyncWrite::is_write_vectored`].
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let buf = bufs
+            .iter()
+            .find(|buf| !buf.is_empty())
+            .map_or(&[][..], |buf| &**buf);
+        self.poll_write(cx, buf)
+    }
+
+    /// Whether this writer has an efficient [`AsyncWrite::poll_write_vectored`]
+    ///
+    /// If it doesn't, callers are better off copying into one buffer themselves.
+    fn is_write_vectored(&self) -> bool {
+        false
+    }
+
+    /// Attempt to flush any buffered data
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>>;
+
+    /// Attempt to close the object, flushing first
+    ///
+    /// For sockets, this shuts down the write half, so the peer sees the end of the stream.
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>>;
+}
+
+impl<T: ?Sized + AsyncRead + Unpin> AsyncRead for &mut T {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_read_vectored(cx, bufs)
+    }
+}
+
+impl<T: ?Sized + AsyncRead + Unpin> AsyncRead for Box<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_read_vectored(cx, bufs)
+    }
+}
+
+impl<P> AsyncRead for Pin<P>
+where
+    P: DerefMut + Unpin,
+    P::Target: AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.get_mut().as_mut().poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.get_mut().as_mut().poll_read_vectored(cx, bufs)
+    }
+}
+
+impl<T: ?Sized + AsyncWrite + Unpin> AsyncWrite for &mut T {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        (**self).is_write_vectored()
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut **self).poll_flush(cx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut **self).poll_close(cx)
+    }
+}
+
+impl<T: ?Sized + AsyncWrite + Unpin> AsyncWrite for Box<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut **self).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        (**self).is_write_vectored()
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut **self).poll_flush(cx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut **self).poll_close(cx)
+    }
+}
+
+impl<P> AsyncWrite for Pin<P>
+where
+    P: DerefMut + Unpin,
+    P::Target: AsyncWrite,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.get_mut().as_mut().poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.get_mut().as_mut().poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        (**self).is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        self.get_mut().as_mut().poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        self.get_mut().as_mut().poll_close(cx)
+    }
+}
+
+/// Read bytes asynchronously, out of an internal buffer
+///
+/// This is what makes it possible to look for a delimiter without reading past it: the caller gets
+/// to see what's in the buffer, and then says how much of it they actually used.
+pub trait AsyncBufRead: AsyncRead {
+    /// Attempt to get the contents of the internal buffer, filling it from the underlying reader
+    /// first if it's empty
+    ///
+    /// An empty buffer means the end of the stream.
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<&[u8], std::io::Error>>;
+
+    /// Mark `amt` bytes of the buffer as used, so they aren't returned by `poll_fill_buf` again
+    fn consume(self: Pin<&mut Self>, amt: usize);
+}
+
+impl<T: ?Sized + AsyncBufRead + Unpin> AsyncBufRead for &mut T {
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<&[u8], std::io::Error>> {
+        Pin::new(&mut **self.get_mut()).poll_fill_buf(cx)
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut **self).consume(amt)
+    }
+}
+
+impl<T: ?Sized + AsyncBufRead + Unpin> AsyncBufRead for Box<T> {
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<&[u8], std::io::Error>> {
+        Pin::new(&mut **self.get_mut()).poll_fill_buf(cx)
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut **self).consume(amt)
+    }
+}
+
+impl<P> AsyncBufRead for Pin<P>
+where
+    P: DerefMut + Unpin,
+    P::Target: AsyncBufRead,
+{
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<&[u8], std::io::Error>> {
+        self.get_mut().as_mut().poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().as_mut().consume(amt)
+    }
+}
+
+/// Run a non-blocking operation on a file descriptor, registering the file descriptor with the
+/// runtime if the operation would block
+///
+/// The socket and timer futures in this crate, and the trait methods, all come down to this.
+/// Nothing keeps track of whether it's registered already, so this registers every time it would
+/// block, and relies on the runtime ignoring duplicate registrations.
+///
+/// `interest` says which way the operation goes. If the last operation that way said it would
+/// block, and epoll hasn't said anything since, this one would too, so it isn't even tried.
+pub(crate) fn poll_fd<T>(
+    cx: &Context<'_>,
+    fd: RawFd,
+    interest: Interest,
+    op: impl FnOnce() -> Result<T, std::io::Error>,
+) -> Poll<Result<T, std::io::Error>> {
+    if !crate::runtime::might_be_ready(fd, interest) {
+        crate::runtime::register_file_descriptor(cx, &fd, interest)?;
+        return Poll::Pending;
+    }
+    match op() {
+        Err(err) if err.kind() == ErrorKind::WouldBlock => {
+            crate::runtime::register_file_descriptor(cx, &fd, interest)?;
+            crate::runtime::clear_readiness(fd, interest);
+            Poll::Pending
+        }
+        result => Poll::Ready(result),
+    }
+}
@@ -0,0 +1,256 @@
+use std::alloc::Layout;
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::rc::Rc;
+
+/// The alignment of buffers from [`BufferPool::new`], a cache line
+const DEFAULT_ALIGNMENT: usize = 64;
+
+/// A pool of fixed-size buffers that get reused instead of reallocated
+///
+/// A server with tens of thousands of connections that each allocate their own 64 KiB buffer
+/// spends a lot of time in the allocator, and a lot of memory on buffers that are mostly sitting
+/// idle. With a pool, each handler [leases](BufferPool::lease) a buffer when it needs one, and the
+/// buffer goes back into the pool when the lease is dropped.
+///
+/// The pool is cheap to clone; clones share the same buffers. Like the rest of the runtime it's
+/// single-threaded, so it can't be sent to other threads.
+///
+/// ```
+/// use guillotine::io::{AsyncReadExt, BufferPool};
+///
+/// let pool = BufferPool::new(64 * 1024);
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async move {
+///     let (mut a, mut b) = guillotine::net::unix::pair().unwrap();
+///     a.write(b"hello").await.unwrap();
+///
+///     let mut buf = pool.lease();
+///     let read = b.read(buf.spare_mut()).await.unwrap();
+///     buf.set_len(read);
+///     assert_eq!(&buf[..], b"hello");
+///
+///     drop(buf);
+///     assert_eq!(pool.idle(), 1);
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct BufferPool {
+    inner: Rc<PoolInner>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    layout: Layout,
+    /// The most buffers to keep around when they aren't leased; any more are freed
+    max_idle: usize,
+    /// Buffers that are ready to be leased again
+    idle: RefCell<Vec<AlignedBuf>>,
+}
+
+impl BufferPool {
+    /// Create a new pool of `buffer_size`-byte buffers, aligned to a cache line
+    pub fn new(buffer_size: usize) -> Self {
+        Self::with_alignment(buffer_size, DEFAULT_ALIGNMENT)
+    }
+
+    /// Create a new pool of `buffer_size`-byte buffers with the provided alignment
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alignment` isn't a power of two, or if `buffer_size` is zero.
+    pub fn with_alignment(buffer_size: usize, alignment: usize) -> Self {
+        assert!(buffer_size > 0, "buffers in a pool can't be empty");
+        let layout = Layout::from_size_align(buffer_size, alignment)
+            .expect("alignment must be a power of two");
+        Self {
+            inner: Rc::new(PoolInner {
+                layout,
+                max_idle: usize::MAX,
+                idle: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Limit how many idle buffers the pool holds on to
+    ///
+    /// Buffers that come back when the pool already has this many are freed instead. By default,
+    /// there is no limit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool has already been cloned.
+    pub fn max_idle(mut self, max_idle: usize) -> Self {
+        let inner = Rc::get_mut(&mut self.inner).expect("max_idle must be set before cloning");
+        inner.max_idle = max_idle;
+        self
+    }
+
+    /// Lease a buffer from the pool, allocating a new one if none are idle
+    ///
+    /// The buffer starts out empty, and goes back into the pool when it's dropped.
+    pub fn lease(&self) -> PooledBuffer {
+        let buf = self
+            .inner
+            .idle
+            .borrow_mut()
+            .pop()
+            .unwrap_or_else(|| AlignedBuf::new(self.inner.layout));
+        PooledBuffer {
+            buf: Some(buf),
+            len: 0,
+            pool: self.inner.clone(),
+        }
+    }
+
+    /// The size of each buffer
+    pub fn buffer_size(&self) -> usize {
+        self.inner.layout.size()
+    }
+
+    /// How many buffers are sitting in the pool, waiting to be leased
+    pub fn idle(&self) -> usize {
+        self.inner.idle.borrow().len()
+    }
+}
+
+/// A buffer leased from a [`BufferPool`]
+///
+/// It works like a `Vec<u8>` that can't grow: it has a fixed capacity (the pool's buffer size) and
+/// a length, and it dereferences to the filled part. Fill it by reading into
+/// [`spare_mut`](PooledBuffer::spare_mut) and then calling [`set_len`](PooledBuffer::set_len), or
+/// (with the `bytes` feature) by handing it to `read_buf`, which does both.
+#[derive(Debug)]
+pub struct PooledBuffer {
+    /// Only `None` while being dropped
+    buf: Option<AlignedBuf>,
+    len: usize,
+    pool: Rc<PoolInner>,
+}
+
+impl PooledBuffer {
+    /// How many bytes the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.pool.layout.size()
+    }
+
+    /// The part of the buffer after the filled part
+    pub fn spare_mut(&mut self) -> &mut [u8] {
+        let len = self.len;
+        &mut self.full_mut()[len..]
+    }
+
+    /// Set how much of the buffer is filled
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is more than the capacity.
+    pub fn set_len(&mut self, len: usize) {
+        assert!(
+            len <= self.capacity(),
+            "length is past the end of the buffer"
+        );
+        self.len = len;
+    }
+
+    /// Empty the buffer, keeping its capacity
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn full(&self) -> &[u8] {
+        self.buf
+            .as_ref()
+            .expect("buffer is only taken on drop")
+            .as_slice()
+    }
+
+    fn full_mut(&mut self) -> &mut [u8] {
+        self.buf
+            .as_mut()
+            .expect("buffer is only taken on drop")
+            .as_mut_slice()
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.full()[..self.len]
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let len = self.len;
+        &mut self.full_mut()[..len]
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            let mut idle = self.pool.idle.borrow_mut();
+            if idle.len() < self.pool.max_idle {
+                idle.push(buf);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "bytes")]
+unsafe impl bytes::BufMut for PooledBuffer {
+    fn remaining_mut(&self) -> usize {
+        self.capacity() - self.len
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        self.set_len(self.len + cnt);
+    }
+
+    fn chunk_mut(&mut self) -> &mut bytes::buf::UninitSlice {
+        bytes::buf::UninitSlice::new(self.spare_mut())
+    }
+}
+
+/// A heap allocation with a particular alignment
+///
+/// It's zeroed when it's allocated, so it's always safe to look at, even before anything has been
+/// read into it.
+#[derive(Debug)]
+pub(crate) struct AlignedBuf {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+// The allocation is owned by the `AlignedBuf`, like a `Box<[u8]>`'s is
+unsafe impl Send for AlignedBuf {}
+
+impl AlignedBuf {
+    pub(crate) fn new(layout: Layout) -> Self {
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| std::alloc::handle_alloc_error(layout));
+        Self { ptr, layout }
+    }
+
+    pub(crate) fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
@@ -0,0 +1,324 @@
+//! Serial ports (and other terminal-like character devices)
+
+use super::{poll_fd, AsyncRead, AsyncWrite};
+use crate::runtime::Interest;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::prelude::{AsRawFd, FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A serial port, for talking to sensors, radios, and microcontrollers
+///
+/// The port is opened non-blocking, and configured for raw bytes: no echo, no line editing, no
+/// translating newlines. Reading and writing go through the [`AsyncRead`] and [`AsyncWrite`]
+/// traits.
+///
+/// ```no_run
+/// use guillotine::io::{AsyncReadExt, AsyncWriteExt, Parity, SerialPort};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let mut port = SerialPort::builder("/dev/ttyUSB0")
+///         .baud_rate(115_200)
+///         .parity(Parity::Even)
+///         .open()
+///         .unwrap();
+///
+///     port.write_all(b"AT\r").await.unwrap();
+///     let mut buf = [0; 64];
+///     let read = port.read(&mut buf).await.unwrap();
+///     println!("{:?}", &buf[..read]);
+/// });
+/// ```
+#[derive(Debug)]
+pub struct SerialPort {
+    file: File,
+}
+
+/// Whether (and how) each character carries a parity bit
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Parity {
+    /// No parity bit
+    None,
+    /// The parity bit makes the number of set bits odd
+    Odd,
+    /// The parity bit makes the number of set bits even
+    Even,
+}
+
+/// How many stop bits end each character
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StopBits {
+    /// One stop bit
+    One,
+    /// Two stop bits
+    Two,
+}
+
+/// How the two ends tell each other to slow down
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FlowControl {
+    /// They don't
+    None,
+    /// With the RTS and CTS lines
+    Hardware,
+    /// With XON and XOFF characters in the data
+    Software,
+}
+
+/// Settings for opening a [`SerialPort`], created by [`SerialPort::builder`]
+///
+/// Unless told otherwise, ports are opened at 9600 baud, with eight data bits, no parity, one stop
+/// bit, and no flow control.
+#[derive(Clone, Debug)]
+pub struct SerialPortBuilder {
+    path: PathBuf,
+    baud_rate: u32,
+    data_bits: u8,
+    parity: Parity,
+    stop_bits: StopBits,
+    flow_control: FlowControl,
+}
+
+impl SerialPortBuilder {
+    /// Set the speed, in bits per second
+    ///
+    /// Only the standard rates (9600, 115200, and so on) are supported; anything else fails when
+    /// the port is opened.
+    pub fn baud_rate(mut self, baud_rate: u32) -> Self {
+        self.baud_rate = baud_rate;
+        self
+    }
+
+    /// Set how many bits make up a character, from 5 to 8
+    pub fn data_bits(mut self, data_bits: u8) -> Self {
+        self.data_bits = data_bits;
+        self
+    }
+
+    /// Set the parity
+    pub fn parity(mut self, parity: Parity) -> Self {
+        self.parity = parity;
+        self
+    }
+
+    /// Set the number of stop bits
+    pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = stop_bits;
+        self
+    }
+
+    /// Set the flow control
+    pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    /// Open and configure the port
+    pub fn open(self) -> Result<SerialPort, std::io::Error> {
+        let path = CString::new(self.path.as_os_str().as_bytes())
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "path contains a nul byte"))?;
+        let fd = unsafe {
+            libc::open(
+                path.as_ptr(),
+                libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK | libc::O_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        let port = SerialPort {
+            file: unsafe { File::from_raw_fd(fd) },
+        };
+        port.configure(&self)?;
+        Ok(port)
+    }
+}
+
+impl SerialPort {
+    /// Open a serial port at the given baud rate, with eight data bits, no parity, one stop bit,
+    /// and no flow control
+    pub fn open(path: impl AsRef<Path>, baud_rate: u32) -> Result<Self, std::io::Error> {
+        Self::builder(path).baud_rate(baud_rate).open()
+    }
+
+    /// Start configuring a serial port, to open it with something other than the usual settings
+    pub fn builder(path: impl AsRef<Path>) -> SerialPortBuilder {
+        SerialPortBuilder {
+            path: path.as_ref().to_path_buf(),
+            baud_rate: 9600,
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+        }
+    }
+
+    /// Throw away anything that has been received but not read, and anything that has been
+    /// written but not sent
+    pub fn discard_buffers(&self) -> Result<(), std::io::Error> {
+        if unsafe { libc::tcflush(self.file.as_raw_fd(), libc::TCIOFLUSH) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Apply a builder's settings with termios
+    fn configure(&self, settings: &SerialPortBuilder) -> Result<(), std::io::Error> {
+        let speed = baud_rate_to_speed(settings.baud_rate)?;
+        let size = match settings.data_bits {
+            5 => libc::CS5,
+            6 => libc::CS6,
+            7 => libc::CS7,
+            8 => libc::CS8,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "data bits must be between 5 and 8",
+                ))
+            }
+        };
+
+        let fd = self.file.as_raw_fd();
+        unsafe {
+            let mut termios: MaybeUninit<libc::termios> = MaybeUninit::uninit();
+            if libc::tcgetattr(fd, termios.as_mut_ptr()) < 0 {
+                return Err(Error::last_os_error());
+            }
+            let mut termios = termios.assume_init();
+
+            libc::cfmakeraw(&mut termios);
+            // Don't hang up the line when the port is closed, and do receive
+            termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+
+            termios.c_cflag &= !libc::CSIZE;
+            termios.c_cflag |= size;
+
+            termios.c_cflag &= !(libc::PARENB | libc::PARODD);
+            match settings.parity {
+                Parity::None => {}
+                Parity::Odd => termios.c_cflag |= libc::PARENB | libc::PARODD,
+                Parity::Even => termios.c_cflag |= libc::PARENB,
+            }
+
+            match settings.stop_bits {
+                StopBits::One => termios.c_cflag &= !libc::CSTOPB,
+                StopBits::Two => termios.c_cflag |= libc::CSTOPB,
+            }
+
+            termios.c_cflag &= !libc::CRTSCTS;
+            termios.c_iflag &= !(libc::IXON | libc::IXOFF | libc::IXANY);
+            match settings.flow_control {
+                FlowControl::None => {}
+                FlowControl::Hardware => termios.c_cflag |= libc::CRTSCTS,
+                FlowControl::Software => termios.c_iflag |= libc::IXON | libc::IXOFF,
+            }
+
+            if libc::cfsetispeed(&mut termios, speed) < 0
+                || libc::cfsetospeed(&mut termios, speed) < 0
+            {
+                return Err(Error::last_os_error());
+            }
+            if libc::tcsetattr(fd, libc::TCSANOW, &termios) < 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SerialPort {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.file.as_raw_fd());
+    }
+}
+
+/// Turn a baud rate into one of termios's speed constants
+fn baud_rate_to_speed(baud_rate: u32) -> Result<libc::speed_t, std::io::Error> {
+    let speed = match baud_rate {
+        50 => libc::B50,
+        75 => libc::B75,
+        110 => libc::B110,
+        134 => libc::B134,
+        150 => libc::B150,
+        200 => libc::B200,
+        300 => libc::B300,
+        600 => libc::B600,
+        1200 => libc::B1200,
+        1800 => libc::B1800,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        460800 => libc::B460800,
+        500000 => libc::B500000,
+        576000 => libc::B576000,
+        921600 => libc::B921600,
+        1000000 => libc::B1000000,
+        1152000 => libc::B1152000,
+        1500000 => libc::B1500000,
+        2000000 => libc::B2000000,
+        2500000 => libc::B2500000,
+        3000000 => libc::B3000000,
+        3500000 => libc::B3500000,
+        4000000 => libc::B4000000,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unsupported baud rate {}", baud_rate),
+            ))
+        }
+    };
+    Ok(speed)
+}
+
+impl AsRawFd for SerialPort {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl AsyncRead for SerialPort {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        use std::io::Read;
+
+        let mut file = &self.file;
+        poll_fd(cx, file.as_raw_fd(), Interest::READABLE, || file.read(buf))
+    }
+}
+
+impl AsyncWrite for SerialPort {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        use std::io::Write;
+
+        let mut file = &self.file;
+        poll_fd(cx, file.as_raw_fd(), Interest::WRITABLE, || file.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // Writes go straight to the driver. Waiting for them to actually go out over the wire
+        // (`tcdrain`) would block, so this doesn't.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
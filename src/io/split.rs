@@ -0,0 +1,176 @@
+use super::{AsyncRead, AsyncWrite};
+use std::cell::RefCell;
+use std::io::{IoSlice, IoSliceMut};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+/// Split a stream into a read half and a write half
+///
+/// This works for anything that can be read from and written to. The halves share the stream, so
+/// one task can be reading while another is writing. Use [`ReadHalf::unsplit`] to put the stream
+/// back together.
+///
+/// The runtime only has one thread, so the halves don't need a lock, but it also means they can't
+/// be sent to other threads.
+///
+/// ```
+/// use guillotine::io::{AsyncReadExt, AsyncWriteExt};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (a, mut b) = guillotine::net::unix::pair().unwrap();
+///     let (mut reader, mut writer) = guillotine::io::split(a);
+///
+///     writer.write_all(b"hello").await.unwrap();
+///     b.write(b"world").await.unwrap();
+///
+///     let mut buf = [0_u8; 5];
+///     reader.read_exact(&mut buf).await.unwrap();
+///     assert_eq!(&buf, b"world");
+///
+///     let _a = reader.unsplit(writer);
+/// });
+/// ```
+///
+/// Each half can go to its own task, even while both of them are waiting on the stream:
+///
+/// ```
+/// use guillotine::io::{AsyncReadExt, AsyncWriteExt};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (a, mut b) = guillotine::net::unix::pair().unwrap();
+///     let (mut reader, mut writer) = guillotine::io::split(a);
+///
+///     let reading = guillotine::task::spawn(async move {
+///         let mut buf = [0_u8; 5];
+///         reader.read_exact(&mut buf).await.unwrap();
+///         buf
+///     });
+///     // More than the socket can hold, so this waits for `b` to read some of it
+///     let writing = guillotine::task::spawn(async move {
+///         writer.write_all(&vec![7; 1024 * 1024]).await.unwrap();
+///     });
+///
+///     guillotine::time::sleep(std::time::Duration::from_millis(10))
+///         .await
+///         .unwrap();
+///     b.write_all(b"world").await.unwrap();
+///     assert_eq!(&reading.await.unwrap(), b"world");
+///
+///     let mut buf = vec![0_u8; 1024 * 1024];
+///     b.read_exact(&mut buf).await.unwrap();
+///     writing.await.unwrap();
+/// });
+/// ```
+pub fn split<T>(stream: T) -> (ReadHalf<T>, WriteHalf<T>)
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let stream = Rc::new(RefCell::new(stream));
+    (
+        ReadHalf {
+            stream: stream.clone(),
+        },
+        WriteHalf { stream },
+    )
+}
+
+/// The read half of a stream, created by [`split`]
+#[derive(Debug)]
+pub struct ReadHalf<T> {
+    stream: Rc<RefCell<T>>,
+}
+
+/// The write half of a stream, created by [`split`]
+#[derive(Debug)]
+pub struct WriteHalf<T> {
+    stream: Rc<RefCell<T>>,
+}
+
+impl<T> ReadHalf<T> {
+    /// Whether this half and `other` came from the same call to [`split`]
+    pub fn is_pair_of(&self, other: &WriteHalf<T>) -> bool {
+        Rc::ptr_eq(&self.stream, &other.stream)
+    }
+
+    /// Put the two halves back together
+    ///
+    /// # Panics
+    ///
+    /// Panics if the halves didn't come from the same call to [`split`].
+    pub fn unsplit(self, other: WriteHalf<T>) -> T {
+        assert!(
+            self.is_pair_of(&other),
+            "tried to unsplit halves of two different streams"
+        );
+        drop(other);
+        match Rc::try_unwrap(self.stream) {
+            Ok(stream) => stream.into_inner(),
+            // Both halves were just consumed, and they held the only two references
+            Err(_) => unreachable!("the halves of a split stream are the only references to it"),
+        }
+    }
+}
+
+impl<T> WriteHalf<T> {
+    /// Whether this half and `other` came from the same call to [`split`]
+    pub fn is_pair_of(&self, other: &ReadHalf<T>) -> bool {
+        other.is_pair_of(self)
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for ReadHalf<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut stream = self.stream.borrow_mut();
+        Pin::new(&mut *stream).poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut stream = self.stream.borrow_mut();
+        Pin::new(&mut *stream).poll_read_vectored(cx, bufs)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for WriteHalf<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut stream = self.stream.borrow_mut();
+        Pin::new(&mut *stream).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut stream = self.stream.borrow_mut();
+        Pin::new(&mut *stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.borrow().is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        let mut stream = self.stream.borrow_mut();
+        Pin::new(&mut *stream).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        let mut stream = self.stream.borrow_mut();
+        Pin::new(&mut *stream).poll_close(cx)
+    }
+}
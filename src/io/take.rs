@@ -0,0 +1,123 @@
+use super::{AsyncBufRead, AsyncRead};
+use pin_project::pin_project;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A reader that stops after a certain number of bytes, created by
+/// [`AsyncReadExt::take`](super::AsyncReadExt::take)
+///
+/// Handy for reading a body whose length is known up front, like one with a `Content-Length`.
+///
+/// ```
+/// use guillotine::io::{AsyncReadExt, BufReader};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (mut a, b) = guillotine::net::unix::pair().unwrap();
+///     a.write(b"helloGET / HTTP/1.1").await.unwrap();
+///
+///     let mut reader = BufReader::new(b);
+///     let mut body = String::new();
+///     (&mut reader).take(5).read_to_string(&mut body).await.unwrap();
+///     assert_eq!(body, "hello");
+/// });
+/// ```
+#[pin_project]
+#[derive(Debug)]
+pub struct Take<R> {
+    #[pin]
+    inner: R,
+    /// How many more bytes can be read
+    limit: u64,
+}
+
+impl<R> Take<R> {
+    pub(crate) fn new(inner: R, limit: u64) -> Self {
+        Self { inner, limit }
+    }
+
+    /// How many more bytes can be read before hitting the limit
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Change the limit
+    ///
+    /// The limit is counted from now, not from when the `Take` was created.
+    pub fn set_limit(&mut self, limit: u64) {
+        self.limit = limit;
+    }
+
+    /// Get access to the wrapped reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get mutable access to the wrapped reader
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Get pinned mutable access to the wrapped reader
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut R> {
+        self.project().inner
+    }
+
+    /// Unwrap the reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for Take<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let projected = self.project();
+        if *projected.limit == 0 {
+            return Poll::Ready(Ok(0));
+        }
+
+        let max = buf
+            .len()
+            .min(usize::try_from(*projected.limit).unwrap_or(usize::MAX));
+        match projected.inner.poll_read(cx, &mut buf[..max]) {
+            Poll::Ready(Ok(read)) => {
+                *projected.limit -= read as u64;
+                Poll::Ready(Ok(read))
+            }
+            other => other,
+        }
+    }
+}
+
+impl<R: AsyncBufRead> AsyncBufRead for Take<R> {
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<&[u8], std::io::Error>> {
+        let projected = self.project();
+        if *projected.limit == 0 {
+            return Poll::Ready(Ok(&[]));
+        }
+
+        match projected.inner.poll_fill_buf(cx) {
+            Poll::Ready(Ok(buf)) => {
+                let max = buf
+                    .len()
+                    .min(usize::try_from(*projected.limit).unwrap_or(usize::MAX));
+                Poll::Ready(Ok(&buf[..max]))
+            }
+            other => other,
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let projected = self.project();
+        let amt = (amt as u64).min(*projected.limit);
+        *projected.limit -= amt;
+        projected.inner.consume(amt as usize);
+    }
+}
@@ -0,0 +1,103 @@
+//! Readers and writers that don't need anything underneath them
+
+use super::{AsyncBufRead, AsyncRead, AsyncWrite};
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A reader that is always at the end of the stream, created by [`empty`]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Empty {
+    _private: (),
+}
+
+/// Create a reader that is always at the end of the stream
+pub fn empty() -> Empty {
+    Empty { _private: () }
+}
+
+impl AsyncRead for Empty {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Poll::Ready(Ok(0))
+    }
+}
+
+impl AsyncBufRead for Empty {
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<&[u8], std::io::Error>> {
+        Poll::Ready(Ok(&[]))
+    }
+
+    fn consume(self: Pin<&mut Self>, _amt: usize) {}
+}
+
+/// A writer that throws away everything written to it, created by [`sink`]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Sink {
+    _private: (),
+}
+
+/// Create a writer that throws away everything written to it
+pub fn sink() -> Sink {
+    Sink { _private: () }
+}
+
+impl AsyncWrite for Sink {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Poll::Ready(Ok(bufs.iter().map(|buf| buf.len()).sum()))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A reader that produces the same byte forever, created by [`repeat`]
+#[derive(Copy, Clone, Debug)]
+pub struct Repeat {
+    byte: u8,
+}
+
+/// Create a reader that produces `byte` over and over again, forever
+///
+/// Combine it with [`take`](super::AsyncReadExt::take) to get a fixed amount of filler.
+pub fn repeat(byte: u8) -> Repeat {
+    Repeat { byte }
+}
+
+impl AsyncRead for Repeat {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        buf.fill(self.byte);
+        Poll::Ready(Ok(buf.len()))
+    }
+}
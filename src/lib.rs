@@ -1,6 +1,33 @@
 #![doc = include_str!("../README.md")]
+#![allow(clippy::needless_doctest_main)]
 
+#[cfg(feature = "async-io")]
+pub mod async_io;
+#[cfg(feature = "codec")]
+pub mod codec;
+#[cfg(feature = "tokio-compat")]
+pub mod compat;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fs;
+pub mod future;
+#[cfg(feature = "hyper")]
+pub mod hyper;
+pub mod io;
 pub mod net;
+pub mod process;
+mod rng;
 pub mod runtime;
+pub mod signal;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod sync;
 pub mod task;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 pub mod time;
+mod trace;
+pub mod tty;
+
+pub use error::Error;
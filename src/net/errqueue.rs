@@ -0,0 +1,106 @@
+use super::sys::ControlMessage;
+use super::timestamping::Timestamps;
+use std::mem::size_of;
+use std::net::SocketAddr;
+
+/// Where an [`ExtendedError`] came from
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ErrorOrigin {
+    /// No origin was given
+    None,
+    /// The local network stack generated the error (for example, a local `EMSGSIZE`)
+    Local,
+    /// An ICMP message from somewhere along the path
+    Icmp,
+    /// An ICMPv6 message from somewhere along the path
+    Icmp6,
+    /// A transmit status report, like a transmit timestamp
+    TxStatus,
+    /// Something newer than we know about
+    Other(u8),
+}
+
+impl From<u8> for ErrorOrigin {
+    fn from(origin: u8) -> Self {
+        match origin {
+            libc::SO_EE_ORIGIN_NONE => ErrorOrigin::None,
+            libc::SO_EE_ORIGIN_LOCAL => ErrorOrigin::Local,
+            libc::SO_EE_ORIGIN_ICMP => ErrorOrigin::Icmp,
+            libc::SO_EE_ORIGIN_ICMP6 => ErrorOrigin::Icmp6,
+            libc::SO_EE_ORIGIN_TXSTATUS => ErrorOrigin::TxStatus,
+            other => ErrorOrigin::Other(other),
+        }
+    }
+}
+
+/// An error pulled off of a socket's error queue
+///
+/// With `IP_RECVERR` enabled, the kernel queues up ICMP errors (destination unreachable, packet too
+/// big, and friends) instead of just setting a pending error on the socket. Each one comes with a
+/// `sock_extended_err`, which this is a slightly friendlier version of.
+#[derive(Debug)]
+pub struct ExtendedError {
+    /// The error itself, like `ECONNREFUSED` or `EMSGSIZE`
+    pub error: std::io::Error,
+    /// Who generated the error
+    pub origin: ErrorOrigin,
+    /// The ICMP type, if the origin was ICMP
+    pub icmp_type: u8,
+    /// The ICMP code, if the origin was ICMP
+    pub icmp_code: u8,
+    /// Extra information about the error
+    ///
+    /// For `EMSGSIZE`, this is the MTU of the path. See [`ExtendedError::path_mtu`].
+    pub info: u32,
+    /// The address of the node that reported the error, if there was one
+    pub offender: Option<SocketAddr>,
+    /// Transmit timestamps, if this is a timestamping report (origin [`ErrorOrigin::TxStatus`])
+    ///
+    /// See [`UdpSocket::set_timestamping`](super::UdpSocket::set_timestamping).
+    pub timestamps: Option<Timestamps>,
+}
+
+impl ExtendedError {
+    /// The path MTU the kernel discovered, if this error is a "packet too big"
+    pub fn path_mtu(&self) -> Option<u32> {
+        if self.error.raw_os_error() == Some(libc::EMSGSIZE) {
+            Some(self.info)
+        } else {
+            None
+        }
+    }
+
+    /// Try to read an extended error out of an `IP_RECVERR` or `IPV6_RECVERR` control message
+    pub(crate) fn from_control_message(message: &ControlMessage<'_>) -> Option<Self> {
+        let is_recverr = (message.level == libc::SOL_IP && message.ty == libc::IP_RECVERR)
+            || (message.level == libc::SOL_IPV6 && message.ty == libc::IPV6_RECVERR);
+        if !is_recverr || message.data.len() < size_of::<libc::sock_extended_err>() {
+            return None;
+        }
+
+        unsafe {
+            let ee = message.data.as_ptr() as *const libc::sock_extended_err;
+            let extended = std::ptr::read_unaligned(ee);
+
+            // The offending address is tacked on right after the `sock_extended_err`, but only if
+            // there's room for at least the address family.
+            let offender_offset = size_of::<libc::sock_extended_err>();
+            let offender = if message.data.len() >= offender_offset + size_of::<libc::sa_family_t>()
+            {
+                super::sys::sockaddr_to_socket_addr(libc::SO_EE_OFFENDER(ee))
+            } else {
+                None
+            };
+
+            Some(ExtendedError {
+                error: std::io::Error::from_raw_os_error(extended.ee_errno as i32),
+                origin: ErrorOrigin::from(extended.ee_origin),
+                icmp_type: extended.ee_type,
+                icmp_code: extended.ee_code,
+                info: extended.ee_info,
+                offender,
+                timestamps: None,
+            })
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Unprivileged ICMP echo ("ping") sockets
+//!
+//! Linux lets regular users send ICMP echo requests through `SOCK_DGRAM` + `IPPROTO_ICMP` sockets,
+//! as long as their group is inside the `net.ipv4.ping_group_range` sysctl. The kernel takes care
+//! of the checksum and the identifier, and only hands back the echo replies that belong to this
+//! socket.
+//!
+//! ```no_run
+//! use std::net::{IpAddr, Ipv4Addr};
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let socket = guillotine::net::PingSocket::v4().unwrap();
+//!     socket.send_ping(IpAddr::V4(Ipv4Addr::LOCALHOST), 1, b"hello").await.unwrap();
+//!
+//!     let mut buf = [0_u8; 64];
+//!     let (len, reply) = socket.recv_reply(&mut buf).await.unwrap();
+//!     assert_eq!(reply.sequence, 1);
+//!     assert_eq!(&buf[..len], b"hello");
+//! });
+//! ```
+
+use super::socket::{Retry, Socket};
+use super::sys;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+use std::os::unix::prelude::{AsRawFd, RawFd};
+
+/// The length of an ICMP echo header: type, code, checksum, identifier, sequence
+const HEADER_LEN: usize = 8;
+
+/// An ICMP echo reply
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct EchoReply {
+    /// Who sent the reply
+    pub source: IpAddr,
+    /// The identifier of the echo request this is a reply to
+    pub identifier: u16,
+    /// The sequence number of the echo request this is a reply to
+    pub sequence: u16,
+}
+
+/// A socket for sending ICMP echo requests and receiving their replies
+#[derive(Debug)]
+pub struct PingSocket {
+    socket: Socket,
+    /// Whether this is an ICMPv6 socket
+    v6: bool,
+}
+
+impl PingSocket {
+    /// Create a new ICMP (IPv4) ping socket
+    pub fn v4() -> Result<Self, std::io::Error> {
+        let socket = Socket::new(libc::AF_INET, libc::SOCK_DGRAM, libc::IPPROTO_ICMP)?;
+        Ok(Self { socket, v6: false })
+    }
+
+    /// Create a new ICMPv6 ping socket
+    pub fn v6() -> Result<Self, std::io::Error> {
+        let socket = Socket::new(libc::AF_INET6, libc::SOCK_DGRAM, libc::IPPROTO_ICMPV6)?;
+        Ok(Self { socket, v6: true })
+    }
+
+    /// Bind the socket to a local address
+    ///
+    /// The port of the address becomes the identifier of every echo request sent on this socket.
+    /// Without binding, the kernel picks an identifier the first time a request is sent.
+    pub fn bind(&self, addr: SocketAddr) -> Result<(), std::io::Error> {
+        match sys::socket_addr_to_raw(addr) {
+            sys::RawSocketAddr::V4(raw) => self.socket.bind(&raw),
+            sys::RawSocketAddr::V6(raw) => self.socket.bind(&raw),
+        }
+    }
+
+    /// The identifier the kernel is using for this socket's echo requests
+    pub fn identifier(&self) -> Result<u16, std::io::Error> {
+        let addr = self.socket.local_addr::<libc::sockaddr_storage>()?;
+        let addr =
+            unsafe { sys::sockaddr_to_socket_addr(&addr as *const _ as *const libc::sockaddr) };
+        addr.map(|addr| addr.port())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "unexpected address family"))
+    }
+
+    /// Send an echo request, as a _future_.
+    ///
+    /// Returns the number of bytes of payload that were sent.
+    pub async fn send_ping(
+        &self,
+        addr: IpAddr,
+        sequence: u16,
+        payload: &[u8],
+    ) -> Result<usize, std::io::Error> {
+        // The kernel fills in the identifier and the checksum, so all we need is the type and the
+        // sequence number.
+        let echo_request = if self.v6 { 128 } else { 8 };
+        let mut packet = Vec::with_capacity(HEADER_LEN + payload.len());
+        packet.extend_from_slice(&[echo_request, 0, 0, 0, 0, 0]);
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(payload);
+
+        let sent = match sys::socket_addr_to_raw(SocketAddr::new(addr, 0)) {
+            sys::RawSocketAddr::V4(raw) => {
+                Retry::writing(self.socket.as_raw_fd(), || {
+                    self.socket.send_to(&packet, &raw)
+                })
+                .await?
+            }
+            sys::RawSocketAddr::V6(raw) => {
+                Retry::writing(self.socket.as_raw_fd(), || {
+                    self.socket.send_to(&packet, &raw)
+                })
+                .await?
+            }
+        };
+        Ok(sent.saturating_sub(HEADER_LEN))
+    }
+
+    /// Receive an echo reply, as a _future_.
+    ///
+    /// The payload of the reply is copied to the start of the provided buffer, and its length is
+    /// returned along with the reply itself.
+    pub async fn recv_reply(&self, buf: &mut [u8]) -> Result<(usize, EchoReply), std::io::Error> {
+        let (received, addr) = Retry::reading(self.socket.as_raw_fd(), || {
+            self.socket.recv_from::<libc::sockaddr_storage>(buf)
+        })
+        .await?;
+
+        let source =
+            unsafe { sys::sockaddr_to_socket_addr(&addr as *const _ as *const libc::sockaddr) }
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "unexpected address family"))?;
+
+        if received < HEADER_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "ICMP reply was too short",
+            ));
+        }
+        let reply = EchoReply {
+            source: source.ip(),
+            identifier: u16::from_be_bytes([buf[4], buf[5]]),
+            sequence: u16::from_be_bytes([buf[6], buf[7]]),
+        };
+
+        // Move the payload to the front, so callers don't have to know about the header
+        buf.copy_within(HEADER_LEN..received, 0);
+        Ok((received - HEADER_LEN, reply))
+    }
+}
+
+impl AsRawFd for PingSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
@@ -1,7 +1,23 @@
 //! Network-related futures
 
+mod errqueue;
+pub mod icmp;
+pub mod packet;
+pub mod sctp;
+mod socket;
+mod sys;
 mod tcp;
+mod timestamping;
 mod udp;
+pub mod unix;
+pub mod vsock;
 
+pub use errqueue::{ErrorOrigin, ExtendedError};
+pub use icmp::{EchoReply, PingSocket};
+pub use packet::{PacketAddr, PacketKind, PacketSocket};
+pub use sctp::{SctpListener, SctpSocket, SctpStream};
 pub use tcp::{TcpListener, TcpStream};
+pub use timestamping::{TimestampingFlags, Timestamps};
 pub use udp::UdpSocket;
+pub use unix::{UnixDatagram, UnixListener, UnixStream};
+pub use vsock::{VsockAddr, VsockDatagram, VsockListener, VsockStream};
@@ -0,0 +1,186 @@
+//! `AF_PACKET` sockets, for sending and receiving packets at the link layer
+//!
+//! These need `CAP_NET_RAW`, so they're mostly useful for packet capture and for speaking
+//! protocols that the kernel doesn't know about.
+
+use super::socket::{Retry, Socket};
+use super::sys;
+use std::ffi::CString;
+use std::io::Error;
+use std::mem::size_of;
+use std::os::unix::prelude::{AsRawFd, RawFd};
+
+/// `SO_ATTACH_FILTER`, which `libc` doesn't export for every target
+///
+/// Unlike some socket options, these two are the same on every architecture Rust targets: mips and
+/// sparc both use the generic numbers for them. parisc doesn't, but Rust doesn't target it, so
+/// there's nothing to pick between.
+const SO_ATTACH_FILTER: libc::c_int = 26;
+
+/// `SO_DETACH_FILTER`, which `libc` doesn't export for every target
+const SO_DETACH_FILTER: libc::c_int = 27;
+
+/// Whether packets include the link-level header or not
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PacketKind {
+    /// `SOCK_RAW`: packets include the link-level header (the ethernet header, for example)
+    Raw,
+    /// `SOCK_DGRAM`: the kernel strips the link-level header on receive and builds it on send
+    Cooked,
+}
+
+/// The link-level address of a packet, the `AF_PACKET` version of a `SocketAddr`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PacketAddr {
+    /// The index of the interface the packet came in on or should go out of
+    pub interface: u32,
+    /// The ethertype of the packet, like `0x0800` for IPv4 (in host byte order)
+    pub protocol: u16,
+    /// The `ARPHRD_*` hardware type of the interface
+    pub hardware_type: u16,
+    /// The `PACKET_*` type of the packet, like `PACKET_HOST` or `PACKET_BROADCAST`
+    pub packet_type: u8,
+    /// The hardware address, padded out to eight bytes
+    address: [u8; 8],
+    /// How much of `address` is actually used
+    address_len: u8,
+}
+
+impl PacketAddr {
+    /// Create a new address for sending
+    ///
+    /// Hardware addresses longer than eight bytes are truncated.
+    pub fn new(interface: u32, protocol: u16, hardware_address: &[u8]) -> Self {
+        let mut address = [0_u8; 8];
+        let address_len = hardware_address.len().min(8);
+        address[..address_len].copy_from_slice(&hardware_address[..address_len]);
+        Self {
+            interface,
+            protocol,
+            hardware_type: 0,
+            packet_type: 0,
+            address,
+            address_len: address_len as u8,
+        }
+    }
+
+    /// The hardware address, like a MAC address
+    pub fn hardware_address(&self) -> &[u8] {
+        &self.address[..self.address_len as usize]
+    }
+
+    /// Convert to the `sockaddr_ll` the kernel wants
+    fn to_raw(self) -> libc::sockaddr_ll {
+        let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as libc::c_ushort;
+        addr.sll_protocol = self.protocol.to_be();
+        addr.sll_ifindex = self.interface as libc::c_int;
+        addr.sll_hatype = self.hardware_type;
+        addr.sll_pkttype = self.packet_type;
+        addr.sll_halen = self.address_len;
+        addr.sll_addr = self.address;
+        addr
+    }
+
+    /// Convert from the `sockaddr_ll` the kernel hands back
+    fn from_raw(addr: libc::sockaddr_ll) -> Self {
+        Self {
+            interface: addr.sll_ifindex as u32,
+            protocol: u16::from_be(addr.sll_protocol),
+            hardware_type: addr.sll_hatype,
+            packet_type: addr.sll_pkttype,
+            address: addr.sll_addr,
+            address_len: addr.sll_halen.min(8),
+        }
+    }
+}
+
+/// Look up the index of a network interface by its name, like `"eth0"`
+pub fn interface_index(name: &str) -> Result<u32, std::io::Error> {
+    let name = CString::new(name)?;
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        Err(Error::last_os_error())
+    } else {
+        Ok(index)
+    }
+}
+
+/// A link-layer packet socket
+#[derive(Debug)]
+pub struct PacketSocket(Socket);
+
+impl PacketSocket {
+    /// Create a new packet socket for the provided ethertype
+    ///
+    /// Use `libc::ETH_P_ALL` to see every packet, regardless of protocol.
+    pub fn new(kind: PacketKind, protocol: u16) -> Result<Self, std::io::Error> {
+        let ty = match kind {
+            PacketKind::Raw => libc::SOCK_RAW,
+            PacketKind::Cooked => libc::SOCK_DGRAM,
+        };
+        let socket = Socket::new(libc::AF_PACKET, ty, protocol.to_be() as libc::c_int)?;
+        Ok(Self(socket))
+    }
+
+    /// Only receive packets from the interface with the provided index
+    ///
+    /// See [`interface_index`] to look up the index by name.
+    pub fn bind(&self, interface: u32) -> Result<(), std::io::Error> {
+        let protocol = self.0.local_addr::<libc::sockaddr_ll>()?.sll_protocol;
+        let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as libc::c_ushort;
+        addr.sll_protocol = protocol;
+        addr.sll_ifindex = interface as libc::c_int;
+        self.0.bind(&addr)
+    }
+
+    /// Attach a classic BPF program that decides which packets the socket sees
+    ///
+    /// This is the same kind of program that `tcpdump -dd` prints out.
+    pub fn attach_filter(&self, program: &[libc::sock_filter]) -> Result<(), std::io::Error> {
+        let fprog = libc::sock_fprog {
+            len: program.len() as libc::c_ushort,
+            filter: program.as_ptr() as *mut libc::sock_filter,
+        };
+        unsafe {
+            let r = libc::setsockopt(
+                self.0.as_raw_fd(),
+                libc::SOL_SOCKET,
+                SO_ATTACH_FILTER,
+                &fprog as *const libc::sock_fprog as *const libc::c_void,
+                size_of::<libc::sock_fprog>() as libc::socklen_t,
+            );
+            if r < 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a filter attached with [`PacketSocket::attach_filter`]
+    pub fn detach_filter(&self) -> Result<(), std::io::Error> {
+        sys::setsockopt_int(self.0.as_raw_fd(), libc::SOL_SOCKET, SO_DETACH_FILTER, 0)
+    }
+
+    /// Receive a packet from the socket, as a _future_.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, PacketAddr), std::io::Error> {
+        let (received, addr) = Retry::reading(self.0.as_raw_fd(), || {
+            self.0.recv_from::<libc::sockaddr_ll>(buf)
+        })
+        .await?;
+        Ok((received, PacketAddr::from_raw(addr)))
+    }
+
+    /// Send a packet on the socket, as a _future_.
+    pub async fn send_to(&self, buf: &[u8], addr: PacketAddr) -> Result<usize, std::io::Error> {
+        let addr = addr.to_raw();
+        Retry::writing(self.0.as_raw_fd(), || self.0.send_to(buf, &addr)).await
+    }
+}
+
+impl AsRawFd for PacketSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
@@ -0,0 +1,288 @@
+//! SCTP sockets
+//!
+//! SCTP comes in two styles. One-to-one sockets ([`SctpListener`] and [`SctpStream`]) work like
+//! TCP: one socket per association. One-to-many sockets ([`SctpSocket`]) work more like UDP: a
+//! single socket talks to every peer, and each message says which association it belongs to.
+//!
+//! Either way, every message is sent on a numbered stream and carries a payload protocol
+//! identifier, which is what [`SendInfo`] and [`RecvInfo`] are for.
+
+use super::socket::{self, Retry, Socket};
+use super::sys::{self, RawSocketAddr};
+use std::io::{Error, ErrorKind};
+use std::mem::size_of;
+use std::net::SocketAddr;
+use std::os::unix::prelude::{AsRawFd, RawFd};
+
+/// `SCTP_EVENTS`, the socket option for subscribing to SCTP events
+const SCTP_EVENTS: libc::c_int = 11;
+
+/// `SCTP_SNDRCV`, the control message type that carries a `sctp_sndrcvinfo`
+const SCTP_SNDRCV: libc::c_int = 1;
+
+/// `struct sctp_sndrcvinfo` from `linux/sctp.h`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+struct SctpSndRcvInfo {
+    stream: u16,
+    ssn: u16,
+    flags: u16,
+    ppid: u32,
+    context: u32,
+    timetolive: u32,
+    tsn: u32,
+    cumtsn: u32,
+    assoc_id: i32,
+}
+
+/// How to send an SCTP message
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SendInfo {
+    /// Which stream to send the message on
+    pub stream: u16,
+    /// The payload protocol identifier (in host byte order; it is sent in network byte order)
+    pub ppid: u32,
+}
+
+/// Information about a received SCTP message
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct RecvInfo {
+    /// Which stream the message came in on
+    pub stream: u16,
+    /// The payload protocol identifier (in host byte order)
+    pub ppid: u32,
+    /// Which association the message belongs to
+    pub association: i32,
+    /// Whether this is the end of the message
+    ///
+    /// If the provided buffer is smaller than the message, the message is delivered over several
+    /// receives, and only the last one is the end of the record.
+    pub end_of_record: bool,
+}
+
+/// Create a new SCTP socket that reports a `sctp_sndrcvinfo` with every message it receives
+fn new_socket(addr: &SocketAddr, ty: libc::c_int) -> Result<Socket, std::io::Error> {
+    let domain = match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    };
+    let socket = Socket::new(domain, ty, libc::IPPROTO_SCTP)?;
+    subscribe_data_io(&socket)?;
+    Ok(socket)
+}
+
+/// Subscribe to `sctp_data_io_event`, the first field of `struct sctp_event_subscribe`
+///
+/// Without this, the kernel doesn't tell us which stream a message came in on.
+fn subscribe_data_io(socket: &Socket) -> Result<(), std::io::Error> {
+    let events = [1_u8];
+    unsafe {
+        let r = libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_SCTP,
+            SCTP_EVENTS,
+            events.as_ptr() as *const libc::c_void,
+            events.len() as libc::socklen_t,
+        );
+        if r < 0 {
+            return Err(Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Bind a socket to a [`SocketAddr`]
+fn bind(socket: &Socket, addr: SocketAddr) -> Result<(), std::io::Error> {
+    match sys::socket_addr_to_raw(addr) {
+        RawSocketAddr::V4(raw) => socket.bind(&raw),
+        RawSocketAddr::V6(raw) => socket.bind(&raw),
+    }
+}
+
+/// Convert a raw `sockaddr_storage` from `accept` into a [`SocketAddr`]
+fn storage_to_socket_addr(storage: &libc::sockaddr_storage) -> Result<SocketAddr, std::io::Error> {
+    unsafe { sys::sockaddr_to_socket_addr(storage as *const _ as *const libc::sockaddr) }
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "unexpected address family"))
+}
+
+/// Send a message with an `SCTP_SNDRCV` control message, as a _future_.
+async fn send(
+    socket: &Socket,
+    buf: &[u8],
+    addr: Option<SocketAddr>,
+    info: SendInfo,
+) -> Result<usize, std::io::Error> {
+    let sndrcvinfo = SctpSndRcvInfo {
+        stream: info.stream,
+        ppid: info.ppid.to_be(),
+        ..Default::default()
+    };
+    let data = unsafe {
+        std::slice::from_raw_parts(
+            &sndrcvinfo as *const SctpSndRcvInfo as *const u8,
+            size_of::<SctpSndRcvInfo>(),
+        )
+    };
+    let control = sys::control_message(libc::IPPROTO_SCTP, SCTP_SNDRCV, data);
+    let addr = addr.map(sys::socket_addr_to_raw);
+
+    Retry::writing(socket.as_raw_fd(), || {
+        sys::sendmsg(
+            socket.as_raw_fd(),
+            buf,
+            &control,
+            addr.as_ref().map(RawSocketAddr::as_ptr),
+        )
+    })
+    .await
+}
+
+/// Receive a message and its `SCTP_SNDRCV` control message, as a _future_.
+async fn recv(
+    socket: &Socket,
+    buf: &mut [u8],
+) -> Result<(usize, Option<SocketAddr>, RecvInfo), std::io::Error> {
+    let mut control = [0_u8; 128];
+    let received = Retry::reading(socket.as_raw_fd(), || {
+        sys::recvmsg(socket.as_raw_fd(), buf, &mut control, 0)
+    })
+    .await?;
+
+    let mut info = sys::control_messages(&control[..received.control_len])
+        .find(|message| message.level == libc::IPPROTO_SCTP && message.ty == SCTP_SNDRCV)
+        .filter(|message| message.data.len() >= size_of::<SctpSndRcvInfo>())
+        .map(|message| {
+            let raw: SctpSndRcvInfo =
+                unsafe { std::ptr::read_unaligned(message.data.as_ptr() as *const SctpSndRcvInfo) };
+            RecvInfo {
+                stream: raw.stream,
+                ppid: u32::from_be(raw.ppid),
+                association: raw.assoc_id,
+                end_of_record: false,
+            }
+        })
+        .unwrap_or_default();
+    info.end_of_record = received.flags & libc::MSG_EOR != 0;
+
+    Ok((received.len, received.address, info))
+}
+
+/// A one-to-one style SCTP listener, the SCTP equivalent of [`TcpListener`](super::TcpListener).
+#[derive(Debug)]
+pub struct SctpListener(Socket);
+
+impl SctpListener {
+    /// Create a new listener bound to the provided address
+    pub fn bind(addr: SocketAddr) -> Result<Self, std::io::Error> {
+        let socket = new_socket(&addr, libc::SOCK_STREAM)?;
+        bind(&socket, addr)?;
+        socket.listen(128)?;
+        Ok(Self(socket))
+    }
+
+    /// Wait until a new association is available and accept it
+    pub async fn accept(&self) -> Result<(SctpStream, SocketAddr), std::io::Error> {
+        let (socket, addr) = Retry::reading(self.0.as_raw_fd(), || {
+            self.0.accept::<libc::sockaddr_storage>()
+        })
+        .await?;
+        subscribe_data_io(&socket)?;
+        Ok((SctpStream(socket), storage_to_socket_addr(&addr)?))
+    }
+}
+
+impl AsRawFd for SctpListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// A one-to-one style SCTP association, the SCTP equivalent of [`TcpStream`](super::TcpStream).
+#[derive(Debug)]
+pub struct SctpStream(Socket);
+
+impl SctpStream {
+    /// Connect to the provided address
+    pub async fn connect(addr: SocketAddr) -> Result<Self, std::io::Error> {
+        let socket = new_socket(&addr, libc::SOCK_STREAM)?;
+        match sys::socket_addr_to_raw(addr) {
+            RawSocketAddr::V4(raw) => socket::connect(&socket, &raw).await?,
+            RawSocketAddr::V6(raw) => socket::connect(&socket, &raw).await?,
+        }
+        Ok(Self(socket))
+    }
+
+    /// Send a message, as a _future_.
+    pub async fn send(&self, buf: &[u8], info: SendInfo) -> Result<usize, std::io::Error> {
+        send(&self.0, buf, None, info).await
+    }
+
+    /// Receive a message, as a _future_.
+    pub async fn recv(&self, buf: &mut [u8]) -> Result<(usize, RecvInfo), std::io::Error> {
+        let (received, _, info) = recv(&self.0, buf).await?;
+        Ok((received, info))
+    }
+
+    /// Shut down the read half, write half, or both halves of the association
+    pub fn shutdown(&self, how: std::net::Shutdown) -> Result<(), std::io::Error> {
+        self.0.shutdown(how)
+    }
+}
+
+impl AsRawFd for SctpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// A one-to-many style SCTP socket
+///
+/// Associations are set up implicitly: sending to a new address sets one up, and (once
+/// [`SctpSocket::listen`] has been called) peers can set them up by sending to this socket.
+#[derive(Debug)]
+pub struct SctpSocket(Socket);
+
+impl SctpSocket {
+    /// Create a new socket bound to the provided address
+    pub fn bind(addr: SocketAddr) -> Result<Self, std::io::Error> {
+        let socket = new_socket(&addr, libc::SOCK_SEQPACKET)?;
+        bind(&socket, addr)?;
+        Ok(Self(socket))
+    }
+
+    /// Allow peers to set up associations with this socket
+    pub fn listen(&self, backlog: i32) -> Result<(), std::io::Error> {
+        self.0.listen(backlog)
+    }
+
+    /// Send a message to the provided address, as a _future_.
+    pub async fn send_to(
+        &self,
+        buf: &[u8],
+        addr: SocketAddr,
+        info: SendInfo,
+    ) -> Result<usize, std::io::Error> {
+        send(&self.0, buf, Some(addr), info).await
+    }
+
+    /// Receive a message from any association, as a _future_.
+    pub async fn recv_from(
+        &self,
+        buf: &mut [u8],
+    ) -> Result<(usize, SocketAddr, RecvInfo), std::io::Error> {
+        let (received, addr, info) = recv(&self.0, buf).await?;
+        let addr = addr.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                "received a message without an address",
+            )
+        })?;
+        Ok((received, addr, info))
+    }
+}
+
+impl AsRawFd for SctpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
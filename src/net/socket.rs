@@ -0,0 +1,361 @@
+//! A raw socket file descriptor, for the socket families that `std` doesn't cover
+//!
+//! `std::net` only knows about TCP and UDP, and `std::os::unix::net` only knows about unix sockets.
+//! Everything else (vsock, packet sockets, and friends) is built on top of this instead. Addresses
+//! are passed around as raw `sockaddr` bytes so that each family can deal with its own.
+
+use crate::runtime::Interest;
+use libc::c_int;
+use pin_project::pin_project;
+use std::future::Future;
+use std::io::{Error, ErrorKind, IoSlice, IoSliceMut};
+use std::mem::{size_of, ManuallyDrop, MaybeUninit};
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// An owned, non-blocking socket file descriptor
+#[derive(Debug)]
+pub(crate) struct Socket(OwnedFd);
+
+impl Socket {
+    /// Create a new non-blocking socket
+    ///
+    /// Roughly equivalent to `socket(domain, ty | SOCK_NONBLOCK | SOCK_CLOEXEC, protocol)`.
+    pub fn new(domain: c_int, ty: c_int, protocol: c_int) -> Result<Self, std::io::Error> {
+        unsafe {
+            let r = libc::socket(
+                domain,
+                ty | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                protocol,
+            );
+            if r < 0 {
+                Err(Error::last_os_error())
+            } else {
+                Ok(Self(OwnedFd::from_raw_fd(r)))
+            }
+        }
+    }
+
+    /// Bind the socket to an address
+    pub fn bind<A>(&self, addr: &A) -> Result<(), std::io::Error> {
+        unsafe {
+            let r = libc::bind(
+                self.as_raw_fd(),
+                addr as *const A as *const libc::sockaddr,
+                size_of::<A>() as libc::socklen_t,
+            );
+            check(r)
+        }
+    }
+
+    /// Start listening for connections
+    pub fn listen(&self, backlog: c_int) -> Result<(), std::io::Error> {
+        unsafe { check(libc::listen(self.as_raw_fd(), backlog)) }
+    }
+
+    /// Start connecting to an address
+    ///
+    /// The socket is non-blocking, so for connection-oriented sockets this usually fails with
+    /// `EINPROGRESS`; see [`Socket::connect_finished`].
+    pub fn connect<A>(&self, addr: &A) -> Result<(), std::io::Error> {
+        unsafe {
+            let r = libc::connect(
+                self.as_raw_fd(),
+                addr as *const A as *const libc::sockaddr,
+                size_of::<A>() as libc::socklen_t,
+            );
+            check(r)
+        }
+    }
+
+    /// Check on a connection that was started with [`Socket::connect`]
+    ///
+    /// Returns `Ok(true)` once the connection is established, `Ok(false)` while it is still in
+    /// progress, and the connection's error (from `SO_ERROR`) if it failed.
+    pub fn connect_finished(&self) -> Result<bool, std::io::Error> {
+        if let Some(err) = self.take_error()? {
+            return Err(err);
+        }
+
+        // `SO_ERROR` is zero both while connecting and once connected. The way to tell those apart
+        // is to ask for the peer: there isn't one until the connection is established.
+        unsafe {
+            let mut addr: MaybeUninit<libc::sockaddr_storage> = MaybeUninit::uninit();
+            let mut len = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            let r = libc::getpeername(
+                self.as_raw_fd(),
+                addr.as_mut_ptr() as *mut libc::sockaddr,
+                &mut len,
+            );
+            if r == 0 {
+                return Ok(true);
+            }
+        }
+        let err = Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ENOTCONN) {
+            Ok(false)
+        } else {
+            Err(err)
+        }
+    }
+
+    /// Get (and clear) the pending error on the socket, from `SO_ERROR`
+    pub fn take_error(&self) -> Result<Option<std::io::Error>, std::io::Error> {
+        let err = self.getsockopt_int(libc::SOL_SOCKET, libc::SO_ERROR)?;
+        if err == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(Error::from_raw_os_error(err)))
+        }
+    }
+
+    /// Get an integer socket option
+    pub fn getsockopt_int(&self, level: c_int, name: c_int) -> Result<c_int, std::io::Error> {
+        unsafe {
+            let mut value: c_int = 0;
+            let mut len = size_of::<c_int>() as libc::socklen_t;
+            let r = libc::getsockopt(
+                self.as_raw_fd(),
+                level,
+                name,
+                &mut value as *mut c_int as *mut libc::c_void,
+                &mut len,
+            );
+            check(r)?;
+            Ok(value)
+        }
+    }
+
+    /// Accept a new connection
+    ///
+    /// The new socket is non-blocking, just like this one.
+    pub fn accept<A>(&self) -> Result<(Socket, A), std::io::Error> {
+        unsafe {
+            let mut addr: MaybeUninit<A> = MaybeUninit::zeroed();
+            let mut len = size_of::<A>() as libc::socklen_t;
+            let r = libc::accept4(
+                self.as_raw_fd(),
+                addr.as_mut_ptr() as *mut libc::sockaddr,
+                &mut len,
+                libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            );
+            if r < 0 {
+                Err(Error::last_os_error())
+            } else {
+                Ok((Socket(OwnedFd::from_raw_fd(r)), addr.assume_init()))
+            }
+        }
+    }
+
+    /// Read bytes from the socket
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        unsafe {
+            let r = libc::read(
+                self.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+            );
+            check_len(r)
+        }
+    }
+
+    /// Write bytes to the socket
+    pub fn write(&self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        unsafe {
+            let r = libc::write(
+                self.as_raw_fd(),
+                buf.as_ptr() as *const libc::c_void,
+                buf.len(),
+            );
+            check_len(r)
+        }
+    }
+
+    /// Read bytes from the socket into a series of buffers
+    pub fn read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize, std::io::Error> {
+        unsafe {
+            // `IoSliceMut` is guaranteed to be ABI compatible with `iovec`
+            let r = libc::readv(
+                self.as_raw_fd(),
+                bufs.as_mut_ptr() as *mut libc::iovec,
+                bufs.len().min(libc::c_int::MAX as usize) as c_int,
+            );
+            check_len(r)
+        }
+    }
+
+    /// Write bytes from a series of buffers to the socket
+    pub fn write_vectored(&self, bufs: &[IoSlice<'_>]) -> Result<usize, std::io::Error> {
+        unsafe {
+            let r = libc::writev(
+                self.as_raw_fd(),
+                bufs.as_ptr() as *const libc::iovec,
+                bufs.len().min(libc::c_int::MAX as usize) as c_int,
+            );
+            check_len(r)
+        }
+    }
+
+    /// Receive a packet, along with the address it came from
+    pub fn recv_from<A>(&self, buf: &mut [u8]) -> Result<(usize, A), std::io::Error> {
+        unsafe {
+            let mut addr: MaybeUninit<A> = MaybeUninit::zeroed();
+            let mut len = size_of::<A>() as libc::socklen_t;
+            let r = libc::recvfrom(
+                self.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+                addr.as_mut_ptr() as *mut libc::sockaddr,
+                &mut len,
+            );
+            let received = check_len(r)?;
+            Ok((received, addr.assume_init()))
+        }
+    }
+
+    /// Send a packet to an address
+    pub fn send_to<A>(&self, buf: &[u8], addr: &A) -> Result<usize, std::io::Error> {
+        unsafe {
+            let r = libc::sendto(
+                self.as_raw_fd(),
+                buf.as_ptr() as *const libc::c_void,
+                buf.len(),
+                0,
+                addr as *const A as *const libc::sockaddr,
+                size_of::<A>() as libc::socklen_t,
+            );
+            check_len(r)
+        }
+    }
+
+    /// Get the address the socket is bound to
+    pub fn local_addr<A>(&self) -> Result<A, std::io::Error> {
+        unsafe {
+            let mut addr: MaybeUninit<A> = MaybeUninit::zeroed();
+            let mut len = size_of::<A>() as libc::socklen_t;
+            let r = libc::getsockname(
+                self.as_raw_fd(),
+                addr.as_mut_ptr() as *mut libc::sockaddr,
+                &mut len,
+            );
+            check(r)?;
+            Ok(addr.assume_init())
+        }
+    }
+
+    /// Shut down the read half, write half, or both halves of the socket
+    pub fn shutdown(&self, how: std::net::Shutdown) -> Result<(), std::io::Error> {
+        let how = match how {
+            std::net::Shutdown::Read => libc::SHUT_RD,
+            std::net::Shutdown::Write => libc::SHUT_WR,
+            std::net::Shutdown::Both => libc::SHUT_RDWR,
+        };
+        unsafe { check(libc::shutdown(self.as_raw_fd(), how)) }
+    }
+
+    /// Give up the file descriptor, for one of `std`'s types to take over
+    ///
+    /// The runtime doesn't forget about it: it's still the same socket, whether it's been
+    /// registered or not.
+    pub fn into_owned_fd(self) -> OwnedFd {
+        let this = ManuallyDrop::new(self);
+        // `Drop` would only forget the file descriptor, and there's nothing else to drop.
+        unsafe { std::ptr::read(&this.0) }
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.0.as_raw_fd());
+    }
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// Turn a `-1` return value into the last OS error
+fn check(r: c_int) -> Result<(), std::io::Error> {
+    if r < 0 {
+        Err(Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Turn a `-1` return value into the last OS error, and anything else into a length
+fn check_len(r: isize) -> Result<usize, std::io::Error> {
+    if r < 0 {
+        Err(Error::last_os_error())
+    } else {
+        Ok(r as usize)
+    }
+}
+
+/// A future that keeps calling a non-blocking operation until it stops saying `WouldBlock`
+///
+/// The raw socket types have a lot more operations than TCP and UDP do, and every one of them
+/// looks exactly the same, so they share this instead of each having a future of its own.
+#[pin_project]
+pub(crate) struct Retry<F> {
+    /// The file descriptor to register with the runtime if the operation would block
+    fd: RawFd,
+    /// Which way the operation needs the file descriptor to be ready
+    interest: Interest,
+    /// The operation itself
+    op: F,
+}
+
+impl<F> Retry<F> {
+    /// Create a new future that runs `op`, which reads (or accepts), until it doesn't block
+    pub fn reading(fd: RawFd, op: F) -> Self {
+        Self::new(fd, Interest::READABLE, op)
+    }
+
+    /// Create a new future that runs `op`, which writes (or connects), until it doesn't block
+    pub fn writing(fd: RawFd, op: F) -> Self {
+        Self::new(fd, Interest::WRITABLE, op)
+    }
+
+    fn new(fd: RawFd, interest: Interest, op: F) -> Self {
+        Self { fd, interest, op }
+    }
+}
+
+impl<F, T> Future for Retry<F>
+where
+    F: FnMut() -> Result<T, std::io::Error>,
+{
+    type Output = Result<T, std::io::Error>;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let projected = self.project();
+        let op = projected.op;
+        crate::io::poll_fd(cx, *projected.fd, *projected.interest, op)
+    }
+}
+
+/// Connect a socket, waiting for the connection to finish
+pub(crate) async fn connect<A>(socket: &Socket, addr: &A) -> Result<(), std::io::Error> {
+    match socket.connect(addr) {
+        Ok(()) => return Ok(()),
+        Err(err) if err.raw_os_error() == Some(libc::EINPROGRESS) => {}
+        Err(err) => return Err(err),
+    }
+
+    // The connection is in progress. The socket becomes writable once it's done, one way or the
+    // other; until then, treat it like any other operation that would block.
+    Retry::writing(socket.as_raw_fd(), || {
+        if socket.connect_finished()? {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::WouldBlock))
+        }
+    })
+    .await
+}
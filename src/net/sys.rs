@@ -0,0 +1,295 @@
+//! Thin wrappers around the socket syscalls that `std::net` doesn't expose
+//!
+//! Everything in here works on raw file descriptors, so the socket types in this module can share
+//! it regardless of which `std` type they happen to wrap.
+
+use libc::c_int;
+use std::io::Error;
+use std::mem::{size_of, MaybeUninit};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::unix::prelude::{OsStrExt, RawFd};
+use std::path::Path;
+
+/// Set an integer socket option
+///
+/// Roughly equivalent to `setsockopt(fd, level, name, &value, sizeof(int))`.
+pub(crate) fn setsockopt_int(
+    fd: RawFd,
+    level: c_int,
+    name: c_int,
+    value: c_int,
+) -> Result<(), std::io::Error> {
+    unsafe {
+        let r = libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const c_int as *const libc::c_void,
+            size_of::<c_int>() as libc::socklen_t,
+        );
+        if r < 0 {
+            Err(Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The result of a successful [`recvmsg`]
+pub(crate) struct RecvMsg {
+    /// How many bytes of the data buffer were filled
+    pub len: usize,
+    /// How many bytes of the control buffer were filled
+    pub control_len: usize,
+    /// The address the message came from, if the kernel told us
+    pub address: Option<SocketAddr>,
+    /// The `msg_flags` the kernel handed back, like `MSG_EOR` or `MSG_TRUNC`
+    pub flags: c_int,
+}
+
+/// Receive a message, including its ancillary data
+///
+/// Roughly equivalent to `recvmsg` with a single data buffer. `MSG_DONTWAIT` is always added to the
+/// provided flags, so this will never block.
+pub(crate) fn recvmsg(
+    fd: RawFd,
+    buf: &mut [u8],
+    control: &mut [u8],
+    flags: c_int,
+) -> Result<RecvMsg, std::io::Error> {
+    unsafe {
+        let mut address: MaybeUninit<libc::sockaddr_storage> = MaybeUninit::zeroed();
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_name = address.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_namelen = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        msg.msg_iov = &mut iov as *mut libc::iovec;
+        msg.msg_iovlen = 1;
+        if !control.is_empty() {
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = control.len() as _;
+        }
+
+        let r = libc::recvmsg(
+            fd,
+            &mut msg as *mut libc::msghdr,
+            flags | libc::MSG_DONTWAIT,
+        );
+        if r < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let address = if msg.msg_namelen > 0 {
+            sockaddr_to_socket_addr(address.as_ptr() as *const libc::sockaddr)
+        } else {
+            None
+        };
+
+        Ok(RecvMsg {
+            len: r as usize,
+            control_len: msg.msg_controllen as usize,
+            address,
+            flags: msg.msg_flags,
+        })
+    }
+}
+
+/// Send a message, including ancillary data
+///
+/// Roughly equivalent to `sendmsg` with a single data buffer and `MSG_DONTWAIT`. The destination
+/// is a raw `sockaddr`, or `None` for connected sockets.
+pub(crate) fn sendmsg(
+    fd: RawFd,
+    buf: &[u8],
+    control: &[u8],
+    address: Option<(*const libc::sockaddr, libc::socklen_t)>,
+) -> Result<usize, std::io::Error> {
+    unsafe {
+        let mut iov = libc::iovec {
+            iov_base: buf.as_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        if let Some((address, len)) = address {
+            msg.msg_name = address as *mut libc::c_void;
+            msg.msg_namelen = len;
+        }
+        msg.msg_iov = &mut iov as *mut libc::iovec;
+        msg.msg_iovlen = 1;
+        if !control.is_empty() {
+            msg.msg_control = control.as_ptr() as *mut libc::c_void;
+            msg.msg_controllen = control.len() as _;
+        }
+
+        let r = libc::sendmsg(fd, &msg as *const libc::msghdr, libc::MSG_DONTWAIT);
+        if r < 0 {
+            Err(Error::last_os_error())
+        } else {
+            Ok(r as usize)
+        }
+    }
+}
+
+/// Build a control buffer holding a single ancillary message
+///
+/// This is what filling in a buffer with `CMSG_FIRSTHDR`, `CMSG_LEN`, and `CMSG_DATA` does in C.
+pub(crate) fn control_message(level: c_int, ty: c_int, data: &[u8]) -> Vec<u8> {
+    let header_len = cmsg_align(size_of::<libc::cmsghdr>());
+    let mut control = vec![0_u8; header_len + cmsg_align(data.len())];
+    let mut header: libc::cmsghdr = unsafe { std::mem::zeroed() };
+    header.cmsg_len = (header_len + data.len()) as _;
+    header.cmsg_level = level;
+    header.cmsg_type = ty;
+    unsafe {
+        std::ptr::write_unaligned(control.as_mut_ptr() as *mut libc::cmsghdr, header);
+    }
+    control[header_len..header_len + data.len()].copy_from_slice(data);
+    control
+}
+
+/// A single ancillary message pulled out of a control buffer
+pub(crate) struct ControlMessage<'a> {
+    /// The `cmsg_level`, like `SOL_IP`
+    pub level: c_int,
+    /// The `cmsg_type`, like `IP_RECVERR`
+    pub ty: c_int,
+    /// Everything after the header
+    pub data: &'a [u8],
+}
+
+/// Walk the ancillary messages in a control buffer filled in by [`recvmsg`]
+///
+/// This is what the `CMSG_FIRSTHDR` / `CMSG_NXTHDR` macros do in C, but without needing to keep
+/// the `msghdr` around.
+pub(crate) fn control_messages(control: &[u8]) -> impl Iterator<Item = ControlMessage<'_>> {
+    let header_len = cmsg_align(size_of::<libc::cmsghdr>());
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        if offset + header_len > control.len() {
+            return None;
+        }
+        let header: libc::cmsghdr =
+            unsafe { std::ptr::read_unaligned(control[offset..].as_ptr() as *const libc::cmsghdr) };
+        let len = header.cmsg_len as usize;
+        if len < header_len || offset + len > control.len() {
+            return None;
+        }
+        let message = ControlMessage {
+            level: header.cmsg_level,
+            ty: header.cmsg_type,
+            data: &control[offset + header_len..offset + len],
+        };
+        offset += cmsg_align(len);
+        Some(message)
+    })
+}
+
+/// Round up to the alignment the kernel uses for control messages
+fn cmsg_align(len: usize) -> usize {
+    let align = size_of::<usize>();
+    (len + align - 1) & !(align - 1)
+}
+
+/// Turn a `sockaddr` that the kernel filled in into a [`SocketAddr`]
+///
+/// Returns `None` for address families that aren't IPv4 or IPv6.
+///
+/// # Safety
+///
+/// The pointer must point to a valid `sockaddr` that is large enough for its own `sa_family`.
+pub(crate) unsafe fn sockaddr_to_socket_addr(addr: *const libc::sockaddr) -> Option<SocketAddr> {
+    match (*addr).sa_family as c_int {
+        libc::AF_INET => {
+            let addr = std::ptr::read_unaligned(addr as *const libc::sockaddr_in);
+            let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            let port = u16::from_be(addr.sin_port);
+            Some(SocketAddr::V4(SocketAddrV4::new(ip, port)))
+        }
+        libc::AF_INET6 => {
+            let addr = std::ptr::read_unaligned(addr as *const libc::sockaddr_in6);
+            let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+            let port = u16::from_be(addr.sin6_port);
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                ip,
+                port,
+                addr.sin6_flowinfo,
+                addr.sin6_scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
+
+/// A [`SocketAddr`] converted to the `sockaddr` flavor the kernel wants
+pub(crate) enum RawSocketAddr {
+    V4(libc::sockaddr_in),
+    V6(libc::sockaddr_in6),
+}
+
+impl RawSocketAddr {
+    /// A pointer to the `sockaddr` and its length, for passing to syscalls
+    pub fn as_ptr(&self) -> (*const libc::sockaddr, libc::socklen_t) {
+        match self {
+            RawSocketAddr::V4(raw) => (
+                raw as *const libc::sockaddr_in as *const libc::sockaddr,
+                size_of::<libc::sockaddr_in>() as libc::socklen_t,
+            ),
+            RawSocketAddr::V6(raw) => (
+                raw as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+            ),
+        }
+    }
+}
+
+/// Turn a [`SocketAddr`] into a `sockaddr_in` or `sockaddr_in6`
+pub(crate) fn socket_addr_to_raw(addr: SocketAddr) -> RawSocketAddr {
+    match addr {
+        SocketAddr::V4(addr) => {
+            let mut raw: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+            raw.sin_family = libc::AF_INET as libc::sa_family_t;
+            raw.sin_port = addr.port().to_be();
+            raw.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+            RawSocketAddr::V4(raw)
+        }
+        SocketAddr::V6(addr) => {
+            let mut raw: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+            raw.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            raw.sin6_port = addr.port().to_be();
+            raw.sin6_flowinfo = addr.flowinfo();
+            raw.sin6_addr.s6_addr = addr.ip().octets();
+            raw.sin6_scope_id = addr.scope_id();
+            RawSocketAddr::V6(raw)
+        }
+    }
+}
+
+/// Turn a path into a `sockaddr_un`, for a unix socket
+///
+/// Fails the same way `std` does for paths that don't fit, or that have a nul byte in them.
+pub(crate) fn unix_path_to_raw(path: &Path) -> Result<libc::sockaddr_un, std::io::Error> {
+    let mut raw: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    raw.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    let bytes = path.as_os_str().as_bytes();
+    if bytes.contains(&0) {
+        return Err(Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "paths must not contain interior null bytes",
+        ));
+    }
+    // There needs to be room left for the nul at the end.
+    if bytes.len() >= raw.sun_path.len() {
+        return Err(Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "path must be shorter than SUN_LEN",
+        ));
+    }
+    for (dst, &src) in raw.sun_path.iter_mut().zip(bytes) {
+        *dst = src as libc::c_char;
+    }
+    Ok(raw)
+}
@@ -1,8 +1,12 @@
-use crate::runtime::RuntimeContext;
-use pin_project::pin_project;
-use std::future::Future;
-use std::io::ErrorKind;
+use super::socket::{self, Socket};
+use super::sys::{self, RawSocketAddr};
+use crate::io::{poll_fd, AsyncRead, AsyncWrite};
+use crate::runtime::Interest;
+use std::io::{IoSlice, IoSliceMut};
 use std::net::SocketAddr;
+use std::os::unix::prelude::AsRawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 /// A wrapper around [`std::net::TcpListener`] that enables _futures_.
 pub struct TcpListener(std::net::TcpListener);
@@ -28,11 +32,20 @@ impl TcpListener {
 
     /// Wait until a new connection is available and accept that connection
     pub async fn accept(&self) -> Result<(TcpStream, SocketAddr), std::io::Error> {
-        Accept {
-            listener: &self,
-            state: RegisteredState::Unregistered,
-        }
-        .await
+        // Since the listener is non-blocking, `accept` returns immediately.
+        let (stream, addr) = std::future::poll_fn(|cx| {
+            poll_fd(cx, self.0.as_raw_fd(), Interest::READABLE, || {
+                self.0.accept()
+            })
+        })
+        .await?;
+        Ok((TcpStream::new(stream)?, addr))
+    }
+}
+
+impl Drop for TcpListener {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.0.as_raw_fd());
     }
 }
 
@@ -48,6 +61,49 @@ impl TcpStream {
         Ok(Self(stream))
     }
 
+    /// Connect to the provided address, as a _future_.
+    ///
+    /// The connect is non-blocking, so the rest of the runtime keeps going while the handshake
+    /// happens. If the connection fails, this fails with the error the kernel reports for it
+    /// (`ECONNREFUSED`, `ETIMEDOUT`, and so on).
+    ///
+    /// ```
+    /// use guillotine::net::{TcpListener, TcpStream};
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let listener = TcpListener::new(std::net::TcpListener::bind("127.0.0.1:0").unwrap())
+    ///         .unwrap();
+    ///     let addr = listener.inner().local_addr().unwrap();
+    ///
+    ///     let mut client = TcpStream::connect(addr).await.unwrap();
+    ///     let (mut server, _) = listener.accept().await.unwrap();
+    ///
+    ///     client.write(b"hello").await.unwrap();
+    ///     let mut buf = [0; 5];
+    ///     let read = server.read(&mut buf).await.unwrap();
+    ///     assert_eq!(&buf[..read], b"hello");
+    ///
+    ///     // Nobody's listening anymore
+    ///     drop(listener);
+    ///     let err = TcpStream::connect(addr).await.err().unwrap();
+    ///     assert_eq!(err.kind(), std::io::ErrorKind::ConnectionRefused);
+    /// });
+    /// ```
+    pub async fn connect(addr: SocketAddr) -> Result<Self, std::io::Error> {
+        let domain = match addr {
+            SocketAddr::V4(_) => libc::AF_INET,
+            SocketAddr::V6(_) => libc::AF_INET6,
+        };
+        let socket = Socket::new(domain, libc::SOCK_STREAM, 0)?;
+        match sys::socket_addr_to_raw(addr) {
+            RawSocketAddr::V4(raw) => socket::connect(&socket, &raw).await?,
+            RawSocketAddr::V6(raw) => socket::connect(&socket, &raw).await?,
+        }
+        // The socket is non-blocking already.
+        Ok(Self(std::net::TcpStream::from(socket.into_owned_fd())))
+    }
+
     /// Get access to the wrapped TcpStream
     pub fn inner(&self) -> &std::net::TcpStream {
         &self.0
@@ -59,149 +115,114 @@ impl TcpStream {
     }
 
     /// Read bytes from the stream, as a future
-    pub async fn read<'a, 'b>(&'a mut self, buf: &'b mut [u8]) -> Result<usize, std::io::Error> {
-        Read {
-            stream: self,
-            buf,
-            state: RegisteredState::Unregistered,
-        }
-        .await
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, buf)).await
     }
 
     /// Write bytes to the stream, as a future
-    pub async fn write<'a, 'b>(&'a mut self, buf: &'b [u8]) -> Result<usize, std::io::Error> {
-        Write {
-            stream: self,
-            buf,
-            state: RegisteredState::Unregistered,
-        }
-        .await
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_write(cx, buf)).await
     }
-}
 
-/// Track whether the file descriptor has been registered with the runtime or not
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum RegisteredState {
-    Unregistered,
-    Registered,
-}
+    /// Read bytes from the stream into the spare capacity of `buf`, as a future
+    ///
+    /// The bytes go straight from the socket into `buf`, without zeroing it first.
+    #[cfg(feature = "bytes")]
+    pub async fn read_buf<B: bytes::BufMut + ?Sized>(
+        &mut self,
+        buf: &mut B,
+    ) -> Result<usize, std::io::Error> {
+        crate::io::read_fd_buf(self.0.as_raw_fd(), buf).await
+    }
 
-/// The future that runs [`TcpListener::accept`]
-#[pin_project]
-struct Accept<'a> {
-    listener: &'a TcpListener,
-    state: RegisteredState,
-}
+    /// Read bytes from the stream into a series of buffers, as a future
+    pub async fn read_vectored(
+        &mut self,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_read_vectored(cx, bufs)).await
+    }
 
-impl<'a> Future for Accept<'a> {
-    type Output = Result<(TcpStream, SocketAddr), std::io::Error>;
-
-    fn poll(
-        self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Self::Output> {
-        let projected = self.project();
-
-        // Call `.accept` on the inner listener. Since the listener is set to non-blocking, this
-        // should return immediately.
-        let result = projected.listener.0.accept();
-        match result {
-            // Success! Return the accepted stream
-            Ok((stream, addr)) => match TcpStream::new(stream) {
-                Ok(stream) => std::task::Poll::Ready(Ok((stream, addr))),
-                Err(err) => std::task::Poll::Ready(Err(err)),
-            },
-            Err(err) if err.kind() == ErrorKind::WouldBlock => {
-                // Not ready yet. If we haven't registered the file descriptor with the runtime,
-                // do it now.
-                if *projected.state == RegisteredState::Unregistered {
-                    let context = RuntimeContext::current();
-                    context.register_file_descriptor(&projected.listener.0);
-                    *projected.state = RegisteredState::Registered;
-                }
-                std::task::Poll::Pending
-            }
-            Err(err) => std::task::Poll::Ready(Err(err)),
-        }
+    /// Write bytes from a series of buffers to the stream, as a future
+    ///
+    /// The buffers go out in a single `writev`, so there's no need to copy a header and a body
+    /// into one buffer first.
+    pub async fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_write_vectored(cx, bufs)).await
     }
 }
 
-/// The future that runs [`TcpStream::read`]
-#[pin_project]
-struct Read<'a, 'b> {
-    stream: &'a mut TcpStream,
-    buf: &'b mut [u8],
-    state: RegisteredState,
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.0.as_raw_fd());
+    }
 }
 
-impl<'a, 'b> Future for Read<'a, 'b> {
-    type Output = Result<usize, std::io::Error>;
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        use std::io::Read;
+
+        let stream = &mut self.get_mut().0;
+        poll_fd(cx, stream.as_raw_fd(), Interest::READABLE, || {
+            stream.read(buf)
+        })
+    }
 
-    fn poll(
-        self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Self::Output> {
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
         use std::io::Read;
 
-        let projected = self.project();
-
-        // Call `.read` on the inner stream. Since the stream is set to non-blocking, this should
-        // return immediately.
-        let result = projected.stream.0.read(*projected.buf);
-        match result {
-            // Successs! Return the number of bytes read
-            Ok(ok) => std::task::Poll::Ready(Ok(ok)),
-            Err(err) if err.kind() == ErrorKind::WouldBlock => {
-                // Not ready yet. If we haven't registered the file descriptor with the runtime,
-                // do it now.
-                if *projected.state == RegisteredState::Unregistered {
-                    let context = RuntimeContext::current();
-                    context.register_file_descriptor(&projected.stream.0);
-                    *projected.state = RegisteredState::Registered;
-                }
-                std::task::Poll::Pending
-            }
-            Err(err) => std::task::Poll::Ready(Err(err)),
-        }
+        let stream = &mut self.get_mut().0;
+        poll_fd(cx, stream.as_raw_fd(), Interest::READABLE, || {
+            stream.read_vectored(bufs)
+        })
     }
 }
 
-/// The future that runs [`TcpStream::write`]
-#[pin_project]
-struct Write<'a, 'b> {
-    stream: &'a mut TcpStream,
-    buf: &'b [u8],
-    state: RegisteredState,
-}
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        use std::io::Write;
 
-impl<'a, 'b> Future for Write<'a, 'b> {
-    type Output = Result<usize, std::io::Error>;
+        let stream = &mut self.get_mut().0;
+        poll_fd(cx, stream.as_raw_fd(), Interest::WRITABLE, || {
+            stream.write(buf)
+        })
+    }
 
-    fn poll(
-        self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Self::Output> {
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
         use std::io::Write;
 
-        let projected = self.project();
-
-        // Call `.write` on the inner stream. Since the stream is set to non-blocking, this should
-        // return immediately.
-        let result = projected.stream.0.write(*projected.buf);
-        match result {
-            // Successs! Return the number of bytes written
-            Ok(ok) => std::task::Poll::Ready(Ok(ok)),
-            Err(err) if err.kind() == ErrorKind::WouldBlock => {
-                // Not ready yet. If we haven't registered the file descriptor with the runtime,
-                // do it now.
-                if *projected.state == RegisteredState::Unregistered {
-                    let context = RuntimeContext::current();
-                    context.register_file_descriptor(&projected.stream.0);
-                    *projected.state = RegisteredState::Registered;
-                }
-                std::task::Poll::Pending
-            }
-            Err(err) => std::task::Poll::Ready(Err(err)),
-        }
+        let stream = &mut self.get_mut().0;
+        poll_fd(cx, stream.as_raw_fd(), Interest::WRITABLE, || {
+            stream.write_vectored(bufs)
+        })
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // Writes go straight to the socket; there's nothing to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(self.0.shutdown(std::net::Shutdown::Write))
     }
 }
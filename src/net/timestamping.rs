@@ -0,0 +1,118 @@
+use super::sys::ControlMessage;
+use std::mem::size_of;
+use std::ops::BitOr;
+use std::time::Duration;
+
+/// `SO_TIMESTAMPING`, which `libc` only exports for some targets
+///
+/// This is the "old" value, which is what the kernel uses for the 64-bit `timespec` layout on
+/// 64-bit targets. Most architectures share the generic socket option numbers (mips included, for
+/// this one), but sparc has its own. parisc does too, but Rust doesn't target it.
+#[cfg(not(any(target_arch = "sparc", target_arch = "sparc64")))]
+pub(crate) const SO_TIMESTAMPING: libc::c_int = 37;
+/// `SO_TIMESTAMPING`, which `libc` only exports for some targets
+#[cfg(any(target_arch = "sparc", target_arch = "sparc64"))]
+pub(crate) const SO_TIMESTAMPING: libc::c_int = 0x23;
+
+/// `SCM_TIMESTAMPING` is the same value as `SO_TIMESTAMPING`
+const SCM_TIMESTAMPING: libc::c_int = SO_TIMESTAMPING;
+
+/// Which timestamps to generate and report, as passed to `SO_TIMESTAMPING`
+///
+/// Combine these with `|`:
+///
+/// ```
+/// use guillotine::net::TimestampingFlags;
+///
+/// let flags = TimestampingFlags::RX_SOFTWARE | TimestampingFlags::SOFTWARE;
+/// assert_eq!(flags.bits(), libc::SOF_TIMESTAMPING_RX_SOFTWARE | libc::SOF_TIMESTAMPING_SOFTWARE);
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct TimestampingFlags(u32);
+
+impl TimestampingFlags {
+    /// Generate a timestamp when a packet is handed to the network card
+    pub const TX_HARDWARE: Self = Self(libc::SOF_TIMESTAMPING_TX_HARDWARE);
+    /// Generate a timestamp when a packet leaves the kernel
+    pub const TX_SOFTWARE: Self = Self(libc::SOF_TIMESTAMPING_TX_SOFTWARE);
+    /// Generate a timestamp when the network card receives a packet
+    pub const RX_HARDWARE: Self = Self(libc::SOF_TIMESTAMPING_RX_HARDWARE);
+    /// Generate a timestamp when a packet enters the kernel
+    pub const RX_SOFTWARE: Self = Self(libc::SOF_TIMESTAMPING_RX_SOFTWARE);
+    /// Report software timestamps that were generated
+    pub const SOFTWARE: Self = Self(libc::SOF_TIMESTAMPING_SOFTWARE);
+    /// Report hardware timestamps that were generated
+    pub const RAW_HARDWARE: Self = Self(libc::SOF_TIMESTAMPING_RAW_HARDWARE);
+    /// Don't loop the packet contents back along with transmit timestamps
+    pub const OPT_TSONLY: Self = Self(libc::SOF_TIMESTAMPING_OPT_TSONLY);
+
+    /// No flags at all, which turns timestamping off
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Create flags from the raw `SOF_TIMESTAMPING_*` bits
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Get the raw `SOF_TIMESTAMPING_*` bits
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for TimestampingFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The timestamps the kernel attached to a packet
+///
+/// Each one is measured from the start of its clock: `CLOCK_REALTIME` for software timestamps,
+/// and whatever the network card's clock is for hardware timestamps.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Timestamps {
+    /// The software timestamp, if one was generated and reported
+    pub software: Option<Duration>,
+    /// The raw hardware timestamp, if one was generated and reported
+    pub hardware: Option<Duration>,
+}
+
+impl Timestamps {
+    /// Try to read timestamps out of an `SCM_TIMESTAMPING` control message
+    pub(crate) fn from_control_message(message: &ControlMessage<'_>) -> Option<Self> {
+        let timestamps_len = 3 * size_of::<libc::timespec>();
+        if message.level != libc::SOL_SOCKET
+            || message.ty != SCM_TIMESTAMPING
+            || message.data.len() < timestamps_len
+        {
+            return None;
+        }
+
+        // The kernel hands back a `struct scm_timestamping`, which is three timespecs: software,
+        // a deprecated one that is always zero, and raw hardware. A zero timespec means "not
+        // reported".
+        let timespec = |index: usize| {
+            let ts: libc::timespec = unsafe {
+                std::ptr::read_unaligned(
+                    message.data[index * size_of::<libc::timespec>()..].as_ptr()
+                        as *const libc::timespec,
+                )
+            };
+            if ts.tv_sec == 0 && ts.tv_nsec == 0 {
+                None
+            } else {
+                Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+            }
+        };
+
+        Some(Timestamps {
+            software: timespec(0),
+            hardware: timespec(2),
+        })
+    }
+}
@@ -1,8 +1,13 @@
-use crate::runtime::RuntimeContext;
-use pin_project::pin_project;
-use std::future::Future;
+use super::errqueue::ExtendedError;
+use super::sys;
+use super::timestamping::{self, TimestampingFlags, Timestamps};
+use crate::io::poll_fd;
+use crate::runtime::Interest;
+use std::future::poll_fn;
 use std::io::ErrorKind;
 use std::net::SocketAddr;
+use std::os::unix::prelude::AsRawFd;
+use std::task::{Context, Poll};
 
 /// A wrapper around [`std::net::UdpSocket`] that enables _futures_.
 pub struct UdpSocket(std::net::UdpSocket);
@@ -27,162 +32,154 @@ impl UdpSocket {
     }
 
     /// Receive a packet from the socket, as a _future_.
-    pub async fn recv<'a, 'b>(&'a self, buf: &'b mut [u8]) -> Result<usize, std::io::Error> {
-        Recv {
-            socket: &self,
-            buf,
-            state: RegisteredState::Unregistered,
-        }
+    pub async fn recv(&self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        poll_fn(|cx| {
+            poll_fd(cx, self.0.as_raw_fd(), Interest::READABLE, || {
+                self.0.recv(buf)
+            })
+        })
         .await
     }
 
     /// Receive a packet from the socket, as a _future_.
-    pub async fn recv_from<'a, 'b>(
-        &'a self,
-        buf: &'b mut [u8],
-    ) -> Result<(usize, SocketAddr), std::io::Error> {
-        RecvFrom {
-            socket: &self,
-            buf,
-            state: RegisteredState::Unregistered,
-        }
-        .await
+    pub async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), std::io::Error> {
+        poll_fn(|cx| self.poll_recv_from(cx, buf)).await
     }
 
     /// Send a packet on the socket, as a _future_.
-    pub async fn send_to<'a, 'b>(
-        &'a self,
-        buf: &'b [u8],
-        addr: SocketAddr,
-    ) -> Result<usize, std::io::Error> {
-        SendTo {
-            socket: &self,
-            buf,
-            addr,
-            state: RegisteredState::Unregistered,
-        }
-        .await
+    pub async fn send_to(&self, buf: &[u8], addr: SocketAddr) -> Result<usize, std::io::Error> {
+        poll_fn(|cx| self.poll_send_to(cx, buf, addr)).await
     }
-}
 
-/// Track whether the file descriptor has been registered with the runtime or not
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum RegisteredState {
-    Unregistered,
-    Registered,
-}
+    /// Receive a packet from the socket, as a poll function
+    ///
+    /// This is for implementing futures and streams on top of the socket, like
+    /// [`UdpFramed`](crate::codec::UdpFramed) does. The task in `cx` is woken once there might be
+    /// a packet to receive.
+    pub fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<(usize, SocketAddr), std::io::Error>> {
+        poll_fd(cx, self.0.as_raw_fd(), Interest::READABLE, || {
+            self.0.recv_from(buf)
+        })
+    }
 
-/// The future that runs [`UdpSocket::recv`]
-#[pin_project]
-struct Recv<'a, 'b> {
-    socket: &'a UdpSocket,
-    buf: &'b mut [u8],
-    state: RegisteredState,
-}
+    /// Send a packet on the socket, as a poll function
+    ///
+    /// The task in `cx` is woken once there might be room to send.
+    pub fn poll_send_to(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        addr: SocketAddr,
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_fd(cx, self.0.as_raw_fd(), Interest::WRITABLE, || {
+            self.0.send_to(buf, addr)
+        })
+    }
 
-impl<'a, 'b> Future for Recv<'a, 'b> {
-    type Output = Result<usize, std::io::Error>;
+    /// Enable or disable `IP_RECVERR` (or `IPV6_RECVERR`) on the socket
+    ///
+    /// With this enabled, ICMP errors like "destination unreachable" and "packet too big" are
+    /// queued up on the socket's error queue, where they can be read with
+    /// [`UdpSocket::recv_err`].
+    pub fn set_recverr(&self, on: bool) -> Result<(), std::io::Error> {
+        let (level, name) = match self.0.local_addr()? {
+            SocketAddr::V4(_) => (libc::SOL_IP, libc::IP_RECVERR),
+            SocketAddr::V6(_) => (libc::SOL_IPV6, libc::IPV6_RECVERR),
+        };
+        sys::setsockopt_int(self.0.as_raw_fd(), level, name, on as libc::c_int)
+    }
 
-    fn poll(
-        self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Self::Output> {
-        let projected = self.project();
+    /// Configure `SO_TIMESTAMPING` on the socket
+    ///
+    /// Receive timestamps are reported by [`UdpSocket::recv_from_timestamped`]. Transmit timestamps
+    /// are looped back through the error queue and are reported by [`UdpSocket::recv_err`], in
+    /// [`ExtendedError::timestamps`].
+    pub fn set_timestamping(&self, flags: TimestampingFlags) -> Result<(), std::io::Error> {
+        sys::setsockopt_int(
+            self.0.as_raw_fd(),
+            libc::SOL_SOCKET,
+            timestamping::SO_TIMESTAMPING,
+            flags.bits() as libc::c_int,
+        )
+    }
 
-        // Call `.recv` on the inner socket. Since the socket is set to non-blocking, this
-        // should return immediately.
-        let result = projected.socket.0.recv(*projected.buf);
-        match result {
-            // Success! Return the number of bytes read
-            Ok(ok) => std::task::Poll::Ready(Ok(ok)),
-            Err(err) if err.kind() == ErrorKind::WouldBlock => {
-                // Not ready yet. If we haven't registered the file descriptor with the runtime,
-                // do it now.
-                if *projected.state == RegisteredState::Unregistered {
-                    let context = RuntimeContext::current();
-                    context.register_file_descriptor(&projected.socket.0);
-                    *projected.state = RegisteredState::Registered;
+    /// Receive a packet from the socket along with its receive timestamps, as a _future_.
+    ///
+    /// Timestamps are only reported once they are turned on with
+    /// [`UdpSocket::set_timestamping`]; until then, the returned [`Timestamps`] are empty.
+    pub async fn recv_from_timestamped(
+        &self,
+        buf: &mut [u8],
+    ) -> Result<(usize, SocketAddr, Timestamps), std::io::Error> {
+        let fd = self.0.as_raw_fd();
+        poll_fn(|cx| {
+            poll_fd(cx, fd, Interest::READABLE, || {
+                // Call `recvmsg` on the socket, because plain `recv_from` doesn't give us the
+                // control messages the timestamps are in.
+                let mut control = [0_u8; 256];
+                let received = sys::recvmsg(fd, buf, &mut control, 0)?;
+                let timestamps = sys::control_messages(&control[..received.control_len])
+                    .find_map(|message| Timestamps::from_control_message(&message))
+                    .unwrap_or_default();
+                match received.address {
+                    Some(addr) => Ok((received.len, addr, timestamps)),
+                    None => Err(std::io::Error::new(
+                        ErrorKind::InvalidData,
+                        "received a packet without a source address",
+                    )),
                 }
-                std::task::Poll::Pending
-            }
-            Err(err) => std::task::Poll::Ready(Err(err)),
-        }
+            })
+        })
+        .await
     }
-}
-
-/// The future that runs [`UdpSocket::recv_from`]
-#[pin_project]
-struct RecvFrom<'a, 'b> {
-    socket: &'a UdpSocket,
-    buf: &'b mut [u8],
-    state: RegisteredState,
-}
-
-impl<'a, 'b> Future for RecvFrom<'a, 'b> {
-    type Output = Result<(usize, SocketAddr), std::io::Error>;
 
-    fn poll(
-        self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Self::Output> {
-        let projected = self.project();
-
-        // Call `.recv_from` on the inner socket. Since the listener is set to non-blocking, this
-        // should return immediately.
-        let result = projected.socket.0.recv_from(*projected.buf);
-        match result {
-            // Success! Return the information
-            Ok(ok) => std::task::Poll::Ready(Ok(ok)),
-            Err(err) if err.kind() == ErrorKind::WouldBlock => {
-                // Not ready yet. If we haven't registered the file descriptor with the runtime,
-                // do it now.
-                if *projected.state == RegisteredState::Unregistered {
-                    let context = RuntimeContext::current();
-                    context.register_file_descriptor(&projected.socket.0);
-                    *projected.state = RegisteredState::Registered;
+    /// Receive an error from the socket's error queue, as a _future_.
+    ///
+    /// The error queue is only filled in when [`UdpSocket::set_recverr`] is enabled. The provided
+    /// buffer is filled with (the start of) the packet that caused the error.
+    pub async fn recv_err(&self, buf: &mut [u8]) -> Result<(usize, ExtendedError), std::io::Error> {
+        let fd = self.0.as_raw_fd();
+        poll_fn(|cx| {
+            // Reads from the error queue never block, and when it's empty they return `EAGAIN`
+            // just like a normal non-blocking read would. When something does get queued, epoll
+            // reports `EPOLLERR` for the socket, which counts as readable.
+            poll_fd(cx, fd, Interest::READABLE, || {
+                let mut control = [0_u8; 512];
+                let received = sys::recvmsg(fd, buf, &mut control, libc::MSG_ERRQUEUE)?;
+                // Find the extended error in the control messages. Transmit timestamps come
+                // through here too, in their own control message right alongside the extended
+                // error.
+                let mut extended = None;
+                let mut timestamps = None;
+                for message in sys::control_messages(&control[..received.control_len]) {
+                    if let Some(found) = ExtendedError::from_control_message(&message) {
+                        extended = Some(found);
+                    } else if let Some(found) = Timestamps::from_control_message(&message) {
+                        timestamps = Some(found);
+                    }
                 }
-                std::task::Poll::Pending
-            }
-            Err(err) => std::task::Poll::Ready(Err(err)),
-        }
+                match extended {
+                    Some(mut extended) => {
+                        extended.timestamps = timestamps;
+                        Ok((received.len, extended))
+                    }
+                    None => Err(std::io::Error::new(
+                        ErrorKind::InvalidData,
+                        "error queue message did not contain an extended error",
+                    )),
+                }
+            })
+        })
+        .await
     }
 }
 
-/// The future that runs [`UdpSocket::send_to`]
-#[pin_project]
-struct SendTo<'a, 'b> {
-    socket: &'a UdpSocket,
-    buf: &'b [u8],
-    addr: SocketAddr,
-    state: RegisteredState,
-}
-
-impl<'a, 'b> Future for SendTo<'a, 'b> {
-    type Output = Result<usize, std::io::Error>;
-
-    fn poll(
-        self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Self::Output> {
-        let projected = self.project();
-
-        // Call `.send_to` on the inner listener. Since the socket is set to non-blocking, this
-        // should return immediately.
-        let result = projected.socket.0.send_to(*projected.buf, *projected.addr);
-        match result {
-            // Success! Return the number of bytes written
-            Ok(ok) => std::task::Poll::Ready(Ok(ok)),
-            Err(err) if err.kind() == ErrorKind::WouldBlock => {
-                // Not ready yet. If we haven't registered the file descriptor with the runtime,
-                // do it now.
-                if *projected.state == RegisteredState::Unregistered {
-                    let context = RuntimeContext::current();
-                    context.register_file_descriptor(&projected.socket.0);
-                    *projected.state = RegisteredState::Registered;
-                }
-                std::task::Poll::Pending
-            }
-            Err(err) => std::task::Poll::Ready(Err(err)),
-        }
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.0.as_raw_fd());
     }
 }
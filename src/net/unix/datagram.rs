@@ -0,0 +1,123 @@
+use crate::io::poll_fd;
+use crate::runtime::Interest;
+use crate::trace::warn;
+use std::future::poll_fn;
+use std::os::unix::net::SocketAddr;
+use std::os::unix::prelude::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// A wrapper around [`std::os::unix::net::UnixDatagram`] that enables _futures_.
+pub struct UnixDatagram {
+    /// The wrapped socket
+    socket: std::os::unix::net::UnixDatagram,
+    /// The path of the socket file, if we created it and are responsible for removing it
+    path: Option<PathBuf>,
+}
+
+impl UnixDatagram {
+    /// Create a new socket
+    ///
+    /// This will set the socket to be non-blocking.
+    pub fn new(socket: std::os::unix::net::UnixDatagram) -> Result<Self, std::io::Error> {
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, path: None })
+    }
+
+    /// Create a new socket bound to the provided path
+    ///
+    /// The socket takes ownership of the socket file: it is removed when the socket is dropped.
+    pub fn bind(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let path = path.as_ref();
+        let socket = std::os::unix::net::UnixDatagram::bind(path)?;
+        let mut socket = Self::new(socket)?;
+        socket.path = Some(path.to_path_buf());
+        Ok(socket)
+    }
+
+    /// Create a new socket that isn't bound to any path
+    ///
+    /// This is what you want for sending to a well-known socket (like syslog's) when you don't
+    /// need replies.
+    pub fn unbound() -> Result<Self, std::io::Error> {
+        Self::new(std::os::unix::net::UnixDatagram::unbound()?)
+    }
+
+    /// Connect the socket to the provided path, so that [`UnixDatagram::send`] and
+    /// [`UnixDatagram::recv`] can be used
+    ///
+    /// Connecting a datagram socket just records the peer address, so this doesn't need to be a
+    /// future.
+    pub fn connect(&self, path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+        self.socket.connect(path)
+    }
+
+    /// Get access to the wrapped UnixDatagram
+    pub fn inner(&self) -> &std::os::unix::net::UnixDatagram {
+        &self.socket
+    }
+
+    /// Get mutable access to the wrapped UnixDatagram
+    pub fn inner_mut(&mut self) -> &mut std::os::unix::net::UnixDatagram {
+        &mut self.socket
+    }
+
+    /// Receive a packet from the connected peer, as a _future_.
+    pub async fn recv(&self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        let socket = &self.socket;
+        poll_fn(|cx| {
+            poll_fd(cx, socket.as_raw_fd(), Interest::READABLE, || {
+                socket.recv(buf)
+            })
+        })
+        .await
+    }
+
+    /// Receive a packet from the socket, as a _future_.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), std::io::Error> {
+        let socket = &self.socket;
+        poll_fn(|cx| {
+            poll_fd(cx, socket.as_raw_fd(), Interest::READABLE, || {
+                socket.recv_from(buf)
+            })
+        })
+        .await
+    }
+
+    /// Send a packet to the connected peer, as a _future_.
+    pub async fn send(&self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        let socket = &self.socket;
+        poll_fn(|cx| {
+            poll_fd(cx, socket.as_raw_fd(), Interest::WRITABLE, || {
+                socket.send(buf)
+            })
+        })
+        .await
+    }
+
+    /// Send a packet to the socket at the provided path, as a _future_.
+    pub async fn send_to(
+        &self,
+        buf: &[u8],
+        path: impl AsRef<Path>,
+    ) -> Result<usize, std::io::Error> {
+        let socket = &self.socket;
+        let path = path.as_ref();
+        poll_fn(|cx| {
+            poll_fd(cx, socket.as_raw_fd(), Interest::WRITABLE, || {
+                socket.send_to(buf, path)
+            })
+        })
+        .await
+    }
+}
+
+impl Drop for UnixDatagram {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.socket.as_raw_fd());
+        if let Some(path) = &self.path {
+            if let Err(error) = std::fs::remove_file(path) {
+                warn!(error = %error, path = ?path, "failed to remove unix socket file");
+            }
+        }
+    }
+}
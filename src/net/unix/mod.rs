@@ -0,0 +1,39 @@
+//! Unix domain sockets
+//!
+//! These mirror the TCP and UDP types, but talk over a path on the local filesystem instead of
+//! over the network.
+
+mod datagram;
+mod stream;
+
+pub use datagram::UnixDatagram;
+pub use stream::{ReadHalf, UnixListener, UnixStream, WriteHalf};
+
+/// Create a pair of connected [`UnixStream`]s
+///
+/// This is `socketpair(AF_UNIX, SOCK_STREAM)`: whatever is written to one end can be read from the
+/// other. Handy for talking to a child process, or between two tasks.
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (mut a, mut b) = guillotine::net::unix::pair().unwrap();
+///     a.write(b"ping").await.unwrap();
+///
+///     let mut buf = [0_u8; 4];
+///     let read = b.read(&mut buf).await.unwrap();
+///     assert_eq!(&buf[..read], b"ping");
+/// });
+/// ```
+pub fn pair() -> Result<(UnixStream, UnixStream), std::io::Error> {
+    let (a, b) = std::os::unix::net::UnixStream::pair()?;
+    Ok((UnixStream::new(a)?, UnixStream::new(b)?))
+}
+
+/// Create a pair of connected [`UnixDatagram`]s
+///
+/// This is `socketpair(AF_UNIX, SOCK_DGRAM)`, the datagram version of [`pair`].
+pub fn datagram_pair() -> Result<(UnixDatagram, UnixDatagram), std::io::Error> {
+    let (a, b) = std::os::unix::net::UnixDatagram::pair()?;
+    Ok((UnixDatagram::new(a)?, UnixDatagram::new(b)?))
+}
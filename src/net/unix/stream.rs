@@ -0,0 +1,360 @@
+use crate::io::{poll_fd, AsyncRead, AsyncWrite};
+use crate::net::socket::{self, Socket};
+use crate::net::sys;
+use crate::runtime::Interest;
+use crate::trace::warn;
+use std::io::{IoSlice, IoSliceMut};
+use std::os::unix::net::SocketAddr;
+use std::os::unix::prelude::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A wrapper around [`std::os::unix::net::UnixListener`] that enables _futures_.
+pub struct UnixListener {
+    /// The wrapped listener
+    listener: std::os::unix::net::UnixListener,
+    /// The path of the socket file, if we created it and are responsible for removing it
+    path: Option<PathBuf>,
+}
+
+impl UnixListener {
+    /// Create a new listener
+    ///
+    /// This will set the listener to be non-blocking.
+    pub fn new(listener: std::os::unix::net::UnixListener) -> Result<Self, std::io::Error> {
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            path: None,
+        })
+    }
+
+    /// Create a new listener bound to the provided path
+    ///
+    /// Unlike [`UnixListener::new`], the listener takes ownership of the socket file: it is
+    /// removed when the listener is dropped.
+    pub fn bind(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let path = path.as_ref();
+        let listener = std::os::unix::net::UnixListener::bind(path)?;
+        let mut listener = Self::new(listener)?;
+        listener.path = Some(path.to_path_buf());
+        Ok(listener)
+    }
+
+    /// Get access to the wrapped UnixListener
+    pub fn inner(&self) -> &std::os::unix::net::UnixListener {
+        &self.listener
+    }
+
+    /// Get mutable access to the wrapped UnixListener
+    pub fn inner_mut(&mut self) -> &mut std::os::unix::net::UnixListener {
+        &mut self.listener
+    }
+
+    /// Wait until a new connection is available and accept that connection
+    pub async fn accept(&self) -> Result<(UnixStream, SocketAddr), std::io::Error> {
+        // Since the listener is non-blocking, `accept` returns immediately.
+        let listener = &self.listener;
+        let (stream, addr) = std::future::poll_fn(|cx| {
+            poll_fd(cx, listener.as_raw_fd(), Interest::READABLE, || {
+                listener.accept()
+            })
+        })
+        .await?;
+        Ok((UnixStream::new(stream)?, addr))
+    }
+}
+
+impl Drop for UnixListener {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.listener.as_raw_fd());
+        if let Some(path) = &self.path {
+            if let Err(error) = std::fs::remove_file(path) {
+                warn!(error = %error, path = ?path, "failed to remove unix socket file");
+            }
+        }
+    }
+}
+
+/// A wrapper around [`std::os::unix::net::UnixStream`] that enables _futures_.
+pub struct UnixStream(std::os::unix::net::UnixStream);
+
+impl UnixStream {
+    /// Create a new stream
+    ///
+    /// This will set the stream to be non-blocking.
+    pub fn new(stream: std::os::unix::net::UnixStream) -> Result<Self, std::io::Error> {
+        stream.set_nonblocking(true)?;
+        Ok(Self(stream))
+    }
+
+    /// Connect to the socket at the provided path, as a _future_.
+    ///
+    /// The connect is non-blocking. If nobody's listening, this fails with `ECONNREFUSED`, and if
+    /// the listener's backlog is full, with `EAGAIN` (the kernel has no way of saying when there's
+    /// room again).
+    ///
+    /// ```
+    /// use guillotine::net::{UnixListener, UnixStream};
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let name = format!("guillotine-connect-{}", std::process::id());
+    ///     let dir = std::env::temp_dir().join(name);
+    ///     std::fs::create_dir_all(&dir).unwrap();
+    ///     let path = dir.join("socket");
+    ///     let listener = UnixListener::bind(&path).unwrap();
+    ///
+    ///     let mut client = UnixStream::connect(&path).await.unwrap();
+    ///     let (mut server, _) = listener.accept().await.unwrap();
+    ///
+    ///     client.write(b"hello").await.unwrap();
+    ///     let mut buf = [0; 5];
+    ///     let read = server.read(&mut buf).await.unwrap();
+    ///     assert_eq!(&buf[..read], b"hello");
+    ///
+    ///     // Nobody's listening anymore
+    ///     drop(listener);
+    ///     assert!(UnixStream::connect(&path).await.is_err());
+    ///     std::fs::remove_dir(&dir).unwrap();
+    /// });
+    /// ```
+    pub async fn connect(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let addr = sys::unix_path_to_raw(path.as_ref())?;
+        let socket = Socket::new(libc::AF_UNIX, libc::SOCK_STREAM, 0)?;
+        socket::connect(&socket, &addr).await?;
+        // The socket is non-blocking already.
+        Ok(Self(std::os::unix::net::UnixStream::from(
+            socket.into_owned_fd(),
+        )))
+    }
+
+    /// Get access to the wrapped UnixStream
+    pub fn inner(&self) -> &std::os::unix::net::UnixStream {
+        &self.0
+    }
+
+    /// Get mutable access to the wrapped UnixStream
+    pub fn inner_mut(&mut self) -> &mut std::os::unix::net::UnixStream {
+        &mut self.0
+    }
+
+    /// Read bytes from the stream, as a future
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| poll_read(cx, &self.0, buf)).await
+    }
+
+    /// Read bytes from the stream into the spare capacity of `buf`, as a future
+    ///
+    /// The bytes go straight from the socket into `buf`, without zeroing it first.
+    #[cfg(feature = "bytes")]
+    pub async fn read_buf<B: bytes::BufMut + ?Sized>(
+        &mut self,
+        buf: &mut B,
+    ) -> Result<usize, std::io::Error> {
+        crate::io::read_fd_buf(self.0.as_raw_fd(), buf).await
+    }
+
+    /// Write bytes to the stream, as a future
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| poll_write(cx, &self.0, buf)).await
+    }
+
+    /// Split the stream into a read half and a write half
+    ///
+    /// The halves borrow the stream, so they can be used at the same time from the same task (for
+    /// example, one inside a `select`), but not moved into separate spawned tasks.
+    pub fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
+        (ReadHalf(&self.0), WriteHalf(&self.0))
+    }
+}
+
+impl Drop for UnixStream {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.0.as_raw_fd());
+    }
+}
+
+/// The read half of a [`UnixStream`], created by [`UnixStream::split`]
+pub struct ReadHalf<'a>(&'a std::os::unix::net::UnixStream);
+
+impl<'a> ReadHalf<'a> {
+    /// Read bytes from the stream, as a future
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| poll_read(cx, self.0, buf)).await
+    }
+
+    /// Read bytes from the stream into the spare capacity of `buf`, as a future
+    ///
+    /// The bytes go straight from the socket into `buf`, without zeroing it first.
+    #[cfg(feature = "bytes")]
+    pub async fn read_buf<B: bytes::BufMut + ?Sized>(
+        &mut self,
+        buf: &mut B,
+    ) -> Result<usize, std::io::Error> {
+        crate::io::read_fd_buf(self.0.as_raw_fd(), buf).await
+    }
+}
+
+/// The write half of a [`UnixStream`], created by [`UnixStream::split`]
+pub struct WriteHalf<'a>(&'a std::os::unix::net::UnixStream);
+
+impl<'a> WriteHalf<'a> {
+    /// Write bytes to the stream, as a future
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| poll_write(cx, self.0, buf)).await
+    }
+}
+
+impl AsyncRead for UnixStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_read(cx, &self.0, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_read_vectored(cx, &self.0, bufs)
+    }
+}
+
+impl AsyncWrite for UnixStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_write(cx, &self.0, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_write_vectored(cx, &self.0, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // Writes go straight to the socket; there's nothing to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(self.0.shutdown(std::net::Shutdown::Write))
+    }
+}
+
+impl<'a> AsyncRead for ReadHalf<'a> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_read(cx, self.0, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_read_vectored(cx, self.0, bufs)
+    }
+}
+
+impl<'a> AsyncWrite for WriteHalf<'a> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_write(cx, self.0, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_write_vectored(cx, self.0, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // Writes go straight to the socket; there's nothing to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(self.0.shutdown(std::net::Shutdown::Write))
+    }
+}
+
+/// Read from a stream for [`AsyncRead::poll_read`] and the `read` methods
+///
+/// `Read` is implemented for `&UnixStream`, so the whole stream and the read half can share this.
+fn poll_read(
+    cx: &mut Context<'_>,
+    mut stream: &std::os::unix::net::UnixStream,
+    buf: &mut [u8],
+) -> Poll<Result<usize, std::io::Error>> {
+    use std::io::Read;
+
+    poll_fd(cx, stream.as_raw_fd(), Interest::READABLE, || {
+        stream.read(buf)
+    })
+}
+
+/// Write to a stream for [`AsyncWrite::poll_write`] and the `write` methods
+fn poll_write(
+    cx: &mut Context<'_>,
+    mut stream: &std::os::unix::net::UnixStream,
+    buf: &[u8],
+) -> Poll<Result<usize, std::io::Error>> {
+    use std::io::Write;
+
+    poll_fd(cx, stream.as_raw_fd(), Interest::WRITABLE, || {
+        stream.write(buf)
+    })
+}
+
+/// Read from a stream for [`AsyncRead::poll_read_vectored`]
+fn poll_read_vectored(
+    cx: &mut Context<'_>,
+    mut stream: &std::os::unix::net::UnixStream,
+    bufs: &mut [IoSliceMut<'_>],
+) -> Poll<Result<usize, std::io::Error>> {
+    use std::io::Read;
+
+    poll_fd(cx, stream.as_raw_fd(), Interest::READABLE, || {
+        stream.read_vectored(bufs)
+    })
+}
+
+/// Write to a stream for [`AsyncWrite::poll_write_vectored`]
+fn poll_write_vectored(
+    cx: &mut Context<'_>,
+    mut stream: &std::os::unix::net::UnixStream,
+    bufs: &[IoSlice<'_>],
+) -> Poll<Result<usize, std::io::Error>> {
+    use std::io::Write;
+
+    poll_fd(cx, stream.as_raw_fd(), Interest::WRITABLE, || {
+        stream.write_vectored(bufs)
+    })
+}
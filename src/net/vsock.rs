@@ -0,0 +1,242 @@
+//! `AF_VSOCK` sockets, for talking between a virtual machine and its host
+//!
+//! The address of a vsock socket is a context ID (the "CID"; the host is
+//! [`VsockAddr::CID_HOST`], each guest gets its own) and a port.
+
+use super::socket::{self, Retry, Socket};
+use crate::io::{poll_fd, AsyncRead, AsyncWrite};
+use crate::runtime::Interest;
+use std::fmt::Display;
+use std::io::{IoSlice, IoSliceMut};
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The address of a vsock socket
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct VsockAddr {
+    /// The context ID
+    pub cid: u32,
+    /// The port
+    pub port: u32,
+}
+
+impl VsockAddr {
+    /// Bind to any context ID
+    pub const CID_ANY: u32 = libc::VMADDR_CID_ANY;
+    /// The context ID of the local machine (loopback)
+    pub const CID_LOCAL: u32 = libc::VMADDR_CID_LOCAL;
+    /// The context ID of the host
+    pub const CID_HOST: u32 = libc::VMADDR_CID_HOST;
+    /// Bind to any port
+    pub const PORT_ANY: u32 = libc::VMADDR_PORT_ANY;
+
+    /// Create a new address
+    pub fn new(cid: u32, port: u32) -> Self {
+        Self { cid, port }
+    }
+
+    /// Convert to the `sockaddr_vm` the kernel wants
+    fn to_raw(self) -> libc::sockaddr_vm {
+        let mut addr: libc::sockaddr_vm = unsafe { std::mem::zeroed() };
+        addr.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+        addr.svm_cid = self.cid;
+        addr.svm_port = self.port;
+        addr
+    }
+
+    /// Convert from the `sockaddr_vm` the kernel hands back
+    fn from_raw(addr: libc::sockaddr_vm) -> Self {
+        Self {
+            cid: addr.svm_cid,
+            port: addr.svm_port,
+        }
+    }
+}
+
+impl Display for VsockAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "vsock:{}:{}", self.cid, self.port)
+    }
+}
+
+/// A vsock stream listener, the vsock equivalent of [`TcpListener`](super::TcpListener).
+#[derive(Debug)]
+pub struct VsockListener(Socket);
+
+impl VsockListener {
+    /// Create a new listener bound to the provided address
+    pub fn bind(addr: VsockAddr) -> Result<Self, std::io::Error> {
+        let socket = Socket::new(libc::AF_VSOCK, libc::SOCK_STREAM, 0)?;
+        socket.bind(&addr.to_raw())?;
+        socket.listen(128)?;
+        Ok(Self(socket))
+    }
+
+    /// Get the address the listener is bound to
+    pub fn local_addr(&self) -> Result<VsockAddr, std::io::Error> {
+        self.0.local_addr().map(VsockAddr::from_raw)
+    }
+
+    /// Wait until a new connection is available and accept that connection
+    pub async fn accept(&self) -> Result<(VsockStream, VsockAddr), std::io::Error> {
+        let (socket, addr) =
+            Retry::reading(self.0.as_raw_fd(), || self.0.accept::<libc::sockaddr_vm>()).await?;
+        Ok((VsockStream(socket), VsockAddr::from_raw(addr)))
+    }
+}
+
+impl AsRawFd for VsockListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// A vsock stream, the vsock equivalent of [`TcpStream`](super::TcpStream).
+#[derive(Debug)]
+pub struct VsockStream(Socket);
+
+impl VsockStream {
+    /// Connect to the provided address
+    pub async fn connect(addr: VsockAddr) -> Result<Self, std::io::Error> {
+        let socket = Socket::new(libc::AF_VSOCK, libc::SOCK_STREAM, 0)?;
+        socket::connect(&socket, &addr.to_raw()).await?;
+        Ok(Self(socket))
+    }
+
+    /// Get the address the stream is bound to
+    pub fn local_addr(&self) -> Result<VsockAddr, std::io::Error> {
+        self.0.local_addr().map(VsockAddr::from_raw)
+    }
+
+    /// Read bytes from the stream, as a future
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        Retry::reading(self.0.as_raw_fd(), || self.0.read(buf)).await
+    }
+
+    /// Read bytes from the stream into the spare capacity of `buf`, as a future
+    ///
+    /// The bytes go straight from the socket into `buf`, without zeroing it first.
+    #[cfg(feature = "bytes")]
+    pub async fn read_buf<B: bytes::BufMut + ?Sized>(
+        &mut self,
+        buf: &mut B,
+    ) -> Result<usize, std::io::Error> {
+        crate::io::read_fd_buf(self.0.as_raw_fd(), buf).await
+    }
+
+    /// Write bytes to the stream, as a future
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        Retry::writing(self.0.as_raw_fd(), || self.0.write(buf)).await
+    }
+
+    /// Shut down the read half, write half, or both halves of the stream
+    pub fn shutdown(&self, how: std::net::Shutdown) -> Result<(), std::io::Error> {
+        self.0.shutdown(how)
+    }
+}
+
+impl AsRawFd for VsockStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsyncRead for VsockStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_fd(cx, self.0.as_raw_fd(), Interest::READABLE, || {
+            self.0.read(buf)
+        })
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_fd(cx, self.0.as_raw_fd(), Interest::READABLE, || {
+            self.0.read_vectored(bufs)
+        })
+    }
+}
+
+impl AsyncWrite for VsockStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_fd(cx, self.0.as_raw_fd(), Interest::WRITABLE, || {
+            self.0.write(buf)
+        })
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        poll_fd(cx, self.0.as_raw_fd(), Interest::WRITABLE, || {
+            self.0.write_vectored(bufs)
+        })
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // Writes go straight to the socket; there's nothing to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(self.0.shutdown(std::net::Shutdown::Write))
+    }
+}
+
+/// A vsock datagram socket, the vsock equivalent of [`UdpSocket`](super::UdpSocket).
+///
+/// Not every vsock transport supports datagrams; virtio-vsock, for one, only gained support
+/// recently.
+#[derive(Debug)]
+pub struct VsockDatagram(Socket);
+
+impl VsockDatagram {
+    /// Create a new socket bound to the provided address
+    pub fn bind(addr: VsockAddr) -> Result<Self, std::io::Error> {
+        let socket = Socket::new(libc::AF_VSOCK, libc::SOCK_DGRAM, 0)?;
+        socket.bind(&addr.to_raw())?;
+        Ok(Self(socket))
+    }
+
+    /// Get the address the socket is bound to
+    pub fn local_addr(&self) -> Result<VsockAddr, std::io::Error> {
+        self.0.local_addr().map(VsockAddr::from_raw)
+    }
+
+    /// Receive a packet from the socket, as a _future_.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, VsockAddr), std::io::Error> {
+        let (received, addr) = Retry::reading(self.0.as_raw_fd(), || {
+            self.0.recv_from::<libc::sockaddr_vm>(buf)
+        })
+        .await?;
+        Ok((received, VsockAddr::from_raw(addr)))
+    }
+
+    /// Send a packet on the socket, as a _future_.
+    pub async fn send_to(&self, buf: &[u8], addr: VsockAddr) -> Result<usize, std::io::Error> {
+        let addr = addr.to_raw();
+        Retry::writing(self.0.as_raw_fd(), || self.0.send_to(buf, &addr)).await
+    }
+}
+
+impl AsRawFd for VsockDatagram {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
@@ -0,0 +1,609 @@
+//! Running child processes, and waiting for them as _futures_
+//!
+//! [`Command`] is a thin layer over [`std::process::Command`]. The difference is in the [`Child`]
+//! it spawns: waiting for it to exit is a _future_, and its piped standard input, output, and
+//! error implement the crate's [`AsyncRead`](crate::io::AsyncRead) and
+//! [`AsyncWrite`](crate::io::AsyncWrite).
+//!
+//! Waiting uses a `pidfd` (Linux 5.3 and later), a file descriptor that refers to the child and
+//! becomes readable when it exits. The runtime waits on it like any other file descriptor, so
+//! there's no need for a thread sitting in `waitpid`, or for a `SIGCHLD` handler.
+//!
+//! ```
+//! use guillotine::io::AsyncReadExt;
+//! use guillotine::process::{Command, Stdio};
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let mut child = Command::new("echo")
+//!         .arg("hello")
+//!         .stdout(Stdio::piped())
+//!         .spawn()
+//!         .unwrap();
+//!
+//!     let mut output = String::new();
+//!     child.stdout.take().unwrap().read_to_string(&mut output).await.unwrap();
+//!     assert_eq!(output, "hello\n");
+//!
+//!     let status = child.wait().await.unwrap();
+//!     assert!(status.success());
+//! });
+//! ```
+
+mod pipe;
+mod pty;
+mod reaper;
+mod sandbox;
+
+pub use pipe::{ChildStderr, ChildStdin, ChildStdout};
+pub use pty::Pty;
+pub use reaper::reap_orphans;
+pub use sandbox::Resource;
+pub use std::process::{ExitStatus, Output, Stdio};
+
+use crate::io::{poll_fd, AsyncFd, AsyncRead};
+use crate::runtime::Interest;
+use crate::signal::SignalKind;
+use crate::time::Sleep;
+use std::ffi::OsStr;
+use std::future::Future;
+use std::io::{Error, ErrorKind};
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// A builder for a child process, like [`std::process::Command`]
+#[derive(Debug)]
+pub struct Command {
+    std: std::process::Command,
+    kill_on_drop: bool,
+    process_group: Option<i32>,
+    /// The uid/gid/chroot/rlimit settings, shared with the hook that applies them in the child
+    sandbox: Option<Arc<Mutex<sandbox::Sandbox>>>,
+}
+
+impl Command {
+    /// Create a new command that runs `program`
+    ///
+    /// Like with `std`, a program without a `/` is looked up in the `PATH`.
+    pub fn new(program: impl AsRef<OsStr>) -> Self {
+        Self::from(std::process::Command::new(program))
+    }
+
+    /// Add an argument
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.std.arg(arg);
+        self
+    }
+
+    /// Add several arguments
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.std.args(args);
+        self
+    }
+
+    /// Set an environment variable
+    pub fn env(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &mut Self {
+        self.std.env(key, value);
+        self
+    }
+
+    /// Set several environment variables
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.std.envs(vars);
+        self
+    }
+
+    /// Remove an environment variable
+    pub fn env_remove(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        self.std.env_remove(key);
+        self
+    }
+
+    /// Start the child with no environment variables except the ones set on this command
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.std.env_clear();
+        self
+    }
+
+    /// Set the working directory of the child
+    pub fn current_dir(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.std.current_dir(dir);
+        self
+    }
+
+    /// Set where the child's standard input comes from
+    pub fn stdin(&mut self, cfg: impl Into<Stdio>) -> &mut Self {
+        self.std.stdin(cfg);
+        self
+    }
+
+    /// Set where the child's standard output goes
+    pub fn stdout(&mut self, cfg: impl Into<Stdio>) -> &mut Self {
+        self.std.stdout(cfg);
+        self
+    }
+
+    /// Set where the child's standard error goes
+    pub fn stderr(&mut self, cfg: impl Into<Stdio>) -> &mut Self {
+        self.std.stderr(cfg);
+        self
+    }
+
+    /// Kill the child when its [`Child`] is dropped, if it hasn't exited yet
+    ///
+    /// This makes sure that a child doesn't outlive a task that gets cancelled while it's
+    /// waiting. If the child is in its own [process group](Self::process_group), the whole group
+    /// is killed, which takes care of the child's own children too.
+    pub fn kill_on_drop(&mut self, kill_on_drop: bool) -> &mut Self {
+        self.kill_on_drop = kill_on_drop;
+        self
+    }
+
+    /// Put the child in a process group
+    ///
+    /// Zero puts it in a new group of its own, with the child's PID as the group ID; anything else
+    /// joins an existing group. A child in its own group doesn't get the `SIGINT` that Ctrl-C in a
+    /// terminal sends to the foreground group, and it (along with anything it starts) can be
+    /// killed all at once with [`Child::kill_group`].
+    pub fn process_group(&mut self, pgroup: i32) -> &mut Self {
+        self.std.process_group(pgroup);
+        self.process_group = Some(pgroup);
+        self
+    }
+
+    /// Get access to the wrapped `std` command
+    pub fn as_std(&self) -> &std::process::Command {
+        &self.std
+    }
+
+    /// Get mutable access to the wrapped `std` command, for the options that aren't mirrored here
+    ///
+    /// A process group set through here isn't known to [`Child::kill_group`] or
+    /// [`kill_on_drop`](Self::kill_on_drop); use [`process_group`](Self::process_group) instead.
+    pub fn as_std_mut(&mut self) -> &mut std::process::Command {
+        &mut self.std
+    }
+
+    /// Start the child process
+    ///
+    /// Starting a process doesn't wait for it to do anything, so this isn't a future.
+    ///
+    /// The actual spawning is done by `std`, which uses `posix_spawn(3)` whenever the command
+    /// allows it. On Linux, glibc implements that with `clone(CLONE_VM | CLONE_VFORK)`, so the
+    /// child borrows this process's memory until it calls `exec`, instead of `fork` copying the
+    /// page tables of the whole address space (which gets slow when there's a lot of it).
+    /// Anything that needs code to run in the child between `fork` and `exec` rules that out and
+    /// falls back to `fork`: [`pre_exec`](Self::pre_exec), [`uid`](Self::uid),
+    /// [`gid`](Self::gid), [`chroot`](Self::chroot), [`rlimit`](Self::rlimit), and
+    /// [`pty`](Self::pty). Setting the environment, working directory, standard input and
+    /// output, and [process group](Self::process_group) all keep the fast path.
+    pub fn spawn(&mut self) -> Result<Child, std::io::Error> {
+        // A good moment to clean up after children that were dropped before they exited
+        reaper::reap();
+
+        let mut std = self.std.spawn()?;
+        let pidfd = match pidfd_open(std.id()) {
+            Ok(pidfd) => pidfd,
+            Err(err) => {
+                // Without a pidfd there's no waiting for the child, so don't leave it running
+                let _ = std.kill();
+                let _ = std.wait();
+                return Err(err);
+            }
+        };
+
+        let process_group = self.process_group.map(|pgroup| match pgroup {
+            0 => std.id() as libc::pid_t,
+            pgroup => pgroup,
+        });
+        Ok(Child {
+            stdin: std.stdin.take().map(ChildStdin::from_std).transpose()?,
+            stdout: std.stdout.take().map(ChildStdout::from_std).transpose()?,
+            stderr: std.stderr.take().map(ChildStderr::from_std).transpose()?,
+            std,
+            pidfd,
+            kill_on_drop: self.kill_on_drop,
+            process_group,
+        })
+    }
+
+    /// Run the command, collecting everything it writes to its standard output and error, and wait
+    /// for it to exit, as a _future_.
+    ///
+    /// Standard output and error are always piped, whatever they were set to. Standard input is
+    /// left as it was set (inheriting from this process by default).
+    ///
+    /// ```
+    /// use guillotine::process::Command;
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let output = Command::new("sh")
+    ///         .args(["-c", "echo out; echo err >&2; exit 2"])
+    ///         .output()
+    ///         .await
+    ///         .unwrap();
+    ///     assert_eq!(output.status.code(), Some(2));
+    ///     assert_eq!(output.stdout, b"out\n");
+    ///     assert_eq!(output.stderr, b"err\n");
+    /// });
+    /// ```
+    pub async fn output(&mut self) -> Result<Output, std::io::Error> {
+        self.stdout(Stdio::piped());
+        self.stderr(Stdio::piped());
+        self.spawn()?.wait_with_output().await
+    }
+
+    /// Run the command and wait for it to exit, as a _future_.
+    ///
+    /// Standard input, output, and error are left as they were set (inheriting from this process
+    /// by default).
+    pub async fn status(&mut self) -> Result<ExitStatus, std::io::Error> {
+        self.spawn()?.wait().await
+    }
+}
+
+impl From<std::process::Command> for Command {
+    fn from(std: std::process::Command) -> Self {
+        Self {
+            std,
+            kill_on_drop: false,
+            process_group: None,
+            sandbox: None,
+        }
+    }
+}
+
+/// A running (or exited) child process, created by [`Command::spawn`]
+///
+/// Dropping a `Child` doesn't kill it or wait for it, and the process keeps running, unless the
+/// command was set to [`kill_on_drop`](Command::kill_on_drop). Either way, the process is waited
+/// for after it exits, the next time a child is spawned (or right away, with [`reap_orphans`]),
+/// so it doesn't stay around as a zombie. That's true whichever thread the `Child` was dropped on.
+///
+/// ```
+/// use guillotine::process::{Command, Stdio};
+/// use std::time::Duration;
+///
+/// // Start a child on one thread, and drop it there while it's still running. `cat` keeps going
+/// // until its input is closed, which is the last thing dropping the `Child` does...
+/// let pid = std::thread::spawn(|| {
+///     let runtime = guillotine::runtime::Runtime::new().unwrap();
+///     runtime.block_on(async {
+///         let child = Command::new("cat").stdin(Stdio::piped()).spawn().unwrap();
+///         child.id()
+///     })
+/// })
+/// .join()
+/// .unwrap();
+///
+/// // ...so wait for it to exit, and become a zombie that nothing has waited for...
+/// let zombie = (0..500).any(|_| {
+///     let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).unwrap();
+///     // The state comes after the command name, which is in parentheses
+///     let state = stat.rsplit(") ").next().unwrap();
+///     state.starts_with('Z') || {
+///         std::thread::sleep(Duration::from_millis(10));
+///         false
+///     }
+/// });
+/// assert!(zombie);
+///
+/// // ...and it's reaped when a child is spawned on another one.
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     Command::new("true").status().await.unwrap();
+/// });
+/// assert!(!std::path::Path::new(&format!("/proc/{pid}")).exists());
+/// ```
+#[derive(Debug)]
+pub struct Child {
+    /// The child's standard input, if it was [piped](Stdio::piped)
+    pub stdin: Option<ChildStdin>,
+    /// The child's standard output, if it was [piped](Stdio::piped)
+    pub stdout: Option<ChildStdout>,
+    /// The child's standard error, if it was [piped](Stdio::piped)
+    pub stderr: Option<ChildStderr>,
+    std: std::process::Child,
+    pidfd: OwnedFd,
+    kill_on_drop: bool,
+    /// The ID of the process group the child was put in, if it was put in one
+    process_group: Option<libc::pid_t>,
+}
+
+impl Child {
+    /// The child's process ID
+    pub fn id(&self) -> u32 {
+        self.std.id()
+    }
+
+    /// Wait for the child to exit, as a _future_.
+    ///
+    /// The child's standard input is closed first (if it was piped), so a child that reads until
+    /// the end of its input doesn't wait forever.
+    pub async fn wait(&mut self) -> Result<ExitStatus, std::io::Error> {
+        drop(self.stdin.take());
+        std::future::poll_fn(|cx| self.poll_wait(cx)).await
+    }
+
+    /// Wait for the child to exit, but give up after `timeout`, as a _future_.
+    ///
+    /// Resolves to `None` if the child is still running when the time is up. Like
+    /// [`wait`](Self::wait), this closes the child's standard input first.
+    ///
+    /// This is the building block for a graceful shutdown: ask nicely, wait a little while, and
+    /// then insist.
+    ///
+    /// ```
+    /// use guillotine::process::Command;
+    /// use guillotine::signal::SignalKind;
+    /// use std::time::Duration;
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     // A child that ignores SIGTERM
+    ///     let mut child = Command::new("sh")
+    ///         .args(["-c", "trap '' TERM; sleep 10"])
+    ///         .spawn()
+    ///         .unwrap();
+    ///
+    ///     child.signal(SignalKind::terminate()).unwrap();
+    ///     let status = match child.wait_timeout(Duration::from_millis(200)).await.unwrap() {
+    ///         Some(status) => status,
+    ///         None => {
+    ///             child.kill().unwrap();
+    ///             child.wait().await.unwrap()
+    ///         }
+    ///     };
+    ///     assert!(!status.success());
+    /// });
+    /// ```
+    pub async fn wait_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<ExitStatus>, std::io::Error> {
+        drop(self.stdin.take());
+        let mut sleep = Sleep::new(timeout)?;
+        std::future::poll_fn(|cx| {
+            if let Poll::Ready(result) = self.poll_wait(cx) {
+                return Poll::Ready(result.map(Some));
+            }
+            Pin::new(&mut sleep).poll(cx).map_ok(|()| None)
+        })
+        .await
+    }
+
+    /// Check for an exit status, registering the `pidfd` if there isn't one yet
+    fn poll_wait(&mut self, cx: &mut Context<'_>) -> Poll<Result<ExitStatus, std::io::Error>> {
+        poll_fd(cx, self.pidfd.as_raw_fd(), Interest::READABLE, || {
+            self.try_wait()?
+                .ok_or_else(|| Error::from(ErrorKind::WouldBlock))
+        })
+    }
+
+    /// Collect everything the child writes to its standard output and error (if they were piped),
+    /// and wait for it to exit, as a _future_.
+    ///
+    /// Both are read at the same time, so a child that fills up the pipe for one of them while
+    /// this is reading the other doesn't get stuck.
+    pub async fn wait_with_output(mut self) -> Result<Output, std::io::Error> {
+        drop(self.stdin.take());
+        let mut stdout = self.stdout.take();
+        let mut stderr = self.stderr.take();
+        let mut stdout_buf = Vec::new();
+        let mut stderr_buf = Vec::new();
+
+        std::future::poll_fn(|cx| {
+            let stdout_done = poll_read_all(&mut stdout, &mut stdout_buf, cx)?;
+            let stderr_done = poll_read_all(&mut stderr, &mut stderr_buf, cx)?;
+            if stdout_done.is_ready() && stderr_done.is_ready() {
+                Poll::Ready(Ok::<_, std::io::Error>(()))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await?;
+
+        let status = self.wait().await?;
+        Ok(Output {
+            status,
+            stdout: stdout_buf,
+            stderr: stderr_buf,
+        })
+    }
+
+    /// Check whether the child has exited, without waiting
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>, std::io::Error> {
+        self.std.try_wait()
+    }
+
+    /// Send a signal to the child
+    ///
+    /// This goes through the child's `pidfd`, so even if the child has exited and been waited for,
+    /// and its PID has been reused by some other process, the signal can't go to the wrong place.
+    pub fn signal(&self, kind: SignalKind) -> Result<(), std::io::Error> {
+        let r = unsafe {
+            libc::syscall(
+                libc::SYS_pidfd_send_signal,
+                self.pidfd.as_raw_fd(),
+                kind.as_raw(),
+                std::ptr::null::<libc::siginfo_t>(),
+                0,
+            )
+        };
+        if r < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Send `SIGKILL` to the child
+    ///
+    /// This doesn't wait for the child to actually exit; follow it with [`wait`](Self::wait).
+    pub fn kill(&mut self) -> Result<(), std::io::Error> {
+        self.std.kill()
+    }
+}
+
+impl Child {
+    /// Send `SIGKILL` to the child's whole process group
+    ///
+    /// This only works if the child was put in a group with [`Command::process_group`];
+    /// otherwise, the child shares this process's group, and killing that would be a bad idea. In
+    /// that case, this fails with `InvalidInput`.
+    pub fn kill_group(&mut self) -> Result<(), std::io::Error> {
+        let pgroup = self.process_group.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "the child wasn't put in a process group",
+            )
+        })?;
+        if unsafe { libc::kill(-pgroup, libc::SIGKILL) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.pidfd.as_raw_fd());
+        if !matches!(self.try_wait(), Ok(None)) {
+            // Already waited for
+            return;
+        }
+        if !self.kill_on_drop {
+            reaper::adopt(self.id() as libc::pid_t);
+            return;
+        }
+
+        let result = if self.process_group.is_some() {
+            self.kill_group()
+        } else {
+            self.kill()
+        };
+        if let Err(err) = result {
+            crate::trace::warn!(?err, pid = self.id(), "Failed to kill child on drop");
+        }
+        // Collect the exit status if it's already there. If the child isn't quite dead yet, the
+        // reaper gets it later.
+        if !matches!(self.try_wait(), Ok(Some(_))) {
+            reaper::adopt(self.id() as libc::pid_t);
+        }
+    }
+}
+
+impl AsRawFd for Child {
+    /// The child's `pidfd`
+    fn as_raw_fd(&self) -> RawFd {
+        self.pidfd.as_raw_fd()
+    }
+}
+
+/// Wait for any process to exit, as a _future_.
+///
+/// The process doesn't have to be one that was spawned here: this works for anything a
+/// `pidfd` can be opened for, which (permissions allowing) is any process at all. Fails with
+/// `NotFound` if there's no process with that PID.
+///
+/// Only a parent can find out how its child exited, so this resolves to the exit status if the
+/// process is a child of this one, and to `None` otherwise. Getting the exit status also reaps the
+/// child, so don't use this on a process that a [`Child`] is going to wait for too.
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let std_child = std::process::Command::new("true").spawn().unwrap();
+///     let status = guillotine::process::wait_for_pid(std_child.id()).await.unwrap();
+///     assert!(status.unwrap().success());
+/// });
+/// ```
+pub async fn wait_for_pid(pid: u32) -> Result<Option<ExitStatus>, std::io::Error> {
+    let pidfd = pidfd_open(pid).map_err(|err| match err.raw_os_error() {
+        Some(libc::ESRCH) => Error::new(ErrorKind::NotFound, "no such process"),
+        _ => err,
+    })?;
+
+    let pidfd = AsyncFd::new(pidfd)?;
+    loop {
+        let mut guard = pidfd.readable().await?;
+        let exited = guard.try_io(|pidfd| {
+            // A pidfd is readable once the process has exited
+            let mut pollfd = libc::pollfd {
+                fd: pidfd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            match unsafe { libc::poll(&mut pollfd, 1, 0) } {
+                r if r < 0 => Err(Error::last_os_error()),
+                0 => Err(ErrorKind::WouldBlock.into()),
+                _ => Ok(()),
+            }
+        });
+        if let Some(result) = exited {
+            result?;
+            break;
+        }
+    }
+
+    let mut status = 0;
+    let r = unsafe { libc::waitpid(pid as libc::pid_t, &mut status, libc::WNOHANG) };
+    if r > 0 {
+        return Ok(Some(ExitStatus::from_raw(status)));
+    }
+    if r < 0 {
+        let err = Error::last_os_error();
+        // ECHILD means it's not our child, so there's no exit status to be had
+        if err.raw_os_error() != Some(libc::ECHILD) {
+            return Err(err);
+        }
+    }
+    Ok(None)
+}
+
+/// Read everything from `reader` into `buf`, and then set `reader` to `None`
+fn poll_read_all<R: AsyncRead + Unpin>(
+    reader: &mut Option<R>,
+    buf: &mut Vec<u8>,
+    cx: &mut Context<'_>,
+) -> Poll<Result<(), std::io::Error>> {
+    let Some(inner) = reader else {
+        return Poll::Ready(Ok(()));
+    };
+    let mut chunk = [0_u8; 4096];
+    loop {
+        match Pin::new(&mut *inner).poll_read(cx, &mut chunk) {
+            Poll::Ready(Ok(0)) => {
+                *reader = None;
+                return Poll::Ready(Ok(()));
+            }
+            Poll::Ready(Ok(read)) => buf.extend_from_slice(&chunk[..read]),
+            Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {}
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
+        }
+    }
+}
+
+/// `pidfd_open(2)`
+fn pidfd_open(pid: u32) -> Result<OwnedFd, std::io::Error> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
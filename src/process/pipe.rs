@@ -0,0 +1,144 @@
+use crate::io::{poll_fd, AsyncRead, AsyncWrite};
+use crate::runtime::Interest;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::prelude::{AsRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Turn one end of a child's pipe into a non-blocking `File`
+fn nonblocking(fd: OwnedFd) -> Result<File, std::io::Error> {
+    let raw = fd.as_raw_fd();
+    let flags = unsafe { libc::fcntl(raw, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(raw, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(File::from(fd))
+}
+
+/// A child's standard input, when it's [piped](std::process::Stdio::piped)
+///
+/// The child sees the end of its input once this is dropped.
+#[derive(Debug)]
+pub struct ChildStdin(File);
+
+/// A child's standard output, when it's [piped](std::process::Stdio::piped)
+#[derive(Debug)]
+pub struct ChildStdout(File);
+
+/// A child's standard error, when it's [piped](std::process::Stdio::piped)
+#[derive(Debug)]
+pub struct ChildStderr(File);
+
+impl ChildStdin {
+    pub(super) fn from_std(std: std::process::ChildStdin) -> Result<Self, std::io::Error> {
+        nonblocking(std.into()).map(Self)
+    }
+
+    /// Write bytes to the child, as a future
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_write(cx, buf)).await
+    }
+}
+
+impl ChildStdout {
+    pub(super) fn from_std(std: std::process::ChildStdout) -> Result<Self, std::io::Error> {
+        nonblocking(std.into()).map(Self)
+    }
+
+    /// Read bytes from the child, as a future
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, buf)).await
+    }
+}
+
+impl ChildStderr {
+    pub(super) fn from_std(std: std::process::ChildStderr) -> Result<Self, std::io::Error> {
+        nonblocking(std.into()).map(Self)
+    }
+
+    /// Read bytes from the child, as a future
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, buf)).await
+    }
+}
+
+impl AsRawFd for ChildStdin {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl Drop for ChildStdin {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.0.as_raw_fd());
+    }
+}
+
+impl AsRawFd for ChildStdout {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl Drop for ChildStdout {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.0.as_raw_fd());
+    }
+}
+
+impl AsRawFd for ChildStderr {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl Drop for ChildStderr {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.0.as_raw_fd());
+    }
+}
+
+impl AsyncWrite for ChildStdin {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut file = &self.0;
+        poll_fd(cx, file.as_raw_fd(), Interest::WRITABLE, || file.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // Writes go straight to the pipe; there's nothing to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // There's no half-closing a pipe. The child sees the end of its input once this is
+        // dropped.
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for ChildStdout {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut file = &self.0;
+        poll_fd(cx, file.as_raw_fd(), Interest::READABLE, || file.read(buf))
+    }
+}
+
+impl AsyncRead for ChildStderr {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut file = &self.0;
+        poll_fd(cx, file.as_raw_fd(), Interest::READABLE, || file.read(buf))
+    }
+}
@@ -0,0 +1,204 @@
+use super::Command;
+use crate::io::{poll_fd, AsyncRead, AsyncWrite};
+use crate::runtime::Interest;
+use crate::tty::WindowSize;
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{Error, Read, Write};
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::task::{Context, Poll};
+
+/// A pseudo-terminal, for running a child that thinks it's talking to a real terminal
+///
+/// Plenty of programs act differently when they aren't attached to a terminal: they turn off
+/// colors and progress bars, buffer their output, or refuse to prompt for passwords. Spawning them
+/// on a pseudo-terminal (with [`Command::pty`]) keeps them in interactive mode, while everything
+/// they write comes out of the `Pty` and everything written to the `Pty` goes to their input.
+///
+/// ```
+/// use guillotine::io::AsyncReadExt;
+/// use guillotine::process::{Command, Pty};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let mut pty = Pty::open().unwrap();
+///     let mut child = Command::new("sh")
+///         .args(["-c", "test -t 1 && echo terminal"])
+///         .pty(&mut pty)
+///         .unwrap()
+///         .spawn()
+///         .unwrap();
+///
+///     let mut output = String::new();
+///     pty.read_to_string(&mut output).await.unwrap();
+///     assert_eq!(output, "terminal\r\n");
+///     assert!(child.wait().await.unwrap().success());
+/// });
+/// ```
+///
+/// Note the `\r\n`: a terminal translates newlines on the way out, just like a real one would.
+#[derive(Debug)]
+pub struct Pty {
+    /// The side this process reads and writes
+    master: File,
+    /// The side the child gets, until it's handed to a command
+    slave: Option<OwnedFd>,
+}
+
+impl Pty {
+    /// Open a new pseudo-terminal
+    pub fn open() -> Result<Self, std::io::Error> {
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+            if master < 0 {
+                return Err(Error::last_os_error());
+            }
+            let master = OwnedFd::from_raw_fd(master);
+            if libc::grantpt(master.as_raw_fd()) < 0 || libc::unlockpt(master.as_raw_fd()) < 0 {
+                return Err(Error::last_os_error());
+            }
+
+            let mut name = [0 as libc::c_char; 64];
+            let r = libc::ptsname_r(master.as_raw_fd(), name.as_mut_ptr(), name.len());
+            if r != 0 {
+                return Err(Error::from_raw_os_error(r));
+            }
+            let slave = libc::open(
+                CStr::from_ptr(name.as_ptr()).as_ptr(),
+                libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC,
+            );
+            if slave < 0 {
+                return Err(Error::last_os_error());
+            }
+
+            let flags = libc::fcntl(master.as_raw_fd(), libc::F_GETFL);
+            if libc::fcntl(master.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+                return Err(Error::last_os_error());
+            }
+
+            Ok(Self {
+                master: File::from(master),
+                slave: Some(OwnedFd::from_raw_fd(slave)),
+            })
+        }
+    }
+
+    /// The size of the terminal
+    pub fn window_size(&self) -> Result<WindowSize, std::io::Error> {
+        crate::tty::window_size(self.master.as_raw_fd())
+    }
+
+    /// Change the size of the terminal
+    ///
+    /// The child gets a `SIGWINCH`, just like when a real terminal window is resized.
+    pub fn set_window_size(&self, size: WindowSize) -> Result<(), std::io::Error> {
+        let winsize = libc::winsize {
+            ws_row: size.rows,
+            ws_col: size.columns,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        if unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &winsize) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Read what the child wrote to the terminal, as a future
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, buf)).await
+    }
+
+    /// Write to the terminal, as if it was typed, as a future
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_write(cx, buf)).await
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.master.as_raw_fd());
+    }
+}
+
+impl Command {
+    /// Attach the child to a pseudo-terminal
+    ///
+    /// The child's standard input, output, and error all become the terminal, and it's started in
+    /// a new session with the terminal as its controlling terminal, so things like Ctrl-C (a
+    /// `\x03` written to the `Pty`) and job control work like they would in a real one.
+    ///
+    /// A `Pty` can only be attached to one command; this fails with `InvalidInput` the second
+    /// time. The command holds on to its end of the terminal until it's dropped, and reads from the
+    /// `Pty` won't see the end of the stream until it has been, so don't keep it around after
+    /// spawning.
+    pub fn pty(&mut self, pty: &mut Pty) -> Result<&mut Self, std::io::Error> {
+        let slave = pty.slave.take().ok_or_else(|| {
+            Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the pty is already attached to a command",
+            )
+        })?;
+        self.stdin(Stdio::from(slave.try_clone()?));
+        self.stdout(Stdio::from(slave.try_clone()?));
+        self.stderr(Stdio::from(slave));
+        unsafe {
+            self.as_std_mut().pre_exec(|| {
+                // A new session, with the terminal (now standard input) as its controlling terminal
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(self)
+    }
+}
+
+impl AsRawFd for Pty {
+    /// The master side of the terminal
+    fn as_raw_fd(&self) -> RawFd {
+        self.master.as_raw_fd()
+    }
+}
+
+impl AsyncRead for Pty {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut file = &self.master;
+        match poll_fd(cx, file.as_raw_fd(), Interest::READABLE, || file.read(buf)) {
+            // Once every process has closed the other side, Linux reports EIO rather than the end
+            // of the stream
+            Poll::Ready(Err(err)) if err.raw_os_error() == Some(libc::EIO) => Poll::Ready(Ok(0)),
+            other => other,
+        }
+    }
+}
+
+impl AsyncWrite for Pty {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut file = &self.master;
+        poll_fd(cx, file.as_raw_fd(), Interest::WRITABLE, || file.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // Writes go straight to the terminal; there's nothing to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // There's no half-closing a terminal. Write a Ctrl-D (`\x04`) to send the child the end
+        // of its input.
+        Poll::Ready(Ok(()))
+    }
+}
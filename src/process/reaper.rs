@@ -0,0 +1,61 @@
+//! Reaping children whose `Child` was dropped before they exited
+//!
+//! A child that exits sticks around as a zombie until its parent waits for it. Normally that's
+//! [`Child::wait`](super::Child::wait)'s job, but once a `Child` has been dropped, nothing is
+//! going to wait for it. Dropped children that are still running get remembered here instead, and
+//! are waited for later.
+
+use crate::signal::{signal, SignalKind};
+use std::sync::Mutex;
+
+/// The PIDs of children whose `Child` was dropped while they were still running
+///
+/// This is for the whole process, not per thread: a `Child` can be dropped on any thread, and
+/// it's still the whole process's child.
+static ORPHANS: Mutex<Vec<libc::pid_t>> = Mutex::new(Vec::new());
+
+/// Remember a child that nothing is going to wait for
+pub(super) fn adopt(pid: libc::pid_t) {
+    ORPHANS.lock().expect("Expected mutex to lock").push(pid);
+}
+
+/// Wait for every orphan that has exited, without blocking
+pub(super) fn reap() {
+    ORPHANS
+        .lock()
+        .expect("Expected mutex to lock")
+        .retain(|&pid| {
+            let r = unsafe { libc::waitpid(pid, std::ptr::null_mut(), libc::WNOHANG) };
+            // Zero means it's still running. Anything else means it's been reaped, or that it's
+            // not our child to reap (anymore), so either way it's done.
+            r == 0
+        });
+}
+
+/// Reap orphaned children as they exit, forever, as a _future_.
+///
+/// When a [`Child`](super::Child) is dropped while its process is still running (on any thread),
+/// nothing waits for that process, and when it exits it lingers as a zombie. A few of those are
+/// harmless, and they're cleaned up whenever another child is spawned, but a long-running daemon
+/// that spawns in bursts and then sits idle can collect a lot of them. Spawning this future as a
+/// task takes care of them as soon as they exit, by listening for `SIGCHLD`.
+///
+/// It only resolves if listening for the signal fails. Since the runtime runs until every task is
+/// done, only spawn this in programs that run forever anyway.
+///
+/// ```no_run
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     guillotine::task::spawn(guillotine::process::reap_orphans());
+///
+///     // ... the rest of the daemon
+/// });
+/// ```
+pub async fn reap_orphans() -> Result<(), std::io::Error> {
+    let mut children = signal(SignalKind::child())?;
+    loop {
+        // Reap first, in case something exited before the listener was set up
+        reap();
+        children.recv().await?;
+    }
+}
@@ -0,0 +1,212 @@
+use super::Command;
+use std::ffi::CString;
+use std::io::Error;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A resource that can be limited with [`Command::rlimit`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Resource {
+    /// CPU time, in seconds (`RLIMIT_CPU`)
+    Cpu,
+    /// The largest file that can be created, in bytes (`RLIMIT_FSIZE`)
+    FileSize,
+    /// The size of the data segment, in bytes (`RLIMIT_DATA`)
+    Data,
+    /// The size of the stack, in bytes (`RLIMIT_STACK`)
+    Stack,
+    /// The largest core dump, in bytes (`RLIMIT_CORE`)
+    Core,
+    /// How many file descriptors can be open (`RLIMIT_NOFILE`)
+    OpenFiles,
+    /// The size of the address space, in bytes (`RLIMIT_AS`)
+    AddressSpace,
+    /// How many processes the user can have (`RLIMIT_NPROC`)
+    Processes,
+    /// How much memory can be locked, in bytes (`RLIMIT_MEMLOCK`)
+    LockedMemory,
+}
+
+/// What `setrlimit` takes to say which resource: glibc has a type of its own for it, and
+/// everything else uses a plain `int`
+#[cfg(target_env = "gnu")]
+type RawResource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type RawResource = libc::c_int;
+
+impl Resource {
+    fn as_raw(self) -> RawResource {
+        match self {
+            Self::Cpu => libc::RLIMIT_CPU,
+            Self::FileSize => libc::RLIMIT_FSIZE,
+            Self::Data => libc::RLIMIT_DATA,
+            Self::Stack => libc::RLIMIT_STACK,
+            Self::Core => libc::RLIMIT_CORE,
+            Self::OpenFiles => libc::RLIMIT_NOFILE,
+            Self::AddressSpace => libc::RLIMIT_AS,
+            Self::Processes => libc::RLIMIT_NPROC,
+            Self::LockedMemory => libc::RLIMIT_MEMLOCK,
+        }
+    }
+}
+
+/// What to change about the child between `fork` and `exec`
+#[derive(Debug, Default)]
+pub(super) struct Sandbox {
+    uid: Option<u32>,
+    gid: Option<u32>,
+    chroot: Option<CString>,
+    rlimits: Vec<(Resource, libc::rlimit)>,
+}
+
+impl Sandbox {
+    /// Apply everything, in the child
+    ///
+    /// This runs after `fork`, so it can't allocate (or take any lock another thread might have
+    /// been holding). The order matters: limits and `chroot` might need privileges that dropping
+    /// to another user gives up, so those go first.
+    fn apply(&self) -> Result<(), Error> {
+        unsafe {
+            for (resource, limit) in &self.rlimits {
+                if libc::setrlimit(resource.as_raw(), limit) < 0 {
+                    return Err(Error::last_os_error());
+                }
+            }
+            if let Some(root) = &self.chroot {
+                if libc::chroot(root.as_ptr()) < 0 || libc::chdir(c"/".as_ptr()) < 0 {
+                    return Err(Error::last_os_error());
+                }
+            }
+            if (self.uid.is_some() || self.gid.is_some())
+                && libc::getuid() == 0
+                && libc::setgroups(0, std::ptr::null()) < 0
+            {
+                // Don't let the child keep root's supplementary groups
+                return Err(Error::last_os_error());
+            }
+            if let Some(gid) = self.gid {
+                if libc::setgid(gid) < 0 {
+                    return Err(Error::last_os_error());
+                }
+            }
+            if let Some(uid) = self.uid {
+                if libc::setuid(uid) < 0 {
+                    return Err(Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Command {
+    /// Run a closure in the child, after `fork` and before `exec`
+    ///
+    /// Hooks run in the order they were added, after the child's standard input, output, and
+    /// error and its working directory have been set up. The
+    /// [`uid`](Self::uid)/[`gid`](Self::gid)/[`chroot`](Self::chroot)/[`rlimit`](Self::rlimit)
+    /// settings are applied by a hook of their own, added the first time one of them is set.
+    ///
+    /// # Safety
+    ///
+    /// This is [`std::os::unix::process::CommandExt::pre_exec`], with all of its caveats: the
+    /// closure runs in a copy of this process that only has one thread, so it mustn't allocate,
+    /// take locks, or do anything else that isn't async-signal-safe.
+    pub unsafe fn pre_exec<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnMut() -> Result<(), std::io::Error> + Send + Sync + 'static,
+    {
+        unsafe { self.as_std_mut().pre_exec(f) };
+        self
+    }
+
+    /// Run the child as another user
+    ///
+    /// Unlike `std`'s version, this happens after [`chroot`](Self::chroot) and
+    /// [`rlimit`](Self::rlimit), which might need the privileges that this gives up. When this
+    /// process is running as root, the child's supplementary groups are cleared too.
+    pub fn uid(&mut self, uid: u32) -> &mut Self {
+        self.sandbox().lock().unwrap().uid = Some(uid);
+        self
+    }
+
+    /// Run the child as another group
+    pub fn gid(&mut self, gid: u32) -> &mut Self {
+        self.sandbox().lock().unwrap().gid = Some(gid);
+        self
+    }
+
+    /// Change the child's root directory (which usually takes root)
+    ///
+    /// The child starts out in the new root directory. [`current_dir`](Self::current_dir)
+    /// happens before this, and the path to the program is looked up before it too, so use an
+    /// absolute path inside the new root for the program.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `root` contains a NUL byte.
+    pub fn chroot(&mut self, root: impl AsRef<Path>) -> &mut Self {
+        let root = CString::new(root.as_ref().as_os_str().as_bytes())
+            .expect("chroot path can't contain a NUL");
+        self.sandbox().lock().unwrap().chroot = Some(root);
+        self
+    }
+
+    /// Limit a resource the child can use, like `ulimit` does
+    ///
+    /// `soft` is the limit the child runs into; `hard` is the ceiling it could raise `soft` to
+    /// itself. Raising the hard limit above this process's takes privileges.
+    ///
+    /// ```
+    /// use guillotine::process::{Command, Resource};
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let output = Command::new("sh")
+    ///         .args(["-c", "ulimit -n"])
+    ///         .rlimit(Resource::OpenFiles, 64, 64)
+    ///         .output()
+    ///         .await
+    ///         .unwrap();
+    ///     assert_eq!(output.stdout, b"64\n");
+    /// });
+    /// ```
+    pub fn rlimit(&mut self, resource: Resource, soft: u64, hard: u64) -> &mut Self {
+        let limit = libc::rlimit {
+            rlim_cur: soft as libc::rlim_t,
+            rlim_max: hard as libc::rlim_t,
+        };
+        let sandbox = self.sandbox();
+        let mut sandbox = sandbox.lock().unwrap();
+        sandbox
+            .rlimits
+            .retain(|(existing, _)| *existing != resource);
+        sandbox.rlimits.push((resource, limit));
+        self
+    }
+
+    /// Get the sandbox settings, adding the hook that applies them if this is the first time
+    fn sandbox(&mut self) -> Arc<Mutex<Sandbox>> {
+        if let Some(sandbox) = &self.sandbox {
+            return sandbox.clone();
+        }
+
+        let sandbox = Arc::new(Mutex::new(Sandbox::default()));
+        let for_child = sandbox.clone();
+        // Nothing else is holding the lock when the child is forked (the `Command` is borrowed
+        // mutably for the whole spawn), so locking it in the child can't deadlock.
+        unsafe {
+            self.as_std_mut().pre_exec(move || {
+                let sandbox = for_child
+                    .lock()
+                    .unwrap_or_else(|poison| poison.into_inner());
+                sandbox.apply()
+            });
+        }
+        self.sandbox = Some(sandbox.clone());
+        sandbox
+    }
+}
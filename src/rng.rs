@@ -0,0 +1,26 @@
+//! A tiny, seedable random number generator, for the things that only need to look random but
+//! have to be reproducible (like which simulated packets get lost)
+
+/// xorshift64*: not remotely cryptographic, but the same seed always gives the same numbers
+#[derive(Clone, Debug)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        // xorshift gets stuck at zero, so make sure it never starts there.
+        Self(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    /// The next random number
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A random number from 0 to 1
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
@@ -0,0 +1,282 @@
+use super::shutdown;
+use super::thread::{check_cores, ThreadConfig, ThreadHook};
+use super::{Instrument, Runtime};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Build a [`Runtime`] with some extra configuration
+///
+/// [`Runtime::new`] is the same as `Builder::new().build()`.
+pub struct Builder {
+    pub(super) instruments: Vec<Box<dyn Instrument>>,
+    pub(super) slow_poll_threshold: Option<Duration>,
+    pub(super) capture_panic_backtraces: bool,
+    pub(super) worker_affinity: Option<Vec<usize>>,
+    pub(super) on_worker_start: Option<ThreadHook>,
+    pub(super) on_worker_stop: Option<ThreadHook>,
+    pub(super) thread_config: ThreadConfig,
+    pub(super) shutdown_timeout: Duration,
+    #[cfg(feature = "test-util")]
+    pub(super) start_paused: bool,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            instruments: Vec::new(),
+            slow_poll_threshold: None,
+            capture_panic_backtraces: false,
+            worker_affinity: None,
+            on_worker_start: None,
+            on_worker_stop: None,
+            thread_config: ThreadConfig::default(),
+            shutdown_timeout: shutdown::DEFAULT_TIMEOUT,
+            #[cfg(feature = "test-util")]
+            start_paused: false,
+        }
+    }
+}
+
+impl Builder {
+    /// Create a new builder, with nothing configured
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an [`Instrument`] to call into as the runtime does its thing
+    ///
+    /// This can be called more than once; every instrument gets called, in the order they were
+    /// added.
+    pub fn instrument(mut self, instrument: impl Instrument + 'static) -> Self {
+        self.instruments.push(Box::new(instrument));
+        self
+    }
+
+    /// Log a warning whenever a single poll of a task takes longer than `threshold`
+    ///
+    /// Everything on the runtime waits while a task is being polled, so a task that does
+    /// something slow (blocking I/O, a big computation) without handing it off to
+    /// [`spawn_blocking`](crate::task::spawn_blocking) holds up every other task. The warning is
+    /// logged inside the task's span, which says which task it was and where it was spawned.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let runtime = guillotine::runtime::Builder::new()
+    ///     .slow_poll_threshold(Duration::from_millis(100))
+    ///     .build()
+    ///     .unwrap();
+    /// runtime.block_on(async {
+    ///     // Oops. Logs something like
+    ///     //   task{task.id=0 spawn.location=src/main.rs:9:13}:
+    ///     //   Task took too long to poll elapsed=200.1ms
+    ///     std::thread::sleep(Duration::from_millis(200));
+    /// });
+    /// ```
+    pub fn slow_poll_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_poll_threshold = Some(threshold);
+        self
+    }
+
+    /// Capture a backtrace whenever a task panics
+    ///
+    /// The backtrace is logged (inside the task's span, along with the panic message) and handed
+    /// to the task's [`JoinHandle`](crate::task::JoinHandle) in
+    /// [`JoinError::Panic`](crate::task::JoinError::Panic), so it shows where the panic happened
+    /// even when `RUST_BACKTRACE` isn't set. Capturing a backtrace is slow, so this is off by
+    /// default.
+    pub fn capture_panic_backtraces(mut self, capture: bool) -> Self {
+        self.capture_panic_backtraces = capture;
+        self
+    }
+
+    /// Keep the thread the runtime runs on to these cores (numbered from zero, like in
+    /// `/proc/cpuinfo`)
+    ///
+    /// The runtime doesn't have a thread of its own: it runs on whichever thread calls
+    /// [`block_on`](Runtime::block_on) (or [`block`](Runtime::block)). That thread is kept on
+    /// these cores while the runtime runs, and goes back to wherever it was allowed to run before
+    /// once it's done. It keeps its name, too; name it yourself if you'd like to spot it in `top`.
+    ///
+    /// ```
+    /// // Whichever core we're on now is one we're allowed on
+    /// let core = unsafe { libc::sched_getcpu() };
+    ///
+    /// let runtime = guillotine::runtime::Builder::new()
+    ///     .worker_affinity([core as usize])
+    ///     .build()
+    ///     .unwrap();
+    /// runtime.block_on(async move {
+    ///     assert_eq!(unsafe { libc::sched_getcpu() }, core);
+    /// });
+    /// ```
+    pub fn worker_affinity(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+        self.worker_affinity = Some(cores.into_iter().collect());
+        self
+    }
+
+    /// Call `f` on the thread the runtime runs on, once it's been kept to its
+    /// [cores](Self::worker_affinity) and before any task is polled
+    ///
+    /// Like [`worker_affinity`](Self::worker_affinity), this is for whichever thread calls
+    /// [`block_on`](Runtime::block_on) (or [`block`](Runtime::block)). It isn't called for
+    /// [`drive`](Runtime::drive), which doesn't have a start or an end.
+    ///
+    /// ```
+    /// use std::cell::Cell;
+    ///
+    /// thread_local! {
+    ///     static WORKER: Cell<bool> = const { Cell::new(false) };
+    /// }
+    ///
+    /// let runtime = guillotine::runtime::Builder::new()
+    ///     .on_worker_start(|| WORKER.with(|worker| worker.set(true)))
+    ///     .on_worker_stop(|| WORKER.with(|worker| worker.set(false)))
+    ///     .build()
+    ///     .unwrap();
+    /// runtime.block_on(async {
+    ///     assert!(WORKER.with(|worker| worker.get()));
+    /// });
+    /// assert!(!WORKER.with(|worker| worker.get()));
+    /// ```
+    pub fn on_worker_start(mut self, f: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_worker_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Call `f` on the thread the runtime runs on, once every task has finished
+    ///
+    /// This is called even if a task panics, or the runtime itself fails.
+    pub fn on_worker_stop(mut self, f: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_worker_stop = Some(Arc::new(f));
+        self
+    }
+
+    /// Name the threads the runtime starts for [`spawn_blocking`](crate::task::spawn_blocking)
+    ///
+    /// This is the name that shows up in `top`, in a debugger, and in panic messages. Without it,
+    /// each thread is named after where it was spawned from.
+    pub fn thread_name(mut self, name: impl Into<String>) -> Self {
+        self.thread_config.name = Some(name.into());
+        self
+    }
+
+    /// Keep the threads the runtime starts for [`spawn_blocking`](crate::task::spawn_blocking)
+    /// to these cores
+    pub fn blocking_affinity(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+        self.thread_config.affinity = Some(cores.into_iter().collect());
+        self
+    }
+
+    /// Call `f` at the start of every thread the runtime starts for
+    /// [`spawn_blocking`](crate::task::spawn_blocking), before it does anything else
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let started = Arc::new(AtomicUsize::new(0));
+    /// let stopped = Arc::new(AtomicUsize::new(0));
+    /// let runtime = guillotine::runtime::Builder::new()
+    ///     .thread_name("worker-bee")
+    ///     .on_thread_start({
+    ///         let started = started.clone();
+    ///         move || {
+    ///             assert_eq!(std::thread::current().name(), Some("worker-bee"));
+    ///             started.fetch_add(1, Ordering::SeqCst);
+    ///         }
+    ///     })
+    ///     .on_thread_stop({
+    ///         let stopped = stopped.clone();
+    ///         move || {
+    ///             stopped.fetch_add(1, Ordering::SeqCst);
+    ///         }
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// runtime.block_on(async {
+    ///     guillotine::task::spawn_blocking(|| ()).await.unwrap();
+    /// });
+    /// assert_eq!(started.load(Ordering::SeqCst), 1);
+    /// // The runtime waits for its threads before it's done, hooks and all
+    /// assert_eq!(stopped.load(Ordering::SeqCst), 1);
+    /// ```
+    pub fn on_thread_start(mut self, f: impl Fn() + Send + Sync + 'static) -> Self {
+        self.thread_config.on_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Call `f` at the end of every thread the runtime starts for
+    /// [`spawn_blocking`](crate::task::spawn_blocking), after it's done everything else
+    ///
+    /// This is called even if the blocking function panics.
+    pub fn on_thread_stop(mut self, f: impl Fn() + Send + Sync + 'static) -> Self {
+        self.thread_config.on_stop = Some(Arc::new(f));
+        self
+    }
+
+    /// How long [shutdown hooks](super::Handle::on_shutdown) get to finish, once the runtime
+    /// starts shutting down
+    ///
+    /// Once it's up, whichever hooks haven't finished are dropped. The default is ten seconds.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Start the runtime with time [paused](crate::time::pause)
+    ///
+    /// Only available with the `test-util` feature.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let runtime = guillotine::runtime::Builder::new()
+    ///     .start_paused(true)
+    ///     .build()
+    ///     .unwrap();
+    /// let start = Instant::now();
+    /// runtime.block_on(async {
+    ///     guillotine::time::sleep(Duration::from_secs(3600)).await.unwrap();
+    /// });
+    /// assert!(start.elapsed() < Duration::from_secs(1));
+    /// ```
+    #[cfg(feature = "test-util")]
+    pub fn start_paused(mut self, paused: bool) -> Self {
+        self.start_paused = paused;
+        self
+    }
+
+    /// Create the runtime
+    ///
+    /// Because this creates the epoll, it could fail. It also fails if a core given to
+    /// [`worker_affinity`](Self::worker_affinity) or [`blocking_affinity`](Self::blocking_affinity)
+    /// couldn't possibly exist, or if either was given no cores at all.
+    pub fn build(self) -> Result<Runtime, std::io::Error> {
+        for cores in [&self.worker_affinity, &self.thread_config.affinity]
+            .into_iter()
+            .flatten()
+        {
+            check_cores(cores)?;
+        }
+        Runtime::from_builder(self)
+    }
+}
+
+impl std::fmt::Debug for Builder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("Builder");
+        debug
+            .field("instruments", &self.instruments.len())
+            .field("slow_poll_threshold", &self.slow_poll_threshold)
+            .field("capture_panic_backtraces", &self.capture_panic_backtraces)
+            .field("worker_affinity", &self.worker_affinity)
+            .field("on_worker_start", &self.on_worker_start.is_some())
+            .field("on_worker_stop", &self.on_worker_stop.is_some())
+            .field("thread_config", &self.thread_config)
+            .field("shutdown_timeout", &self.shutdown_timeout);
+        #[cfg(feature = "test-util")]
+        debug.field("start_paused", &self.start_paused);
+        debug.finish()
+    }
+}
@@ -0,0 +1,186 @@
+//! Live task statistics, served over a Unix socket
+//!
+//! This is a much simpler take on `tokio-console`: a [`Console`] is an [`Instrument`] that keeps
+//! track of how often each task is woken up and polled, and how long the polls take, and
+//! [`Console::serve`] hands a table of all of that to anybody who connects to a Unix socket.
+//!
+//! ```text
+//! $ watch -n1 nc -U /run/my-service/console.sock
+//!    ID  STATE      POLLS      BUSY      IDLE   WAKES  NAME              LOCATION
+//!     0  idle           7  432.02µs   50.49ms       6                    src/main.rs:7:13
+//!     1  idle           2   18.61µs   50.87ms       2  acceptor          src/main.rs:8:56
+//! ```
+
+use super::{Instrument, TaskInfo};
+use crate::io::AsyncWriteExt;
+use crate::net::unix::UnixListener;
+use crate::task::Id;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::panic::Location;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Keeps track of every live task's statistics
+///
+/// Add a clone of it to the runtime with [`Builder::instrument`](super::Builder::instrument), and
+/// keep the original around to look at the statistics.
+///
+/// ```
+/// use guillotine::runtime::console::Console;
+///
+/// let console = Console::new();
+/// let runtime = guillotine::runtime::Builder::new()
+///     .instrument(console.clone())
+///     .build()
+///     .unwrap();
+/// runtime.block_on(async move {
+///     let tasks = console.tasks();
+///     assert_eq!(tasks.len(), 1);
+///     assert!(tasks[0].polling_since.is_some());
+///     println!("{}", console.render());
+/// });
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Console {
+    tasks: Rc<RefCell<BTreeMap<Id, TaskStats>>>,
+}
+
+/// The statistics for one task
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct TaskStats {
+    /// The task's ID
+    pub id: Id,
+    /// The task's name, if it has one
+    pub name: Option<String>,
+    /// Where in the source the task was spawned
+    pub location: &'static Location<'static>,
+    /// When the task was spawned
+    pub spawned: Instant,
+    /// How many times the task has been polled
+    pub polls: u64,
+    /// How many times the task has been woken up
+    pub wakes: u64,
+    /// How long the task has spent being polled, in total
+    pub busy: Duration,
+    /// When the task's current poll started, if it's being polled right now
+    pub polling_since: Option<Instant>,
+}
+
+impl TaskStats {
+    /// How long the task has existed without being polled
+    pub fn idle(&self) -> Duration {
+        self.spawned.elapsed().saturating_sub(self.busy)
+    }
+}
+
+impl Console {
+    /// Start keeping track of statistics
+    ///
+    /// Nothing is tracked until the console is added to a runtime.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The statistics of every live task, in order of ID
+    pub fn tasks(&self) -> Vec<TaskStats> {
+        self.tasks.borrow().values().cloned().collect()
+    }
+
+    /// The statistics of every live task, as a table
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{:>5}  {:<8}  {:>6}  {:>8}  {:>8}  {:>6}  {:<16}  LOCATION",
+            "ID", "STATE", "POLLS", "BUSY", "IDLE", "WAKES", "NAME"
+        );
+        for task in self.tasks.borrow().values() {
+            let state = if task.polling_since.is_some() {
+                "running"
+            } else if task.polls == 0 {
+                "new"
+            } else {
+                "idle"
+            };
+            let _ = writeln!(
+                out,
+                "{:>5}  {:<8}  {:>6}  {:>8}  {:>8}  {:>6}  {:<16}  {}",
+                task.id,
+                state,
+                task.polls,
+                format!("{:.2?}", task.busy),
+                format!("{:.2?}", task.idle()),
+                task.wakes,
+                task.name.as_deref().unwrap_or(""),
+                task.location,
+            );
+        }
+        out
+    }
+
+    /// Serve the [`render`](Self::render)ed table to everything that connects to `listener`, as
+    /// a _future_.
+    ///
+    /// Every connection gets the table once, and is then closed. This never finishes on its own,
+    /// so [spawn](crate::task::spawn) it.
+    pub async fn serve(self, listener: UnixListener) -> Result<(), std::io::Error> {
+        loop {
+            let (mut stream, _) = listener.accept().await?;
+            let table = self.render();
+            crate::task::Builder::new()
+                .name("guillotine::console")
+                .spawn(async move {
+                    // The other end hanging up early is its own business.
+                    let _ = stream.write_all(table.as_bytes()).await;
+                });
+        }
+    }
+
+    fn with_task(&self, id: Id, f: impl FnOnce(&mut TaskStats)) {
+        if let Some(task) = self.tasks.borrow_mut().get_mut(&id) {
+            f(task);
+        }
+    }
+}
+
+impl Instrument for Console {
+    fn on_task_spawn(&self, task: &TaskInfo) {
+        self.tasks.borrow_mut().insert(
+            task.id,
+            TaskStats {
+                id: task.id,
+                name: task.name.clone(),
+                location: task.location,
+                spawned: Instant::now(),
+                polls: 0,
+                wakes: 0,
+                busy: Duration::ZERO,
+                polling_since: None,
+            },
+        );
+    }
+
+    fn on_task_wake(&self, task: Id) {
+        self.with_task(task, |task| task.wakes += 1);
+    }
+
+    fn on_poll_start(&self, task: Id) {
+        self.with_task(task, |task| task.polling_since = Some(Instant::now()));
+    }
+
+    fn on_poll_end(&self, task: Id, _ready: bool) {
+        self.with_task(task, |task| {
+            task.polls += 1;
+            if let Some(since) = task.polling_since.take() {
+                task.busy += since.elapsed();
+            }
+        });
+    }
+
+    fn on_task_complete(&self, task: Id) {
+        self.tasks.borrow_mut().remove(&task);
+    }
+}
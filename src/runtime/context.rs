@@ -1,13 +1,16 @@
-use super::{FutureId, RuntimeInner};
+use super::{FutureId, Interest, RuntimeInner};
 use std::{
-    cell::RefCell, future::Future, io::ErrorKind, os::unix::prelude::AsRawFd, rc::Rc, task::Waker,
+    cell::RefCell,
+    future::Future,
+    os::unix::prelude::{AsRawFd, RawFd},
+    rc::Rc,
 };
 
 /// The current context of the executing runtime.
 ///
 /// The [`Future`] trait does not have any way to get the current runtime from the future being
-/// polled. It does have a [`Waker`] that is *provided* by the current runtime, but the Waker also
-/// has no way to access the current runtime other than to wake it.
+/// polled. It does have a [`Waker`](std::task::Waker) that is *provided* by the current runtime,
+/// but the Waker also has no way to access the current runtime other than to wake it.
 ///
 /// So this structure provides a way to get the current runtime, by setting the context as a
 /// thread-local variable that is set right before a future is polled, and cleared immediately
@@ -16,8 +19,6 @@ use std::{
 pub(crate) struct RuntimeContext {
     /// The part of the runtime that is exposed to the context
     inner: Rc<RefCell<RuntimeInner>>,
-    /// The waker that is associated with the current task
-    waker: Waker,
     /// The future ID that is associated with the current task
     future_id: FutureId,
 }
@@ -27,17 +28,13 @@ thread_local! {
     ///
     /// This is private; external uses (even within the crate) need to use `RuntimeContext::set` and
     /// `RuntimeContext::clear`.
-    static RUNTIME_CONTEXT: RefCell<Option<RuntimeContext>> = RefCell::new(None);
+    static RUNTIME_CONTEXT: RefCell<Option<RuntimeContext>> = const { RefCell::new(None) };
 }
 
 impl RuntimeContext {
     /// Create a new context
-    pub fn new(future_id: FutureId, waker: Waker, inner: Rc<RefCell<RuntimeInner>>) -> Self {
-        Self {
-            inner,
-            waker,
-            future_id,
-        }
+    pub fn new(future_id: FutureId, inner: Rc<RefCell<RuntimeInner>>) -> Self {
+        Self { inner, future_id }
     }
 
     /// Get the current context from the thread-local variable
@@ -55,11 +52,7 @@ impl RuntimeContext {
     ///
     /// Like `current()`, but uses an `Option` instead of panicking.
     pub fn try_current() -> Option<RuntimeContext> {
-        if let Some(context) = RUNTIME_CONTEXT.with(|cell| cell.borrow().clone()) {
-            Some(context)
-        } else {
-            None
-        }
+        RUNTIME_CONTEXT.with(|cell| cell.borrow().clone())
     }
 
     /// Set the provided runtime as the current runtime.
@@ -72,18 +65,25 @@ impl RuntimeContext {
         RUNTIME_CONTEXT.with(|runtime_context| runtime_context.replace(None));
     }
 
-    /// Get a reference to the currently executing future's waker.
-    pub fn waker(&self) -> &Waker {
-        &self.waker
+    /// Get the ID of the currently executing future
+    pub fn future_id(&self) -> FutureId {
+        self.future_id
+    }
+
+    /// Get the part of the runtime that is exposed to the context
+    pub fn inner(&self) -> &Rc<RefCell<RuntimeInner>> {
+        &self.inner
     }
 
     /// Spawn a new futures onto the currently executing runtime.
-    pub fn spawn<F>(&self, future: F) -> FutureId
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F, name: Option<String>) -> crate::task::JoinHandle<F::Output>
     where
-        F: Future<Output = ()> + 'static,
+        F: Future + 'static,
+        F::Output: 'static,
     {
         let mut inner = self.inner.try_borrow_mut().expect("Expected to lock inner");
-        inner.spawn(future)
+        inner.spawn_join_handle(future, name)
     }
 
     /// Register a file descriptor with the currently executing runtime's epoll instance
@@ -91,20 +91,55 @@ impl RuntimeContext {
     /// The provided file descriptor will be associated with the currently executing future's ID, so
     /// any time the file descriptor wakes up epoll because it is ready, the current future will be
     /// polled.
-    pub fn register_file_descriptor(&self, fd: &impl AsRawFd) {
+    pub fn register_file_descriptor(
+        &self,
+        fd: &impl AsRawFd,
+        interest: Interest,
+    ) -> Result<(), std::io::Error> {
         let mut inner = self.inner.try_borrow_mut().expect("Expected to lock inner");
-        match inner.epoll.add(fd, self.future_id) {
-            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
-                // Listen, this isn't a production-grade runtime. We're definitely not using epoll
-                // in the best way. Part of that is that our internal futures will often try to add
-                // the same file descriptor to the epoll multiple times.
-                //
-                // Instead of fixing this problem, we're just going to ignore it. The point of this
-                // library was to learn about future executors, not epoll.
-                //
-                // If this makes you mad, go look at Mio or something.
+        inner.register(self.future_id, fd.as_raw_fd(), interest)
+    }
+
+    /// Take a file descriptor back out of the currently executing runtime's epoll instance
+    pub fn deregister_file_descriptor(&self, fd: RawFd) -> Result<(), std::io::Error> {
+        let mut inner = self.inner.try_borrow_mut().expect("Expected to lock inner");
+        inner.deregister(fd)
+    }
+
+    /// Stop waking the currently executing future when a file descriptor becomes ready for
+    /// `interest`
+    ///
+    /// Does nothing if the runtime is busy, the same as [`forget`](Self::forget).
+    pub fn unregister(&self, fd: RawFd, interest: Interest) {
+        if let Ok(mut inner) = self.inner.try_borrow_mut() {
+            inner.unregister(self.future_id, fd, interest);
+        }
+    }
+
+    /// Forget about a file descriptor that's being closed
+    ///
+    /// Returns `false` if the runtime is busy (which means it's being dropped from somewhere
+    /// unusual), and couldn't.
+    pub fn forget(&self, fd: RawFd) -> bool {
+        match self.inner.try_borrow_mut() {
+            Ok(mut inner) => {
+                inner.forget(fd);
+                true
             }
-            r => r.expect("Expected to add successfully"),
+            Err(_) => false,
         }
     }
+
+    /// Whether an operation on a file descriptor for `interest` might not block, as far as the
+    /// currently executing runtime knows
+    pub fn might_be_ready(&self, fd: RawFd, interest: Interest) -> bool {
+        let inner = self.inner.try_borrow().expect("Expected to lock inner");
+        inner.might_be_ready(fd, interest)
+    }
+
+    /// Record that an operation on a file descriptor for `interest` said it would block
+    pub fn clear_readiness(&self, fd: RawFd, interest: Interest) {
+        let mut inner = self.inner.try_borrow_mut().expect("Expected to lock inner");
+        inner.clear_readiness(fd, interest);
+    }
 }
@@ -1,8 +1,26 @@
-use super::FutureId;
+use super::Interest;
+use crate::trace::error;
 use libc::c_int;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::{io::Error, mem::MaybeUninit};
-use tracing::error;
+
+/// The token for a wakeup file descriptor (the ready queue's, or the reactor's shutdown one)
+///
+/// Everything else is registered under its own file descriptor number, which is never this big.
+pub const WAKEUP: u64 = u64::MAX;
+
+/// Something that happened to a registered file descriptor
+#[derive(Copy, Clone, Debug)]
+pub struct Event {
+    /// The token the file descriptor was registered with
+    pub token: u64,
+    /// Whether a read might not block now
+    ///
+    /// Errors and hangups count: the read will say what went wrong.
+    pub readable: bool,
+    /// Whether a write might not block now
+    pub writable: bool,
+}
 
 /// A slightly safe structure around `epoll_create`, `epoll_wait`, `epoll_ctl`.
 pub struct Epoll {
@@ -29,17 +47,59 @@ impl Epoll {
     ///
     /// Roughly equivalent to `epoll_ctl` with the `EPOLL_CTL_ADD` parameter.
     ///
-    /// The provided file descriptor and epoll event are associated with the provided [`FutureId`];
-    /// when `wait` is woken it will return the provided `FutureId`.
-    pub fn add(&mut self, fd: &impl AsRawFd, future_id: FutureId) -> Result<(), std::io::Error> {
-        let fd = fd.as_raw_fd();
+    /// The provided file descriptor is associated with the provided token; when `wait` is woken
+    /// for it, the [`Event`] it returns has that token.
+    pub fn add(
+        &self,
+        fd: &impl AsRawFd,
+        token: u64,
+        interest: Interest,
+    ) -> Result<(), std::io::Error> {
+        self.ctl(libc::EPOLL_CTL_ADD, fd.as_raw_fd(), token, interest)
+    }
+
+    /// Change what a registered file descriptor is watched for
+    ///
+    /// Roughly equivalent to `epoll_ctl` with the `EPOLL_CTL_MOD` parameter.
+    pub fn modify(
+        &self,
+        fd: &impl AsRawFd,
+        token: u64,
+        interest: Interest,
+    ) -> Result<(), std::io::Error> {
+        self.ctl(libc::EPOLL_CTL_MOD, fd.as_raw_fd(), token, interest)
+    }
+
+    fn ctl(&self, op: c_int, fd: RawFd, token: u64, interest: Interest) -> Result<(), Error> {
         unsafe {
-            let events = libc::EPOLLIN | libc::EPOLLOUT | libc::EPOLLET;
+            // `EPOLLERR` is always reported whether we ask for it or not, but be explicit: sockets
+            // with `IP_RECVERR` enabled signal their error queue this way.
+            let mut events = libc::EPOLLERR | libc::EPOLLET;
+            if interest.is_readable() {
+                events |= libc::EPOLLIN;
+            }
+            if interest.is_writable() {
+                events |= libc::EPOLLOUT;
+            }
             let mut epoll_event = libc::epoll_event {
                 events: events as u32,
-                u64: future_id.to_u64(),
+                u64: token,
             };
-            let r = libc::epoll_ctl(self.fd, libc::EPOLL_CTL_ADD, fd, &mut epoll_event as *mut _);
+            let r = libc::epoll_ctl(self.fd, op, fd, &mut epoll_event as *mut _);
+            if r < 0 {
+                return Err(Error::last_os_error());
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Stop watching a file descriptor
+    ///
+    /// Roughly equivalent to `epoll_ctl` with the `EPOLL_CTL_DEL` parameter.
+    pub fn delete(&self, fd: RawFd) -> Result<(), std::io::Error> {
+        unsafe {
+            let r = libc::epoll_ctl(self.fd, libc::EPOLL_CTL_DEL, fd, std::ptr::null_mut());
             if r < 0 {
                 return Err(Error::last_os_error());
             }
@@ -52,23 +112,55 @@ impl Epoll {
     ///
     /// Roughly equivalent to `epoll_wait` with a single event.
     ///
-    /// When woken up, the event that triggered the wake up will have a [`FutureId`] associated with
-    /// it. This method returns that [`FutureId`] that caused the wake up.
-    pub fn wait(&mut self) -> Result<FutureId, std::io::Error> {
+    /// Returns the event that caused the wake up: which token it was for, and which way the file
+    /// descriptor is ready.
+    pub fn wait(&self) -> Result<Event, std::io::Error> {
+        loop {
+            if let Some(event) = self.wait_timeout(-1)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Check for an event on the epoll instance, without waiting for one
+    ///
+    /// Roughly equivalent to `epoll_wait` with a single event and a timeout of zero.
+    // With the `mio` feature, only the reactor thread uses raw epoll, and it always waits.
+    #[cfg_attr(feature = "mio", allow(dead_code))]
+    pub fn poll(&self) -> Result<Option<Event>, std::io::Error> {
+        self.wait_timeout(0)
+    }
+
+    /// Wait for an event for up to `timeout` milliseconds (or forever, if it's -1)
+    fn wait_timeout(&self, timeout: c_int) -> Result<Option<Event>, std::io::Error> {
         unsafe {
             let mut epoll_event = MaybeUninit::uninit();
-            let r = libc::epoll_wait(self.fd, epoll_event.as_mut_ptr(), 1, -1);
+            let r = libc::epoll_wait(self.fd, epoll_event.as_mut_ptr(), 1, timeout);
             if r < 0 {
                 return Err(Error::last_os_error());
             }
+            if r == 0 {
+                return Ok(None);
+            }
             let epoll_event = epoll_event.assume_init();
-            let future_id = FutureId::from_u64(epoll_event.u64);
+            let events = epoll_event.events as c_int;
+            let failed = events & (libc::EPOLLERR | libc::EPOLLHUP) != 0;
 
-            Ok(future_id)
+            Ok(Some(Event {
+                token: epoll_event.u64,
+                readable: failed || events & (libc::EPOLLIN | libc::EPOLLRDHUP) != 0,
+                writable: failed || events & libc::EPOLLOUT != 0,
+            }))
         }
     }
 }
 
+impl AsRawFd for Epoll {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
 impl Drop for Epoll {
     fn drop(&mut self) {
         unsafe {
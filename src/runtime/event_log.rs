@@ -0,0 +1,194 @@
+//! A record of the last few things the runtime did
+//!
+//! When a program hangs, what it was doing right before is usually the interesting part, and
+//! full tracing is often too expensive to leave on just in case. An [`EventLog`] keeps the most
+//! recent scheduler events in a fixed-size buffer, to look at on demand or when something panics.
+
+use super::{Instrument, TaskInfo};
+use crate::task::Id;
+use std::collections::VecDeque;
+use std::fmt::{Display, Write};
+use std::os::unix::prelude::RawFd;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Keeps the most recent scheduler events
+///
+/// Add a clone of it to the runtime with [`Builder::instrument`](super::Builder::instrument), and
+/// keep the original around to look at the events.
+///
+/// ```
+/// use guillotine::runtime::event_log::{EventKind, EventLog};
+///
+/// let log = EventLog::new(64);
+/// let runtime = guillotine::runtime::Builder::new()
+///     .instrument(log.clone())
+///     .build()
+///     .unwrap();
+/// runtime.block_on(async {});
+///
+/// let kinds: Vec<EventKind> = log.events().into_iter().map(|event| event.kind).collect();
+/// assert!(matches!(kinds[0], EventKind::Spawn { .. }));
+/// assert!(matches!(kinds.last(), Some(EventKind::Complete { .. })));
+/// print!("{}", log.render());
+/// ```
+#[derive(Clone, Debug)]
+pub struct EventLog {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    /// When the log was created, which event times are relative to
+    start: Instant,
+    capacity: usize,
+    events: Mutex<VecDeque<Event>>,
+}
+
+/// Something the runtime did
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub struct Event {
+    /// When it happened, since the log was created
+    pub at: Duration,
+    /// What happened
+    pub kind: EventKind,
+}
+
+/// What the runtime did
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EventKind {
+    /// A task was spawned
+    Spawn { task: Id, parent: Option<Id> },
+    /// A task was woken up
+    Wake { task: Id },
+    /// A task started being polled
+    PollStart { task: Id },
+    /// A task finished being polled
+    PollEnd { task: Id, ready: bool },
+    /// A task finished
+    Complete { task: Id },
+    /// A task registered a file descriptor
+    RegisterFd { task: Id, fd: RawFd },
+    /// The runtime waited for something to happen
+    ReactorWait { waited: Duration },
+}
+
+impl EventLog {
+    /// Create a log that keeps the last `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                start: Instant::now(),
+                capacity,
+                events: Mutex::new(VecDeque::with_capacity(capacity)),
+            }),
+        }
+    }
+
+    /// The events in the log, oldest first
+    pub fn events(&self) -> Vec<Event> {
+        self.shared.lock().iter().copied().collect()
+    }
+
+    /// The events in the log, oldest first, one per line
+    pub fn render(&self) -> String {
+        self.shared.render()
+    }
+
+    /// Print the log to standard error whenever anything panics
+    ///
+    /// Whatever panic hook was installed before still runs first.
+    pub fn dump_on_panic(&self) {
+        let shared = self.shared.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            previous(info);
+            eprint!("Recent runtime events:\n{}", shared.render());
+        }));
+    }
+
+    fn push(&self, kind: EventKind) {
+        let at = self.shared.start.elapsed();
+        let mut events = self.shared.lock();
+        if events.len() == self.shared.capacity {
+            events.pop_front();
+        }
+        if self.shared.capacity > 0 {
+            events.push_back(Event { at, kind });
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Event>> {
+        // A panic while holding the lock can't leave the buffer half-updated, and the panic hook
+        // especially wants to see the events anyway.
+        self.events.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for event in self.lock().iter() {
+            let _ = writeln!(out, "{:>12.6?}  {}", event.at, event.kind);
+        }
+        out
+    }
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Spawn {
+                task,
+                parent: Some(parent),
+            } => write!(f, "spawn task {} from task {}", task, parent),
+            Self::Spawn { task, parent: None } => write!(f, "spawn task {}", task),
+            Self::Wake { task } => write!(f, "wake task {}", task),
+            Self::PollStart { task } => write!(f, "poll task {}", task),
+            Self::PollEnd { task, ready: true } => {
+                write!(f, "task {} finished polling: ready", task)
+            }
+            Self::PollEnd { task, ready: false } => {
+                write!(f, "task {} finished polling: pending", task)
+            }
+            Self::Complete { task } => write!(f, "task {} complete", task),
+            Self::RegisterFd { task, fd } => write!(f, "task {} registered fd {}", task, fd),
+            Self::ReactorWait { waited } => write!(f, "waited {:.2?} in epoll", waited),
+        }
+    }
+}
+
+impl Instrument for EventLog {
+    fn on_task_spawn(&self, task: &TaskInfo) {
+        self.push(EventKind::Spawn {
+            task: task.id,
+            parent: task.parent,
+        });
+    }
+
+    fn on_task_wake(&self, task: Id) {
+        self.push(EventKind::Wake { task });
+    }
+
+    fn on_poll_start(&self, task: Id) {
+        self.push(EventKind::PollStart { task });
+    }
+
+    fn on_poll_end(&self, task: Id, ready: bool) {
+        self.push(EventKind::PollEnd { task, ready });
+    }
+
+    fn on_task_complete(&self, task: Id) {
+        self.push(EventKind::Complete { task });
+    }
+
+    fn on_fd_register(&self, task: Id, fd: RawFd) {
+        self.push(EventKind::RegisterFd { task, fd });
+    }
+
+    fn on_reactor_wait(&self, waited: Duration) {
+        self.push(EventKind::ReactorWait { waited });
+    }
+}
@@ -1,6 +1,6 @@
+use crate::trace::error;
 use libc::c_int;
 use std::{io::Error, os::unix::prelude::AsRawFd};
-use tracing::error;
 
 /// A structure that represents a linux `eventfd` file descriptor.
 pub struct EventFd {
@@ -25,9 +25,9 @@ impl EventFd {
 
     /// Read from the file descriptor.
     ///
-    /// Oh, I guess we never actually do this. Well, what do you expect; this isn't a
-    /// production-grade futures executor.
-    pub fn _read(&self) -> Result<u64, std::io::Error> {
+    /// For an `eventfd` file descriptor, this resets the counter, so the next write causes a new
+    /// wakeup.
+    pub fn read(&self) -> Result<u64, std::io::Error> {
         unsafe {
             let mut bytes = [0_u8; 8];
             let r = libc::read(self.fd, &mut bytes as *mut u8 as *mut libc::c_void, 8);
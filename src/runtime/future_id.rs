@@ -22,17 +22,23 @@ pub struct FutureId(u64);
 impl FutureId {
     /// Convert this ID into its internal u64 value.
     ///
-    /// We need to do this so we can hand it to epoll.
-    pub fn to_u64(&self) -> u64 {
+    /// We need to do this so we can put it in a span's fields.
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    pub fn to_u64(self) -> u64 {
         self.0
     }
 
     /// Convert a u64 value back to a FutureId.
     ///
-    /// We need to do this because epoll only deals with u64s.
+    /// We need to do this because the generator counts in u64s.
     pub fn from_u64(input: u64) -> Self {
         Self(input)
     }
+
+    /// The public face of this ID
+    pub fn task_id(self) -> crate::task::Id {
+        crate::task::Id::from_u64(self.0)
+    }
 }
 
 impl Display for FutureId {
@@ -0,0 +1,244 @@
+use super::{RuntimeContext, RuntimeInner, Shutdown, TaskInfo};
+#[cfg(feature = "tracing")]
+use crate::signal::{signal, SignalKind};
+use std::cell::RefCell;
+use std::future::Future;
+use std::rc::Rc;
+
+/// A handle to a runtime, for looking into it from the outside (or from inside one of its tasks)
+///
+/// Get one from [`Runtime::handle`](super::Runtime::handle) before the runtime starts, or from
+/// [`Handle::current`] inside a task.
+#[derive(Clone)]
+pub struct Handle {
+    inner: Rc<RefCell<RuntimeInner>>,
+}
+
+impl Handle {
+    pub(super) fn new(inner: Rc<RefCell<RuntimeInner>>) -> Self {
+        Self { inner }
+    }
+
+    /// Get a handle to the currently executing runtime
+    ///
+    /// Panics if there is no runtime currently executing
+    pub fn current() -> Self {
+        Self::new(RuntimeContext::current().inner().clone())
+    }
+
+    /// Everything the runtime knows about every task that hasn't finished yet
+    ///
+    /// ```
+    /// use guillotine::runtime::{Handle, TaskState};
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     guillotine::task::Builder::new()
+    ///         .name("napper")
+    ///         .spawn(guillotine::time::sleep(std::time::Duration::from_millis(10)));
+    ///
+    ///     let tasks = Handle::current().tasks();
+    ///     let napper = tasks.iter().find(|t| t.name.as_deref() == Some("napper")).unwrap();
+    ///     assert_eq!(napper.state, TaskState::New);
+    /// });
+    /// ```
+    pub fn tasks(&self) -> Vec<TaskInfo> {
+        let inner = self.inner.try_borrow().expect("Expected to lock inner");
+        let mut tasks: Vec<TaskInfo> = inner.tasks.values().map(|task| task.info.clone()).collect();
+        tasks.sort_by_key(|task| task.id);
+        tasks
+    }
+
+    /// Run `f`'s future when the runtime shuts down, before the reactor goes away
+    ///
+    /// This is for cleanup that needs the runtime: flushing write buffers, deregistering from
+    /// service discovery, saying goodbye to TLS peers. The runtime shuts down once every task has
+    /// finished, or once somebody asks it to with [`shutdown`](Self::shutdown). Then every hook
+    /// is spawned as a task of its own, and the runtime keeps going until they've all finished (along
+    /// with anything they spawn), or until the builder's
+    /// [`shutdown_timeout`](super::Builder::shutdown_timeout) is up, whichever comes first. Hooks
+    /// that haven't finished by then are dropped.
+    ///
+    /// Hooks registered once the runtime has started shutting down never run.
+    ///
+    /// ```
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    /// use std::time::Duration;
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// let flushed = Rc::new(Cell::new(false));
+    /// runtime.handle().on_shutdown({
+    ///     let flushed = flushed.clone();
+    ///     move || async move {
+    ///         guillotine::time::sleep(Duration::from_millis(10)).await.unwrap();
+    ///         flushed.set(true);
+    ///     }
+    /// });
+    /// runtime.block_on(async {});
+    /// assert!(flushed.get());
+    /// ```
+    pub fn on_shutdown<F, Fut>(&self, f: F)
+    where
+        F: FnOnce() -> Fut + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        self.inner
+            .try_borrow_mut()
+            .expect("Expected to lock inner")
+            .shutdown_hooks
+            .push(Box::new(move || Box::pin(f())));
+    }
+
+    /// Shut the runtime down, without waiting for every task to finish
+    ///
+    /// Once the task that called this is done being polled, every task that hasn't finished is
+    /// dropped, and the [shutdown hooks](Self::on_shutdown) run. If the runtime was started with
+    /// [`try_block_on`](super::Runtime::try_block_on), it returns
+    /// [`Error::ShutDown`](crate::Error::ShutDown), since the future it was given never finished.
+    ///
+    /// ```
+    /// use guillotine::runtime::Handle;
+    /// use std::time::Duration;
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// let result = runtime.try_block_on(async {
+    ///     guillotine::task::spawn(async {
+    ///         // Standing in for waiting on SIGTERM
+    ///         guillotine::time::sleep(Duration::from_millis(10)).await.unwrap();
+    ///         Handle::current().shutdown();
+    ///     });
+    ///
+    ///     // Serve forever
+    ///     loop {
+    ///         guillotine::time::sleep(Duration::from_secs(1)).await.unwrap();
+    ///     }
+    /// });
+    /// assert!(matches!(result, Err(guillotine::Error::ShutDown)));
+    /// ```
+    pub fn shutdown(&self) {
+        let mut inner = self.inner.try_borrow_mut().expect("Expected to lock inner");
+        if inner.shutdown == Shutdown::Running {
+            inner.shutdown = Shutdown::Requested;
+        }
+    }
+
+    /// Log every task that hasn't finished yet through `tracing`
+    ///
+    /// Each task gets an event with its ID, name, where it was spawned, what it's up to, and
+    /// which file descriptors it's waiting on. When a service stops responding, this is usually
+    /// the quickest way to find out which task is stuck, and on what.
+    ///
+    /// Only available with the `tracing` feature.
+    #[cfg(feature = "tracing")]
+    pub fn dump(&self) {
+        let tasks = self.tasks();
+        crate::trace::info!(count = tasks.len(), "Runtime task dump");
+        for task in tasks {
+            crate::trace::info!(
+                task_id = task.id.as_u64(),
+                name = task.name.as_deref().unwrap_or(""),
+                location = %task.location,
+                parent_task_id = task.parent.map(|id| id.as_u64()),
+                state = %task.state,
+                fds = ?task.fds,
+                "task",
+            );
+        }
+    }
+
+    /// [`dump`](Self::dump) the runtime's tasks every time the process receives `kind` (usually
+    /// [`SignalKind::user_defined1`]), so that `kill -USR1 <pid>` shows what a running service is
+    /// up to.
+    ///
+    /// This spawns a task that listens for the signal forever. The runtime doesn't finish until
+    /// all of its tasks do, so this is only for programs that run until they're killed anyway.
+    ///
+    /// ```no_run
+    /// use guillotine::signal::SignalKind;
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.handle().dump_on_signal(SignalKind::user_defined1()).unwrap();
+    /// runtime.block_on(async {
+    ///     // ...serve forever...
+    /// });
+    /// ```
+    ///
+    /// Only available with the `tracing` feature.
+    #[cfg(feature = "tracing")]
+    #[track_caller]
+    pub fn dump_on_signal(&self, kind: SignalKind) -> Result<(), std::io::Error> {
+        let mut signal = signal(kind)?;
+        let handle = self.clone();
+        self.spawn(
+            async move {
+                while signal.recv().await.is_ok() {
+                    handle.dump();
+                }
+            },
+            Some("guillotine::dump_on_signal".to_string()),
+        )
+        .detach();
+        Ok(())
+    }
+
+    /// Spawn a future onto the runtime, whether or not it's running yet
+    #[cfg(any(feature = "tracing", feature = "futures-task", feature = "ffi"))]
+    #[track_caller]
+    pub(crate) fn spawn<F>(&self, future: F, name: Option<String>) -> async_task::Task<F::Output>
+    where
+        F: std::future::Future + 'static,
+        F::Output: 'static,
+    {
+        self.inner
+            .try_borrow_mut()
+            .expect("Expected to lock inner")
+            .spawn(future, name)
+    }
+}
+
+/// Spawning through a `Handle` lets libraries that take a generic spawner spawn onto guillotine.
+///
+/// ```
+/// use futures_task::LocalSpawn;
+/// use guillotine::runtime::Handle;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let spawner: &dyn LocalSpawn = &Handle::current();
+///     spawner
+///         .spawn_local_obj(Box::pin(async { println!("Spawned!") }).into())
+///         .unwrap();
+/// });
+/// ```
+///
+/// Only available with the `futures-task` feature.
+#[cfg(feature = "futures-task")]
+impl futures_task::LocalSpawn for Handle {
+    #[track_caller]
+    fn spawn_local_obj(
+        &self,
+        future: futures_task::LocalFutureObj<'static, ()>,
+    ) -> Result<(), futures_task::SpawnError> {
+        self.spawn(future, None).detach();
+        Ok(())
+    }
+}
+
+/// Only available with the `futures-task` feature.
+#[cfg(feature = "futures-task")]
+impl futures_task::Spawn for Handle {
+    #[track_caller]
+    fn spawn_obj(
+        &self,
+        future: futures_task::FutureObj<'static, ()>,
+    ) -> Result<(), futures_task::SpawnError> {
+        futures_task::LocalSpawn::spawn_local_obj(self, future.into())
+    }
+}
+
+impl std::fmt::Debug for Handle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Handle").finish_non_exhaustive()
+    }
+}
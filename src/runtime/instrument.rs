@@ -0,0 +1,63 @@
+use super::TaskInfo;
+use crate::task::Id;
+use std::os::unix::prelude::RawFd;
+use std::time::Duration;
+
+/// Hooks into what the runtime is doing, for plugging in your own telemetry
+///
+/// Every method does nothing by default, so implement only the ones you care about, and add the
+/// implementation to a runtime with [`Builder::instrument`](super::Builder::instrument).
+///
+/// The hooks are called right in the middle of the runtime's own bookkeeping, so they shouldn't
+/// call back into the runtime (like spawning tasks, or using a [`Handle`](super::Handle)), and
+/// they should be quick.
+///
+/// ```
+/// use guillotine::runtime::{Builder, Instrument};
+/// use guillotine::task::Id;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// #[derive(Default)]
+/// struct PollCounter(Rc<Cell<usize>>);
+///
+/// impl Instrument for PollCounter {
+///     fn on_poll_start(&self, _task: Id) {
+///         self.0.set(self.0.get() + 1);
+///     }
+/// }
+///
+/// let polls = Rc::new(Cell::new(0));
+/// let runtime = Builder::new()
+///     .instrument(PollCounter(polls.clone()))
+///     .build()
+///     .unwrap();
+/// runtime.block_on(async {
+///     guillotine::task::spawn(async {}).await.unwrap();
+/// });
+/// assert!(polls.get() >= 2);
+/// ```
+pub trait Instrument {
+    /// A task was spawned. It hasn't been polled yet.
+    fn on_task_spawn(&self, _task: &TaskInfo) {}
+
+    /// Something happened that the task was waiting for, so it's going to be polled
+    fn on_task_wake(&self, _task: Id) {}
+
+    /// A task is about to be polled
+    fn on_poll_start(&self, _task: Id) {}
+
+    /// A task was just polled; `ready` is whether it finished
+    fn on_poll_end(&self, _task: Id, _ready: bool) {}
+
+    /// A task finished, and the runtime has let go of it
+    fn on_task_complete(&self, _task: Id) {}
+
+    /// A task registered a file descriptor, so it gets woken up whenever the file descriptor is
+    /// ready
+    fn on_fd_register(&self, _task: Id, _fd: RawFd) {}
+
+    /// The runtime had nothing to do, so it waited on epoll for something to happen; this is how
+    /// long it waited
+    fn on_reactor_wait(&self, _waited: Duration) {}
+}
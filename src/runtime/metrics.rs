@@ -0,0 +1,74 @@
+use super::{Instrument, TaskInfo};
+use crate::task::Id;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// Publishes the runtime's statistics through the [`metrics`](::metrics) facade
+///
+/// Add it to a runtime with [`Builder::instrument`](super::Builder::instrument), and whichever
+/// exporter is installed (Prometheus, StatsD, ...) picks up:
+///
+/// * `guillotine_tasks_spawned_total` (counter)
+/// * `guillotine_tasks_completed_total` (counter)
+/// * `guillotine_tasks_alive` (gauge)
+/// * `guillotine_task_wakes_total` (counter)
+/// * `guillotine_polls_total` (counter)
+/// * `guillotine_poll_duration_seconds` (histogram)
+/// * `guillotine_reactor_wait_seconds` (histogram): how long the runtime sat in `epoll_wait`
+/// * `guillotine_blocking_threads` (gauge): how many [`spawn_blocking`](crate::task::spawn_blocking)
+///   threads are running, across every runtime
+///
+/// Only available with the `metrics` feature.
+///
+/// ```
+/// let runtime = guillotine::runtime::Builder::new()
+///     .instrument(guillotine::runtime::Metrics::new())
+///     .build()
+///     .unwrap();
+/// runtime.block_on(async {});
+/// ```
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// When the poll that's going on right now started
+    ///
+    /// There's only ever one poll going on at a time, so one of these is enough.
+    poll_started: Cell<Option<Instant>>,
+}
+
+impl Metrics {
+    /// Create a new instrument
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Instrument for Metrics {
+    fn on_task_spawn(&self, _task: &TaskInfo) {
+        ::metrics::counter!("guillotine_tasks_spawned_total").increment(1);
+        ::metrics::gauge!("guillotine_tasks_alive").increment(1.0);
+    }
+
+    fn on_task_wake(&self, _task: Id) {
+        ::metrics::counter!("guillotine_task_wakes_total").increment(1);
+    }
+
+    fn on_poll_start(&self, _task: Id) {
+        self.poll_started.set(Some(Instant::now()));
+    }
+
+    fn on_poll_end(&self, _task: Id, _ready: bool) {
+        ::metrics::counter!("guillotine_polls_total").increment(1);
+        if let Some(started) = self.poll_started.take() {
+            ::metrics::histogram!("guillotine_poll_duration_seconds").record(started.elapsed());
+        }
+    }
+
+    fn on_task_complete(&self, _task: Id) {
+        ::metrics::counter!("guillotine_tasks_completed_total").increment(1);
+        ::metrics::gauge!("guillotine_tasks_alive").decrement(1.0);
+    }
+
+    fn on_reactor_wait(&self, waited: Duration) {
+        ::metrics::histogram!("guillotine_reactor_wait_seconds").record(waited);
+    }
+}
@@ -0,0 +1,118 @@
+//! The same thing as [`epoll`](super::epoll), but with [mio] doing the work
+//!
+//! This is mostly here as something to check our own epoll against: mio has had a lot more people
+//! looking at how it handles edge-triggered readiness than we have. But it also runs anywhere mio
+//! does.
+//!
+//! [mio]: https://docs.rs/mio
+
+use super::epoll::Event;
+use super::Interest;
+use mio::unix::SourceFd;
+use mio::{Events, Poll, Token};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+/// A mio `Poll`, with the same interface as our `Epoll`
+pub struct MioPoll {
+    /// The poll instance itself
+    poll: Poll,
+    /// Somewhere for `poll` to put its events
+    ///
+    /// Like our `Epoll`, we only ever ask for one event at a time.
+    events: Events,
+}
+
+impl MioPoll {
+    /// Create a new poll instance
+    pub fn new() -> Result<Self, std::io::Error> {
+        Ok(Self {
+            poll: Poll::new()?,
+            events: Events::with_capacity(1),
+        })
+    }
+
+    /// Register a file descriptor with this poll instance
+    ///
+    /// mio registrations are always edge-triggered, which is how we register things with epoll
+    /// too. Errors get reported whether we ask for them or not.
+    ///
+    /// The provided file descriptor is associated with the provided token; when `wait` is woken
+    /// for it, the [`Event`] it returns has that token.
+    pub fn add(
+        &self,
+        fd: &impl AsRawFd,
+        token: u64,
+        interest: Interest,
+    ) -> Result<(), std::io::Error> {
+        let fd = fd.as_raw_fd();
+        self.poll.registry().register(
+            &mut SourceFd(&fd),
+            Token(token as usize),
+            convert_interest(interest),
+        )
+    }
+
+    /// Change what a registered file descriptor is watched for
+    pub fn modify(
+        &self,
+        fd: &impl AsRawFd,
+        token: u64,
+        interest: Interest,
+    ) -> Result<(), std::io::Error> {
+        let fd = fd.as_raw_fd();
+        self.poll.registry().reregister(
+            &mut SourceFd(&fd),
+            Token(token as usize),
+            convert_interest(interest),
+        )
+    }
+
+    /// Stop watching a file descriptor
+    pub fn delete(&self, fd: RawFd) -> Result<(), std::io::Error> {
+        self.poll.registry().deregister(&mut SourceFd(&fd))
+    }
+
+    /// Wait for an event on the poll instance
+    ///
+    /// Returns the event that caused the wake up, the same as [`Epoll::wait`](super::epoll::Epoll).
+    pub fn wait(&mut self) -> Result<Event, std::io::Error> {
+        loop {
+            self.poll.poll(&mut self.events, None)?;
+            // mio is allowed to wake up without any events, so go back to sleep if it does.
+            if let Some(event) = self.events.iter().next() {
+                return Ok(convert(event));
+            }
+        }
+    }
+
+    /// Check for an event on the poll instance, without waiting for one
+    pub fn poll(&mut self) -> Result<Option<Event>, std::io::Error> {
+        self.poll.poll(&mut self.events, Some(Duration::ZERO))?;
+        Ok(self.events.iter().next().map(convert))
+    }
+}
+
+/// Turn our interest into mio's
+fn convert_interest(interest: Interest) -> mio::Interest {
+    match (interest.is_readable(), interest.is_writable()) {
+        (true, false) => mio::Interest::READABLE,
+        (false, true) => mio::Interest::WRITABLE,
+        _ => mio::Interest::READABLE | mio::Interest::WRITABLE,
+    }
+}
+
+/// Turn one of mio's events into one of ours
+fn convert(event: &mio::event::Event) -> Event {
+    Event {
+        token: event.token().0 as u64,
+        readable: event.is_readable() || event.is_read_closed() || event.is_error(),
+        writable: event.is_writable() || event.is_write_closed() || event.is_error(),
+    }
+}
+
+impl AsRawFd for MioPoll {
+    fn as_raw_fd(&self) -> RawFd {
+        self.poll.as_raw_fd()
+    }
+}
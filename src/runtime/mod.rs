@@ -1,72 +1,692 @@
 //! The bit that actually runs the futures
 
+mod builder;
+pub mod console;
 mod context;
 mod epoll;
+pub mod event_log;
 mod eventfd;
 mod future_id;
-mod waker;
+mod handle;
+mod instrument;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "mio")]
+mod mio_driver;
+mod panic;
+mod reactor;
+mod ready_queue;
+mod registration;
+mod shutdown;
+mod task_info;
+mod thread;
 
+use crate::trace::{warn, Span};
+pub use builder::Builder;
 pub(crate) use context::RuntimeContext;
+#[cfg(not(feature = "mio"))]
+use epoll::Epoll as Reactor;
+use epoll::Event;
 use future_id::{FutureId, FutureIdGenerator};
-use std::cell::RefCell;
-use std::collections::{HashMap, VecDeque};
-use std::pin::Pin;
+pub use handle::Handle;
+pub use instrument::Instrument;
+#[cfg(feature = "metrics")]
+pub use metrics::Metrics;
+#[cfg(feature = "mio")]
+use mio_driver::MioPoll as Reactor;
+pub(crate) use panic::CatchPanic;
+pub use reactor::{EnterGuard, ReactorHandle};
+pub(crate) use ready_queue::Abort;
+use ready_queue::ReadyQueue;
+pub use registration::{Interest, Registration};
+use shutdown::{Shutdown, ShutdownHook};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::panic::Location;
 use std::rc::Rc;
-use std::{
-    future::Future,
-    task::{Context, Poll, Waker},
-};
-use tracing::warn;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Waker};
+use std::time::{Duration, Instant};
+pub use task_info::{TaskInfo, TaskState};
+use thread::{AffinityGuard, StopGuard, ThreadHook};
+pub(crate) use thread::{BlockingThreads, ThreadConfig};
+
+/// Arrange for the current task to be polled again once `fd` is ready
+///
+/// Inside a runtime, that's the runtime's job, and the current task is whichever one the runtime is
+/// polling. Outside of one, it's the job of whichever [`ReactorHandle`] this thread has entered,
+/// and it wakes `cx`'s waker.
+///
+/// `interest` is what the task is waiting for: a task waiting to read isn't woken up when the file
+/// descriptor becomes writable, and doesn't stop another task from waiting to write.
+///
+/// Fails if epoll won't take the file descriptor: it's been closed (`EBADF`), it's a regular file
+/// (`EPERM`), or the user has too many file descriptors registered already (`ENOSPC`). Panics if
+/// there's no runtime or reactor.
+pub(crate) fn register_file_descriptor(
+    cx: &Context<'_>,
+    fd: &impl AsRawFd,
+    interest: Interest,
+) -> Result<(), std::io::Error> {
+    if let Some(context) = RuntimeContext::try_current() {
+        context.register_file_descriptor(fd, interest)
+    } else if let Some(result) = reactor::register_with_current(fd, cx.waker(), interest) {
+        result
+    } else {
+        panic!("No active runtime or reactor");
+    }
+}
+
+/// Whether an operation on `fd` for `interest` might not block
+///
+/// The runtime remembers, for every file descriptor registered with it, whether the last operation
+/// each way said it would block, and whether epoll has said it's ready since. If it would block and
+/// epoll hasn't, doing the operation again is a wasted syscall. Outside of a runtime, nobody's
+/// keeping track, so anything might be ready.
+pub(crate) fn might_be_ready(fd: RawFd, interest: Interest) -> bool {
+    match RuntimeContext::try_current() {
+        Some(context) => context.might_be_ready(fd, interest),
+        None => true,
+    }
+}
+
+/// Record that an operation on `fd` for `interest` said it would block
+///
+/// This is the only thing that makes the runtime think a file descriptor isn't ready, so only do it
+/// when a syscall actually said so.
+pub(crate) fn clear_readiness(fd: RawFd, interest: Interest) {
+    if let Some(context) = RuntimeContext::try_current() {
+        context.clear_readiness(fd, interest);
+    }
+}
+
+/// How many tasks to poll back to back before checking epoll, even if there are more that are
+/// ready
+///
+/// Checking is a syscall, so not after every task, but it's cheap when there's nothing there.
+const EVENT_INTERVAL: u32 = 61;
+
+/// Counts file descriptors closed outside of any runtime, so every runtime knows it might have lost
+/// track of some
+static CLOSED_ELSEWHERE: AtomicU64 = AtomicU64::new(0);
+
+/// Record that `fd` is about to be closed, so the runtime forgets it was ever registered
+///
+/// Everything in this crate that registers a file descriptor calls this when it's dropped.
+pub(crate) fn forget_file_descriptor(fd: RawFd) {
+    let forgotten = RuntimeContext::try_current().is_some_and(|context| context.forget(fd));
+    if !forgotten {
+        CLOSED_ELSEWHERE.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Stop waking the current task when `fd` becomes ready for `interest`
+///
+/// Any other tasks waiting on it carry on waiting. Outside of a runtime, there's nothing to do: a
+/// reactor only wakes the last waker registered each way, and waking a stale one is harmless.
+pub(crate) fn unregister_file_descriptor(fd: RawFd, interest: Interest) {
+    if let Some(context) = RuntimeContext::try_current() {
+        context.unregister(fd, interest);
+    }
+}
+
+/// Undo [`register_file_descriptor`]
+///
+/// Panics if there's no runtime or reactor, the same as registering does.
+fn deregister_file_descriptor(fd: RawFd) -> Result<(), std::io::Error> {
+    if let Some(context) = RuntimeContext::try_current() {
+        context.deregister_file_descriptor(fd)
+    } else if let Some(result) = reactor::deregister_with_current(fd) {
+        result
+    } else {
+        panic!("No active runtime or reactor");
+    }
+}
+
+/// Everything the runtime keeps about a task that hasn't finished yet
+///
+/// The task itself (its future, and eventually its output) lives in an allocation that
+/// `async-task` manages. This is just what we need to find our way around it.
+struct TaskEntry {
+    /// What we tell people who ask about the task
+    info: TaskInfo,
+    /// The task's span, entered every time it's polled
+    span: Span,
+    /// The task's waker, for when epoll says one of its file descriptors is ready
+    waker: Waker,
+}
+
+/// What the runtime knows about a file descriptor registered with it
+struct IoState {
+    /// The tasks to wake when epoll says the file descriptor is readable
+    ///
+    /// One task can be reading while another is writing, so each direction has its own, and a
+    /// task stays on the list until it finishes (or the file descriptor is forgotten).
+    readers: Vec<FutureId>,
+    /// The tasks to wake when epoll says the file descriptor is writable
+    writers: Vec<FutureId>,
+    /// What epoll is watching it for
+    interest: Interest,
+    /// Whether we're sure it's still registered with epoll
+    ///
+    /// It isn't if it's been closed, and the number reused. We hear about that from this crate's
+    /// own types when they're dropped inside the runtime, but not when they're dropped somewhere
+    /// else, so after that we check with epoll the next time it's registered.
+    verified: bool,
+    /// Whether a read might not block
+    ///
+    /// Registrations are edge-triggered, so once a read says it would block, nothing is going to
+    /// change until epoll says so.
+    readable: bool,
+    /// Whether a write might not block
+    writable: bool,
+}
 
 /// The parts of the runtime that need to be exposed to internal futures
 pub(crate) struct RuntimeInner {
-    /// The epoll instance that drives the entire runtime
+    /// The epoll instance that drives the entire runtime (or mio's, with the `mio` feature)
     ///
     /// This needs to be exposed because we allow internal futures to register their file
     /// descriptors with this instance.
-    epoll: epoll::Epoll,
+    epoll: Reactor,
     /// The next future ID to hand out
     ///
     /// This needs to be exposed for when we spawn a new future, we need to give that future a
     /// unique identifier
     future_id_generator: FutureIdGenerator,
-    /// All of the new futures that have been spawned
+    /// The tasks that are ready to be polled
+    ///
+    /// This needs to be exposed because a new task is ready to be polled as soon as it's spawned.
+    ready: Arc<ReadyQueue>,
+    /// Every task that hasn't finished yet
+    ///
+    /// This needs to be exposed because tasks are added when they're spawned, and pick up file
+    /// descriptors as they register them.
+    tasks: HashMap<FutureId, TaskEntry>,
+    /// Every file descriptor registered with epoll, which is registered under the file
+    /// descriptor's own number
+    ///
+    /// This needs to be exposed because internal futures register their file descriptors, and
+    /// check whether they're ready before bothering to read or write.
+    io: HashMap<RawFd, IoState>,
+    /// What [`CLOSED_ELSEWHERE`] was the last time we looked
+    closed_elsewhere: u64,
+    /// Set by a task's future when it finishes, so we know to forget about the task
+    ///
+    /// Only one task is ever polled at a time, so they can all share it.
+    finished: Rc<Cell<bool>>,
+    /// Everything that wants to hear about what the runtime is doing
+    ///
+    /// This needs to be exposed because spawning a task is one of the things they hear about.
+    instruments: Vec<Box<dyn Instrument>>,
+    /// How long a poll can take before we complain about it, if we're complaining at all
+    slow_poll_threshold: Option<Duration>,
+    /// How to set up threads for `spawn_blocking`
+    ///
+    /// This needs to be exposed because `spawn_blocking` is called from inside tasks.
+    thread_config: Arc<ThreadConfig>,
+    /// The threads `spawn_blocking` has started, to wait for when the runtime shuts down
+    ///
+    /// This needs to be exposed because `spawn_blocking` is called from inside tasks.
+    blocking_threads: Arc<BlockingThreads>,
+    /// When the poll that's going on right now started, if we're timing polls
+    poll_started: Option<Instant>,
+    /// Async cleanup to run when the runtime shuts down
     ///
-    /// This needs to be exposed because when we span a new future, we need a place to put it
-    new_futures: VecDeque<(FutureId, Pin<Box<dyn Future<Output = ()>>>)>,
+    /// This needs to be exposed because hooks are registered through a [`Handle`].
+    shutdown_hooks: Vec<ShutdownHook>,
+    /// How long the shutdown hooks get before they're dropped
+    shutdown_timeout: Duration,
+    /// Where the runtime is in shutting down
+    ///
+    /// This needs to be exposed because a task can ask for the runtime to shut down.
+    shutdown: Shutdown,
+    /// The clock that sleeps and intervals wait on, once time has been paused
+    #[cfg(feature = "test-util")]
+    clock: Option<crate::time::Clock>,
 }
 
 impl RuntimeInner {
     /// Create a new instance of this.
-    fn new() -> Result<Self, std::io::Error> {
-        let epoll = epoll::Epoll::new()?;
+    fn new(builder: Builder) -> Result<Self, std::io::Error> {
+        let epoll = Reactor::new()?;
         let future_id_generator = FutureIdGenerator::default();
-        let new_futures = VecDeque::new();
+        let ready = Arc::new(ReadyQueue::new()?);
+        epoll.add(ready.eventfd(), epoll::WAKEUP, Interest::READABLE)?;
+        let tasks = HashMap::new();
 
         Ok(Self {
             epoll,
             future_id_generator,
-            new_futures,
+            ready,
+            tasks,
+            io: HashMap::new(),
+            closed_elsewhere: CLOSED_ELSEWHERE.load(Ordering::Relaxed),
+            finished: Rc::new(Cell::new(false)),
+            instruments: builder.instruments,
+            slow_poll_threshold: builder.slow_poll_threshold,
+            thread_config: Arc::new(builder.thread_config),
+            blocking_threads: Default::default(),
+            poll_started: None,
+            shutdown_hooks: Vec::new(),
+            shutdown_timeout: builder.shutdown_timeout,
+            shutdown: Shutdown::Running,
+            #[cfg(feature = "test-util")]
+            clock: builder.start_paused.then(crate::time::Clock::default),
         })
     }
 
-    /// Spawn a new future into the runtime by adding it to the `new_futures` list.
-    pub fn spawn<F>(&mut self, future: F) -> FutureId
+    /// Spawn a new future into the runtime by putting it on the ready queue.
+    ///
+    /// The returned task resolves to the future's output. Drop it to cancel the future, or
+    /// `detach` it to let the future run on its own.
+    #[track_caller]
+    pub fn spawn<F>(&mut self, future: F, name: Option<String>) -> async_task::Task<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        self.spawn_with_id(future, name).1
+    }
+
+    /// Like [`spawn`](Self::spawn), but hands back a [`JoinHandle`](crate::task::JoinHandle) that
+    /// can abort the task
+    ///
+    /// A panic in the future is caught and handed to the `JoinHandle`, rather than unwinding out
+    /// of the runtime.
+    #[track_caller]
+    pub fn spawn_join_handle<F>(
+        &mut self,
+        future: F,
+        name: Option<String>,
+    ) -> crate::task::JoinHandle<F::Output>
     where
-        F: Future<Output = ()> + 'static,
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let (future_id, task) = self.spawn_with_id(CatchPanic::new(future), name);
+        let waker = self.tasks[&future_id].waker.clone();
+        let abort = Abort::new(future_id, waker, self.ready.clone());
+        crate::task::JoinHandle::new(task, Some(abort))
+    }
+
+    /// Like [`spawn`](Self::spawn), but also says which ID the task got
+    #[track_caller]
+    fn spawn_with_id<F>(
+        &mut self,
+        future: F,
+        name: Option<String>,
+    ) -> (FutureId, async_task::Task<F::Output>)
+    where
+        F: Future + 'static,
+        F::Output: 'static,
     {
         // Get a unique future identifier
         let future_id = self.future_id_generator.fresh();
 
-        // Pin the future. This does the type erasure right here, and we need it to be pinned anyway
-        // so here is as good of a place as any.
-        let future = Box::pin(future);
+        // If we're being spawned from inside another task, that one is our parent.
+        let parent = RuntimeContext::try_current().map(|context| context.future_id().task_id());
+        let location = Location::caller();
+
+        // Every task gets its own span, which lives as long as the task does and is entered every
+        // time the task is polled. It deliberately has no parent span: the task outlives the poll
+        // of whatever spawned it, so the parent task's ID is recorded as a field instead.
+        let span = crate::trace::info_span!(
+            parent: None,
+            "task",
+            task.id = future_id.to_u64(),
+            task.name = name.as_deref(),
+            task.parent = parent.map(|id| id.as_u64()),
+            spawn.location = %location,
+        );
+
+        // Remember what we know about it, for when somebody asks for a task dump.
+        let info = TaskInfo {
+            id: future_id.task_id(),
+            name,
+            location,
+            parent,
+            state: TaskState::New,
+            fds: Vec::new(),
+        };
+        for instrument in &self.instruments {
+            instrument.on_task_spawn(&info);
+        }
+
+        // Running a task doesn't say whether it finished, so have the future say so itself.
+        let finished = self.finished.clone();
+        let future = async move {
+            let output = future.await;
+            finished.set(true);
+            output
+        };
+
+        // `async-task` puts the future, its output, and everything it needs to wake it up into a
+        // single allocation. Waking the task hands us back a `Runnable`, which goes on the ready
+        // queue until we get around to running it.
+        //
+        // That means spawning a small future costs exactly one allocation: the future is stored
+        // inline, not boxed. Only futures of 2KiB or more get boxed (by `async-task`, so that
+        // building the task doesn't copy something that big around on the stack).
+        let ready = self.ready.clone();
+        let schedule =
+            async_task::WithInfo(move |runnable, info| ready.schedule(future_id, runnable, info));
+        let (runnable, task) = async_task::spawn_local(future, schedule);
+        self.tasks.insert(
+            future_id,
+            TaskEntry {
+                info,
+                span,
+                waker: runnable.waker(),
+            },
+        );
+
+        // A new task is ready to go right away. It goes to the back of the queue (never the LIFO
+        // slot, even when it's spawned by a running task), and the executor will get to it.
+        self.ready.push(future_id, runnable);
+
+        (future_id, task)
+    }
+
+    /// Record what a task is up to now
+    fn set_state(&mut self, future_id: FutureId, state: TaskState) {
+        if let Some(task) = self.tasks.get_mut(&future_id) {
+            task.info.state = state;
+        }
+    }
+
+    /// Update a task's bookkeeping before it's polled
+    fn start_poll(&mut self, future_id: FutureId) {
+        let woken = self
+            .tasks
+            .get(&future_id)
+            .is_some_and(|task| task.info.state != TaskState::New);
+        self.set_state(future_id, TaskState::Running);
+        for instrument in &self.instruments {
+            if woken {
+                instrument.on_task_wake(future_id.task_id());
+            }
+            instrument.on_poll_start(future_id.task_id());
+        }
+        if self.slow_poll_threshold.is_some() {
+            self.poll_started = Some(Instant::now());
+        }
+    }
+
+    /// Update a task's bookkeeping after it's been polled: forget about it if it finished, or note
+    /// that it's waiting if it didn't.
+    fn finish_poll(&mut self, future_id: FutureId, finished: bool) {
+        if let (Some(threshold), Some(started)) = (self.slow_poll_threshold, self.poll_started) {
+            let elapsed = started.elapsed();
+            if elapsed > threshold {
+                // This happens inside the task's span, which says which task it is, and where it
+                // was spawned.
+                warn!(?elapsed, "Task took too long to poll");
+            }
+        }
+        for instrument in &self.instruments {
+            instrument.on_poll_end(future_id.task_id(), finished);
+        }
+        if finished {
+            self.forget_task(future_id);
+        } else {
+            self.set_state(future_id, TaskState::Pending);
+        }
+    }
+
+    /// Forget about a task that's finished, or been dropped
+    fn forget_task(&mut self, future_id: FutureId) {
+        // A task that finished before an abort got to it doesn't need aborting anymore.
+        self.ready.take_aborted(future_id);
+        if let Some(task) = self.tasks.remove(&future_id) {
+            // It's not waiting on anything anymore.
+            for fd in &task.info.fds {
+                if let Some(io) = self.io.get_mut(fd) {
+                    io.readers.retain(|&reader| reader != future_id);
+                    io.writers.retain(|&writer| writer != future_id);
+                }
+            }
+            for instrument in &self.instruments {
+                instrument.on_task_complete(future_id.task_id());
+            }
+        }
+    }
+
+    /// Wait on epoll for the next file descriptor that's ready
+    fn wait(&mut self) -> Result<Event, std::io::Error> {
+        if self.instruments.is_empty() {
+            return self.epoll.wait();
+        }
+
+        let start = Instant::now();
+        let event = self.epoll.wait()?;
+        let waited = start.elapsed();
+        for instrument in &self.instruments {
+            instrument.on_reactor_wait(waited);
+        }
+        Ok(event)
+    }
 
-        // Throw it into the list of new futures! Next time the executor gets around to executing,
-        // it will pull futures off out of this list.
-        self.new_futures.push_back((future_id, future));
+    /// The paused clock, if time is paused
+    #[cfg(feature = "test-util")]
+    pub(crate) fn clock(&self) -> Option<&crate::time::Clock> {
+        self.clock.as_ref()
+    }
+
+    /// Pause time, if it isn't already
+    #[cfg(feature = "test-util")]
+    pub(crate) fn pause(&mut self) {
+        self.clock.get_or_insert_with(Default::default);
+    }
 
-        future_id
+    /// Deal with whatever epoll woke up for
+    fn dispatch(&mut self, event: Event) {
+        if event.token == epoll::WAKEUP {
+            // Whatever was woken up is on the ready queue now.
+            self.ready.clear_wakeup();
+            return;
+        }
+
+        let fd = event.token as RawFd;
+        let Some(io) = self.io.get_mut(&fd) else {
+            warn!(
+                fd = fd,
+                "epoll returned a file descriptor that was not expected"
+            );
+            return;
+        };
+        io.readable |= event.readable;
+        io.writable |= event.writable;
+        let readers = io.readers.iter().filter(|_| event.readable);
+        let writers = io.writers.iter().filter(|_| event.writable);
+        for future_id in readers.chain(writers) {
+            if let Some(task) = self.tasks.get(future_id) {
+                // One of the task's file descriptors is ready, so wake the task up, which puts it
+                // on the ready queue. A task waiting both ways gets woken twice, which is fine:
+                // it's only queued once.
+                task.waker.wake_by_ref();
+            }
+        }
+    }
+
+    /// Register a file descriptor for a task that's waiting on it for `interest`, or add the task
+    /// to the ones waiting on it if it's registered already
+    ///
+    /// If epoll won't have it, nothing changes.
+    fn register(
+        &mut self,
+        future_id: FutureId,
+        fd: RawFd,
+        interest: Interest,
+    ) -> Result<(), std::io::Error> {
+        self.notice_closed_elsewhere();
+
+        let io = self.io.get(&fd);
+        let wanted = io.map_or(interest, |io| io.interest | interest);
+        match io {
+            // The usual case for a long-lived socket: it's been registered for everything we need
+            // since the first time it would have blocked, so there's nothing to tell epoll.
+            Some(io) if io.verified && io.interest == wanted => {}
+            // It's registered, but only for the other direction
+            Some(io) if io.verified => self.epoll.modify(&fd, fd as u64, wanted)?,
+            _ => match self.epoll.add(&fd, fd as u64, wanted) {
+                Ok(()) => {
+                    // Either this is new, or it was closed (which takes it out of epoll) and the
+                    // number reused. Either way, we don't know anything about it yet.
+                    self.io.insert(
+                        fd,
+                        IoState {
+                            readers: Vec::new(),
+                            writers: Vec::new(),
+                            interest: wanted,
+                            verified: true,
+                            readable: true,
+                            writable: true,
+                        },
+                    );
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                    // We'd lost track of it, but it was never closed. Make sure it's registered
+                    // for what we want.
+                    self.epoll.modify(&fd, fd as u64, wanted)?;
+                }
+                Err(err) => {
+                    // If we thought we knew about it, we were wrong: it's been closed, and the
+                    // number reused for something epoll won't watch.
+                    self.forget(fd);
+                    return Err(err);
+                }
+            },
+        }
+
+        // Whichever task is registering it is waiting on it now, along with any others: one task
+        // can be reading from a socket while another writes to it.
+        let io = self.io.entry(fd).or_insert(IoState {
+            readers: Vec::new(),
+            writers: Vec::new(),
+            interest: wanted,
+            verified: true,
+            readable: true,
+            writable: true,
+        });
+        io.interest = wanted;
+        io.verified = true;
+        let mut added = false;
+        if interest.is_readable() && !io.readers.contains(&future_id) {
+            io.readers.push(future_id);
+            added = true;
+        }
+        if interest.is_writable() && !io.writers.contains(&future_id) {
+            io.writers.push(future_id);
+            added = true;
+        }
+        if added {
+            self.add_fd(future_id, fd);
+        }
+        Ok(())
+    }
+
+    /// Stop waking a task when a file descriptor becomes ready for `interest`
+    ///
+    /// Anyone else waiting on the file descriptor carries on waiting. Once nobody is, we forget
+    /// about it, in case it's about to be closed: registering it again only costs a syscall, but
+    /// thinking the next file descriptor to get the same number was registered would be a hang.
+    fn unregister(&mut self, future_id: FutureId, fd: RawFd, interest: Interest) {
+        let Some(io) = self.io.get_mut(&fd) else {
+            return;
+        };
+        if interest.is_readable() {
+            io.readers.retain(|&reader| reader != future_id);
+        }
+        if interest.is_writable() {
+            io.writers.retain(|&writer| writer != future_id);
+        }
+        if io.readers.is_empty() && io.writers.is_empty() {
+            self.forget(fd);
+            return;
+        }
+        if io.readers.contains(&future_id) || io.writers.contains(&future_id) {
+            return;
+        }
+        if let Some(task) = self.tasks.get_mut(&future_id) {
+            task.info.fds.retain(|&registered| registered != fd);
+        }
+    }
+
+    /// Take a file descriptor back out of epoll
+    fn deregister(&mut self, fd: RawFd) -> Result<(), std::io::Error> {
+        self.epoll.delete(fd)?;
+        self.forget(fd);
+        Ok(())
+    }
+
+    /// Forget about a file descriptor that's being closed
+    ///
+    /// Closing a file descriptor takes it out of epoll, so there's nothing to tell epoll. But if
+    /// we didn't forget about it, we'd think the next file descriptor to get the same number was
+    /// registered already.
+    fn forget(&mut self, fd: RawFd) {
+        if self.io.remove(&fd).is_some() {
+            self.remove_fd(fd);
+        }
+    }
+
+    /// If file descriptors have been closed where we couldn't see it, stop trusting what we know
+    /// about every file descriptor until it's registered again
+    fn notice_closed_elsewhere(&mut self) {
+        let closed_elsewhere = CLOSED_ELSEWHERE.load(Ordering::Relaxed);
+        if closed_elsewhere != self.closed_elsewhere {
+            self.closed_elsewhere = closed_elsewhere;
+            for io in self.io.values_mut() {
+                io.verified = false;
+            }
+        }
+    }
+
+    /// Whether an operation on a file descriptor for `interest` might not block
+    ///
+    /// File descriptors that haven't been registered yet might be ready for anything.
+    fn might_be_ready(&self, fd: RawFd, interest: Interest) -> bool {
+        self.io.get(&fd).is_none_or(|io| {
+            !io.verified
+                || (interest.is_readable() && io.readable)
+                || (interest.is_writable() && io.writable)
+        })
+    }
+
+    /// Record that an operation on a file descriptor for `interest` said it would block
+    fn clear_readiness(&mut self, fd: RawFd, interest: Interest) {
+        if let Some(io) = self.io.get_mut(&fd) {
+            if interest.is_readable() {
+                io.readable = false;
+            }
+            if interest.is_writable() {
+                io.writable = false;
+            }
+        }
+    }
+
+    /// Record that a file descriptor was deregistered, whichever task it was registered for
+    fn remove_fd(&mut self, fd: RawFd) {
+        for task in self.tasks.values_mut() {
+            task.info.fds.retain(|&registered| registered != fd);
+        }
+    }
+
+    /// Record that a task registered a file descriptor
+    fn add_fd(&mut self, future_id: FutureId, fd: RawFd) {
+        if let Some(task) = self.tasks.get_mut(&future_id) {
+            if !task.info.fds.contains(&fd) {
+                task.info.fds.push(fd);
+            }
+        }
+        for instrument in &self.instruments {
+            instrument.on_fd_register(future_id.task_id(), fd);
+        }
     }
 }
 
@@ -74,23 +694,49 @@ impl RuntimeInner {
 pub struct Runtime {
     /// A good chunk is in `RuntimeInner`, so we can spawn futures and such into it
     inner: Rc<RefCell<RuntimeInner>>,
-    /// The list of all of the futures we know about, though, doesn't need to be shared around.
-    /// we'll hord that all to ourselves.
-    ///
-    /// When we register a file descriptor with epoll, we register what [`FutureId`] it's for. So
-    /// when we get an event from epoll, we need a way to look up the relevant future by its ID.
-    futures: HashMap<FutureId, (Waker, Pin<Box<dyn Future<Output = ()>>>)>,
+    /// Whether to capture a backtrace when a task panics
+    capture_panic_backtraces: bool,
+    /// Which cores to keep the thread on while the runtime runs, if it matters
+    worker_affinity: Option<Vec<usize>>,
+    /// Called on the thread the runtime runs on, before it starts running
+    on_worker_start: Option<ThreadHook>,
+    /// Called on the thread the runtime runs on, once it's done running
+    on_worker_stop: Option<ThreadHook>,
 }
 
 impl Runtime {
     /// Create a new runtime
     ///
     /// Because this creates the epoll, it could fail.
+    ///
+    /// To configure the runtime, use a [`Builder`] instead.
     pub fn new() -> Result<Self, std::io::Error> {
-        let inner = Rc::new(RefCell::new(RuntimeInner::new()?));
-        let futures = HashMap::new();
+        Builder::new().build()
+    }
+
+    /// Create a new runtime from a builder's configuration
+    fn from_builder(builder: Builder) -> Result<Self, std::io::Error> {
+        let capture_panic_backtraces = builder.capture_panic_backtraces;
+        if capture_panic_backtraces {
+            panic::install_hook();
+        }
+        let worker_affinity = builder.worker_affinity.clone();
+        let on_worker_start = builder.on_worker_start.clone();
+        let on_worker_stop = builder.on_worker_stop.clone();
+        let inner = Rc::new(RefCell::new(RuntimeInner::new(builder)?));
+
+        Ok(Self {
+            inner,
+            capture_panic_backtraces,
+            worker_affinity,
+            on_worker_start,
+            on_worker_stop,
+        })
+    }
 
-        Ok(Self { inner, futures })
+    /// Get a [`Handle`] to this runtime, to look into it while it runs
+    pub fn handle(&self) -> Handle {
+        Handle::new(self.inner.clone())
     }
 
     /// Block the runtime until the future completes, returning the result of the future
@@ -100,12 +746,38 @@ impl Runtime {
     /// Technically, this blocks until *all* futures are complete. And the returns the results of
     /// the future given.
     ///
+    /// Panics if the runtime itself fails; use [`Runtime::try_block_on`] to handle that instead.
+    ///
     /// ```
     /// let runtime = guillotine::runtime::Runtime::new().unwrap();
     /// let r = runtime.block_on(async { 42 });
     /// assert_eq!(r, 42);
     /// ```
+    #[track_caller]
     pub fn block_on<F>(self, future: F) -> F::Output
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        match self.try_block_on(future) {
+            Ok(output) => output,
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    /// Like [`Runtime::block_on`], but returns an error if the runtime itself fails, instead of
+    /// panicking
+    ///
+    /// The runtime can't keep going after that. Whatever tasks hadn't finished are dropped without
+    /// running any further.
+    ///
+    /// ```
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// let r = runtime.try_block_on(async { 42 }).unwrap();
+    /// assert_eq!(r, 42);
+    /// ```
+    #[track_caller]
+    pub fn try_block_on<F>(self, future: F) -> Result<F::Output, crate::Error>
     where
         F: Future + 'static,
         F::Output: 'static,
@@ -119,13 +791,19 @@ impl Runtime {
             tx.send(result).unwrap();
         };
 
-        // Put the future into the runtime and then run the runtime until it's done.
-        self.spawn(wrapped_future);
-        self.block();
+        // Put the future into the runtime and then run the runtime until it's done. Unlike
+        // other tasks, a panic in this one isn't caught: it carries on out of `block_on`.
+        self.inner
+            .try_borrow_mut()
+            .expect("Expected mutex to lock")
+            .spawn(wrapped_future, None)
+            .detach();
+        self.try_block()?;
 
         // Because all of the futures are done, we know our wrapped future is done. So we can now
-        // grab the result out of the channel and away we go!
-        rx.recv().expect("Expected to recv")
+        // grab the result out of the channel and away we go! Unless the runtime was shut down,
+        // and the future dropped before it could finish.
+        rx.recv().map_err(|_| crate::Error::ShutDown)
     }
 
     /// Block until all of the futures have executed to completion
@@ -136,6 +814,8 @@ impl Runtime {
     /// This method doesn't take a future: to use this, [`Runtime::spawn`] futures onto the runtime
     /// before calling this method.
     ///
+    /// Panics if the runtime itself fails; use [`Runtime::try_block`] to handle that instead.
+    ///
     /// ```
     /// let runtime = guillotine::runtime::Runtime::new().unwrap();
     ///
@@ -147,156 +827,348 @@ impl Runtime {
     /// // Block until all of them have completed
     /// runtime.block();
     /// ```
-    pub fn block(mut self) {
-        let _block_guard = tracing::info_span!("block").entered();
+    pub fn block(self) {
+        if let Err(error) = self.try_block() {
+            panic!("{error}");
+        }
+    }
+
+    /// Like [`Runtime::block`], but returns an error if the runtime itself fails, instead of
+    /// panicking
+    ///
+    /// The runtime can't keep going after that. Whatever tasks hadn't finished are dropped without
+    /// running any further.
+    pub fn try_block(self) -> Result<(), crate::Error> {
+        let _block_guard = crate::trace::info_span!("block").entered();
+        let _affinity = AffinityGuard::new(self.worker_affinity.as_deref());
+        if let Some(on_worker_start) = &self.on_worker_start {
+            on_worker_start();
+        }
+        let _stop = StopGuard(self.on_worker_stop.clone());
 
         // Run until we've exhaused every future
+        let mut polled = 0;
         loop {
-            // Check if there are any *new* futures that have been spawned that we need to deal
-            // with. If there are, take the first one.
+            // If there's a task that's ready to be polled, take the first one.
             let front = {
-                let mut inner = self.inner.try_borrow_mut().expect("Expected mutex to lock");
-                inner.new_futures.pop_front()
+                let inner = self.inner.try_borrow().expect("Expected mutex to lock");
+                inner.ready.pop()
             };
 
-            // If there weren't any new futures *AND* there aren't any existing futures, then, uh,
-            // there are no futures. We're done.
-            if front.is_none() && self.futures.is_empty() {
+            if let Some((future_id, runnable)) = front {
+                self.run(future_id, runnable);
+                if self.shutdown_step() {
+                    return Ok(());
+                }
+                self.check_events(&mut polled)?;
+                continue;
+            }
+            polled = 0;
+
+            // Nothing is ready. If there aren't any tasks left at all, then, uh, there are no
+            // futures. Once the shutdown hooks have had their turn, we're done.
+            if self.shutdown_step() {
                 // Later, gator.
-                break;
+                return Ok(());
             }
+            let mut inner = self.inner.try_borrow_mut().expect("Expected mutex to lock");
 
-            if let Some((future_id, mut new_future)) = front {
-                // If there was a new future that needs to be dealt with
-                let _new_future_guard =
-                    tracing::info_span!("future", future_id = %future_id, status = "new").entered();
-
-                // Create a new waker. `Future::poll` requires that we have a waker so that a future
-                // can be woken up later when it's ready. Our waker wraps an eventfd file descriptor
-                // that we've put into epoll. When the waker gets called, it writes to that eventfd
-                // which wakes the epoll, and things can continue.
-                let waker = self.create_waker(future_id);
-                let mut context = Context::from_waker(&waker);
-
-                // Our internal futures need a way to access this Runtime. There's nothing in the
-                // Future trait that lets that happen, so we set a thread local variable with some
-                // context that our futures can use while they're being polled, and then we clear
-                // it afterward.
-                //
-                // So set it here...
-                RuntimeContext::set(RuntimeContext::new(
-                    future_id,
-                    waker.clone(),
-                    self.inner.clone(),
-                ));
-
-                // ...poll the future...
-                let result = {
-                    let _poll_guard = tracing::info_span!("poll").entered();
-                    new_future.as_mut().poll(&mut context)
-                };
-
-                // ...and clear the context.
-                RuntimeContext::clear();
-
-                // What should we do with the result of the poll?
-                match result {
-                    Poll::Ready(()) => {
-                        // It ran to completion already!? That was quick. Then we don't even need
-                        // to save it. Let it go out of scope. See ya!
-                    }
-                    Poll::Pending => {
-                        // It didn't finish. So we need to store it away in our list of long-term
-                        // futures that we continue to poll until comppletion.
-                        self.futures.insert(future_id, (waker, new_future));
-                    }
+            // If time is paused and something's waiting on it, there's no point waiting for it to
+            // pass. As long as epoll doesn't have anything for us right now, skip ahead to the next
+            // deadline.
+            #[cfg(feature = "test-util")]
+            if let Some(clock) = inner.clock.clone().filter(|clock| clock.has_timers()) {
+                match inner.epoll.poll().map_err(crate::Error::Reactor)? {
+                    Some(event) => inner.dispatch(event),
+                    None => clock.advance_to_next(),
                 }
-            } else {
-                // There are no new futures that need to be dealt with.
-
-                // So let's wait until one of our current futures needs to be dealt with. epoll will
-                // block until a file descriptor says it's ready. This could be a TCP or UDP file
-                // descriptor. Or it could be one of our eventfd file descriptors that exists to
-                // wake us up when a future should be polled again. Either way, wait until
-                // *something* wakes us up again.
-                //
-                // When epoll does wake up, it will tell us which future it woke up for.
-                let future_id = {
-                    let mut inner = self.inner.try_borrow_mut().expect("Expected mutex to lock");
-                    inner
-                        .epoll
-                        .wait()
-                        .expect("What do we do if epoll_wait fails?")
-                };
-
-                let _future_guard =
-                    tracing::info_span!("future", future_id = %future_id, status = "existing")
-                        .entered();
-
-                // Lifetimes. There's maybe a way to do this better, but let's use a bool to
-                // determine if the future we're going to execute is finished or not.
-                let mut should_remove = false;
-
-                // Get the future that woke us up.
-                if let Some((waker, future)) = self.futures.get_mut(&future_id) {
-                    let mut context = Context::from_waker(&waker);
-
-                    // Our internal futures need a way to access this Runtime. There's nothing in
-                    // the Future trait that lets that happen, so we set a thread local variable
-                    // with some context that our futures can use while they're being polled, and
-                    // then we clear it afterward.
-                    //
-                    // So set it here...
-                    RuntimeContext::set(RuntimeContext::new(
-                        future_id,
-                        waker.clone(),
-                        self.inner.clone(),
-                    ));
-
-                    // ...poll the future...
-                    let result = {
-                        let _poll_guard = tracing::info_span!("poll").entered();
-                        future.as_mut().poll(&mut context)
-                    };
-
-                    // ...and clear the context.
-                    RuntimeContext::clear();
-                    match result {
-                        Poll::Ready(()) => {
-                            // The future is done. We no longer need to deal with it.
-                            should_remove = true;
-                        }
-                        Poll::Pending => {
-                            // The future did not complete. So we leave it in our stash of running
-                            // futures until the next time it's ready to be polled.
-                        }
-                    }
-                } else {
-                    warn!(future_id = ?future_id, "epoll returned future_id that was not expected");
+                continue;
+            }
+
+            // So let's wait until one of our tasks needs to be dealt with. epoll will block until a
+            // file descriptor says it's ready. This could be a TCP or UDP file descriptor that a
+            // task registered. Or it could be the ready queue's eventfd, which a waker writes to
+            // when it puts a task on the queue. Either way, wait until *something* wakes us up
+            // again.
+            //
+            // When epoll does wake up, it will tell us which file descriptor it woke up for.
+            let event = inner.wait().map_err(crate::Error::Reactor)?;
+            inner.dispatch(event);
+        }
+    }
+
+    /// Move shutting down along, if the runtime has started to, or should
+    ///
+    /// The runtime shuts down once every task has finished, or once somebody asks it to. Either
+    /// way, the shutdown hooks get to run (for a while) before it's done.
+    ///
+    /// Returns whether the runtime is done: every task, and every shutdown hook, has either
+    /// finished or been dropped.
+    fn shutdown_step(&self) -> bool {
+        let shutdown = self
+            .inner
+            .try_borrow()
+            .expect("Expected mutex to lock")
+            .shutdown;
+        match shutdown {
+            Shutdown::Running => {
+                let finished = self
+                    .inner
+                    .try_borrow()
+                    .expect("Expected mutex to lock")
+                    .tasks
+                    .is_empty();
+                finished && self.start_shutdown()
+            }
+            Shutdown::Requested => {
+                self.cancel_all();
+                self.start_shutdown()
+            }
+            Shutdown::Draining(deadline) => {
+                // Once the only thing left is the task waiting for the deadline, there's no point
+                // waiting for it.
+                let finished = self
+                    .inner
+                    .try_borrow()
+                    .expect("Expected mutex to lock")
+                    .tasks
+                    .keys()
+                    .all(|&future_id| future_id == deadline);
+                if finished {
+                    self.cancel_all();
                 }
+                finished
+            }
+            Shutdown::TimedOut => {
+                self.cancel_all();
+                true
+            }
+        }
+    }
+
+    /// Spawn the shutdown hooks, along with a task that says when their time is up
+    ///
+    /// Returns whether there weren't any hooks, so the runtime is done already.
+    fn start_shutdown(&self) -> bool {
+        let hooks = std::mem::take(
+            &mut self
+                .inner
+                .try_borrow_mut()
+                .expect("Expected mutex to lock")
+                .shutdown_hooks,
+        );
+        if hooks.is_empty() {
+            return true;
+        }
 
-                // If we should remove it, then, uh, remove it.
-                if should_remove {
-                    self.futures.remove(&future_id);
+        // Calling a hook only creates its future, but it's the user's code, so don't have the
+        // runtime borrowed while it runs.
+        let futures: Vec<_> = hooks.into_iter().map(|hook| hook()).collect();
+
+        let mut inner = self.inner.try_borrow_mut().expect("Expected mutex to lock");
+        for future in futures {
+            inner
+                .spawn(future, Some("guillotine::on_shutdown".to_string()))
+                .detach();
+        }
+        let timeout = inner.shutdown_timeout;
+        let (deadline, task) = inner.spawn_with_id(
+            async move {
+                if let Err(error) = crate::time::sleep(timeout).await {
+                    warn!(error = %error, "failed to wait for the shutdown hooks");
                 }
+                RuntimeContext::current()
+                    .inner()
+                    .try_borrow_mut()
+                    .expect("Expected mutex to lock")
+                    .shutdown = Shutdown::TimedOut;
+            },
+            Some("guillotine::shutdown_deadline".to_string()),
+        );
+        task.detach();
+        inner.shutdown = Shutdown::Draining(deadline);
+        false
+    }
+
+    /// Drop every task that hasn't finished, without polling it again
+    fn cancel_all(&self) {
+        loop {
+            // Wake every task up, so it's on the ready queue, and then drop it off the queue
+            // instead of running it. Dropping a task's `Runnable` drops its future.
+            let runnables = {
+                let mut inner = self.inner.try_borrow_mut().expect("Expected mutex to lock");
+                for task in inner.tasks.values() {
+                    task.waker.wake_by_ref();
+                }
+                let mut runnables = Vec::new();
+                while let Some((future_id, runnable)) = inner.ready.pop() {
+                    inner.forget_task(future_id);
+                    runnables.push(runnable);
+                }
+                runnables
+            };
+            if runnables.is_empty() {
+                break;
             }
+
+            // Dropping a future can wake up (or even spawn) others, which then need dropping too.
+            // And since a future can do anything when it's dropped, don't have the runtime
+            // borrowed while it does.
+            drop(runnables);
+        }
+
+        // Anything that's left couldn't be woken up, so it's never going to run again anyway.
+        let mut inner = self.inner.try_borrow_mut().expect("Expected mutex to lock");
+        let future_ids: Vec<_> = inner.tasks.keys().copied().collect();
+        for future_id in future_ids {
+            inner.forget_task(future_id);
         }
     }
 
-    /// Create a waker for a particular future
-    fn create_waker(&mut self, future_id: FutureId) -> Waker {
-        let fd = eventfd::EventFd::new().expect("What do we do when this panics!?");
+    /// Do whatever there is to do right now, without waiting for anything, for when the runtime is
+    /// embedded in some other event loop
+    ///
+    /// This polls every task that's ready, and every task whose file descriptors epoll says are
+    /// ready, until there aren't any. The runtime's [file descriptor](AsRawFd::as_raw_fd) becomes
+    /// readable when there's something to do again; have the other event loop watch it and call
+    /// this when it is.
+    ///
+    /// Returns whether the runtime has anything left to do: tasks that haven't finished, or
+    /// [shutdown hooks](Handle::on_shutdown) that haven't run.
+    ///
+    /// ```
+    /// use std::os::unix::prelude::AsRawFd;
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.spawn(async {
+    ///     guillotine::time::sleep(std::time::Duration::from_millis(10))
+    ///         .await
+    ///         .unwrap();
+    /// });
+    ///
+    /// // Standing in for a GUI toolkit's main loop, or some such
+    /// while runtime.drive().unwrap() {
+    ///     let mut pollfd = libc::pollfd {
+    ///         fd: runtime.as_raw_fd(),
+    ///         events: libc::POLLIN,
+    ///         revents: 0,
+    ///     };
+    ///     unsafe { libc::poll(&mut pollfd, 1, -1) };
+    /// }
+    /// ```
+    pub fn drive(&self) -> Result<bool, crate::Error> {
+        let _drive_guard = crate::trace::info_span!("drive").entered();
 
-        {
+        let mut polled = 0;
+        loop {
+            let front = {
+                let inner = self.inner.try_borrow().expect("Expected mutex to lock");
+                inner.ready.pop()
+            };
+
+            if let Some((future_id, runnable)) = front {
+                self.run(future_id, runnable);
+                if self.shutdown_step() {
+                    return Ok(false);
+                }
+                self.check_events(&mut polled)?;
+                continue;
+            }
+            polled = 0;
+
+            if self.shutdown_step() {
+                return Ok(false);
+            }
+
+            // Nothing is ready, but maybe epoll has something for us. If it doesn't, we're done
+            // for now.
+            let mut inner = self.inner.try_borrow_mut().expect("Expected mutex to lock");
+            match inner.epoll.poll().map_err(crate::Error::Reactor)? {
+                Some(event) => inner.dispatch(event),
+                None => return Ok(true),
+            }
+        }
+    }
+
+    /// Every so often, in between polling tasks, see whether epoll has anything, without waiting
+    ///
+    /// `polled` counts how many tasks have been polled since the last check. Without this, epoll
+    /// is only checked once the ready queue is empty, and tasks that keep waking each other up
+    /// can keep it from ever being empty. Then nobody waiting on a socket or a timer would ever
+    /// get a look in.
+    fn check_events(&self, polled: &mut u32) -> Result<(), crate::Error> {
+        *polled += 1;
+        if *polled < EVENT_INTERVAL {
+            return Ok(());
+        }
+        *polled = 0;
+
+        // A busy socket can keep producing events, so don't stay here forever either.
+        let mut inner = self.inner.try_borrow_mut().expect("Expected mutex to lock");
+        for _ in 0..EVENT_INTERVAL {
+            match inner.epoll.poll().map_err(crate::Error::Reactor)? {
+                Some(event) => inner.dispatch(event),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Poll a task that was on the ready queue
+    fn run(&self, future_id: FutureId, runnable: async_task::Runnable) {
+        let (span, ready) = {
+            let inner = self.inner.try_borrow().expect("Expected mutex to lock");
+            match inner.tasks.get(&future_id) {
+                Some(task) => (task.span.clone(), inner.ready.clone()),
+                None => {
+                    warn!(future_id = ?future_id, "ready queue had a task that was not expected");
+                    return;
+                }
+            }
+        };
+        let _future_guard = span.enter();
+
+        // Our internal futures need a way to access this Runtime. There's nothing in the Future
+        // trait that lets that happen, so we set a thread local variable with some context that
+        // our futures can use while they're being polled, and then we clear it afterward.
+        //
+        // So set it here...
+        RuntimeContext::set(RuntimeContext::new(future_id, self.inner.clone()));
+
+        // An aborted task gets dropped instead of polled. Dropping the runnable drops the future
+        // (with the context set, so its sockets and timers can clean up after themselves), and its
+        // `JoinHandle` resolves to `JoinError::Cancelled`.
+        if ready.take_aborted(future_id) {
+            drop(runnable);
+            RuntimeContext::clear();
             self.inner
                 .try_borrow_mut()
                 .expect("Expected mutex to lock")
-                .epoll
-                .add(&fd, future_id)
-                .expect("What do we do if epoll add fails?");
+                .forget_task(future_id);
+            return;
+        }
+
+        // ...poll the future...
+        self.inner
+            .try_borrow_mut()
+            .expect("Expected mutex to lock")
+            .start_poll(future_id);
+        {
+            let _poll_guard = crate::trace::info_span!("poll").entered();
+            // Anything this task wakes up while it's being polled goes in the LIFO slot.
+            let _polling = ready.polling();
+            if self.capture_panic_backtraces {
+                panic::run_capturing_backtrace(|| runnable.run());
+            } else {
+                runnable.run();
+            }
         }
 
-        waker::build(fd)
+        // ...and clear the context.
+        RuntimeContext::clear();
+        let mut inner = self.inner.try_borrow_mut().expect("Expected mutex to lock");
+        let finished = inner.finished.replace(false);
+        inner.finish_poll(future_id, finished);
     }
 
     /// Spawn a future onto the runtime before running
@@ -304,11 +1176,52 @@ impl Runtime {
     /// Typically, you'll want to use [`Runtime::block_on`] and run a single future to completion.
     /// But if for some reason you want to spawn a handful of futures onto the executor to all be
     /// run at the same time, well here you go.
-    pub fn spawn<F>(&self, future: F)
+    ///
+    /// The handle works the same as the one from [`task::spawn`](crate::task::spawn): await it
+    /// (from another task) for the future's output, abort it, or drop it and let the future run on
+    /// its own.
+    ///
+    /// ```
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    ///
+    /// let a = runtime.spawn(async { 20 });
+    /// let b = runtime.spawn(async { 22 });
+    ///
+    /// let sum = runtime.block_on(async move { a.await.unwrap() + b.await.unwrap() });
+    /// assert_eq!(sum, 42);
+    /// ```
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F) -> crate::task::JoinHandle<F::Output>
     where
-        F: Future<Output = ()> + 'static,
+        F: Future + 'static,
+        F::Output: 'static,
     {
         let mut inner = self.inner.try_borrow_mut().expect("Expected mutex to lock");
-        inner.spawn(future);
+        inner.spawn_join_handle(future, None)
+    }
+}
+
+/// Wait for every thread [`spawn_blocking`](crate::task::spawn_blocking) started, so nothing the
+/// runtime started outlives it
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        let blocking_threads = match self.inner.try_borrow() {
+            Ok(inner) => inner.blocking_threads.clone(),
+            Err(_) => return,
+        };
+        blocking_threads.join_all();
+    }
+}
+
+/// The runtime's epoll file descriptor, which is readable whenever the runtime has something to do
+///
+/// For embedding the runtime in another event loop; see [`Runtime::drive`].
+impl AsRawFd for Runtime {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner
+            .try_borrow()
+            .expect("Expected mutex to lock")
+            .epoll
+            .as_raw_fd()
     }
 }
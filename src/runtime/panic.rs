@@ -0,0 +1,104 @@
+//! Catching panics in spawned tasks, and capturing their backtraces
+//!
+//! A panic in a spawned task is caught as the task is polled, and handed to whoever awaits its
+//! [`JoinHandle`](crate::task::JoinHandle) instead of taking the runtime down.
+//!
+//! By the time `catch_unwind` hands back a panic, the stack it happened on is gone, so the
+//! backtrace has to be captured from inside a panic hook, while the panic is still happening. The
+//! hook only does that while a runtime that asked for backtraces is polling a task, and otherwise
+//! leaves everything to whatever hook was there before.
+
+use crate::task::JoinError;
+use pin_project::pin_project;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::Once;
+use std::task::{Context, Poll};
+
+thread_local! {
+    /// Whether a task is being polled, and the runtime polling it wants a backtrace if it panics
+    static CAPTURING: Cell<bool> = const { Cell::new(false) };
+    /// The backtrace of the last panic while `CAPTURING` was set
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+/// Install our panic hook (once), in front of whatever hook is there already
+pub(super) fn install_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if CAPTURING.with(|capturing| capturing.get()) {
+                let backtrace = Backtrace::force_capture();
+                BACKTRACE.with(|cell| *cell.borrow_mut() = Some(backtrace));
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Run (poll) a task, with the hook capturing a backtrace if it panics
+pub(super) fn run_capturing_backtrace<T>(run: impl FnOnce() -> T) -> T {
+    struct Reset;
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            CAPTURING.with(|capturing| capturing.set(false));
+        }
+    }
+
+    CAPTURING.with(|capturing| capturing.set(true));
+    let _reset = Reset;
+    run()
+}
+
+/// A spawned future that catches its own panics, so a panic ends the task rather than the runtime
+///
+/// The panic comes out as [`JoinError::Panic`], along with the backtrace the hook captured (if the
+/// runtime asked for one).
+#[pin_project]
+pub(crate) struct CatchPanic<F> {
+    #[pin]
+    future: F,
+}
+
+impl<F> CatchPanic<F> {
+    pub(crate) fn new(future: F) -> Self {
+        Self { future }
+    }
+}
+
+impl<F: Future> Future for CatchPanic<F> {
+    type Output = Result<F::Output, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self.project().future;
+        match std::panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(poll) => poll.map(Ok),
+            Err(payload) => {
+                // The task finishes here, so the future that panicked is never polled again.
+                let backtrace = BACKTRACE.with(|cell| cell.borrow_mut().take());
+                crate::trace::error!(
+                    panic = panic_message(&*payload),
+                    backtrace = %backtrace.as_ref().map(|b| b.to_string()).unwrap_or_default(),
+                    "Task panicked",
+                );
+                Poll::Ready(Err(JoinError::Panic { payload, backtrace }))
+            }
+        }
+    }
+}
+
+/// The message a panic was started with, if it was a string (which it almost always is)
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
@@ -0,0 +1,284 @@
+//! Just the reactor, without the rest of the runtime
+//!
+//! Inside a runtime, a socket or timer that isn't ready registers its file descriptor with the
+//! runtime's epoll, and the runtime polls the task again when epoll says it's ready. Outside of one
+//! (in some other executor, say), there's nothing to do that. A [`ReactorHandle`] runs an epoll
+//! loop on a background thread that does the waking instead.
+
+use super::epoll::{Epoll, WAKEUP};
+use super::eventfd::EventFd;
+use super::Interest;
+use crate::trace::error;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::marker::PhantomData;
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+
+thread_local! {
+    /// The reactor that futures polled on this thread register with, if one has been entered
+    static CURRENT: RefCell<Option<ReactorHandle>> = const { RefCell::new(None) };
+}
+
+/// A reactor running on a background thread, so that this crate's sockets and timers can be
+/// awaited from any executor
+///
+/// [`enter`](Self::enter) the reactor on whichever thread is going to poll them, and they'll wake
+/// up whatever waker they were last polled with. Inside a runtime, the runtime's own reactor is
+/// used instead.
+///
+/// The background thread stops once every handle to the reactor has been dropped.
+///
+/// ```
+/// use guillotine::io::{AsyncReadExt, AsyncWriteExt};
+/// use guillotine::runtime::ReactorHandle;
+/// use std::sync::Arc;
+/// use std::task::{Context, Poll, Wake, Waker};
+///
+/// // A bare-bones executor that knows nothing about guillotine
+/// fn block_on<F: std::future::Future>(future: F) -> F::Output {
+///     struct Unpark(std::thread::Thread);
+///     impl Wake for Unpark {
+///         fn wake(self: Arc<Self>) {
+///             self.0.unpark()
+///         }
+///     }
+///
+///     let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+///     let mut cx = Context::from_waker(&waker);
+///     let mut future = std::pin::pin!(future);
+///     loop {
+///         if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+///             return output;
+///         }
+///         std::thread::park();
+///     }
+/// }
+///
+/// let reactor = ReactorHandle::new().unwrap();
+/// let _guard = reactor.enter();
+/// block_on(async {
+///     guillotine::time::sleep(std::time::Duration::from_millis(10))
+///         .await
+///         .unwrap();
+///
+///     let (mut a, mut b) = guillotine::net::unix::pair().unwrap();
+///     a.write_all(b"hello").await.unwrap();
+///     let mut buf = [0; 5];
+///     b.read_exact(&mut buf).await.unwrap();
+///     assert_eq!(&buf, b"hello");
+/// });
+/// ```
+#[derive(Clone)]
+pub struct ReactorHandle {
+    inner: Arc<HandleInner>,
+}
+
+/// Tells the background thread to stop when the last handle goes away
+struct HandleInner {
+    shared: Arc<Shared>,
+}
+
+/// Who's waiting on a file descriptor, and what epoll is watching it for
+///
+/// One task can be reading while another is writing, so each direction has its own waker.
+#[derive(Default)]
+struct Wakers {
+    read: Option<Waker>,
+    write: Option<Waker>,
+    interest: Option<Interest>,
+}
+
+/// The parts of the reactor that are shared with the background thread
+struct Shared {
+    /// The epoll instance everything is registered with
+    epoll: Epoll,
+    /// The wakers to wake when each file descriptor is ready
+    ///
+    /// The reactor has no futures of its own, so it registers each file descriptor with epoll
+    /// under the file descriptor's own number. Entries are never taken back out; a file
+    /// descriptor that gets closed and reused just gets its wakers replaced.
+    wakers: Mutex<HashMap<RawFd, Wakers>>,
+    /// Written to when the last handle is dropped, to stop the background thread
+    shutdown: EventFd,
+}
+
+impl ReactorHandle {
+    /// Start a reactor on a new background thread
+    pub fn new() -> Result<Self, std::io::Error> {
+        let shared = Arc::new(Shared {
+            epoll: Epoll::new()?,
+            wakers: Mutex::new(HashMap::new()),
+            shutdown: EventFd::new()?,
+        });
+        shared
+            .epoll
+            .add(&shared.shutdown, WAKEUP, Interest::READABLE)?;
+
+        let thread_shared = shared.clone();
+        std::thread::Builder::new()
+            .name("guillotine reactor".to_string())
+            .spawn(move || thread_shared.run())?;
+
+        Ok(Self {
+            inner: Arc::new(HandleInner { shared }),
+        })
+    }
+
+    /// Use this reactor for sockets and timers polled on this thread (outside of a runtime) until
+    /// the guard is dropped
+    pub fn enter(&self) -> EnterGuard {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        EnterGuard {
+            previous,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Wake `waker` when `fd` is ready
+    fn register(&self, fd: RawFd, waker: &Waker, interest: Interest) -> Result<(), std::io::Error> {
+        let shared = &self.inner.shared;
+        let mut wakers = shared.wakers.lock().expect("Expected mutex to lock");
+        let entry = wakers.entry(fd).or_default();
+        // Unlike the runtime, we don't keep track of whether it's been closed, and just let epoll
+        // tell us.
+        match shared.epoll.add(&fd, fd as u64, interest) {
+            Ok(()) => {
+                // New, or closed and the number reused, so whoever was waiting isn't anymore.
+                *entry = Wakers {
+                    interest: Some(interest),
+                    ..Wakers::default()
+                };
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                let wanted = entry
+                    .interest
+                    .map_or(interest, |existing| existing | interest);
+                if entry.interest != Some(wanted) {
+                    shared.epoll.modify(&fd, fd as u64, wanted)?;
+                    entry.interest = Some(wanted);
+                }
+            }
+            Err(err) => return Err(err),
+        }
+        for (wants, slot) in [
+            (interest.is_readable(), &mut entry.read),
+            (interest.is_writable(), &mut entry.write),
+        ] {
+            match slot {
+                Some(existing) if existing.will_wake(waker) => {}
+                _ if wants => *slot = Some(waker.clone()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop watching `fd`
+    fn deregister(&self, fd: RawFd) -> Result<(), std::io::Error> {
+        let shared = &self.inner.shared;
+        shared
+            .wakers
+            .lock()
+            .expect("Expected mutex to lock")
+            .remove(&fd);
+        shared.epoll.delete(fd)
+    }
+}
+
+impl std::fmt::Debug for ReactorHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReactorHandle").finish_non_exhaustive()
+    }
+}
+
+impl Drop for HandleInner {
+    fn drop(&mut self) {
+        if let Err(error) = self.shared.shutdown.write(1) {
+            error!(error = %error, "failed to stop the reactor thread");
+        }
+    }
+}
+
+impl Shared {
+    /// Wake up wakers until told to stop
+    fn run(&self) {
+        loop {
+            let event = match self.epoll.wait() {
+                Ok(event) => event,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    error!(error = %error, "reactor thread failed to wait on epoll");
+                    return;
+                }
+            };
+            if event.token == WAKEUP {
+                return;
+            }
+
+            let fd = event.token as RawFd;
+            let (read, write) = match self.wakers.lock().expect("Expected mutex to lock").get(&fd) {
+                Some(wakers) => (
+                    wakers.read.clone().filter(|_| event.readable),
+                    wakers.write.clone().filter(|_| event.writable),
+                ),
+                None => continue,
+            };
+            // Wake outside of the lock, since a waker could be polling right away. If the same
+            // task is waiting both ways, it's woken twice, which does no harm.
+            for waker in read.into_iter().chain(write) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// The guard from [`ReactorHandle::enter`], which puts back whichever reactor was entered before
+/// when it's dropped
+pub struct EnterGuard {
+    previous: Option<ReactorHandle>,
+    /// The reactor was entered on this thread, so it has to be left on this thread too
+    _not_send: PhantomData<Rc<()>>,
+}
+
+impl std::fmt::Debug for EnterGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnterGuard").finish_non_exhaustive()
+    }
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| current.replace(previous));
+    }
+}
+
+/// Register with the reactor this thread has entered, if it's entered one
+///
+/// Returns `None` if there isn't one.
+pub(super) fn register_with_current(
+    fd: &impl AsRawFd,
+    waker: &Waker,
+    interest: Interest,
+) -> Option<Result<(), std::io::Error>> {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map(|reactor| reactor.register(fd.as_raw_fd(), waker, interest))
+    })
+}
+
+/// Deregister from the reactor this thread has entered, if it's entered one
+pub(super) fn deregister_with_current(fd: RawFd) -> Option<Result<(), std::io::Error>> {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map(|reactor| reactor.deregister(fd))
+    })
+}
@@ -0,0 +1,180 @@
+//! The queue of tasks that have been woken up and are waiting for their turn to be polled
+
+use super::eventfd::EventFd;
+use super::FutureId;
+use crate::trace::error;
+use async_task::{Runnable, ScheduleInfo};
+use std::cell::Cell;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+
+/// How many tasks in a row can come out of the LIFO slot before the rest of the queue gets a turn
+///
+/// Without a limit, two tasks that keep waking each other up would have the runtime to themselves.
+const MAX_LIFO_STREAK: usize = 3;
+
+thread_local! {
+    /// The queue whose task is being polled on this thread right now, if there is one
+    ///
+    /// This is only ever compared against, never dereferenced.
+    static POLLING: Cell<*const ReadyQueue> = const { Cell::new(std::ptr::null()) };
+}
+
+/// Tasks that have been woken up and are waiting to be polled
+///
+/// A task's waker can be used from any thread, so this is shared with the wakers behind an `Arc`.
+/// Pushing onto an empty queue also writes to an eventfd that the runtime registered with its
+/// epoll, in case the runtime is asleep in `epoll_wait` and needs waking up. That's the only
+/// eventfd the runtime needs, no matter how many tasks it has.
+///
+/// A task woken up by the task that's being polled skips the queue, though. It goes in the LIFO
+/// slot and gets polled next, because whatever woke it up (sending it a message, say) probably
+/// means it has something to do right away, and everything it needs is still in the CPU's cache.
+pub(super) struct ReadyQueue {
+    queue: Mutex<Queue>,
+    /// The file descriptor that wakes up the runtime
+    eventfd: EventFd,
+}
+
+struct Queue {
+    /// The tasks themselves, in the order they were woken up
+    tasks: VecDeque<(FutureId, Runnable)>,
+    /// The last task woken up by the task being polled, to be polled next
+    lifo: Option<(FutureId, Runnable)>,
+    /// How many tasks in a row have come out of the LIFO slot
+    lifo_streak: usize,
+    /// Tasks whose handles asked for them to be aborted, to be dropped instead of polled
+    aborted: HashSet<FutureId>,
+}
+
+impl ReadyQueue {
+    /// Create a new, empty queue
+    pub fn new() -> Result<Self, std::io::Error> {
+        Ok(Self {
+            queue: Mutex::new(Queue {
+                tasks: VecDeque::new(),
+                lifo: None,
+                lifo_streak: 0,
+                aborted: HashSet::new(),
+            }),
+            eventfd: EventFd::new()?,
+        })
+    }
+
+    /// The file descriptor to register with epoll
+    pub fn eventfd(&self) -> &EventFd {
+        &self.eventfd
+    }
+
+    /// Put a woken task in line to be polled
+    ///
+    /// This is what a task's waker ends up calling.
+    pub fn schedule(&self, future_id: FutureId, runnable: Runnable, info: ScheduleInfo) {
+        // A task that wakes itself up (to yield, say) goes to the back of the line like everybody
+        // else. Only tasks woken up by somebody else get to jump it.
+        let polling = POLLING.with(|polling| polling.get());
+        if std::ptr::eq(polling, self) && !info.woken_while_running {
+            let mut queue = self.queue.lock().expect("Expected mutex to lock");
+            if let Some(bumped) = queue.lifo.replace((future_id, runnable)) {
+                queue.tasks.push_back(bumped);
+            }
+            // No need to wake up the runtime: it's busy polling, and will look here next.
+        } else {
+            self.push(future_id, runnable);
+        }
+    }
+
+    /// Put a task at the back of the queue
+    pub fn push(&self, future_id: FutureId, runnable: Runnable) {
+        let was_empty = {
+            let mut queue = self.queue.lock().expect("Expected mutex to lock");
+            let was_empty = queue.tasks.is_empty() && queue.lifo.is_none();
+            queue.tasks.push_back((future_id, runnable));
+            was_empty
+        };
+
+        // If the queue already had something in it, the runtime is going to come back for it
+        // before it goes to sleep, so there's no need to wake it up.
+        if was_empty {
+            if let Err(error) = self.eventfd.write(1) {
+                error!(error = %error, "failed to wake up the runtime");
+            }
+        }
+    }
+
+    /// Take the task that should be polled next
+    pub fn pop(&self) -> Option<(FutureId, Runnable)> {
+        let mut queue = self.queue.lock().expect("Expected mutex to lock");
+        if let Some(task) = queue.lifo.take() {
+            if queue.lifo_streak < MAX_LIFO_STREAK {
+                queue.lifo_streak += 1;
+                return Some(task);
+            }
+            queue.tasks.push_back(task);
+        }
+        queue.lifo_streak = 0;
+        queue.tasks.pop_front()
+    }
+
+    /// Mark a task as aborted, so it's dropped the next time it comes off the queue
+    pub fn abort(&self, future_id: FutureId) {
+        let mut queue = self.queue.lock().expect("Expected mutex to lock");
+        queue.aborted.insert(future_id);
+    }
+
+    /// Whether a task has been aborted, forgetting that it was
+    pub fn take_aborted(&self, future_id: FutureId) -> bool {
+        let mut queue = self.queue.lock().expect("Expected mutex to lock");
+        queue.aborted.remove(&future_id)
+    }
+
+    /// Mark this queue's runtime as polling a task on this thread, until the guard is dropped
+    pub fn polling(self: &Arc<Self>) -> PollingGuard {
+        let previous = POLLING.with(|polling| polling.replace(Arc::as_ptr(self)));
+        PollingGuard { previous }
+    }
+
+    /// Reset the eventfd after it woke up the runtime
+    pub fn clear_wakeup(&self) {
+        // This fails with `WouldBlock` if there was nothing to reset, which is fine.
+        let _ = self.eventfd.read();
+    }
+}
+
+/// What a [`JoinHandle`](crate::task::JoinHandle) needs to abort its task
+///
+/// Aborting can happen from any thread, so this doesn't touch the runtime itself: it marks the
+/// task as aborted and wakes it up, and the runtime drops it instead of polling it.
+pub(crate) struct Abort {
+    future_id: FutureId,
+    waker: Waker,
+    ready: Arc<ReadyQueue>,
+}
+
+impl Abort {
+    pub(super) fn new(future_id: FutureId, waker: Waker, ready: Arc<ReadyQueue>) -> Self {
+        Self {
+            future_id,
+            waker,
+            ready,
+        }
+    }
+
+    /// Have the runtime drop the task instead of polling it again
+    pub fn abort(&self) {
+        self.ready.abort(self.future_id);
+        self.waker.wake_by_ref();
+    }
+}
+
+/// The guard from [`ReadyQueue::polling`]
+pub(super) struct PollingGuard {
+    previous: *const ReadyQueue,
+}
+
+impl Drop for PollingGuard {
+    fn drop(&mut self) {
+        POLLING.with(|polling| polling.set(self.previous));
+    }
+}
@@ -0,0 +1,282 @@
+//! Hooking a file descriptor up to the runtime, for types outside of this crate
+
+use std::io::ErrorKind;
+use std::ops::BitOr;
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::task::{Context, Poll};
+
+/// Which kinds of readiness to wait for
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Interest {
+    readable: bool,
+    writable: bool,
+}
+
+impl Interest {
+    /// Wait for the file descriptor to be readable
+    pub const READABLE: Interest = Interest {
+        readable: true,
+        writable: false,
+    };
+
+    /// Wait for the file descriptor to be writable
+    pub const WRITABLE: Interest = Interest {
+        readable: false,
+        writable: true,
+    };
+
+    /// Whether this includes readability
+    pub fn is_readable(self) -> bool {
+        self.readable
+    }
+
+    /// Whether this includes writability
+    pub fn is_writable(self) -> bool {
+        self.writable
+    }
+}
+
+impl BitOr for Interest {
+    type Output = Interest;
+
+    fn bitor(self, other: Interest) -> Interest {
+        Interest {
+            readable: self.readable || other.readable,
+            writable: self.writable || other.writable,
+        }
+    }
+}
+
+/// A file descriptor that the runtime wakes tasks up for
+///
+/// This is what this crate's own sockets and timers do under the hood, for implementing your own
+/// leaf futures: try a non-blocking operation, and if it would block, register the file
+/// descriptor and return `Poll::Pending`. Once the file descriptor is ready, the runtime polls the
+/// task again (outside of a runtime, whichever [`ReactorHandle`](super::ReactorHandle) has been
+/// entered wakes the waker instead). [`poll_io`](Self::poll_io) does all of that in one go.
+///
+/// Registrations are edge-triggered: the task is only woken up when the file descriptor
+/// *becomes* ready, so keep going until the operation would block before waiting.
+///
+/// ```
+/// use guillotine::runtime::{Interest, Registration};
+/// use std::io::{Read, Write};
+/// use std::os::unix::net::UnixStream;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (mut a, mut b) = UnixStream::pair().unwrap();
+///     a.set_nonblocking(true).unwrap();
+///     let registration = Registration::new(&a, Interest::READABLE);
+///
+///     guillotine::task::spawn(async move {
+///         guillotine::time::sleep(std::time::Duration::from_millis(10))
+///             .await
+///             .unwrap();
+///         b.write_all(b"hello").unwrap();
+///     });
+///
+///     let mut buf = [0; 5];
+///     let read = std::future::poll_fn(|cx| registration.poll_io(cx, || a.read(&mut buf)))
+///         .await
+///         .unwrap();
+///     assert_eq!(&buf[..read], b"hello");
+///
+///     registration.deregister().unwrap();
+/// });
+/// ```
+///
+/// Each task is woken up for what it registered for, so one task can wait to read a file
+/// descriptor while another waits to write to it:
+///
+/// ```
+/// use guillotine::runtime::{Interest, Registration};
+/// use std::io::{Read, Write};
+/// use std::os::unix::net::UnixStream;
+/// use std::rc::Rc;
+/// use std::time::Duration;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (a, mut b) = UnixStream::pair().unwrap();
+///     a.set_nonblocking(true).unwrap();
+///     b.set_nonblocking(true).unwrap();
+///     let a = Rc::new(a);
+///
+///     let reader = guillotine::task::spawn({
+///         let a = a.clone();
+///         async move {
+///             let registration = Registration::new(&*a, Interest::READABLE);
+///             let mut buf = [0; 5];
+///             std::future::poll_fn(|cx| registration.poll_io(cx, || (&*a).read(&mut buf)))
+///                 .await
+///                 .unwrap()
+///         }
+///     });
+///     let writer = guillotine::task::spawn({
+///         let a = a.clone();
+///         async move {
+///             // Fill the socket up, so the next write has to wait
+///             while (&*a).write(&[0; 1024]).is_ok() {}
+///             let registration = Registration::new(&*a, Interest::WRITABLE);
+///             std::future::poll_fn(|cx| registration.poll_io(cx, || (&*a).write(b"x")))
+///                 .await
+///                 .unwrap()
+///         }
+///     });
+///
+///     // Let both of them start waiting, then give each what it's waiting for
+///     guillotine::time::sleep(Duration::from_millis(10)).await.unwrap();
+///     b.write_all(b"hello").unwrap();
+///     let mut drain = [0; 1024];
+///     while b.read(&mut drain).is_ok() {}
+///
+///     let limit = Duration::from_secs(5);
+///     let read = guillotine::time::timeout(limit, reader).await.unwrap().unwrap();
+///     assert_eq!(read, 5);
+///     let written = guillotine::time::timeout(limit, writer).await.unwrap().unwrap();
+///     assert_eq!(written, 1);
+/// });
+/// ```
+///
+/// Dropping a registration only stops its own task waiting, so a short-lived one doesn't get in
+/// the way of another task waiting on the same file descriptor:
+///
+/// ```
+/// use guillotine::runtime::{Interest, Registration};
+/// use std::io::{Read, Write};
+/// use std::os::unix::net::UnixStream;
+/// use std::rc::Rc;
+/// use std::time::Duration;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (a, mut b) = UnixStream::pair().unwrap();
+///     a.set_nonblocking(true).unwrap();
+///     b.set_nonblocking(true).unwrap();
+///     let a = Rc::new(a);
+///
+///     let writer = guillotine::task::spawn({
+///         let a = a.clone();
+///         async move {
+///             while (&*a).write(&[0; 1024]).is_ok() {}
+///             let registration = Registration::new(&*a, Interest::WRITABLE);
+///             std::future::poll_fn(|cx| registration.poll_io(cx, || (&*a).write(b"x")))
+///                 .await
+///                 .unwrap()
+///         }
+///     });
+///
+///     // Wait to read for a bit, while the writer waits to write, then give up
+///     let registration = Registration::new(&*a, Interest::READABLE);
+///     let mut buf = [0; 5];
+///     let read = std::future::poll_fn(|cx| registration.poll_io(cx, || (&*a).read(&mut buf)));
+///     let limit = Duration::from_millis(10);
+///     assert!(guillotine::time::timeout(limit, read).await.is_err());
+///     drop(registration);
+///
+///     let mut drain = [0; 1024];
+///     while b.read(&mut drain).is_ok() {}
+///
+///     let limit = Duration::from_secs(5);
+///     let written = guillotine::time::timeout(limit, writer).await.unwrap().unwrap();
+///     assert_eq!(written, 1);
+/// });
+/// ```
+#[derive(Debug)]
+pub struct Registration {
+    /// The file descriptor
+    fd: RawFd,
+    /// What to wait for
+    interest: Interest,
+}
+
+impl Registration {
+    /// Get ready to wait on a file descriptor, which should be in non-blocking mode
+    ///
+    /// Nothing is registered until the first time something would block. The file descriptor
+    /// needs to stay open for as long as this is around.
+    pub fn new(fd: &impl AsRawFd, interest: Interest) -> Self {
+        Self {
+            fd: fd.as_raw_fd(),
+            interest,
+        }
+    }
+
+    /// Arrange for the current task to be woken up when the file descriptor becomes ready, and
+    /// return `Poll::Pending`
+    ///
+    /// Only call this after an operation on the file descriptor has said it would block: the
+    /// runtime takes that as gospel, and [`poll_io`](Self::poll_io) won't bother trying again
+    /// until epoll says something changed. Registering more than once is fine.
+    ///
+    /// If epoll won't watch the file descriptor (a regular file, say, or one that's been closed),
+    /// this returns the error instead, and the task won't be woken up for it.
+    ///
+    /// Panics if there's no runtime currently executing, and no reactor entered.
+    ///
+    /// ```
+    /// use guillotine::runtime::{Interest, Registration};
+    ///
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     // epoll won't watch regular files
+    ///     let path = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+    ///     let file = std::fs::File::open(path).unwrap();
+    ///     let registration = Registration::new(&file, Interest::READABLE);
+    ///
+    ///     let result = std::future::poll_fn(|cx| registration.poll_ready(cx)).await;
+    ///     assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EPERM));
+    /// });
+    /// ```
+    pub fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        super::register_file_descriptor(cx, &self.fd, self.interest)?;
+        super::clear_readiness(self.fd, self.interest);
+        Poll::Pending
+    }
+
+    /// Try a non-blocking operation on the file descriptor, and if it would block, arrange for the
+    /// current task to be woken up when the file descriptor becomes ready
+    ///
+    /// If the last operation said it would block and the file descriptor hasn't become ready
+    /// since, the operation isn't tried at all. If the file descriptor can't be registered, that's
+    /// the error this returns.
+    pub fn poll_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        op: impl FnOnce() -> Result<R, std::io::Error>,
+    ) -> Poll<Result<R, std::io::Error>> {
+        if !super::might_be_ready(self.fd, self.interest) {
+            super::register_file_descriptor(cx, &self.fd, self.interest)?;
+            return Poll::Pending;
+        }
+        match op() {
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                // This is only ever ready if registering failed.
+                match self.poll_ready(cx) {
+                    Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                    _ => Poll::Pending,
+                }
+            }
+            result => Poll::Ready(result),
+        }
+    }
+
+    /// Stop waiting on the file descriptor
+    ///
+    /// Closing the file descriptor does this too, so this is only needed if the file descriptor is
+    /// going to keep being used for something else.
+    ///
+    /// Panics if there's no runtime currently executing, and no reactor entered.
+    pub fn deregister(self) -> Result<(), std::io::Error> {
+        super::deregister_file_descriptor(self.fd)
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        // Other tasks might still be waiting on the same file descriptor, so only stop waiting
+        // ourselves. Whoever closes it is the one who says it's gone.
+        super::unregister_file_descriptor(self.fd, self.interest);
+    }
+}
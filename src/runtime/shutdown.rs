@@ -0,0 +1,26 @@
+//! Giving things a chance to clean up before the runtime goes away
+
+use super::FutureId;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// How long shutdown hooks get, unless the builder says otherwise
+pub(super) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something registered with [`Handle::on_shutdown`](super::Handle::on_shutdown)
+pub(super) type ShutdownHook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>>>;
+
+/// Where the runtime is in shutting down
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum Shutdown {
+    /// Not shutting down, yet
+    Running,
+    /// Somebody asked for the runtime to shut down. Once the task that asked has been polled,
+    /// every task is dropped, and the hooks start.
+    Requested,
+    /// The hooks are running, along with a task that sleeps until their time is up
+    Draining(FutureId),
+    /// The hooks' time is up, so whatever's left gets dropped
+    TimedOut,
+}
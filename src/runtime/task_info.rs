@@ -0,0 +1,48 @@
+use crate::task::Id;
+use std::fmt::Display;
+use std::os::unix::prelude::RawFd;
+use std::panic::Location;
+
+/// What the runtime knows about one of its tasks, as reported by [`Handle::tasks`]
+///
+/// [`Handle::tasks`]: super::Handle::tasks
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct TaskInfo {
+    /// The task's ID
+    pub id: Id,
+    /// The task's name, if it was spawned with one (see [`task::Builder`](crate::task::Builder))
+    pub name: Option<String>,
+    /// Where in the source the task was spawned
+    pub location: &'static Location<'static>,
+    /// The task that spawned this one, if it was spawned from inside a task
+    pub parent: Option<Id>,
+    /// What the task is up to
+    pub state: TaskState,
+    /// The file descriptors that wake this task up when they're ready
+    ///
+    /// These are never taken back out, so a file descriptor that has been closed since (or even
+    /// reused for something else) still shows up here.
+    pub fds: Vec<RawFd>,
+}
+
+/// What a task is up to
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TaskState {
+    /// The task has been spawned, but hasn't been polled yet
+    New,
+    /// The task is being polled right now
+    Running,
+    /// The task has been polled, and is waiting to be woken up
+    Pending,
+}
+
+impl Display for TaskState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::New => write!(f, "new"),
+            Self::Running => write!(f, "running"),
+            Self::Pending => write!(f, "pending"),
+        }
+    }
+}
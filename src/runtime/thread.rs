@@ -0,0 +1,209 @@
+//! The threads the runtime starts, and the thread it runs on
+
+use super::RuntimeContext;
+use crate::trace::warn;
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// A callback for when a thread starts or stops
+pub(crate) type ThreadHook = Arc<dyn Fn() + Send + Sync>;
+
+/// How the runtime sets up the threads it starts for
+/// [`spawn_blocking`](crate::task::spawn_blocking)
+#[derive(Clone, Default)]
+pub(crate) struct ThreadConfig {
+    /// What to name each thread, instead of after where it was spawned from
+    pub(crate) name: Option<String>,
+    /// Which cores to keep each thread on
+    pub(crate) affinity: Option<Vec<usize>>,
+    /// Called on each thread before it does anything else
+    pub(crate) on_start: Option<ThreadHook>,
+    /// Called on each thread after it's done everything else
+    pub(crate) on_stop: Option<ThreadHook>,
+}
+
+impl ThreadConfig {
+    /// The current runtime's configuration, or the defaults outside of a runtime
+    pub(crate) fn current() -> Arc<Self> {
+        match RuntimeContext::try_current() {
+            Some(context) => context
+                .inner()
+                .try_borrow()
+                .expect("Expected to lock inner")
+                .thread_config
+                .clone(),
+            None => Default::default(),
+        }
+    }
+
+    /// Start a thread that runs `f`
+    pub(crate) fn spawn(
+        self: &Arc<Self>,
+        location: &'static Location<'static>,
+        f: impl FnOnce() + Send + 'static,
+    ) -> Result<std::thread::JoinHandle<()>, std::io::Error> {
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => format!("blocking {}", location),
+        };
+        let config = self.clone();
+        std::thread::Builder::new().name(name).spawn(move || {
+            if let Some(cores) = &config.affinity {
+                if let Err(error) = set_affinity(cores) {
+                    warn!(error = %error, "failed to set a blocking thread's CPU affinity");
+                }
+            }
+            if let Some(on_start) = &config.on_start {
+                on_start();
+            }
+            // Run the stop hook even if `f` panics, the same as the thread would be cleaned up
+            // either way.
+            let _stop = StopGuard(config.on_stop.clone());
+            f();
+        })
+    }
+}
+
+impl std::fmt::Debug for ThreadConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThreadConfig")
+            .field("name", &self.name)
+            .field("affinity", &self.affinity)
+            .field("on_start", &self.on_start.is_some())
+            .field("on_stop", &self.on_stop.is_some())
+            .finish()
+    }
+}
+
+/// The threads a runtime has started for [`spawn_blocking`](crate::task::spawn_blocking), so it
+/// can wait for them when it shuts down
+#[derive(Default)]
+pub(crate) struct BlockingThreads {
+    threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl BlockingThreads {
+    /// The current runtime's threads, or `None` outside of a runtime, where nobody's going to wait
+    /// for them
+    pub(crate) fn current() -> Option<Arc<Self>> {
+        RuntimeContext::try_current().map(|context| {
+            context
+                .inner()
+                .try_borrow()
+                .expect("Expected to lock inner")
+                .blocking_threads
+                .clone()
+        })
+    }
+
+    /// Keep track of a thread that's just been started
+    pub(crate) fn track(&self, thread: JoinHandle<()>) {
+        let mut threads = self.threads.lock().expect("Expected mutex to lock");
+        // Threads that have already finished don't need waiting for, and a long-running runtime
+        // would otherwise hang on to every thread it ever started.
+        threads.retain(|thread| !thread.is_finished());
+        threads.push(thread);
+    }
+
+    /// Wait for every thread that's been started to finish
+    ///
+    /// A blocking function that never returns keeps this from ever returning, too.
+    pub(crate) fn join_all(&self) {
+        let threads = std::mem::take(&mut *self.threads.lock().expect("Expected mutex to lock"));
+        for thread in threads {
+            // The panic has already been reported on the thread itself.
+            if thread.join().is_err() {
+                warn!("a blocking thread panicked");
+            }
+        }
+    }
+}
+
+/// Calls the stop hook when the thread's work is done
+pub(super) struct StopGuard(pub(super) Option<ThreadHook>);
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        if let Some(on_stop) = &self.0 {
+            on_stop();
+        }
+    }
+}
+
+/// Make sure every core in `cores` is one that `sched_setaffinity` can be told about
+pub(crate) fn check_cores(cores: &[usize]) -> Result<(), std::io::Error> {
+    let max = libc::CPU_SETSIZE as usize;
+    match cores.iter().find(|&&core| core >= max) {
+        Some(core) => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("core {core} is out of range (there can be at most {max})"),
+        )),
+        None if cores.is_empty() => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "a thread has to be allowed to run on at least one core",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Keep the calling thread on `cores`
+fn set_affinity(cores: &[usize]) -> Result<(), std::io::Error> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &core in cores {
+        unsafe { libc::CPU_SET(core, &mut set) };
+    }
+    set_affinity_mask(&set)
+}
+
+fn set_affinity_mask(set: &libc::cpu_set_t) -> Result<(), std::io::Error> {
+    let r = unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), set) };
+    if r < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Keeps the calling thread on some cores until it's dropped, and then lets it go back to wherever
+/// it was allowed to run before
+pub(crate) struct AffinityGuard {
+    previous: Option<libc::cpu_set_t>,
+}
+
+impl AffinityGuard {
+    /// Keep the calling thread on `cores`, if there are any
+    ///
+    /// Failing to isn't worth failing the runtime over, so it's only logged.
+    pub(crate) fn new(cores: Option<&[usize]>) -> Self {
+        let Some(cores) = cores else {
+            return Self { previous: None };
+        };
+
+        let mut previous: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        let r = unsafe {
+            libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut previous)
+        };
+        if r < 0 {
+            let error = std::io::Error::last_os_error();
+            warn!(error = %error, "failed to get the runtime thread's CPU affinity");
+            return Self { previous: None };
+        }
+        if let Err(error) = set_affinity(cores) {
+            warn!(error = %error, "failed to set the runtime thread's CPU affinity");
+            return Self { previous: None };
+        }
+        Self {
+            previous: Some(previous),
+        }
+    }
+}
+
+impl Drop for AffinityGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = &self.previous {
+            if let Err(error) = set_affinity_mask(previous) {
+                warn!(error = %error, "failed to restore the runtime thread's CPU affinity");
+            }
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Receiving Unix signals as _futures_
+//!
+//! Signals are delivered through a `signalfd`, which the runtime can wait on like any other file
+//! descriptor. For that to work, the signal has to be blocked, so that the kernel queues it up
+//! for the `signalfd` instead of interrupting the program; [`signal`] takes care of that.
+//!
+//! Blocking only applies to the current thread and the threads it creates afterwards, so create
+//! signal listeners early, before spawning any threads of your own (or blocking tasks).
+//!
+//! ```no_run
+//! use guillotine::signal::{signal, SignalKind};
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let mut hangups = signal(SignalKind::hangup()).unwrap();
+//!     loop {
+//!         hangups.recv().await.unwrap();
+//!         println!("Reloading configuration");
+//!     }
+//! });
+//! ```
+
+mod registry;
+
+use crate::io::poll_fd;
+use crate::runtime::Interest;
+use registry::Shared;
+use std::os::unix::prelude::{AsRawFd, OwnedFd, RawFd};
+use std::rc::Rc;
+use std::task::Poll;
+
+/// A kind of signal
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct SignalKind(libc::c_int);
+
+impl SignalKind {
+    /// Any signal, by its number
+    pub const fn from_raw(signum: libc::c_int) -> Self {
+        Self(signum)
+    }
+
+    /// The signal's number
+    pub const fn as_raw(&self) -> libc::c_int {
+        self.0
+    }
+
+    /// `SIGINT`, sent by Ctrl-C in a terminal
+    pub const fn interrupt() -> Self {
+        Self(libc::SIGINT)
+    }
+
+    /// `SIGTERM`, the polite way of asking a process to exit
+    pub const fn terminate() -> Self {
+        Self(libc::SIGTERM)
+    }
+
+    /// `SIGHUP`, when the terminal goes away; often used to ask a daemon to reload
+    pub const fn hangup() -> Self {
+        Self(libc::SIGHUP)
+    }
+
+    /// `SIGQUIT`, sent by Ctrl-\ in a terminal
+    pub const fn quit() -> Self {
+        Self(libc::SIGQUIT)
+    }
+
+    /// `SIGUSR1`, for whatever the program wants
+    pub const fn user_defined1() -> Self {
+        Self(libc::SIGUSR1)
+    }
+
+    /// `SIGUSR2`, for whatever the program wants
+    pub const fn user_defined2() -> Self {
+        Self(libc::SIGUSR2)
+    }
+
+    /// `SIGWINCH`, when the terminal changes size
+    pub const fn window_change() -> Self {
+        Self(libc::SIGWINCH)
+    }
+
+    /// `SIGCHLD`, when a child process exits or stops
+    pub const fn child() -> Self {
+        Self(libc::SIGCHLD)
+    }
+
+    /// `SIGPIPE`, when writing to a pipe or socket with no reader
+    pub const fn pipe() -> Self {
+        Self(libc::SIGPIPE)
+    }
+}
+
+/// Information about a received signal
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SignalInfo {
+    /// Which signal it was
+    pub kind: SignalKind,
+    /// The process that sent the signal, if it was sent by a process
+    pub pid: u32,
+    /// The real user ID of the process that sent it
+    pub uid: u32,
+}
+
+/// A stream of signals of one kind, created by [`signal`]
+#[derive(Debug)]
+pub struct Signal {
+    shared: Rc<Shared>,
+    /// This listener's duplicate of the shared `signalfd`
+    fd: OwnedFd,
+    id: u64,
+    kind: SignalKind,
+}
+
+/// Start listening for a kind of signal
+///
+/// This blocks the signal for the current thread (see the [module documentation](self)), so from
+/// here on, the signal's default action (like exiting, for `SIGINT`) doesn't happen.
+///
+/// Any number of `Signal`s can exist for the same kind, and every one of them receives every
+/// signal (from the moment it's created). Signals that a `Signal` hasn't gotten around to
+/// receiving yet are queued up, but only the most recent 128 are kept.
+///
+/// ```
+/// use guillotine::signal::{signal, SignalKind};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let mut config = signal(SignalKind::user_defined1()).unwrap();
+///     let mut logging = signal(SignalKind::user_defined1()).unwrap();
+///
+///     unsafe { libc::raise(libc::SIGUSR1) };
+///
+///     config.recv().await.unwrap();
+///     logging.recv().await.unwrap();
+/// });
+/// ```
+pub fn signal(kind: SignalKind) -> Result<Signal, std::io::Error> {
+    let shared = registry::shared(kind)?;
+    let (id, fd) = shared.add_listener()?;
+    Ok(Signal {
+        shared,
+        fd,
+        id,
+        kind,
+    })
+}
+
+/// Wait for Ctrl-C (`SIGINT`), as a _future_.
+///
+/// This is a shortcut for creating a [`signal`] listener for [`SignalKind::interrupt`] and waiting
+/// for the first signal. Once it's been called, Ctrl-C stops killing the program, so make sure
+/// whatever is waiting on it actually shuts down.
+///
+/// ```no_run
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let listener = std::net::TcpListener::bind("127.0.0.1:8080").unwrap();
+///     let listener = guillotine::net::TcpListener::new(listener).unwrap();
+///     guillotine::task::spawn(async move {
+///         loop {
+///             let (stream, _) = listener.accept().await.unwrap();
+///             // ...
+///             # drop(stream);
+///         }
+///     });
+///
+///     guillotine::signal::ctrl_c().await.unwrap();
+///     println!("Shutting down");
+/// });
+/// ```
+pub async fn ctrl_c() -> Result<(), std::io::Error> {
+    signal(SignalKind::interrupt())?.recv().await
+}
+
+impl Signal {
+    /// The kind of signal this is listening for
+    pub fn kind(&self) -> SignalKind {
+        self.kind
+    }
+
+    /// Wait for the next signal, as a _future_.
+    pub async fn recv(&mut self) -> Result<(), std::io::Error> {
+        self.recv_info().await.map(|_| ())
+    }
+
+    /// Wait for the next signal, as a _future_, along with who sent it
+    pub async fn recv_info(&mut self) -> Result<SignalInfo, std::io::Error> {
+        std::future::poll_fn(|cx| loop {
+            if let Some(info) = self.shared.pop(self.id, cx.waker()) {
+                return Poll::Ready(Ok(info));
+            }
+            match poll_fd(cx, self.fd.as_raw_fd(), Interest::READABLE, || {
+                self.shared.read_all()
+            }) {
+                // Something was read, so there's something in the queue now
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+impl Drop for Signal {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.fd.as_raw_fd());
+        self.shared.remove_listener(self.id);
+    }
+}
+
+impl AsRawFd for Signal {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
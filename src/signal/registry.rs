@@ -0,0 +1,169 @@
+//! The fan-out layer between each kind of signal's one `signalfd` and its `Signal`s
+//!
+//! A signal only comes out of a `signalfd` once, so if every `Signal` had its own, each signal
+//! would only go to whichever one happened to read it first. Instead, each kind of signal gets one
+//! shared `signalfd`, and whoever reads a signal from it copies it into every listener's queue.
+//!
+//! Reading only happens when a listener is being polled, so every listener also needs to be woken
+//! up when a signal arrives. Each one registers its own `dup` of the shared `signalfd` with epoll:
+//! the duplicates all refer to the same file, so they all become readable together, and each
+//! wakes up the task of the listener it belongs to.
+
+use super::{SignalInfo, SignalKind};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Error, ErrorKind};
+use std::mem::{size_of, MaybeUninit};
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd};
+use std::rc::{Rc, Weak};
+use std::task::Waker;
+
+/// The most signals a listener keeps queued up before it starts dropping the oldest ones
+const MAX_QUEUED: usize = 128;
+
+thread_local! {
+    /// The shared state for each kind of signal that has listeners on this thread
+    static REGISTRY: RefCell<HashMap<libc::c_int, Weak<Shared>>> = RefCell::new(HashMap::new());
+}
+
+/// Everything about one kind of signal
+#[derive(Debug)]
+pub(super) struct Shared {
+    fd: OwnedFd,
+    listeners: RefCell<HashMap<u64, Listener>>,
+    next_id: Cell<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Listener {
+    queue: VecDeque<SignalInfo>,
+    /// The waker from the last time the listener was polled and had nothing to return
+    waker: Option<Waker>,
+}
+
+/// Get the shared state for `kind`, creating (and blocking the signal) if this is the first
+/// listener on this thread
+pub(super) fn shared(kind: SignalKind) -> Result<Rc<Shared>, Error> {
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        if let Some(shared) = registry.get(&kind.0).and_then(Weak::upgrade) {
+            return Ok(shared);
+        }
+
+        let shared = Rc::new(Shared {
+            fd: open_signalfd(kind)?,
+            listeners: RefCell::new(HashMap::new()),
+            next_id: Cell::new(0),
+        });
+        registry.insert(kind.0, Rc::downgrade(&shared));
+        Ok(shared)
+    })
+}
+
+impl Shared {
+    /// Add a listener, returning its ID and the duplicate `signalfd` it should wait on
+    pub(super) fn add_listener(&self) -> Result<(u64, OwnedFd), Error> {
+        let fd = unsafe { libc::fcntl(self.fd.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.listeners.borrow_mut().insert(id, Listener::default());
+        Ok((id, unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+
+    pub(super) fn remove_listener(&self, id: u64) {
+        self.listeners.borrow_mut().remove(&id);
+    }
+
+    /// Take the next queued signal for a listener, or remember its waker if there isn't one
+    pub(super) fn pop(&self, id: u64, waker: &Waker) -> Option<SignalInfo> {
+        let mut listeners = self.listeners.borrow_mut();
+        let listener = listeners.get_mut(&id).expect("listener is registered");
+        let info = listener.queue.pop_front();
+        if info.is_none() {
+            listener.waker = Some(waker.clone());
+        }
+        info
+    }
+
+    /// Read every signal that's waiting in the `signalfd` and hand it to every listener
+    ///
+    /// Fails with `WouldBlock` if there weren't any.
+    pub(super) fn read_all(&self) -> Result<(), Error> {
+        let mut read_any = false;
+        loop {
+            match self.read_info() {
+                Ok(info) => {
+                    read_any = true;
+                    self.distribute(info);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock && read_any => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn distribute(&self, info: SignalInfo) {
+        let wakers: Vec<Waker> = {
+            let mut listeners = self.listeners.borrow_mut();
+            listeners
+                .values_mut()
+                .filter_map(|listener| {
+                    if listener.queue.len() == MAX_QUEUED {
+                        listener.queue.pop_front();
+                    }
+                    listener.queue.push_back(info);
+                    listener.waker.take()
+                })
+                .collect()
+        };
+        // Wake outside of the borrow, in case waking polls something that wants it
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Read one `signalfd_siginfo` from the file descriptor
+    fn read_info(&self) -> Result<SignalInfo, Error> {
+        unsafe {
+            let mut info: MaybeUninit<libc::signalfd_siginfo> = MaybeUninit::zeroed();
+            let r = libc::read(
+                self.fd.as_raw_fd(),
+                info.as_mut_ptr() as *mut libc::c_void,
+                size_of::<libc::signalfd_siginfo>(),
+            );
+            if r < 0 {
+                return Err(Error::last_os_error());
+            }
+            let info = info.assume_init();
+            Ok(SignalInfo {
+                kind: SignalKind(info.ssi_signo as libc::c_int),
+                pid: info.ssi_pid,
+                uid: info.ssi_uid,
+            })
+        }
+    }
+}
+
+/// Block `kind` for the current thread and create a `signalfd` for it
+fn open_signalfd(kind: SignalKind) -> Result<OwnedFd, Error> {
+    unsafe {
+        let mut set: MaybeUninit<libc::sigset_t> = MaybeUninit::uninit();
+        libc::sigemptyset(set.as_mut_ptr());
+        libc::sigaddset(set.as_mut_ptr(), kind.0);
+        let set = set.assume_init();
+
+        let r = libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+        if r != 0 {
+            return Err(Error::from_raw_os_error(r));
+        }
+
+        let fd = libc::signalfd(-1, &set, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC);
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(OwnedFd::from_raw_fd(fd))
+    }
+}
@@ -0,0 +1,465 @@
+//! A simulated network, for testing distributed systems deterministically
+//!
+//! A [`SimNetwork`] is a pretend network of hosts, each with its own IP address, that can talk to
+//! each other over simulated [TCP](TcpStream) and [UDP](UdpSocket). Nothing goes near the
+//! operating system: every byte that's "sent" is put on the network's queue, and shows up on the
+//! other end once the network's latency has passed. The network can also drop datagrams, and be
+//! split up into partitions that can't reach each other.
+//!
+//! The network keeps its own simulated time. It only moves forward when every task is waiting on
+//! the network (or on [`SimNetwork::sleep`]), and then it jumps straight to the next thing that's
+//! going to happen, so a test that simulates minutes of timeouts runs in no time at all. Given the
+//! same seed, a simulation makes the same choices every time it runs.
+//!
+//! Only available with the `sim` feature.
+//!
+//! ```
+//! use guillotine::io::{AsyncReadExt, AsyncWriteExt};
+//! use guillotine::sim::SimNetwork;
+//! use std::time::Duration;
+//!
+//! let net = SimNetwork::new(42);
+//! net.set_latency(Duration::from_millis(50));
+//!
+//! let server = net.host("10.0.0.1".parse().unwrap());
+//! let client = net.host("10.0.0.2".parse().unwrap());
+//!
+//! let sim = net.clone();
+//! net.block_on(async move {
+//!     let listener = server.bind_tcp(80).unwrap();
+//!     guillotine::task::spawn(async move {
+//!         let (mut stream, _) = listener.accept().await.unwrap();
+//!         let mut buf = [0_u8; 4];
+//!         stream.read_exact(&mut buf).await.unwrap();
+//!         stream.write_all(b"pong").await.unwrap();
+//!     });
+//!
+//!     let mut stream = client.connect_tcp("10.0.0.1:80".parse().unwrap()).await.unwrap();
+//!     stream.write_all(b"ping").await.unwrap();
+//!     let mut buf = [0_u8; 4];
+//!     stream.read_exact(&mut buf).await.unwrap();
+//!     assert_eq!(&buf, b"pong");
+//!
+//!     // A round trip to connect, and another for the ping and the pong
+//!     assert_eq!(sim.elapsed(), Duration::from_millis(200));
+//! });
+//! ```
+
+mod tcp;
+mod udp;
+
+pub use tcp::{TcpListener, TcpStream};
+pub use udp::UdpSocket;
+
+use crate::rng::Rng;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tcp::{Connecting, Pipe};
+use udp::Inbox;
+
+/// A simulated network of hosts
+///
+/// Cloning a `SimNetwork` gives another handle to the same network.
+#[derive(Clone)]
+pub struct SimNetwork {
+    state: Rc<RefCell<State>>,
+}
+
+/// Everything about the network, shared between all of its handles, hosts, and sockets
+struct State {
+    /// How much simulated time has passed since the network was created
+    now: Duration,
+    /// Decides which datagrams are lost
+    rng: Rng,
+    /// How long it takes anything to get from one host to another
+    latency: Duration,
+    /// The probability (from 0 to 1) that any particular datagram is dropped
+    loss: f64,
+    /// Pairs of hosts that can't reach each other, smallest address first
+    partitions: HashSet<(IpAddr, IpAddr)>,
+    /// Everything that's on its way somewhere, in the order it'll get there
+    in_flight: BTreeMap<(Duration, u64), Message>,
+    /// TCP messages that ran into a partition, waiting for it to be repaired
+    held: Vec<Message>,
+    /// Tasks sleeping until a simulated time
+    timers: BTreeMap<(Duration, u64), Waker>,
+    /// Tie-breaker for things scheduled for the same time, so they happen in the order they were
+    /// scheduled
+    sequence: u64,
+    /// The listening TCP sockets, by address
+    listeners: HashMap<SocketAddr, Rc<RefCell<tcp::Backlog>>>,
+    /// The bound UDP sockets, by address
+    udp: HashMap<SocketAddr, Rc<RefCell<Inbox>>>,
+    /// The next port to hand out when a socket doesn't ask for one
+    next_ephemeral_port: u16,
+}
+
+/// Something travelling across the network
+enum Message {
+    /// A TCP connection being opened
+    Syn {
+        from: SocketAddr,
+        to: SocketAddr,
+        connecting: Rc<RefCell<Connecting>>,
+    },
+    /// The answer to a [`Message::Syn`], on its way back
+    SynAck {
+        from: SocketAddr,
+        to: SocketAddr,
+        connecting: Rc<RefCell<Connecting>>,
+        accepted: bool,
+    },
+    /// Bytes written to a TCP stream, or the end of the stream if `data` is `None`
+    Segment {
+        from: SocketAddr,
+        to: SocketAddr,
+        pipe: Rc<RefCell<Pipe>>,
+        data: Option<Vec<u8>>,
+    },
+    /// A UDP datagram
+    Datagram {
+        from: SocketAddr,
+        to: SocketAddr,
+        data: Vec<u8>,
+    },
+}
+
+impl Message {
+    fn endpoints(&self) -> (SocketAddr, SocketAddr) {
+        match self {
+            Message::Syn { from, to, .. }
+            | Message::SynAck { from, to, .. }
+            | Message::Segment { from, to, .. }
+            | Message::Datagram { from, to, .. } => (*from, *to),
+        }
+    }
+}
+
+impl SimNetwork {
+    /// Create a new network with no latency, no loss, and no partitions
+    ///
+    /// The seed decides which datagrams get lost; the same seed loses the same datagrams.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(State {
+                now: Duration::ZERO,
+                rng: Rng::new(seed),
+                latency: Duration::ZERO,
+                loss: 0.0,
+                partitions: HashSet::new(),
+                in_flight: BTreeMap::new(),
+                held: Vec::new(),
+                timers: BTreeMap::new(),
+                sequence: 0,
+                listeners: HashMap::new(),
+                udp: HashMap::new(),
+                next_ephemeral_port: 49152,
+            })),
+        }
+    }
+
+    /// Set how long it takes anything sent to get where it's going
+    ///
+    /// This only affects things sent from now on.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.borrow_mut().latency = latency;
+    }
+
+    /// Set the probability (from 0 to 1) that a datagram is lost on the way
+    ///
+    /// TCP is reliable, so this only affects UDP.
+    pub fn set_packet_loss(&self, probability: f64) {
+        self.state.borrow_mut().loss = probability.clamp(0.0, 1.0);
+    }
+
+    /// Stop `a` and `b` from reaching each other, in either direction
+    ///
+    /// Datagrams between them are dropped. TCP connections don't break, but nothing gets through
+    /// until the partition is [repaired](Self::repair), just like a real TCP connection that keeps
+    /// retransmitting.
+    pub fn partition(&self, a: IpAddr, b: IpAddr) {
+        self.state.borrow_mut().partitions.insert(ordered(a, b));
+    }
+
+    /// Let `a` and `b` reach each other again
+    pub fn repair(&self, a: IpAddr, b: IpAddr) {
+        let mut state = self.state.borrow_mut();
+        state.partitions.remove(&ordered(a, b));
+
+        // Anything that was held up can carry on now, in the order it was sent.
+        let held = std::mem::take(&mut state.held);
+        for message in held {
+            state.send(message);
+        }
+    }
+
+    /// A host on the network, with the given address
+    ///
+    /// Hosts don't need to be created ahead of time; any address is a host as soon as something
+    /// uses it.
+    pub fn host(&self, ip: IpAddr) -> Host {
+        Host {
+            net: self.clone(),
+            ip,
+        }
+    }
+
+    /// How much simulated time has passed since the network was created
+    pub fn elapsed(&self) -> Duration {
+        self.state.borrow().now
+    }
+
+    /// Sleep for the provided amount of simulated time
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        let deadline = self.state.borrow().now + duration;
+        Sleep {
+            net: self.clone(),
+            deadline,
+            timer: None,
+        }
+    }
+
+    /// Run a future to completion on a new runtime, moving simulated time forward whenever all of
+    /// the runtime's tasks are waiting on it
+    ///
+    /// Panics if the tasks are stuck: nothing can happen, because nothing is in flight and nobody
+    /// is sleeping, but the future hasn't finished.
+    pub fn block_on<F>(&self, future: F) -> F::Output
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let runtime = crate::runtime::Runtime::new().expect("Expected to create a runtime");
+
+        let output = Rc::new(RefCell::new(None));
+        let slot = output.clone();
+        runtime.spawn(async move {
+            let result = future.await;
+            *slot.borrow_mut() = Some(result);
+        });
+
+        loop {
+            runtime.drive().expect("Expected to drive the runtime");
+            if let Some(output) = output.borrow_mut().take() {
+                return output;
+            }
+
+            if !self.advance() {
+                panic!("simulation is stuck: nothing is in flight and no task is sleeping");
+            }
+        }
+    }
+
+    /// Move simulated time forward to whatever happens next, and make it happen
+    ///
+    /// Returns false if nothing is ever going to happen.
+    fn advance(&self) -> bool {
+        let mut state = self.state.borrow_mut();
+        let next_message = state.in_flight.keys().next().map(|(at, _)| *at);
+        let next_timer = state.timers.keys().next().map(|(at, _)| *at);
+        let next = match (next_message, next_timer) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(a), None) | (None, Some(a)) => a,
+            (None, None) => return false,
+        };
+        state.now = state.now.max(next);
+
+        // Wake everything that was waiting for this moment. Tasks only get run once we've let go
+        // of the state, so this is safe to do while holding it.
+        while let Some(entry) = state.timers.first_entry() {
+            if entry.key().0 > next {
+                break;
+            }
+            entry.remove().wake();
+        }
+        while let Some(entry) = state.in_flight.first_entry() {
+            if entry.key().0 > next {
+                break;
+            }
+            let message = entry.remove();
+            state.deliver(message);
+        }
+
+        true
+    }
+}
+
+impl std::fmt::Debug for SimNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("SimNetwork")
+            .field("elapsed", &state.now)
+            .field("latency", &state.latency)
+            .field("packet_loss", &state.loss)
+            .field("in_flight", &state.in_flight.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl State {
+    /// Put a message on the network, to arrive after the latency
+    fn send(&mut self, message: Message) {
+        let at = self.now + self.latency;
+        let sequence = self.next_sequence();
+        self.in_flight.insert((at, sequence), message);
+    }
+
+    /// A message got where it was going
+    fn deliver(&mut self, message: Message) {
+        let (from, to) = message.endpoints();
+        if self.partitions.contains(&ordered(from.ip(), to.ip())) {
+            // Datagrams are just gone, but TCP keeps trying until the partition is repaired.
+            if !matches!(message, Message::Datagram { .. }) {
+                self.held.push(message);
+            }
+            return;
+        }
+
+        match message {
+            Message::Syn {
+                from,
+                to,
+                connecting,
+            } => {
+                let accepted = match self.listeners.get(&to) {
+                    Some(backlog) => {
+                        backlog.borrow_mut().push(from, connecting.clone());
+                        true
+                    }
+                    None => false,
+                };
+                self.send(Message::SynAck {
+                    from: to,
+                    to: from,
+                    connecting,
+                    accepted,
+                });
+            }
+            Message::SynAck {
+                connecting,
+                accepted,
+                ..
+            } => connecting.borrow_mut().finish(accepted),
+            Message::Segment { pipe, data, .. } => pipe.borrow_mut().deliver(data),
+            Message::Datagram { from, to, data } => {
+                let lost = self.rng.next_f64() < self.loss;
+                if let (false, Some(inbox)) = (lost, self.udp.get(&to)) {
+                    inbox.borrow_mut().deliver(from, data);
+                }
+            }
+        }
+    }
+
+    fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    /// A port nobody on `ip` is using
+    fn ephemeral_port(&mut self, ip: IpAddr) -> u16 {
+        loop {
+            let port = self.next_ephemeral_port;
+            self.next_ephemeral_port = self.next_ephemeral_port.checked_add(1).unwrap_or(49152);
+            let addr = SocketAddr::new(ip, port);
+            if !self.listeners.contains_key(&addr) && !self.udp.contains_key(&addr) {
+                return port;
+            }
+        }
+    }
+}
+
+/// A pair of addresses in a consistent order, so that partitions work in both directions
+fn ordered(a: IpAddr, b: IpAddr) -> (IpAddr, IpAddr) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// One host on a [`SimNetwork`], for opening sockets from
+#[derive(Clone, Debug)]
+pub struct Host {
+    net: SimNetwork,
+    ip: IpAddr,
+}
+
+impl Host {
+    /// The host's address
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    /// Listen for TCP connections on a port
+    ///
+    /// Fails with `AddrInUse` if something is already listening there.
+    pub fn bind_tcp(&self, port: u16) -> Result<TcpListener, std::io::Error> {
+        TcpListener::bind(self.net.clone(), SocketAddr::new(self.ip, port))
+    }
+
+    /// Open a TCP connection to another host, as a _future_.
+    ///
+    /// Fails with `ConnectionRefused` if nothing is listening there.
+    pub async fn connect_tcp(&self, addr: SocketAddr) -> Result<TcpStream, std::io::Error> {
+        TcpStream::connect(self.net.clone(), self.ip, addr).await
+    }
+
+    /// Bind a UDP socket to a port, or to any free port if `port` is 0
+    ///
+    /// Fails with `AddrInUse` if something is already bound there.
+    pub fn bind_udp(&self, port: u16) -> Result<UdpSocket, std::io::Error> {
+        UdpSocket::bind(self.net.clone(), self.ip, port)
+    }
+}
+
+/// The future returned from [`SimNetwork::sleep`]
+pub struct Sleep {
+    net: SimNetwork,
+    deadline: Duration,
+    /// Where our waker is in the network's timers, once we've put it there
+    timer: Option<(Duration, u64)>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.net.state.borrow_mut();
+        if state.now >= this.deadline {
+            return Poll::Ready(());
+        }
+
+        let timer = match this.timer {
+            Some(timer) => timer,
+            None => (this.deadline, state.next_sequence()),
+        };
+        this.timer = Some(timer);
+        state.timers.insert(timer, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        // A sleep that's given up on (because it lost a race with something else, say) shouldn't
+        // hold simulated time up.
+        if let Some(timer) = self.timer {
+            self.net.state.borrow_mut().timers.remove(&timer);
+        }
+    }
+}
+
+impl std::fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sleep")
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
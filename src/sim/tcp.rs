@@ -0,0 +1,314 @@
+use super::{Message, SimNetwork};
+use crate::io::{AsyncRead, AsyncWrite};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+/// One direction of a connection: the bytes that have arrived, and whoever's waiting for more
+#[derive(Default)]
+pub(super) struct Pipe {
+    buf: VecDeque<u8>,
+    /// Whether the end of the stream has arrived
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Pipe {
+    /// Some bytes (or the end of the stream) arrived
+    pub(super) fn deliver(&mut self, data: Option<Vec<u8>>) {
+        match data {
+            Some(data) => self.buf.extend(data),
+            None => self.closed = true,
+        }
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A connection on its way to being opened
+#[derive(Default)]
+pub(super) struct Connecting {
+    /// Client to server
+    upstream: Rc<RefCell<Pipe>>,
+    /// Server to client
+    downstream: Rc<RefCell<Pipe>>,
+    /// Whether the server accepted the connection, once the answer gets back
+    accepted: Option<bool>,
+    waker: Option<Waker>,
+}
+
+impl Connecting {
+    /// The answer got back to the client
+    pub(super) fn finish(&mut self, accepted: bool) {
+        self.accepted = Some(accepted);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A listener's queue of connections that haven't been accepted yet
+#[derive(Default)]
+pub(super) struct Backlog {
+    /// Each connection's peer address, and the pipes it'll use
+    queue: VecDeque<(SocketAddr, Rc<RefCell<Connecting>>)>,
+    waker: Option<Waker>,
+}
+
+impl Backlog {
+    /// A new connection arrived
+    pub(super) fn push(&mut self, from: SocketAddr, connecting: Rc<RefCell<Connecting>>) {
+        self.queue.push_back((from, connecting));
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A simulated TCP listener, from [`Host::bind_tcp`](super::Host::bind_tcp)
+pub struct TcpListener {
+    net: SimNetwork,
+    addr: SocketAddr,
+    backlog: Rc<RefCell<Backlog>>,
+}
+
+impl TcpListener {
+    pub(super) fn bind(net: SimNetwork, addr: SocketAddr) -> Result<Self, std::io::Error> {
+        let backlog = Rc::new(RefCell::new(Backlog::default()));
+        {
+            let mut state = net.state.borrow_mut();
+            if state.listeners.contains_key(&addr) {
+                return Err(ErrorKind::AddrInUse.into());
+            }
+            state.listeners.insert(addr, backlog.clone());
+        }
+
+        Ok(Self { net, addr, backlog })
+    }
+
+    /// The address the listener is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Wait until a new connection is available and accept that connection
+    pub async fn accept(&self) -> Result<(TcpStream, SocketAddr), std::io::Error> {
+        std::future::poll_fn(|cx| {
+            let mut backlog = self.backlog.borrow_mut();
+            match backlog.queue.pop_front() {
+                Some((peer, connecting)) => {
+                    Poll::Ready(Ok((self.server_side(peer, &connecting), peer)))
+                }
+                None => {
+                    backlog.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// The server's end of a connection
+    fn server_side(&self, peer: SocketAddr, connecting: &RefCell<Connecting>) -> TcpStream {
+        let connecting = connecting.borrow();
+        TcpStream::new(
+            self.net.clone(),
+            self.addr,
+            peer,
+            connecting.upstream.clone(),
+            connecting.downstream.clone(),
+        )
+    }
+}
+
+impl Drop for TcpListener {
+    fn drop(&mut self) {
+        self.net.state.borrow_mut().listeners.remove(&self.addr);
+
+        // Connections that were never accepted get closed, so their clients don't wait forever.
+        let queue = std::mem::take(&mut self.backlog.borrow_mut().queue);
+        for (peer, connecting) in queue {
+            drop(self.server_side(peer, &connecting));
+        }
+    }
+}
+
+impl std::fmt::Debug for TcpListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TcpListener")
+            .field("addr", &self.addr)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A simulated TCP stream, from [`Host::connect_tcp`](super::Host::connect_tcp) or
+/// [`TcpListener::accept`]
+///
+/// Reads and writes go through the [`AsyncRead`] and [`AsyncWrite`] traits. Writes never wait:
+/// the other end's buffer is as big as it needs to be.
+pub struct TcpStream {
+    net: SimNetwork,
+    local: SocketAddr,
+    peer: SocketAddr,
+    read: Rc<RefCell<Pipe>>,
+    write: Rc<RefCell<Pipe>>,
+    /// Whether we've sent the end of the stream
+    write_closed: bool,
+}
+
+impl TcpStream {
+    fn new(
+        net: SimNetwork,
+        local: SocketAddr,
+        peer: SocketAddr,
+        read: Rc<RefCell<Pipe>>,
+        write: Rc<RefCell<Pipe>>,
+    ) -> Self {
+        Self {
+            net,
+            local,
+            peer,
+            read,
+            write,
+            write_closed: false,
+        }
+    }
+
+    pub(super) async fn connect(
+        net: SimNetwork,
+        ip: IpAddr,
+        addr: SocketAddr,
+    ) -> Result<Self, std::io::Error> {
+        let connecting = Rc::new(RefCell::new(Connecting::default()));
+        let local = {
+            let mut state = net.state.borrow_mut();
+            let local = SocketAddr::new(ip, state.ephemeral_port(ip));
+            state.send(Message::Syn {
+                from: local,
+                to: addr,
+                connecting: connecting.clone(),
+            });
+            local
+        };
+
+        let accepted = std::future::poll_fn(|cx| {
+            let mut connecting = connecting.borrow_mut();
+            match connecting.accepted {
+                Some(accepted) => Poll::Ready(accepted),
+                None => {
+                    connecting.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await;
+        if !accepted {
+            return Err(ErrorKind::ConnectionRefused.into());
+        }
+
+        let connecting = connecting.borrow();
+        Ok(Self::new(
+            net,
+            local,
+            addr,
+            connecting.downstream.clone(),
+            connecting.upstream.clone(),
+        ))
+    }
+
+    /// The address of this end of the connection
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// The address of the other end of the connection
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Send the end of the stream, if we haven't already
+    fn close(&mut self) {
+        if !self.write_closed {
+            self.write_closed = true;
+            self.net.state.borrow_mut().send(Message::Segment {
+                from: self.local,
+                to: self.peer,
+                pipe: self.write.clone(),
+                data: None,
+            });
+        }
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let mut pipe = self.read.borrow_mut();
+        if pipe.buf.is_empty() && !buf.is_empty() {
+            if pipe.closed {
+                return Poll::Ready(Ok(0));
+            }
+            pipe.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let len = pipe.buf.len().min(buf.len());
+        for (to, from) in buf.iter_mut().zip(pipe.buf.drain(..len)) {
+            *to = from;
+        }
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        if self.write_closed {
+            return Poll::Ready(Err(ErrorKind::BrokenPipe.into()));
+        }
+
+        self.net.state.borrow_mut().send(Message::Segment {
+            from: self.local,
+            to: self.peer,
+            pipe: self.write.clone(),
+            data: Some(buf.to_vec()),
+        });
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // Writes go straight onto the network; there's nothing to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        self.get_mut().close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl std::fmt::Debug for TcpStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TcpStream")
+            .field("local", &self.local)
+            .field("peer", &self.peer)
+            .finish_non_exhaustive()
+    }
+}
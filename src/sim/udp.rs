@@ -0,0 +1,112 @@
+use super::{Message, SimNetwork};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+/// The datagrams that have arrived at a socket, and whoever's waiting for more
+#[derive(Default)]
+pub(super) struct Inbox {
+    queue: VecDeque<(SocketAddr, Vec<u8>)>,
+    waker: Option<Waker>,
+}
+
+impl Inbox {
+    /// A datagram arrived
+    pub(super) fn deliver(&mut self, from: SocketAddr, data: Vec<u8>) {
+        self.queue.push_back((from, data));
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A simulated UDP socket, from [`Host::bind_udp`](super::Host::bind_udp)
+pub struct UdpSocket {
+    net: SimNetwork,
+    addr: SocketAddr,
+    inbox: Rc<RefCell<Inbox>>,
+}
+
+impl UdpSocket {
+    pub(super) fn bind(net: SimNetwork, ip: IpAddr, port: u16) -> Result<Self, std::io::Error> {
+        let inbox = Rc::new(RefCell::new(Inbox::default()));
+        let addr = {
+            let mut state = net.state.borrow_mut();
+            let port = match port {
+                0 => state.ephemeral_port(ip),
+                port => port,
+            };
+            let addr = SocketAddr::new(ip, port);
+            if state.udp.contains_key(&addr) {
+                return Err(ErrorKind::AddrInUse.into());
+            }
+            state.udp.insert(addr, inbox.clone());
+            addr
+        };
+
+        Ok(Self { net, addr, inbox })
+    }
+
+    /// The address the socket is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Receive a datagram, as a _future_.
+    ///
+    /// Like a real UDP socket, if the datagram is bigger than `buf`, the rest of it is lost.
+    pub async fn recv(&self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        let (len, _) = self.recv_from(buf).await?;
+        Ok(len)
+    }
+
+    /// Receive a datagram and the address it came from, as a _future_.
+    ///
+    /// Like a real UDP socket, if the datagram is bigger than `buf`, the rest of it is lost.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), std::io::Error> {
+        std::future::poll_fn(|cx| {
+            let mut inbox = self.inbox.borrow_mut();
+            match inbox.queue.pop_front() {
+                Some((from, data)) => {
+                    let len = data.len().min(buf.len());
+                    buf[..len].copy_from_slice(&data[..len]);
+                    Poll::Ready(Ok((len, from)))
+                }
+                None => {
+                    inbox.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Send a datagram to `addr`, as a _future_.
+    ///
+    /// This always succeeds right away, whether or not the datagram ever gets there.
+    pub async fn send_to(&self, buf: &[u8], addr: SocketAddr) -> Result<usize, std::io::Error> {
+        self.net.state.borrow_mut().send(Message::Datagram {
+            from: self.addr,
+            to: addr,
+            data: buf.to_vec(),
+        });
+        Ok(buf.len())
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        self.net.state.borrow_mut().udp.remove(&self.addr);
+    }
+}
+
+impl std::fmt::Debug for UdpSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UdpSocket")
+            .field("addr", &self.addr)
+            .finish_non_exhaustive()
+    }
+}
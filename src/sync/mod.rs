@@ -0,0 +1,4 @@
+//! Passing values between tasks
+
+pub mod mpsc;
+pub mod oneshot;
@@ -0,0 +1,453 @@
+//! Channels for sending a stream of values to a task
+//!
+//! `std::sync::mpsc` doesn't know anything about wakers, so a task waiting on one of its receivers
+//! either blocks the whole runtime or never gets polled again. These channels keep hold of the
+//! waiting task's waker, and wake it when there's something for it to do.
+//!
+//! Senders can be used from any thread (a [`spawn_blocking`](crate::task::spawn_blocking)
+//! function reporting progress, say), as long as the values can be sent between threads.
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Create a channel that holds up to `capacity` values that haven't been received yet
+///
+/// Once it's full, [`Sender::send`] waits for the receiver to make room, which keeps a fast
+/// producer from getting too far ahead of a slow consumer.
+///
+/// Panics if `capacity` is zero.
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (tx, mut rx) = guillotine::sync::mpsc::channel(2);
+///
+///     guillotine::task::spawn(async move {
+///         for i in 0..5 {
+///             tx.send(i).await.unwrap();
+///         }
+///     });
+///
+///     let mut received = Vec::new();
+///     while let Some(i) = rx.recv().await {
+///         received.push(i);
+///     }
+///     assert_eq!(received, [0, 1, 2, 3, 4]);
+/// });
+/// ```
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "a channel needs room for at least one value");
+    let chan = Chan::new(Some(capacity));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// Create a channel with no limit on how many values it holds
+///
+/// Sending never waits, so it works from outside of the runtime too. Nothing stops a fast producer
+/// from filling up memory, though.
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (tx, mut rx) = guillotine::sync::mpsc::unbounded_channel();
+///
+///     guillotine::task::spawn_blocking(move || {
+///         tx.send("from another thread").unwrap();
+///     });
+///
+///     assert_eq!(rx.recv().await, Some("from another thread"));
+///     assert_eq!(rx.recv().await, None);
+/// });
+/// ```
+pub fn unbounded_channel<T>() -> (UnboundedSender<T>, Receiver<T>) {
+    let chan = Chan::new(None);
+    (UnboundedSender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// What the senders and the receiver share
+struct Chan<T> {
+    state: Mutex<State<T>>,
+}
+
+struct State<T> {
+    /// Values that have been sent but not received
+    queue: VecDeque<T>,
+    /// How many values `queue` can hold, if there's a limit
+    capacity: Option<usize>,
+    /// How many senders are left. Once there are none, the receiver gets `None` after the queue
+    /// runs dry.
+    senders: usize,
+    /// Whether the receiver has been dropped (or closed the channel), so sending is pointless
+    closed: bool,
+    /// The task waiting to receive, if there is one
+    receiver: Option<Waker>,
+    /// The tasks waiting for room to send
+    senders_waiting: Vec<Waker>,
+}
+
+impl<T> Chan<T> {
+    fn new(capacity: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                capacity,
+                senders: 1,
+                closed: false,
+                receiver: None,
+                senders_waiting: Vec::new(),
+            }),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State<T>> {
+        self.state.lock().expect("Expected mutex to lock")
+    }
+
+    /// Queue up a value if there's room, waking the receiver
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let receiver = {
+            let mut state = self.lock();
+            if state.closed {
+                return Err(TrySendError::Closed(value));
+            }
+            if state
+                .capacity
+                .is_some_and(|capacity| state.queue.len() >= capacity)
+            {
+                return Err(TrySendError::Full(value));
+            }
+            state.queue.push_back(value);
+            state.receiver.take()
+        };
+        // Wake outside the lock, in case the waker wants to come right back for it.
+        if let Some(receiver) = receiver {
+            receiver.wake();
+        }
+        Ok(())
+    }
+
+    fn add_sender(&self) {
+        self.lock().senders += 1;
+    }
+
+    fn drop_sender(&self) {
+        let receiver = {
+            let mut state = self.lock();
+            state.senders -= 1;
+            if state.senders > 0 {
+                return;
+            }
+            state.receiver.take()
+        };
+        // The receiver needs to find out there's nothing more coming.
+        if let Some(receiver) = receiver {
+            receiver.wake();
+        }
+    }
+}
+
+/// The sending half of a [`channel`]
+///
+/// Clone it to have more than one task sending. The receiver finds out the channel is done once
+/// every clone is dropped.
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Send a value, waiting for room in the channel if it's full, as a _future_.
+    ///
+    /// If the receiver has been dropped, the value is handed back in the error.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut value = Some(value);
+        poll_fn(|cx| {
+            let sent = value.take().expect("Expected a value to send");
+            match self.chan.try_send(sent) {
+                Ok(()) => Poll::Ready(Ok(())),
+                Err(TrySendError::Closed(sent)) => Poll::Ready(Err(SendError(sent))),
+                Err(TrySendError::Full(sent)) => {
+                    value = Some(sent);
+                    let mut state = self.chan.lock();
+                    // The receiver might have made room (or gone away) since `try_send` looked.
+                    if state.closed
+                        || state
+                            .capacity
+                            .is_some_and(|capacity| state.queue.len() < capacity)
+                    {
+                        cx.waker().wake_by_ref();
+                    } else if !state
+                        .senders_waiting
+                        .iter()
+                        .any(|waker| waker.will_wake(cx.waker()))
+                    {
+                        state.senders_waiting.push(cx.waker().clone());
+                    }
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Send a value if there's room for it right now, without waiting
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.chan.try_send(value)
+    }
+
+    /// Whether the receiver has been dropped, or has closed the channel
+    pub fn is_closed(&self) -> bool {
+        self.chan.lock().closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.add_sender();
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.chan.drop_sender();
+    }
+}
+
+impl<T> std::fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+/// The sending half of an [`unbounded_channel`]
+///
+/// Clone it to have more than one task (or thread) sending. The receiver finds out the channel is
+/// done once every clone is dropped.
+pub struct UnboundedSender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> UnboundedSender<T> {
+    /// Send a value
+    ///
+    /// This never waits. If the receiver has been dropped, the value is handed back in the error.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.chan.try_send(value).map_err(|error| match error {
+            TrySendError::Closed(value) | TrySendError::Full(value) => SendError(value),
+        })
+    }
+
+    /// Whether the receiver has been dropped, or has closed the channel
+    pub fn is_closed(&self) -> bool {
+        self.chan.lock().closed
+    }
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        self.chan.add_sender();
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for UnboundedSender<T> {
+    fn drop(&mut self) {
+        self.chan.drop_sender();
+    }
+}
+
+impl<T> std::fmt::Debug for UnboundedSender<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UnboundedSender").finish_non_exhaustive()
+    }
+}
+
+/// The receiving half of a [`channel`] or an [`unbounded_channel`]
+///
+/// Values come out of [`recv`](Self::recv), or out of the receiver as a
+/// [`Stream`](futures_core::Stream). Dropping it closes the channel, so senders stop waiting.
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receive the next value, as a _future_.
+    ///
+    /// This resolves to `None` once every sender has been dropped and every value they sent has
+    /// been received.
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Receive the next value, or arrange for the current task to be woken up when there is one
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        match self.try_recv() {
+            Ok(value) => Poll::Ready(Some(value)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => {
+                let mut state = self.chan.lock();
+                // A sender might have sent something (or gone away) since `try_recv` looked.
+                if !state.queue.is_empty() || state.closed || state.senders == 0 {
+                    cx.waker().wake_by_ref();
+                } else {
+                    state.receiver = Some(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Receive the next value if there is one, without waiting
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let (value, senders_waiting) = {
+            let mut state = self.chan.lock();
+            match state.queue.pop_front() {
+                // There's room now, so let every waiting sender have a go at it.
+                Some(value) => (value, std::mem::take(&mut state.senders_waiting)),
+                None if state.closed || state.senders == 0 => {
+                    return Err(TryRecvError::Disconnected)
+                }
+                None => return Err(TryRecvError::Empty),
+            }
+        };
+        for waker in senders_waiting {
+            waker.wake();
+        }
+        Ok(value)
+    }
+
+    /// Close the channel, so nothing else can be sent, without dropping the receiver
+    ///
+    /// Values that were already sent can still be received, and after that, there's nothing more
+    /// coming.
+    ///
+    /// ```
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let (tx, mut rx) = guillotine::sync::mpsc::channel(4);
+    ///     tx.send(1).await.unwrap();
+    ///     rx.close();
+    ///     assert!(tx.send(2).await.is_err());
+    ///
+    ///     assert_eq!(rx.recv().await, Some(1));
+    ///     assert_eq!(rx.recv().await, None);
+    /// });
+    /// ```
+    pub fn close(&mut self) {
+        let senders_waiting = {
+            let mut state = self.chan.lock();
+            state.closed = true;
+            std::mem::take(&mut state.senders_waiting)
+        };
+        for waker in senders_waiting {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+        // Drop whatever never got received now, rather than whenever the last sender goes away.
+        let unreceived = std::mem::take(&mut self.chan.lock().queue);
+        drop(unreceived);
+    }
+}
+
+impl<T> futures_core::Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+impl<T> std::fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+/// The error from sending on a channel whose receiver has been dropped
+///
+/// The value that couldn't be sent is handed back.
+pub struct SendError<T>(pub T);
+
+impl<T> std::fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> Display for SendError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the channel is closed")
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+/// The error from [`Sender::try_send`], with the value that couldn't be sent
+pub enum TrySendError<T> {
+    /// The channel has no room right now
+    Full(T),
+    /// The receiver has been dropped
+    Closed(T),
+}
+
+impl<T> TrySendError<T> {
+    /// Get back the value that couldn't be sent
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(value) | TrySendError::Closed(value) => value,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.debug_tuple("Full").finish_non_exhaustive(),
+            TrySendError::Closed(_) => f.debug_tuple("Closed").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl<T> Display for TrySendError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "the channel is full"),
+            TrySendError::Closed(_) => write!(f, "the channel is closed"),
+        }
+    }
+}
+
+impl<T> std::error::Error for TrySendError<T> {}
+
+/// The error from [`Receiver::try_recv`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// Nothing has been sent, but more might be
+    Empty,
+    /// Nothing has been sent, and every sender has been dropped
+    Disconnected,
+}
+
+impl Display for TryRecvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "the channel is empty"),
+            TryRecvError::Disconnected => write!(f, "the channel is empty and closed"),
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
@@ -0,0 +1,209 @@
+//! A channel for sending a single value to a task
+//!
+//! This is the channel for handing back a result: spawn something with the sender, and await the
+//! receiver. Like [`mpsc`](super::mpsc), the sender can be used from any thread.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Create a channel for sending a single value
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let (tx, rx) = guillotine::sync::oneshot::channel();
+///
+///     guillotine::task::spawn(async move {
+///         tx.send(42).unwrap();
+///     });
+///
+///     assert_eq!(rx.await.unwrap(), 42);
+/// });
+/// ```
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Mutex::new(State {
+        value: None,
+        sender_dropped: false,
+        closed: false,
+        receiver: None,
+    }));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+struct State<T> {
+    /// The value, once it's been sent and until it's received
+    value: Option<T>,
+    /// Whether the sender is gone, with or without sending anything
+    sender_dropped: bool,
+    /// Whether the receiver has been dropped (or closed the channel), so sending is pointless
+    closed: bool,
+    /// The task waiting to receive, if there is one
+    receiver: Option<Waker>,
+}
+
+fn lock<T>(chan: &Mutex<State<T>>) -> std::sync::MutexGuard<'_, State<T>> {
+    chan.lock().expect("Expected mutex to lock")
+}
+
+/// The sending half of a [`channel`]
+pub struct Sender<T> {
+    chan: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Send the value, waking up the receiver
+    ///
+    /// If the receiver has been dropped, the value is handed back instead.
+    pub fn send(self, value: T) -> Result<(), T> {
+        let receiver = {
+            let mut state = lock(&self.chan);
+            if state.closed {
+                return Err(value);
+            }
+            state.value = Some(value);
+            state.receiver.take()
+        };
+        // The `Drop` that comes next finds the waker already taken, so this is the only wakeup.
+        if let Some(receiver) = receiver {
+            receiver.wake();
+        }
+        Ok(())
+    }
+
+    /// Whether the receiver has been dropped, or has closed the channel
+    pub fn is_closed(&self) -> bool {
+        lock(&self.chan).closed
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let receiver = {
+            let mut state = lock(&self.chan);
+            state.sender_dropped = true;
+            state.receiver.take()
+        };
+        // The receiver needs to find out there's nothing coming.
+        if let Some(receiver) = receiver {
+            receiver.wake();
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+/// The receiving half of a [`channel`]
+///
+/// Await it for the value. If the sender is dropped without sending anything, it resolves to
+/// [`RecvError`] instead.
+pub struct Receiver<T> {
+    chan: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Take the value if it's been sent, without waiting
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut state = lock(&self.chan);
+        match state.value.take() {
+            Some(value) => Ok(value),
+            None if state.sender_dropped || state.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Close the channel, so the sender can't send, without dropping the receiver
+    ///
+    /// A value that was already sent can still be received. If there isn't one, there never will
+    /// be.
+    ///
+    /// ```
+    /// let runtime = guillotine::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let (tx, mut rx) = guillotine::sync::oneshot::channel::<u32>();
+    ///     rx.close();
+    ///     assert!(tx.send(42).is_err());
+    ///     assert!(rx.await.is_err());
+    /// });
+    /// ```
+    pub fn close(&mut self) {
+        lock(&self.chan).closed = true;
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = lock(&self.chan);
+        if let Some(value) = state.value.take() {
+            return Poll::Ready(Ok(value));
+        }
+        // Once the channel's closed, nothing else is coming either.
+        if state.sender_dropped || state.closed {
+            return Poll::Ready(Err(RecvError));
+        }
+        // Whichever task polled most recently is the one to wake, wherever the receiver has been
+        // moved to since.
+        state.receiver = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        // Drop a value that never got received now, rather than whenever the sender goes away.
+        let unreceived = {
+            let mut state = lock(&self.chan);
+            state.closed = true;
+            state.value.take()
+        };
+        drop(unreceived);
+    }
+}
+
+impl<T> std::fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+/// The error from a [`Receiver`] whose sender was dropped without sending anything
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+impl Display for RecvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the sender was dropped without sending anything")
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// The error from [`Receiver::try_recv`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// Nothing has been sent yet
+    Empty,
+    /// Nothing was sent, and the sender has been dropped
+    Disconnected,
+}
+
+impl Display for TryRecvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "nothing has been sent yet"),
+            TryRecvError::Disconnected => {
+                write!(f, "the sender was dropped without sending anything")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
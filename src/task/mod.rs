@@ -1,147 +1,350 @@
 //! Spawning tasks separate from the primary future
 
-use pin_project::pin_project;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::fmt::Display;
 use std::future::Future;
 use std::pin::Pin;
-use std::task::{Context, Poll, Waker};
+use std::task::{Context, Poll};
+
+/// A task's ID, unique within its runtime
+///
+/// This is what shows up as `task.id` in the runtime's tracing spans and in
+/// [task dumps](crate::runtime::Handle::dump).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id(u64);
+
+impl Id {
+    pub(crate) fn from_u64(id: u64) -> Self {
+        Self(id)
+    }
+
+    /// The ID as a plain number
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Display for Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// The ID of the task that is currently executing
+///
+/// Panics if there is no runtime currently executing
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let parent = guillotine::task::id();
+///     let child = guillotine::task::spawn(async { guillotine::task::id() })
+///         .await
+///         .unwrap();
+///     assert_ne!(parent, child);
+/// });
+/// ```
+pub fn id() -> Id {
+    crate::runtime::RuntimeContext::current()
+        .future_id()
+        .task_id()
+}
 
 /// Spawn a new future onto the currently executing runtime
 ///
 /// Panics if there is no runtime currently executing
+#[track_caller]
 pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
 where
     F: Future + 'static,
     F::Output: 'static,
 {
-    // Get access to the currently executing runtime, or panic if one isn't running.
-    let context = crate::runtime::RuntimeContext::current();
-
-    // When the *spawned* future is completed, the JoinHandle that is returned from this function
-    // will need to be polled. To do that, we will need to wake up the future that the JoinHandle is
-    // in, which is the *current* future. So get the waker for the current future.
-    let waker = context.waker().clone();
-
-    // And with that waker, create the JoinHandle and the "completer", or the thing that will
-    // trigger the JoinHandle when the spawned future is done.
-    let (handle, completer) = join_handle_pair(waker);
-
-    // Ah, but we're not actually going to spawn the provided future as is. Let's create a new
-    // future that waits for the provided future, and then hits the "completer" to tell the
-    // JoinHandle the the provided future is done.
-    let wrapped_future = async move {
-        let result = future.await;
-        completer.complete(result)
-    };
-
-    // And then add that new wrapped future to the runtime, so it can start executing it when it
-    // gets the chance.
-    context.spawn(wrapped_future);
+    Builder::new().spawn(future)
+}
 
-    // And finally, hand the JoinHandle back to current future so it can wait for completion if it
-    // wants.
-    handle
+/// Spawn a task with some extra configuration
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let handle = guillotine::task::Builder::new()
+///         .name("answerer")
+///         .spawn(async { 42 });
+///     assert_eq!(handle.await.unwrap(), 42);
+/// });
+/// ```
+#[derive(Debug, Default)]
+pub struct Builder {
+    name: Option<String>,
+}
+
+impl Builder {
+    /// Create a new builder, with nothing configured
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give the task a name, which shows up in [task dumps](crate::runtime::Handle::dump)
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Spawn a new future onto the currently executing runtime
+    ///
+    /// Panics if there is no runtime currently executing
+    #[track_caller]
+    pub fn spawn<F>(self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        // Get access to the currently executing runtime, or panic if one isn't running.
+        let context = crate::runtime::RuntimeContext::current();
+
+        // Add the future to the runtime, so it can start executing it when it gets the chance, and
+        // hand the JoinHandle back to current future so it can wait for completion if it wants.
+        context.spawn(future, self.name)
+    }
 }
 
 /// Spawn a blocking function onto a new thread and provides a join handle to wait for its
 /// completion
 ///
-/// Panics if there is no runtime currently executing
+/// The thread is named after where it was spawned from, so that's what shows up in a panic
+/// message (or in `top` or a debugger). Inside a runtime, the runtime's
+/// [`Builder`](crate::runtime::Builder) can give it a different name, keep it on particular cores,
+/// and run hooks when it starts and stops. The runtime waits for the thread to finish before it
+/// shuts down, even if nobody's waiting on the handle.
+///
+/// There's no stopping a function once it's running, so [`JoinHandle::abort`] does nothing here.
+/// If the function panics, the handle resolves to [`JoinError::Panic`].
+///
+/// Panics if the thread can't be started; use [`try_spawn_blocking`] to handle that instead.
+#[track_caller]
 pub fn spawn_blocking<Fn, O>(f: Fn) -> JoinHandle<O>
 where
     Fn: FnOnce() -> O,
     Fn: Send + 'static,
     O: Send + 'static,
 {
-    // Get access to the currently executing runtime, or panic if one isn't running.
-    let context = crate::runtime::RuntimeContext::current();
-
-    // When the *spawned* future is completed, the JoinHandle that is returned from this function
-    // will need to be polled. To do that, we will need to wake up the future that the JoinHandle is
-    // in, which is the *current* future. So get the waker for the current future.
-    let waker = context.waker().clone();
-
-    // And with that waker, create the JoinHandle and the "completer", or the thing that will
-    // trigger the JoinHandle when the spawned future is done.
-    let (handle, completer) = join_handle_pair(waker);
+    match try_spawn_blocking(f) {
+        Ok(handle) => handle,
+        Err(error) => panic!("failed to spawn a blocking thread: {error}"),
+    }
+}
 
-    // Ah, but we're not actually going to spawn the provided function as is. Let's create a new
-    // function that waits for the provided function, and then hits the "completer" to tell the
-    // JoinHandle the the provided function is done.
-    let wrapped_function = move || {
-        let result = f();
-        completer.complete(result)
-    };
+/// Like [`spawn_blocking`], but returns an error if the thread can't be started (say, because the
+/// process has hit its limit on threads), instead of panicking
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let handle = guillotine::task::try_spawn_blocking(|| 42).unwrap();
+///     assert_eq!(handle.await.unwrap(), 42);
+/// });
+/// ```
+#[track_caller]
+pub fn try_spawn_blocking<Fn, O>(f: Fn) -> Result<JoinHandle<O>, std::io::Error>
+where
+    Fn: FnOnce() -> O,
+    Fn: Send + 'static,
+    O: Send + 'static,
+{
+    let location = std::panic::Location::caller();
 
-    // And then spawn that new wrapped function in a new thread.
-    std::thread::spawn(wrapped_function);
+    // The function gets wrapped up as a task that finishes the first time it's run. A panic is
+    // caught on the thread and handed to whatever awaits the handle, rather than looking like the
+    // task was cancelled.
+    let future = crate::runtime::CatchPanic::new(async move {
+        #[cfg(feature = "metrics")]
+        let _blocking_thread = BlockingThreadGauge::new();
+        f()
+    });
+    // The future never waits on anything, so it's never woken, and never needs scheduling: it's
+    // run once, on a new thread, right here.
+    let (runnable, task) = async_task::spawn(future, |_| {
+        unreachable!("blocking tasks never wait, so they're never woken")
+    });
+    let config = crate::runtime::ThreadConfig::current();
+    // If the thread can't be started, the runnable is dropped along with the closure, which
+    // cancels the task, and there's no handle for anybody to wait on.
+    let thread = config.spawn(location, move || {
+        runnable.run();
+    })?;
+    // The runtime waits for the thread when it shuts down.
+    if let Some(threads) = crate::runtime::BlockingThreads::current() {
+        threads.track(thread);
+    }
 
     // And finally, hand the JoinHandle back to current future so it can wait for completion if it
     // wants.
-    handle
-}
-
-/// Create a new JoinHandle and a "completer", the thing that will trigger the JoinHandle when the
-/// spawned future is done.
-pub(crate) fn join_handle_pair<T>(waker: Waker) -> (JoinHandle<T>, JoinHandleCompleter<T>) {
-    let (tx, rx) = std::sync::mpsc::sync_channel(1);
-    (JoinHandle { rx }, JoinHandleCompleter { tx, waker })
-}
-
-/// The thing that will trigger the JoinHandle when the future is done.
-pub(crate) struct JoinHandleCompleter<T> {
-    /// The channel is a place to hold the spawned future's return value until the JoinHandle has a
-    /// chance to retrieve it
-    tx: std::sync::mpsc::SyncSender<T>,
-    /// The waker that can be used to wake up the original future when the JoinHandle is ready.
-    waker: Waker,
-}
-
-impl<T> JoinHandleCompleter<T> {
-    /// Indicate that the spawned future is complete, and the JoinHandle can finish.
-    pub fn complete(self, t: T) {
-        // Put the spawned future's return value in the channel to indicate that the spawned future
-        // has been completed
-        match self.tx.send(t) {
-            Ok(()) => {
-                // And wake up the original future so that the JoinHandle can be polled
-                self.waker.wake()
-            }
-            Err(_) => {
-                // The JoinHandle disconnected, which means it was dropped. The original future
-                // does not care that the spawned future completed, so it doesn't need to be woken
-                // up.
-            }
-        }
+    Ok(JoinHandle::new(task, None))
+}
+
+/// Keeps the `guillotine_blocking_threads` gauge up to date for as long as it's alive, even if the
+/// blocking function panics
+#[cfg(feature = "metrics")]
+struct BlockingThreadGauge;
+
+#[cfg(feature = "metrics")]
+impl BlockingThreadGauge {
+    fn new() -> Self {
+        metrics::gauge!("guillotine_blocking_threads").increment(1.0);
+        Self
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for BlockingThreadGauge {
+    fn drop(&mut self) {
+        metrics::gauge!("guillotine_blocking_threads").decrement(1.0);
     }
 }
 
 /// The handle returned from a [`spawn`]
 ///
-/// This handle can be awaited and will resolve when the spawned future has completed.
-#[pin_project]
+/// This handle can be awaited and will resolve when the spawned future has completed. Dropping it
+/// doesn't stop the spawned future; it just means nobody is waiting for it. To stop it, use
+/// [`abort`](Self::abort).
+///
+/// ```
+/// use guillotine::task::JoinError;
+/// use std::time::Duration;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let handle = guillotine::task::spawn(async {
+///         loop {
+///             guillotine::time::sleep(Duration::from_secs(1)).await.unwrap();
+///         }
+///     });
+///     assert!(!handle.is_finished());
+///
+///     handle.abort();
+///     assert!(matches!(handle.await, Err(JoinError::Cancelled)));
+/// });
+/// ```
 pub struct JoinHandle<T> {
-    /// The other half of the channel.
+    /// The task, as `async-task` hands it to us
     ///
-    /// If this has something in it, then the spawned future completed and put its result here. If
-    /// it doesn't, then the spawned future is still running.
-    rx: std::sync::mpsc::Receiver<T>,
+    /// This is only ever `None` after `Drop` has detached it.
+    task: Option<async_task::FallibleTask<Result<T, JoinError>>>,
+    /// How to abort the task, unless it's one that can't be
+    abort: Option<crate::runtime::Abort>,
 }
 
-impl<T> Future for JoinHandle<T> {
-    type Output = T;
-
-    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let projected = self.project();
-        match projected.rx.try_recv() {
-            Err(_) => {
-                // Nothing in the channel. The spawned future is still running. Continue to wait.
-                Poll::Pending
-            }
-            Ok(t) => {
-                // We got the spawned future's result from the channel. That means the spawned
-                // future is done, so so is this JoinHandle.
-                Poll::Ready(t)
+impl<T> JoinHandle<T> {
+    pub(crate) fn new(
+        task: async_task::Task<Result<T, JoinError>>,
+        abort: Option<crate::runtime::Abort>,
+    ) -> Self {
+        Self {
+            task: Some(task.fallible()),
+            abort,
+        }
+    }
+
+    /// Stop the spawned future
+    ///
+    /// The future is dropped the next time the runtime gets to it, without being polled again, and
+    /// the handle resolves to [`JoinError::Cancelled`]. A future that's already finished isn't
+    /// affected, and neither is a [`spawn_blocking`] function.
+    ///
+    /// This can be called from any thread.
+    pub fn abort(&self) {
+        if let Some(abort) = &self.abort {
+            if !self.is_finished() {
+                abort.abort();
             }
         }
     }
+
+    /// Whether the spawned future has finished, or been dropped
+    pub fn is_finished(&self) -> bool {
+        self.task.as_ref().is_none_or(|task| task.is_finished())
+    }
 }
+
+impl<T> std::fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JoinHandle").finish_non_exhaustive()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The task keeps hold of our waker, and wakes it up when the spawned future finishes (or
+        // panics). If the future was dropped instead, there's no output.
+        let task = self.task.as_mut().expect("Expected a task");
+        Pin::new(task)
+            .poll(cx)
+            .map(|output| output.unwrap_or(Err(JoinError::Cancelled)))
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        // Dropping an `async_task::Task` would cancel the spawned future, but that's not how
+        // spawned futures work here: they run to completion whether anybody's waiting or not.
+        if let Some(task) = self.task.take() {
+            task.detach();
+        }
+    }
+}
+
+/// Why a [`JoinHandle`] didn't get its future's output
+///
+/// ```
+/// use guillotine::task::JoinError;
+///
+/// let runtime = guillotine::runtime::Builder::new()
+///     .capture_panic_backtraces(true)
+///     .build()
+///     .unwrap();
+/// runtime.block_on(async {
+///     let handle = guillotine::task::spawn(async { panic!("oh no") });
+///     match handle.await {
+///         Err(JoinError::Panic { payload, backtrace }) => {
+///             assert_eq!(payload.downcast_ref::<&str>(), Some(&"oh no"));
+///             assert!(backtrace.is_some());
+///         }
+///         other => panic!("expected a panic, got {other:?}"),
+///     }
+///
+///     // The runtime carries on regardless
+///     assert_eq!(guillotine::task::spawn(async { 42 }).await.unwrap(), 42);
+/// });
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JoinError {
+    /// The future was dropped before it finished, because the handle was
+    /// [aborted](JoinHandle::abort) or the runtime [shut down](crate::runtime::Handle::shutdown)
+    Cancelled,
+    /// The future (or [blocking function](spawn_blocking)) panicked
+    ///
+    /// The panic ends the task, not the runtime. Pass the payload to
+    /// [`std::panic::resume_unwind`] to carry on panicking.
+    Panic {
+        /// What the panic was started with: usually a `&'static str` or a `String`
+        payload: Box<dyn Any + Send>,
+        /// Where the panic happened, if the runtime was
+        /// [asked to capture it](crate::runtime::Builder::capture_panic_backtraces)
+        backtrace: Option<Backtrace>,
+    },
+}
+
+impl Display for JoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinError::Cancelled => write!(f, "the task was cancelled"),
+            JoinError::Panic { .. } => write!(f, "the task panicked"),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
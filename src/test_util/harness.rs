@@ -0,0 +1,154 @@
+//! Running a test's future on a runtime of its own
+
+use crate::runtime::{Handle, TaskInfo};
+use crate::time::Sleep;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+
+/// Runs a test's future on a fresh runtime, and fails the test if it hangs or leaks tasks
+///
+/// * If the future hasn't finished after the [timeout](Self::timeout) (measured in real time, even
+///   if time is paused), the test fails with a list of every task that's still around, and what
+///   they're waiting on.
+/// * If the future finishes but tasks it spawned haven't, the test fails with a list of those
+///   tasks. A test that wants to leave something running in the background should wait for it
+///   instead.
+///
+/// [`test`] is the same as `Harness::new().run(future)`.
+///
+/// ```
+/// use guillotine::test_util::Harness;
+/// use std::time::Duration;
+///
+/// Harness::new()
+///     .timeout(Duration::from_secs(5))
+///     .start_paused(true)
+///     .run(async {
+///         // Paused time means this doesn't take an hour.
+///         guillotine::time::sleep(Duration::from_secs(3600)).await.unwrap();
+///     });
+/// ```
+///
+/// ```should_panic
+/// // Panics with something like
+/// //   the test finished with 1 task(s) still pending:
+/// //     task 1 "forgotten" spawned at src/main.rs:6:10, pending, fds [6]
+/// guillotine::test_util::test(async {
+///     guillotine::task::Builder::new()
+///         .name("forgotten")
+///         .spawn(guillotine::time::sleep(std::time::Duration::from_secs(60)));
+///     guillotine::task::spawn(async {}).await.unwrap();
+/// });
+/// ```
+#[derive(Debug)]
+pub struct Harness {
+    timeout: Duration,
+    start_paused: bool,
+}
+
+impl Harness {
+    /// A harness with a 30 second timeout, and time running normally
+    pub fn new() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            start_paused: false,
+        }
+    }
+
+    /// Fail the test if it hasn't finished after `timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Start the runtime with [time paused](crate::time::pause)
+    pub fn start_paused(mut self, paused: bool) -> Self {
+        self.start_paused = paused;
+        self
+    }
+
+    /// Run the future to completion, or fail the test trying
+    #[track_caller]
+    pub fn run<F>(self, future: F) -> F::Output
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let runtime = crate::runtime::Builder::new()
+            .start_paused(self.start_paused)
+            .build()
+            .expect("Expected to create a runtime");
+        let timeout = self.timeout;
+
+        runtime.block_on(async move {
+            let mut watchdog =
+                Sleep::wall_clock(timeout).expect("Expected to create the watchdog timer");
+            let mut future = std::pin::pin!(future);
+
+            let output = std::future::poll_fn(|cx| {
+                if let Poll::Ready(result) = Pin::new(&mut watchdog).poll(cx) {
+                    result.expect("Expected the watchdog timer to work");
+                    panic!(
+                        "test timed out after {timeout:?}; the test's future and {}",
+                        describe(&other_tasks())
+                    );
+                }
+                future.as_mut().poll(cx)
+            })
+            .await;
+
+            let leaked = other_tasks();
+            if !leaked.is_empty() {
+                panic!("the test finished with {}", describe(&leaked));
+            }
+            output
+        })
+    }
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run a test's future on a fresh runtime, failing the test if it takes more than 30 seconds or
+/// leaves tasks behind
+///
+/// See [`Harness`] for the details, and for changing the timeout.
+#[track_caller]
+pub fn test<F>(future: F) -> F::Output
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    Harness::new().run(future)
+}
+
+/// Every task on the runtime other than the one running the test's future
+fn other_tasks() -> Vec<TaskInfo> {
+    let current = crate::task::id();
+    Handle::current()
+        .tasks()
+        .into_iter()
+        .filter(|task| task.id != current)
+        .collect()
+}
+
+/// A list of tasks, for a panic message
+fn describe(tasks: &[TaskInfo]) -> String {
+    let mut description = format!("{} task(s) still pending:", tasks.len());
+    for task in tasks {
+        description.push_str(&format!("\n  task {}", task.id));
+        if let Some(name) = &task.name {
+            description.push_str(&format!(" {name:?}"));
+        }
+        description.push_str(&format!(
+            " spawned at {}, {}, fds {:?}",
+            task.location, task.state, task.fds
+        ));
+    }
+    description
+}
@@ -0,0 +1,173 @@
+//! A scripted I/O object
+//!
+//! ```
+//! use guillotine::io::{AsyncReadExt, AsyncWriteExt};
+//! use guillotine::test_util::mock::Builder;
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let mut mock = Builder::new()
+//!         .write(b"PING\r\n")
+//!         .read(b"+PONG\r\n")
+//!         .build();
+//!
+//!     mock.write_all(b"PING\r\n").await.unwrap();
+//!     let mut buf = [0_u8; 7];
+//!     mock.read_exact(&mut buf).await.unwrap();
+//!     assert_eq!(&buf, b"+PONG\r\n");
+//! });
+//! ```
+
+use crate::io::{AsyncRead, AsyncWrite};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// One step of the script
+#[derive(Debug)]
+enum Action {
+    /// The next reads get these bytes
+    Read(Vec<u8>),
+    /// The next writes have to be these bytes
+    Write(Vec<u8>),
+    /// The next read fails
+    ReadError(std::io::Error),
+    /// The next write fails
+    WriteError(std::io::Error),
+}
+
+/// Builds a [`Mock`] by listing what it should expect, in order
+#[derive(Debug, Default)]
+pub struct Builder {
+    actions: VecDeque<Action>,
+}
+
+impl Builder {
+    /// Start an empty script
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect the code under test to read, and give it `data`
+    ///
+    /// The data may be read in pieces, over several reads.
+    pub fn read(&mut self, data: &[u8]) -> &mut Self {
+        self.actions.push_back(Action::Read(data.to_vec()));
+        self
+    }
+
+    /// Expect the code under test to write exactly `data`
+    ///
+    /// The data may be written in pieces, over several writes. Writing anything else panics.
+    pub fn write(&mut self, data: &[u8]) -> &mut Self {
+        self.actions.push_back(Action::Write(data.to_vec()));
+        self
+    }
+
+    /// Expect the code under test to read, and fail that read with `error`
+    pub fn read_error(&mut self, error: std::io::Error) -> &mut Self {
+        self.actions.push_back(Action::ReadError(error));
+        self
+    }
+
+    /// Expect the code under test to write, and fail that write with `error`
+    pub fn write_error(&mut self, error: std::io::Error) -> &mut Self {
+        self.actions.push_back(Action::WriteError(error));
+        self
+    }
+
+    /// Create the mock, taking the script out of the builder
+    pub fn build(&mut self) -> Mock {
+        Mock {
+            actions: std::mem::take(&mut self.actions),
+        }
+    }
+}
+
+/// A scripted I/O object, created by [`Builder`]
+///
+/// Once the script is finished, reads return the end of the stream and writes panic. Dropping the
+/// mock before the script is finished panics too, so a test can't pass by accident without doing
+/// everything it was supposed to.
+#[derive(Debug)]
+pub struct Mock {
+    actions: VecDeque<Action>,
+}
+
+impl AsyncRead for Mock {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        match this.actions.front_mut() {
+            None => Poll::Ready(Ok(0)),
+            Some(Action::Read(data)) => {
+                let len = data.len().min(buf.len());
+                buf[..len].copy_from_slice(&data[..len]);
+                data.drain(..len);
+                if data.is_empty() {
+                    this.actions.pop_front();
+                }
+                Poll::Ready(Ok(len))
+            }
+            Some(Action::ReadError(_)) => match this.actions.pop_front() {
+                Some(Action::ReadError(err)) => Poll::Ready(Err(err)),
+                _ => unreachable!(),
+            },
+            Some(action) => panic!("unexpected read; expected {action:?}"),
+        }
+    }
+}
+
+impl AsyncWrite for Mock {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        match this.actions.front_mut() {
+            None => panic!("unexpected write of {buf:?}; the script is finished"),
+            Some(Action::Write(expected)) => {
+                let len = expected.len().min(buf.len());
+                assert_eq!(
+                    &buf[..len],
+                    &expected[..len],
+                    "wrote something other than what was expected"
+                );
+                expected.drain(..len);
+                if expected.is_empty() {
+                    this.actions.pop_front();
+                }
+                Poll::Ready(Ok(len))
+            }
+            Some(Action::WriteError(_)) => match this.actions.pop_front() {
+                Some(Action::WriteError(err)) => Poll::Ready(Err(err)),
+                _ => unreachable!(),
+            },
+            Some(action) => panic!("unexpected write of {buf:?}; expected {action:?}"),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for Mock {
+    fn drop(&mut self) {
+        // Don't turn one panic into two
+        if std::thread::panicking() {
+            return;
+        }
+        if let Some(action) = self.actions.front() {
+            panic!("mock dropped before the script was finished; still expecting {action:?}");
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Utilities for testing futures and the code built on them
+//!
+//! * [`spawn`] wraps a future so that it can be polled by hand, one step at a time, and keeps track
+//!   of whether it has been woken up.
+//! * [`assert_ready!`](crate::assert_ready) and friends check what a poll returned.
+//! * [`mock::Builder`] scripts an I/O object: it expects a particular sequence of reads and writes,
+//!   and panics if the code under test does anything else.
+//! * [`test`] and [`Harness`] run a test's future on a fresh runtime, and fail the test if it
+//!   hangs or leaves tasks behind.
+//!
+//! This module needs the `test-util` feature, which is meant to be enabled from
+//! `[dev-dependencies]`.
+//!
+//! ```
+//! use guillotine::{assert_pending, assert_ready};
+//! use guillotine::test_util::spawn;
+//!
+//! let mut ready = false;
+//! let mut task = spawn(std::future::poll_fn(move |_cx| {
+//!     if ready {
+//!         std::task::Poll::Ready(42)
+//!     } else {
+//!         ready = true;
+//!         std::task::Poll::Pending
+//!     }
+//! }));
+//!
+//! assert_pending!(task.poll());
+//! assert_eq!(assert_ready!(task.poll()), 42);
+//! ```
+
+mod harness;
+pub mod mock;
+
+pub use harness::{test, Harness};
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+/// Assert that a `Poll` is `Ready`, and evaluate to the value inside it
+#[macro_export]
+macro_rules! assert_ready {
+    ($e:expr) => {
+        match $e {
+            ::std::task::Poll::Ready(value) => value,
+            ::std::task::Poll::Pending => panic!("expected Ready, got Pending"),
+        }
+    };
+    ($e:expr, $($msg:tt)+) => {
+        match $e {
+            ::std::task::Poll::Ready(value) => value,
+            ::std::task::Poll::Pending => {
+                panic!("expected Ready, got Pending: {}", format_args!($($msg)+))
+            }
+        }
+    };
+}
+
+/// Assert that a `Poll` is `Pending`
+#[macro_export]
+macro_rules! assert_pending {
+    ($e:expr) => {
+        match $e {
+            ::std::task::Poll::Pending => {}
+            ::std::task::Poll::Ready(value) => panic!("expected Pending, got Ready({:?})", value),
+        }
+    };
+    ($e:expr, $($msg:tt)+) => {
+        match $e {
+            ::std::task::Poll::Pending => {}
+            ::std::task::Poll::Ready(value) => {
+                panic!(
+                    "expected Pending, got Ready({:?}): {}",
+                    value,
+                    format_args!($($msg)+)
+                )
+            }
+        }
+    };
+}
+
+/// Assert that a `Poll` is `Ready(Ok(_))`, and evaluate to the value inside it
+#[macro_export]
+macro_rules! assert_ready_ok {
+    ($e:expr) => {
+        match $crate::assert_ready!($e) {
+            Ok(value) => value,
+            Err(err) => panic!("expected Ready(Ok(_)), got Ready(Err({:?}))", err),
+        }
+    };
+}
+
+/// Assert that a `Poll` is `Ready(Err(_))`, and evaluate to the error inside it
+#[macro_export]
+macro_rules! assert_ready_err {
+    ($e:expr) => {
+        match $crate::assert_ready!($e) {
+            Err(err) => err,
+            Ok(value) => panic!("expected Ready(Err(_)), got Ready(Ok({:?}))", value),
+        }
+    };
+}
+
+/// Wrap a future so that it can be polled by hand
+///
+/// The future doesn't need a runtime to be polled this way, but leaf futures from this crate
+/// (sockets, timers) do, since they register themselves with the runtime when they'd block. Use
+/// this for futures built out of your own state machines, channels, and mocks.
+pub fn spawn<F: Future>(future: F) -> Spawn<F> {
+    Spawn {
+        future: Box::pin(future),
+        waker: Arc::new(CountingWaker::default()),
+    }
+}
+
+/// A future being polled by hand, created by [`spawn`]
+pub struct Spawn<F> {
+    future: Pin<Box<F>>,
+    waker: Arc<CountingWaker>,
+}
+
+impl<F: Future> Spawn<F> {
+    /// Poll the future once
+    pub fn poll(&mut self) -> Poll<F::Output> {
+        self.waker.woken.store(0, Ordering::SeqCst);
+        let waker = Waker::from(self.waker.clone());
+        let mut cx = Context::from_waker(&waker);
+        self.future.as_mut().poll(&mut cx)
+    }
+}
+
+impl<F> Spawn<F> {
+    /// Whether the future has been woken up since it was last polled
+    pub fn is_woken(&self) -> bool {
+        self.waker.woken.load(Ordering::SeqCst) > 0
+    }
+
+    /// How many times the future has been woken up since it was last polled
+    pub fn wake_count(&self) -> usize {
+        self.waker.woken.load(Ordering::SeqCst)
+    }
+
+    /// How many copies of the waker are being held on to, besides ours
+    ///
+    /// A future that returns `Pending` without holding on to the waker will never be woken up.
+    pub fn waker_ref_count(&self) -> usize {
+        Arc::strong_count(&self.waker) - 1
+    }
+
+    /// Get mutable access to the future
+    pub fn future_mut(&mut self) -> Pin<&mut F> {
+        self.future.as_mut()
+    }
+}
+
+impl<F> std::fmt::Debug for Spawn<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Spawn")
+            .field("wake_count", &self.wake_count())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A waker that counts how many times it has been woken
+#[derive(Debug, Default)]
+struct CountingWaker {
+    woken: AtomicUsize,
+}
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.woken.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.fetch_add(1, Ordering::SeqCst);
+    }
+}
@@ -34,29 +34,92 @@
 //! runtime.block_on(future);
 //! ```
 
-use crate::runtime::RuntimeContext;
+#[cfg(feature = "test-util")]
+mod paused;
+mod timeout;
+
+#[cfg(feature = "test-util")]
+pub(crate) use paused::Clock;
+pub use timeout::{timeout, Elapsed, Timeout};
+
+use crate::io::poll_fd;
+use crate::runtime::Interest;
 use libc::c_int;
-use pin_project::pin_project;
 use std::{
     future::Future,
-    io::{Error, ErrorKind},
+    io::Error,
     mem::MaybeUninit,
     os::unix::prelude::AsRawFd,
+    pin::Pin,
+    task::{Context, Poll},
     time::Duration,
 };
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum RegisteredState {
-    Unregistered,
-    Registered,
-}
-
 /// Sleep for the provided amount of time
+///
+/// Sleeping for no time at all still waits for the runtime to get back around to the task, but
+/// no longer than that.
+///
+/// ```
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     guillotine::time::sleep(std::time::Duration::ZERO).await.unwrap();
+/// });
+/// ```
 pub async fn sleep(duration: Duration) -> Result<(), std::io::Error> {
     let sleep = Sleep::new(duration)?;
     sleep.await
 }
 
+/// Pause time on the currently executing runtime
+///
+/// From then on, new sleeps and intervals don't wait for real time to pass. They wait for the
+/// runtime's clock, which only moves when [`advance`] moves it, or when every task is waiting and
+/// the runtime skips straight ahead to the next deadline. So a test full of long timeouts finishes
+/// right away, and finishes the same way every time.
+///
+/// Panics if there is no runtime currently executing.
+///
+/// Only available with the `test-util` feature.
+///
+/// ```
+/// use std::time::{Duration, Instant};
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// let start = Instant::now();
+/// runtime.block_on(async {
+///     guillotine::time::pause();
+///     let mut interval = guillotine::time::interval(Duration::from_secs(60)).unwrap();
+///     for _ in 0..60 {
+///         interval.tick().await.unwrap();
+///     }
+/// });
+/// assert!(start.elapsed() < Duration::from_secs(1));
+/// ```
+#[cfg(feature = "test-util")]
+pub fn pause() {
+    let context = crate::runtime::RuntimeContext::current();
+    let mut inner = context
+        .inner()
+        .try_borrow_mut()
+        .expect("Expected to lock inner");
+    inner.pause();
+}
+
+/// Move paused time forward, waking everything that was sleeping until then
+///
+/// The tasks that were woken up run the next time the current task waits on something.
+///
+/// Panics if there is no runtime currently executing, or if time isn't [paused](pause).
+///
+/// Only available with the `test-util` feature.
+#[cfg(feature = "test-util")]
+pub fn advance(duration: Duration) {
+    Clock::current()
+        .expect("Time isn't paused")
+        .advance(duration);
+}
+
 /// A struct that provides ergonomic access to a `timerfd` file descriptor
 struct TimerFd {
     fd: c_int,
@@ -67,6 +130,10 @@ impl TimerFd {
     ///
     /// Roughly equivalent to calling `timerfd_create` and then `timerfd_settime`.
     fn new(interval: Duration, value: Duration) -> Result<Self, std::io::Error> {
+        // A value of zero disarms the timer instead of firing it right away, and then it never
+        // fires at all. The smallest time that isn't zero has already passed by the time anybody
+        // looks.
+        let value = value.max(Duration::from_nanos(1));
         unsafe {
             let fd = libc::timerfd_create(libc::CLOCK_MONOTONIC, libc::TFD_NONBLOCK);
             if fd < 0 {
@@ -120,6 +187,7 @@ impl AsRawFd for TimerFd {
 
 impl Drop for TimerFd {
     fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.fd);
         unsafe {
             libc::close(self.fd);
         }
@@ -127,21 +195,36 @@ impl Drop for TimerFd {
 }
 
 /// The future that runs [`sleep`]
-#[pin_project]
-struct Sleep {
+pub(crate) struct Sleep {
+    inner: SleepInner,
+}
+
+enum SleepInner {
     /// The timer file descriptor that has been set up for this sleep
-    timer: TimerFd,
-    /// Whether or not the file descriptor has been registered with epoll
-    state: RegisteredState,
+    Timer(TimerFd),
+    /// Waiting on the runtime's clock, because time is paused
+    #[cfg(feature = "test-util")]
+    Paused(paused::Sleep),
 }
 
 impl Sleep {
     /// Create a new Sleep
-    fn new(duration: Duration) -> Result<Self, std::io::Error> {
+    pub(crate) fn new(duration: Duration) -> Result<Self, std::io::Error> {
+        #[cfg(feature = "test-util")]
+        if let Some(clock) = Clock::current() {
+            return Ok(Sleep {
+                inner: SleepInner::Paused(paused::Sleep::new(clock, duration)),
+            });
+        }
+
+        Self::wall_clock(duration)
+    }
+
+    /// Create a new Sleep that waits for real time to pass, even if time is paused
+    pub(crate) fn wall_clock(duration: Duration) -> Result<Self, std::io::Error> {
         let timer = TimerFd::new(Duration::ZERO, duration)?;
         Ok(Sleep {
-            timer,
-            state: RegisteredState::Unregistered,
+            inner: SleepInner::Timer(timer),
         })
     }
 }
@@ -149,29 +232,11 @@ impl Sleep {
 impl Future for Sleep {
     type Output = Result<(), std::io::Error>;
 
-    fn poll(
-        self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Self::Output> {
-        let projected = self.project();
-
-        // Call read on the file descriptor. Since this is a non-blocking file descriptor, this
-        // should return immediately.
-        let result = projected.timer.read();
-        match result {
-            // Success!
-            Ok(_) => std::task::Poll::Ready(Ok(())),
-            Err(err) if err.kind() == ErrorKind::WouldBlock => {
-                // Not ready yet. If we haven't registered the file descriptor with the runtime,
-                // do it now.
-                if *projected.state == RegisteredState::Unregistered {
-                    let context = RuntimeContext::current();
-                    context.register_file_descriptor(projected.timer);
-                    *projected.state = RegisteredState::Registered;
-                }
-                std::task::Poll::Pending
-            }
-            Err(err) => std::task::Poll::Ready(Err(err)),
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.get_mut().inner {
+            SleepInner::Timer(timer) => poll_timer(cx, timer).map_ok(|_| ()),
+            #[cfg(feature = "test-util")]
+            SleepInner::Paused(sleep) => Pin::new(sleep).poll(cx).map(Ok),
         }
     }
 }
@@ -184,16 +249,32 @@ pub fn interval(period: Duration) -> Result<Interval, std::io::Error> {
 
 /// An interval that yields a value on a fixed period
 pub struct Interval {
+    inner: IntervalInner,
+}
+
+enum IntervalInner {
     /// The internal timerfd file descriptor that was set up for this interval
-    timer: TimerFd,
+    Timer(TimerFd),
+    /// Waiting on the runtime's clock, because time is paused
+    #[cfg(feature = "test-util")]
+    Paused(paused::Interval),
 }
 
 impl Interval {
     /// Create a new interval that will wait the provided duration before firing, and then will
     /// continue to fire on that same duration
     fn new(period: Duration) -> Result<Self, std::io::Error> {
+        #[cfg(feature = "test-util")]
+        if let Some(clock) = Clock::current() {
+            return Ok(Interval {
+                inner: IntervalInner::Paused(paused::Interval::new(clock, period)),
+            });
+        }
+
         let timer = TimerFd::new(period, period)?;
-        Ok(Interval { timer })
+        Ok(Interval {
+            inner: IntervalInner::Timer(timer),
+        })
     }
 
     /// Sleep until the interval fires
@@ -201,47 +282,17 @@ impl Interval {
     /// Is the interval would have fired multiple times between calls to this .tick(), the return
     /// value is how many times it would have fired.
     pub async fn tick(&mut self) -> Result<u64, std::io::Error> {
-        Tick {
-            interval: self,
-            state: RegisteredState::Unregistered,
-        }
+        std::future::poll_fn(|cx| match &mut self.inner {
+            IntervalInner::Timer(timer) => poll_timer(cx, timer),
+            #[cfg(feature = "test-util")]
+            IntervalInner::Paused(interval) => interval.poll_tick(cx).map(Ok),
+        })
         .await
     }
 }
 
-/// The future that runs [`Interval::tick`]
-#[pin_project]
-struct Tick<'a> {
-    interval: &'a mut Interval,
-    state: RegisteredState,
-}
-
-impl<'a> Future for Tick<'a> {
-    type Output = Result<u64, std::io::Error>;
-
-    fn poll(
-        self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Self::Output> {
-        let projected = self.project();
-
-        // Call read on the file descriptor. Since this is a non-blocking file descriptor, this
-        // should return immediately.
-        let result = projected.interval.timer.read();
-        match result {
-            // Success!
-            Ok(ok) => std::task::Poll::Ready(Ok(ok)),
-            Err(err) if err.kind() == ErrorKind::WouldBlock => {
-                // Not ready yet. If we haven't registered the file descriptor with the runtime,
-                // do it now.
-                if *projected.state == RegisteredState::Unregistered {
-                    let context = RuntimeContext::current();
-                    context.register_file_descriptor(&projected.interval.timer);
-                    *projected.state = RegisteredState::Registered;
-                }
-                std::task::Poll::Pending
-            }
-            Err(err) => std::task::Poll::Ready(Err(err)),
-        }
-    }
+/// Wait for a timer to fire, and say how many times it has since it was last read
+fn poll_timer(cx: &mut Context<'_>, timer: &TimerFd) -> Poll<Result<u64, std::io::Error>> {
+    // Since the timer is non-blocking, reading it returns immediately.
+    poll_fd(cx, timer.as_raw_fd(), Interest::READABLE, || timer.read())
 }
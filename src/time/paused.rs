@@ -0,0 +1,197 @@
+//! Paused time, for tests
+//!
+//! Once a runtime's time is [paused](super::pause), sleeps and intervals stop using `timerfd`s and
+//! wait on the runtime's own clock instead, which only moves when it's told to: by
+//! [`advance`](super::advance), or by the runtime itself, which jumps straight to the next
+//! deadline whenever it has nothing else to do.
+
+use crate::runtime::RuntimeContext;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// A runtime's paused clock
+///
+/// Cloning it gives another handle to the same clock. Only the runtime's own thread ever uses it,
+/// but hyper wants its timers to be `Send` and `Sync`, so it's behind a mutex anyway.
+#[derive(Clone, Default)]
+pub(crate) struct Clock {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// How far the clock has moved since it was paused
+    now: Duration,
+    /// Everything waiting for the clock to get to a particular time
+    timers: BTreeMap<(Duration, u64), Waker>,
+    /// Tie-breaker for timers with the same deadline, so they fire in the order they were set
+    sequence: u64,
+}
+
+impl Clock {
+    /// The current runtime's clock, if it's paused
+    pub(crate) fn current() -> Option<Self> {
+        let context = RuntimeContext::try_current()?;
+        let inner = context
+            .inner()
+            .try_borrow()
+            .expect("Expected to lock inner");
+        inner.clock().cloned()
+    }
+
+    /// Whether anything is waiting on the clock
+    pub(crate) fn has_timers(&self) -> bool {
+        !self.state().timers.is_empty()
+    }
+
+    /// Move the clock forward, firing every timer that's due
+    pub(crate) fn advance(&self, duration: Duration) {
+        let now = self.state().now + duration;
+        self.advance_to(now);
+    }
+
+    /// Move the clock forward to the next deadline, and fire everything that's due then
+    pub(crate) fn advance_to_next(&self) {
+        let next = self.state().timers.keys().next().map(|(at, _)| *at);
+        if let Some(next) = next {
+            self.advance_to(next);
+        }
+    }
+
+    fn advance_to(&self, now: Duration) {
+        let mut state = self.state();
+        state.now = state.now.max(now);
+        // Waking only puts tasks on the ready queue, so it's fine to do while holding the state.
+        while let Some(entry) = state.timers.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            entry.remove().wake();
+        }
+    }
+
+    /// Wait until the clock gets to `deadline`, keeping track of the timer in `timer`
+    fn poll_deadline(
+        &self,
+        cx: &mut Context<'_>,
+        deadline: Duration,
+        timer: &mut Option<(Duration, u64)>,
+    ) -> Poll<()> {
+        let mut state = self.state();
+        if state.now >= deadline {
+            if let Some(timer) = timer.take() {
+                state.timers.remove(&timer);
+            }
+            return Poll::Ready(());
+        }
+
+        let key = match *timer {
+            Some(key) if key.0 == deadline => key,
+            _ => {
+                if let Some(old) = timer.take() {
+                    state.timers.remove(&old);
+                }
+                state.sequence += 1;
+                (deadline, state.sequence)
+            }
+        };
+        *timer = Some(key);
+        state.timers.insert(key, cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn cancel(&self, timer: Option<(Duration, u64)>) {
+        if let Some(timer) = timer {
+            self.state().timers.remove(&timer);
+        }
+    }
+
+    fn now(&self) -> Duration {
+        self.state().now
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("Expected to lock the clock")
+    }
+}
+
+/// A [`Sleep`](super::Sleep) on a paused clock
+pub(crate) struct Sleep {
+    clock: Clock,
+    deadline: Duration,
+    timer: Option<(Duration, u64)>,
+}
+
+impl Sleep {
+    pub(crate) fn new(clock: Clock, duration: Duration) -> Self {
+        let deadline = clock.now() + duration;
+        Self {
+            clock,
+            deadline,
+            timer: None,
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.clock.poll_deadline(cx, this.deadline, &mut this.timer)
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        // A sleep nobody's waiting on anymore shouldn't be what the clock jumps to next.
+        self.clock.cancel(self.timer.take());
+    }
+}
+
+/// An [`Interval`](super::Interval) on a paused clock
+pub(crate) struct Interval {
+    clock: Clock,
+    period: Duration,
+    /// When the interval fires next
+    next: Duration,
+    timer: Option<(Duration, u64)>,
+}
+
+impl Interval {
+    pub(crate) fn new(clock: Clock, period: Duration) -> Self {
+        let next = clock.now() + period;
+        Self {
+            clock,
+            period,
+            next,
+            timer: None,
+        }
+    }
+
+    /// Wait for the interval to fire, and say how many times it has since the last tick
+    pub(crate) fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<u64> {
+        if self
+            .clock
+            .poll_deadline(cx, self.next, &mut self.timer)
+            .is_pending()
+        {
+            return Poll::Pending;
+        }
+
+        let late = self.clock.now() - self.next;
+        let fired = 1 + (late.as_nanos() / self.period.as_nanos().max(1)) as u64;
+        self.next += self.period * fired as u32;
+        Poll::Ready(fired)
+    }
+}
+
+impl Drop for Interval {
+    fn drop(&mut self) {
+        self.clock.cancel(self.timer.take());
+    }
+}
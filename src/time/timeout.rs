@@ -0,0 +1,136 @@
+//! Giving up on futures that take too long
+
+use super::Sleep;
+use pin_project::pin_project;
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Wait for `future`, but only for so long
+///
+/// If `future` finishes within `duration`, this resolves to its output. Otherwise, `future` is
+/// dropped, and this resolves to [`Elapsed`].
+///
+/// ```
+/// use guillotine::net::TcpListener;
+/// use guillotine::time::timeout;
+/// use std::time::Duration;
+///
+/// let runtime = guillotine::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let listener = TcpListener::new(std::net::TcpListener::bind("127.0.0.1:0").unwrap())
+///         .unwrap();
+///
+///     // Nobody's connecting
+///     let accepted = timeout(Duration::from_millis(10), listener.accept()).await;
+///     assert!(accepted.is_err());
+///
+///     let answer = timeout(Duration::from_secs(1), async { 42 }).await;
+///     assert_eq!(answer.unwrap(), 42);
+///
+///     // No time at all is up right away
+///     let never = timeout(Duration::ZERO, std::future::pending::<()>()).await;
+///     assert!(never.is_err());
+/// });
+/// ```
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        duration,
+        sleep: None,
+    }
+}
+
+/// The future that runs [`timeout`]
+#[pin_project]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Timeout<F> {
+    #[pin]
+    future: F,
+    duration: Duration,
+    /// The timer, once it's been set up
+    ///
+    /// It's set up the first time this is polled, so that it's on the clock of the runtime that's
+    /// polling it (which is paused time, if time is paused).
+    sleep: Option<Sleep>,
+}
+
+impl<F> Timeout<F> {
+    /// Get access to the future being waited on
+    pub fn get_ref(&self) -> &F {
+        &self.future
+    }
+
+    /// Unwrap the future being waited on
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let projected = self.project();
+
+        // The future gets the first look, so one that's ready right away never times out, even
+        // with no time at all.
+        if let Poll::Ready(output) = projected.future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        let sleep = match projected.sleep {
+            Some(sleep) => sleep,
+            None => match Sleep::new(*projected.duration) {
+                Ok(sleep) => projected.sleep.insert(sleep),
+                Err(error) => return Poll::Ready(Err(Elapsed { error: Some(error) })),
+            },
+        };
+        match Pin::new(sleep).poll(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(Err(Elapsed { error: None })),
+            Poll::Ready(Err(error)) => Poll::Ready(Err(Elapsed { error: Some(error) })),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// The error from a [`timeout`] that ran out of time
+///
+/// Without a working timer, there's no telling when the time is up, so a timer that fails counts
+/// as time being up, too. That's vanishingly rare, but when it happens, the timer's error is this
+/// error's [`source`](std::error::Error::source).
+#[derive(Debug)]
+pub struct Elapsed {
+    /// Why the timer failed, if it did
+    error: Option<std::io::Error>,
+}
+
+impl Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error {
+            None => write!(f, "deadline has elapsed"),
+            Some(error) => write!(f, "timer failed: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Elapsed {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error
+            .as_ref()
+            .map(|error| error as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// A timeout is a `TimedOut` I/O error, so `?` works in functions that return those. A timer that
+/// failed is that failure.
+impl From<Elapsed> for std::io::Error {
+    fn from(elapsed: Elapsed) -> Self {
+        match elapsed.error {
+            None => std::io::Error::new(std::io::ErrorKind::TimedOut, "deadline has elapsed"),
+            Some(error) => error,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! A thin layer over `tracing`, so that it can be left out
+//!
+//! With the `tracing` feature (on by default) these forward straight to `tracing`. Without it,
+//! the events compile down to nothing (other than referencing whatever they would have logged, so
+//! that doesn't cause unused variable warnings) and spans are empty.
+
+#[cfg(feature = "tracing")]
+macro_rules! event {
+    ($level:ident, $($arg:tt)*) => {
+        ::tracing::$level!($($arg)*)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! event {
+    ($level:ident, $($arg:tt)*) => {
+        $crate::trace::discard!($($arg)*)
+    };
+}
+
+/// Evaluate nothing, but reference every value in a `tracing` event's arguments
+#[cfg(not(feature = "tracing"))]
+macro_rules! discard {
+    () => {};
+    ($msg:literal $(, $arg:expr)* $(,)?) => {{
+        $(let _ = &$arg;)*
+    }};
+    ($($key:ident).+ = %$value:expr $(, $($rest:tt)*)?) => {{
+        let _ = &$value;
+        $crate::trace::discard!($($($rest)*)?)
+    }};
+    ($($key:ident).+ = ?$value:expr $(, $($rest:tt)*)?) => {{
+        let _ = &$value;
+        $crate::trace::discard!($($($rest)*)?)
+    }};
+    ($($key:ident).+ = $value:expr $(, $($rest:tt)*)?) => {{
+        let _ = &$value;
+        $crate::trace::discard!($($($rest)*)?)
+    }};
+    (%$value:ident $(, $($rest:tt)*)?) => {{
+        let _ = &$value;
+        $crate::trace::discard!($($($rest)*)?)
+    }};
+    (?$value:ident $(, $($rest:tt)*)?) => {{
+        let _ = &$value;
+        $crate::trace::discard!($($($rest)*)?)
+    }};
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::trace::event!(debug, $($arg)*) };
+}
+
+#[cfg_attr(not(feature = "tracing"), allow(unused_macros))]
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::trace::event!(info, $($arg)*) };
+}
+
+// Named differently so it doesn't clash with the built-in `#[warn]` attribute, and renamed on the
+// way out below.
+macro_rules! warn_event {
+    ($($arg:tt)*) => { $crate::trace::event!(warn, $($arg)*) };
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::trace::event!(error, $($arg)*) };
+}
+
+/// Create an info-level [`Span`]
+#[cfg(feature = "tracing")]
+macro_rules! info_span {
+    ($($arg:tt)*) => {
+        $crate::trace::Span::from(::tracing::info_span!($($arg)*))
+    };
+}
+
+/// Create an info-level [`Span`]
+#[cfg(not(feature = "tracing"))]
+macro_rules! info_span {
+    ($($arg:tt)*) => {
+        $crate::trace::Span::none()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use discard;
+#[cfg_attr(not(feature = "tracing"), allow(unused_imports))]
+pub(crate) use {debug, error, event, info, info_span, warn_event as warn};
+
+/// A `tracing::Span`, or nothing at all
+#[derive(Clone, Debug)]
+pub(crate) struct Span {
+    #[cfg(feature = "tracing")]
+    inner: tracing::Span,
+}
+
+/// The guard from [`Span::enter`], which exits the span when it's dropped
+pub(crate) struct Entered<'a> {
+    #[cfg(feature = "tracing")]
+    _inner: tracing::span::Entered<'a>,
+    #[cfg(not(feature = "tracing"))]
+    _span: std::marker::PhantomData<&'a Span>,
+}
+
+/// The guard from [`Span::entered`], which exits the span when it's dropped
+pub(crate) struct EnteredSpan {
+    #[cfg(feature = "tracing")]
+    _inner: tracing::span::EnteredSpan,
+}
+
+impl Span {
+    /// A span that doesn't record anything
+    #[cfg(not(feature = "tracing"))]
+    pub fn none() -> Self {
+        Self {}
+    }
+
+    /// Enter the span until the guard is dropped
+    pub fn enter(&self) -> Entered<'_> {
+        Entered {
+            #[cfg(feature = "tracing")]
+            _inner: self.inner.enter(),
+            #[cfg(not(feature = "tracing"))]
+            _span: std::marker::PhantomData,
+        }
+    }
+
+    /// Enter the span until the guard is dropped, handing the span over to the guard
+    pub fn entered(self) -> EnteredSpan {
+        EnteredSpan {
+            #[cfg(feature = "tracing")]
+            _inner: self.inner.entered(),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl From<tracing::Span> for Span {
+    fn from(inner: tracing::Span) -> Self {
+        Self { inner }
+    }
+}
@@ -0,0 +1,423 @@
+//! Terminals, for interactive programs
+//!
+//! [`Terminal`] reads from and writes to the controlling terminal without blocking the runtime.
+//! Put it in [raw mode](Terminal::raw_mode) to get every keypress as it happens (instead of a line
+//! at a time, after Enter), and use [`Terminal::resizes`] to hear about the window changing size.
+//!
+//! ```no_run
+//! use guillotine::tty::{Key, Terminal};
+//!
+//! let runtime = guillotine::runtime::Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let mut terminal = Terminal::open().unwrap();
+//!     let _raw = terminal.raw_mode().unwrap();
+//!     loop {
+//!         match terminal.read_key().await.unwrap() {
+//!             Key::Char('q') | Key::Ctrl('c') => break,
+//!             key => terminal.write_all(format!("{key:?}\n").as_bytes()).await.unwrap(),
+//!         }
+//!     }
+//! });
+//! ```
+
+use crate::io::{poll_fd, AsyncRead, AsyncWrite};
+use crate::runtime::Interest;
+use crate::signal::{signal, Signal, SignalKind};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::mem::MaybeUninit;
+use std::os::unix::prelude::{AsRawFd, FromRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+/// A key that was pressed, decoded by [`Terminal::read_key`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Key {
+    /// A regular character
+    Char(char),
+    /// A letter with Ctrl held down, like `Ctrl('c')`
+    Ctrl(char),
+    /// Enter (or Return)
+    Enter,
+    /// Tab
+    Tab,
+    /// Backspace
+    Backspace,
+    /// Escape, by itself
+    Escape,
+    /// The up arrow
+    Up,
+    /// The down arrow
+    Down,
+    /// The left arrow
+    Left,
+    /// The right arrow
+    Right,
+    /// Home
+    Home,
+    /// End
+    End,
+    /// Page Up
+    PageUp,
+    /// Page Down
+    PageDown,
+    /// Insert
+    Insert,
+    /// Delete (forward delete, not Backspace)
+    Delete,
+    /// A function key, like `F(1)`
+    F(u8),
+    /// An escape sequence we don't know about
+    Unknown(Vec<u8>),
+}
+
+/// The size of a terminal window
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct WindowSize {
+    /// The width, in characters
+    pub columns: u16,
+    /// The height, in characters
+    pub rows: u16,
+}
+
+/// The controlling terminal
+#[derive(Debug)]
+pub struct Terminal {
+    file: File,
+    /// Bytes that have been read but not handed out yet
+    pending: VecDeque<u8>,
+}
+
+impl Terminal {
+    /// Open the controlling terminal (`/dev/tty`)
+    ///
+    /// This gets a file descriptor of its own, so making it non-blocking doesn't affect anyone
+    /// else using stdin or stdout.
+    pub fn open() -> Result<Self, std::io::Error> {
+        let fd = unsafe {
+            libc::open(
+                c"/dev/tty".as_ptr(),
+                libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK | libc::O_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(Self {
+            file: unsafe { File::from_raw_fd(fd) },
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Switch the terminal to raw mode until the returned guard is dropped
+    ///
+    /// In raw mode, input isn't echoed, doesn't wait for Enter, and Ctrl-C and friends are just
+    /// keys rather than signals. Output processing is left alone, so `\n` still starts a new line.
+    pub fn raw_mode(&self) -> Result<RawMode, std::io::Error> {
+        RawMode::enable(self.file.as_raw_fd())
+    }
+
+    /// The current size of the terminal window
+    pub fn size(&self) -> Result<WindowSize, std::io::Error> {
+        window_size(self.file.as_raw_fd())
+    }
+
+    /// Start listening for the terminal window changing size
+    ///
+    /// This listens for `SIGWINCH` (see [`signal`](crate::signal) for the caveats), so it should
+    /// only be called once.
+    pub fn resizes(&self) -> Result<Resizes, std::io::Error> {
+        Ok(Resizes {
+            signal: signal(SignalKind::window_change())?,
+            file: self.file.try_clone()?,
+        })
+    }
+
+    /// Read bytes from the terminal, as a _future_.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, buf)).await
+    }
+
+    /// Read a single byte from the terminal, as a _future_.
+    pub async fn read_byte(&mut self) -> Result<u8, std::io::Error> {
+        std::future::poll_fn(|cx| {
+            if let Some(byte) = self.pending.pop_front() {
+                return Poll::Ready(Ok(byte));
+            }
+            match self.poll_fill(cx) {
+                Poll::Ready(Ok(())) => Poll::Ready(Ok(self.pending.pop_front().unwrap())),
+                Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                Poll::Pending => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Read a keypress from the terminal, as a _future_.
+    ///
+    /// This only makes sense in [raw mode](Terminal::raw_mode).
+    pub async fn read_key(&mut self) -> Result<Key, std::io::Error> {
+        let first = self.read_byte().await?;
+        let key = match first {
+            b'\r' | b'\n' => Key::Enter,
+            b'\t' => Key::Tab,
+            0x7f | 0x08 => Key::Backspace,
+            0x1b => self.read_escape()?,
+            0x01..=0x1a => Key::Ctrl((b'a' + first - 1) as char),
+            _ => Key::Char(self.read_char(first).await?),
+        };
+        Ok(key)
+    }
+
+    /// Write bytes to the terminal, as a _future_.
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_write(cx, buf)).await
+    }
+
+    /// Write all of `buf` to the terminal, as a _future_.
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> Result<(), std::io::Error> {
+        while !buf.is_empty() {
+            let written = self.write(buf).await?;
+            if written == 0 {
+                return Err(ErrorKind::WriteZero.into());
+            }
+            buf = &buf[written..];
+        }
+        Ok(())
+    }
+
+    /// Read whatever is available into `pending`
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        use std::io::Read;
+
+        let mut buf = [0_u8; 64];
+        let mut file = &self.file;
+        match poll_fd(cx, file.as_raw_fd(), Interest::READABLE, || {
+            file.read(&mut buf)
+        }) {
+            Poll::Ready(Ok(0)) => Poll::Ready(Err(ErrorKind::UnexpectedEof.into())),
+            Poll::Ready(Ok(read)) => {
+                self.pending.extend(&buf[..read]);
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Take another byte if one has already arrived, without waiting for it
+    fn next_available(&mut self) -> Option<u8> {
+        if self.pending.is_empty() {
+            // An escape sequence arrives all at once, so anything that isn't there yet isn't
+            // part of it. Pressing Escape by itself is just the one byte. Nothing's waiting on this
+            // poll, so there's nobody to wake up either.
+            let _ = self.poll_fill(&mut Context::from_waker(Waker::noop()));
+        }
+        self.pending.pop_front()
+    }
+
+    /// Decode what comes after an escape byte
+    fn read_escape(&mut self) -> Result<Key, std::io::Error> {
+        let mut sequence = vec![0x1b];
+        let Some(intro) = self.next_available() else {
+            return Ok(Key::Escape);
+        };
+        sequence.push(intro);
+        if intro != b'[' && intro != b'O' {
+            return Ok(Key::Unknown(sequence));
+        }
+
+        // Parameters are digits and semicolons; the sequence ends with anything else
+        let mut last = None;
+        while let Some(byte) = self.next_available() {
+            sequence.push(byte);
+            if !(byte.is_ascii_digit() || byte == b';') {
+                last = Some(byte);
+                break;
+            }
+        }
+        let params = std::str::from_utf8(&sequence[2..sequence.len().saturating_sub(1).max(2)])
+            .unwrap_or_default();
+
+        let key = match (last, params) {
+            (Some(b'A'), _) => Key::Up,
+            (Some(b'B'), _) => Key::Down,
+            (Some(b'C'), _) => Key::Right,
+            (Some(b'D'), _) => Key::Left,
+            (Some(b'H'), _) => Key::Home,
+            (Some(b'F'), _) => Key::End,
+            (Some(b'P'), "") => Key::F(1),
+            (Some(b'Q'), "") => Key::F(2),
+            (Some(b'R'), "") => Key::F(3),
+            (Some(b'S'), "") => Key::F(4),
+            (Some(b'~'), "1" | "7") => Key::Home,
+            (Some(b'~'), "2") => Key::Insert,
+            (Some(b'~'), "3") => Key::Delete,
+            (Some(b'~'), "4" | "8") => Key::End,
+            (Some(b'~'), "5") => Key::PageUp,
+            (Some(b'~'), "6") => Key::PageDown,
+            (Some(b'~'), "15") => Key::F(5),
+            (Some(b'~'), "17") => Key::F(6),
+            (Some(b'~'), "18") => Key::F(7),
+            (Some(b'~'), "19") => Key::F(8),
+            (Some(b'~'), "20") => Key::F(9),
+            (Some(b'~'), "21") => Key::F(10),
+            (Some(b'~'), "23") => Key::F(11),
+            (Some(b'~'), "24") => Key::F(12),
+            _ => Key::Unknown(sequence),
+        };
+        Ok(key)
+    }
+
+    /// Decode a UTF-8 character that starts with `first`
+    async fn read_char(&mut self, first: u8) -> Result<char, std::io::Error> {
+        let len = match first {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            _ => 4,
+        };
+        let mut bytes = vec![first];
+        for _ in 1..len {
+            bytes.push(self.read_byte().await?);
+        }
+        std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "input is not valid UTF-8"))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        crate::runtime::forget_file_descriptor(self.file.as_raw_fd());
+    }
+}
+
+impl AsRawFd for Terminal {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl AsyncRead for Terminal {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        use std::io::Read;
+
+        let this = self.get_mut();
+        // Hand out anything left over from decoding keys first
+        if !this.pending.is_empty() {
+            let len = this.pending.len().min(buf.len());
+            for (slot, byte) in buf.iter_mut().zip(this.pending.drain(..len)) {
+                *slot = byte;
+            }
+            return Poll::Ready(Ok(len));
+        }
+        let mut file = &this.file;
+        poll_fd(cx, file.as_raw_fd(), Interest::READABLE, || file.read(buf))
+    }
+}
+
+impl AsyncWrite for Terminal {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        use std::io::Write;
+
+        let mut file = &self.file;
+        poll_fd(cx, file.as_raw_fd(), Interest::WRITABLE, || file.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        // Writes go straight to the terminal; there's nothing to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Keeps a terminal in raw mode, created by [`Terminal::raw_mode`] or [`RawMode::enable`]
+///
+/// The terminal's original settings are put back when this is dropped.
+#[derive(Debug)]
+pub struct RawMode {
+    fd: RawFd,
+    original: libc::termios,
+}
+
+impl RawMode {
+    /// Switch any terminal file descriptor (stdin, say) to raw mode
+    ///
+    /// The file descriptor has to stay open for as long as the `RawMode` is around.
+    pub fn enable(fd: RawFd) -> Result<Self, std::io::Error> {
+        unsafe {
+            let mut termios: MaybeUninit<libc::termios> = MaybeUninit::uninit();
+            if libc::tcgetattr(fd, termios.as_mut_ptr()) < 0 {
+                return Err(Error::last_os_error());
+            }
+            let original = termios.assume_init();
+
+            let mut raw = original;
+            libc::cfmakeraw(&mut raw);
+            // Keep output processing, so `\n` still means a new line
+            raw.c_oflag |= libc::OPOST;
+            if libc::tcsetattr(fd, libc::TCSANOW, &raw) < 0 {
+                return Err(Error::last_os_error());
+            }
+            Ok(Self { fd, original })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            if libc::tcsetattr(self.fd, libc::TCSANOW, &self.original) < 0 {
+                crate::trace::warn!(
+                    "Failed to restore terminal settings: {}",
+                    Error::last_os_error()
+                );
+            }
+        }
+    }
+}
+
+/// A stream of terminal window size changes, created by [`Terminal::resizes`]
+#[derive(Debug)]
+pub struct Resizes {
+    signal: Signal,
+    /// Our own handle on the terminal, for asking it its size
+    file: File,
+}
+
+impl Resizes {
+    /// Wait for the window to change size, as a _future_, and return the new size
+    pub async fn next(&mut self) -> Result<WindowSize, std::io::Error> {
+        self.signal.recv().await?;
+        window_size(self.file.as_raw_fd())
+    }
+}
+
+/// Ask a terminal for its size
+pub(crate) fn window_size(fd: RawFd) -> Result<WindowSize, std::io::Error> {
+    unsafe {
+        let mut size: MaybeUninit<libc::winsize> = MaybeUninit::zeroed();
+        if libc::ioctl(fd, libc::TIOCGWINSZ, size.as_mut_ptr()) < 0 {
+            return Err(Error::last_os_error());
+        }
+        let size = size.assume_init();
+        Ok(WindowSize {
+            columns: size.ws_col,
+            rows: size.ws_row,
+        })
+    }
+}